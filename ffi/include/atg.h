@@ -0,0 +1,72 @@
+/*
+ * C API for atglib
+ *
+ * All transcripts and strings returned by the accessors are owned by the
+ * AtgTranscripts handle and are valid until atg_transcripts_free is called.
+ * Functions that can fail return NULL or -1, atg_last_error returns the
+ * error message of the current thread.
+ *
+ * All coordinates are 1-based and inclusive.
+ */
+
+#ifndef ATG_H
+#define ATG_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define ATG_FORMAT_GTF 0
+#define ATG_FORMAT_REFGENE 1
+#define ATG_FORMAT_GENEPREDEXT 2
+/* output only */
+#define ATG_FORMAT_GENEPRED 3
+/* output only */
+#define ATG_FORMAT_BED 4
+
+typedef struct AtgTranscripts AtgTranscripts;
+typedef struct AtgTranscript AtgTranscript;
+
+typedef struct AtgBuffer {
+    uint8_t *data;
+    size_t len;
+} AtgBuffer;
+
+const char *atg_last_error(void);
+
+/* Reading */
+AtgTranscripts *atg_read_file(const char *path, int format);
+AtgTranscripts *atg_read_buffer(const uint8_t *data, size_t len, int format);
+void atg_transcripts_free(AtgTranscripts *transcripts);
+
+/* Iteration */
+size_t atg_transcripts_len(const AtgTranscripts *transcripts);
+const AtgTranscript *atg_transcripts_get(const AtgTranscripts *transcripts, size_t idx);
+
+/* Writing */
+int atg_write_buffer(const AtgTranscripts *transcripts, int format, AtgBuffer *buffer);
+void atg_buffer_free(AtgBuffer *buffer);
+
+/* Transcript fields */
+const char *atg_transcript_name(const AtgTranscript *tx);
+const char *atg_transcript_gene(const AtgTranscript *tx);
+const char *atg_transcript_chrom(const AtgTranscript *tx);
+/* '+', '-' or '.' */
+char atg_transcript_strand(const AtgTranscript *tx);
+uint32_t atg_transcript_start(const AtgTranscript *tx);
+uint32_t atg_transcript_end(const AtgTranscript *tx);
+/* 0 for non-coding transcripts */
+uint32_t atg_transcript_cds_start(const AtgTranscript *tx);
+/* 0 for non-coding transcripts */
+uint32_t atg_transcript_cds_end(const AtgTranscript *tx);
+size_t atg_transcript_exon_count(const AtgTranscript *tx);
+int atg_transcript_exon(const AtgTranscript *tx, size_t idx, uint32_t *start, uint32_t *end);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* ATG_H */
@@ -0,0 +1,385 @@
+//! C API for atglib
+//!
+//! The API uses opaque handles: `atg_read_file` and `atg_read_buffer` return
+//! an `AtgTranscripts` handle that owns all transcripts and must be released
+//! with `atg_transcripts_free`. Transcripts and all strings returned by the
+//! accessors are borrowed from this handle and are valid until it is freed.
+//!
+//! Functions that can fail return `NULL` or a negative value, the error
+//! message is available with `atg_last_error`. The declarations are in
+//! `include/atg.h`.
+//!
+//! All coordinates are 1-based and inclusive, like in atglib.
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::io::Cursor;
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+use atglib::bed;
+use atglib::genepred;
+use atglib::genepredext;
+use atglib::gtf;
+use atglib::models::{Transcript, TranscriptRead, TranscriptWrite, Transcripts};
+use atglib::refgene;
+
+pub const ATG_FORMAT_GTF: c_int = 0;
+pub const ATG_FORMAT_REFGENE: c_int = 1;
+pub const ATG_FORMAT_GENEPREDEXT: c_int = 2;
+/// Output only
+pub const ATG_FORMAT_GENEPRED: c_int = 3;
+/// Output only
+pub const ATG_FORMAT_BED: c_int = 4;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error<E: std::fmt::Display>(err: E) {
+    let message = c_string(&err.to_string().replace('\0', " "));
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Returns the message of the last error of the current thread, or `NULL`
+///
+/// The message is valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn atg_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |msg| msg.as_ptr())
+    })
+}
+
+/// A transcript with NUL-terminated copies of its names
+pub struct AtgTranscript {
+    inner: Transcript,
+    name: CString,
+    gene: CString,
+    chrom: CString,
+}
+
+fn c_string(value: &str) -> CString {
+    // the file formats can't contain NUL bytes
+    CString::new(value).unwrap_or_default()
+}
+
+impl AtgTranscript {
+    fn new(tx: Transcript) -> Self {
+        Self {
+            name: c_string(tx.name()),
+            gene: c_string(tx.gene()),
+            chrom: c_string(tx.chrom()),
+            inner: tx,
+        }
+    }
+}
+
+/// An owned list of transcripts
+pub struct AtgTranscripts {
+    transcripts: Vec<AtgTranscript>,
+}
+
+impl AtgTranscripts {
+    fn into_handle(transcripts: Transcripts) -> *mut AtgTranscripts {
+        let transcripts = transcripts
+            .to_vec()
+            .into_iter()
+            .map(AtgTranscript::new)
+            .collect();
+        Box::into_raw(Box::new(AtgTranscripts { transcripts }))
+    }
+}
+
+fn read<R: TranscriptRead>(mut reader: R) -> *mut AtgTranscripts {
+    match reader.transcripts() {
+        Ok(transcripts) => AtgTranscripts::into_handle(transcripts),
+        Err(err) => {
+            set_error(err);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Reads all transcripts from a file
+///
+/// Returns `NULL` on error.
+///
+/// # Safety
+///
+/// `path` must be a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn atg_read_file(path: *const c_char, format: c_int) -> *mut AtgTranscripts {
+    if path.is_null() {
+        set_error("path is NULL");
+        return ptr::null_mut();
+    }
+    let path = match CStr::from_ptr(path).to_str() {
+        Ok(path) => path,
+        Err(err) => {
+            set_error(err);
+            return ptr::null_mut();
+        }
+    };
+    let result = match format {
+        ATG_FORMAT_GTF => gtf::Reader::from_file(path).map(read),
+        ATG_FORMAT_REFGENE => refgene::Reader::from_file(path).map(read),
+        ATG_FORMAT_GENEPREDEXT => genepredext::Reader::from_file(path).map(read),
+        _ => {
+            set_error(format!("format {} can't be read", format));
+            return ptr::null_mut();
+        }
+    };
+    result.unwrap_or_else(|err| {
+        set_error(err);
+        ptr::null_mut()
+    })
+}
+
+/// Reads all transcripts from a buffer with the content of a file
+///
+/// Returns `NULL` on error.
+///
+/// # Safety
+///
+/// `data` must point to at least `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn atg_read_buffer(
+    data: *const u8,
+    len: usize,
+    format: c_int,
+) -> *mut AtgTranscripts {
+    if data.is_null() {
+        set_error("data is NULL");
+        return ptr::null_mut();
+    }
+    let data = Cursor::new(std::slice::from_raw_parts(data, len));
+    match format {
+        ATG_FORMAT_GTF => read(gtf::Reader::new(data)),
+        ATG_FORMAT_REFGENE => read(refgene::Reader::new(data)),
+        ATG_FORMAT_GENEPREDEXT => read(genepredext::Reader::new(data)),
+        _ => {
+            set_error(format!("format {} can't be read", format));
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Releases the transcripts, including all transcripts and strings borrowed from them
+///
+/// # Safety
+///
+/// `transcripts` must be `NULL` or a handle that was not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn atg_transcripts_free(transcripts: *mut AtgTranscripts) {
+    if !transcripts.is_null() {
+        drop(Box::from_raw(transcripts));
+    }
+}
+
+/// Returns the number of transcripts
+///
+/// # Safety
+///
+/// `transcripts` must be a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn atg_transcripts_len(transcripts: *const AtgTranscripts) -> usize {
+    (*transcripts).transcripts.len()
+}
+
+/// Returns the transcript at `idx`, or `NULL` if `idx` is out of range
+///
+/// Iterate all transcripts with `idx` from 0 to `atg_transcripts_len() - 1`.
+///
+/// # Safety
+///
+/// `transcripts` must be a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn atg_transcripts_get(
+    transcripts: *const AtgTranscripts,
+    idx: usize,
+) -> *const AtgTranscript {
+    let transcripts = &*transcripts;
+    transcripts
+        .transcripts
+        .get(idx)
+        .map_or(ptr::null(), |tx| tx as *const AtgTranscript)
+}
+
+/// The output buffer of `atg_write_buffer`
+#[repr(C)]
+pub struct AtgBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+/// Writes all transcripts into a new buffer
+///
+/// Returns 0 on success and -1 on error. The buffer must be released with
+/// `atg_buffer_free`.
+///
+/// # Safety
+///
+/// `transcripts` must be a valid handle and `buffer` must point to an `AtgBuffer`.
+#[no_mangle]
+pub unsafe extern "C" fn atg_write_buffer(
+    transcripts: *const AtgTranscripts,
+    format: c_int,
+    buffer: *mut AtgBuffer,
+) -> c_int {
+    let transcripts: Vec<Transcript> = (*transcripts)
+        .transcripts
+        .iter()
+        .map(|tx| tx.inner.clone())
+        .collect();
+    let mut data = vec![];
+    let result = match format {
+        ATG_FORMAT_GTF => write(gtf::Writer::new(&mut data), &transcripts),
+        ATG_FORMAT_REFGENE => write(refgene::Writer::new(&mut data), &transcripts),
+        ATG_FORMAT_GENEPREDEXT => write(genepredext::Writer::new(&mut data), &transcripts),
+        ATG_FORMAT_GENEPRED => write(genepred::Writer::new(&mut data), &transcripts),
+        ATG_FORMAT_BED => write(bed::Writer::new(&mut data), &transcripts),
+        _ => Err(std::io::Error::other(format!(
+            "format {} can't be written",
+            format
+        ))),
+    };
+    if let Err(err) = result {
+        set_error(err);
+        return -1;
+    }
+    let data = data.into_boxed_slice();
+    (*buffer).len = data.len();
+    (*buffer).data = Box::into_raw(data) as *mut u8;
+    0
+}
+
+fn write<W: TranscriptWrite>(mut writer: W, transcripts: &[Transcript]) -> std::io::Result<()> {
+    writer.write_transcript_vec(transcripts)
+}
+
+/// Releases the data of the buffer
+///
+/// # Safety
+///
+/// `buffer` must point to an `AtgBuffer` that was filled by `atg_write_buffer`.
+#[no_mangle]
+pub unsafe extern "C" fn atg_buffer_free(buffer: *mut AtgBuffer) {
+    let buffer = &mut *buffer;
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            buffer.data,
+            buffer.len,
+        )));
+    }
+    buffer.data = ptr::null_mut();
+    buffer.len = 0;
+}
+
+/// # Safety
+///
+/// `tx` must be a transcript of a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn atg_transcript_name(tx: *const AtgTranscript) -> *const c_char {
+    (*tx).name.as_ptr()
+}
+
+/// # Safety
+///
+/// `tx` must be a transcript of a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn atg_transcript_gene(tx: *const AtgTranscript) -> *const c_char {
+    (*tx).gene.as_ptr()
+}
+
+/// # Safety
+///
+/// `tx` must be a transcript of a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn atg_transcript_chrom(tx: *const AtgTranscript) -> *const c_char {
+    (*tx).chrom.as_ptr()
+}
+
+/// Returns the strand as `+`, `-` or `.`
+///
+/// # Safety
+///
+/// `tx` must be a transcript of a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn atg_transcript_strand(tx: *const AtgTranscript) -> c_char {
+    let strand = (*tx).inner.strand().to_string();
+    strand.bytes().next().unwrap_or(b'.') as c_char
+}
+
+/// # Safety
+///
+/// `tx` must be a transcript of a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn atg_transcript_start(tx: *const AtgTranscript) -> u32 {
+    (*tx).inner.tx_start()
+}
+
+/// # Safety
+///
+/// `tx` must be a transcript of a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn atg_transcript_end(tx: *const AtgTranscript) -> u32 {
+    (*tx).inner.tx_end()
+}
+
+/// Returns the start of the CDS, 0 for non-coding transcripts
+///
+/// # Safety
+///
+/// `tx` must be a transcript of a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn atg_transcript_cds_start(tx: *const AtgTranscript) -> u32 {
+    (*tx).inner.cds_start().unwrap_or(0)
+}
+
+/// Returns the end of the CDS, 0 for non-coding transcripts
+///
+/// # Safety
+///
+/// `tx` must be a transcript of a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn atg_transcript_cds_end(tx: *const AtgTranscript) -> u32 {
+    (*tx).inner.cds_end().unwrap_or(0)
+}
+
+/// # Safety
+///
+/// `tx` must be a transcript of a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn atg_transcript_exon_count(tx: *const AtgTranscript) -> usize {
+    (*tx).inner.exon_count()
+}
+
+/// Writes the start and end of the exon at `idx` (ordered by genomic position)
+///
+/// Returns 0 on success and -1 if `idx` is out of range.
+///
+/// # Safety
+///
+/// `tx` must be a transcript of a valid handle, `start` and `end` must be valid pointers.
+#[no_mangle]
+pub unsafe extern "C" fn atg_transcript_exon(
+    tx: *const AtgTranscript,
+    idx: usize,
+    start: *mut u32,
+    end: *mut u32,
+) -> c_int {
+    match (*tx).inner.exons().get(idx) {
+        Some(exon) => {
+            *start = exon.start();
+            *end = exon.end();
+            0
+        }
+        None => {
+            set_error(format!("exon {} is out of range", idx));
+            -1
+        }
+    }
+}
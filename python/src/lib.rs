@@ -0,0 +1,413 @@
+//! Python bindings for atglib
+//!
+//! Transcripts can be read and written in all formats that atglib supports,
+//! without calling the `atg` CLI:
+//!
+//! ```python
+//! import atg
+//!
+//! transcripts = atg.read_gtf("annotation.gtf")
+//! fasta = atg.Fasta("hg38.fa")
+//! for tx in transcripts.by_gene("EGFR"):
+//!     print(tx.name, len(tx.exons), fasta.cds_sequence(tx))
+//! atg.write_refgene(transcripts, "annotation.refgene")
+//! ```
+//!
+//! All coordinates are 1-based and inclusive, like in atglib.
+
+use std::fs::File;
+
+use pyo3::exceptions::{PyIOError, PyIndexError, PyValueError};
+use pyo3::prelude::*;
+
+use atglib::bed;
+use atglib::fasta::FastaReader;
+use atglib::genepred;
+use atglib::genepredext;
+use atglib::gtf;
+use atglib::models::{Exon, Transcript, TranscriptRead, TranscriptWrite};
+use atglib::refgene;
+
+fn io_error<E: std::fmt::Display>(err: E) -> PyErr {
+    PyIOError::new_err(err.to_string())
+}
+
+/// A single exon of a transcript
+#[pyclass(name = "Exon", module = "atg")]
+#[derive(Clone)]
+struct PyExon {
+    inner: Exon,
+}
+
+#[pymethods]
+impl PyExon {
+    #[getter]
+    fn start(&self) -> u32 {
+        self.inner.start()
+    }
+
+    #[getter]
+    fn end(&self) -> u32 {
+        self.inner.end()
+    }
+
+    /// The start of the coding part of the exon, `None` for non-coding exons
+    #[getter]
+    fn cds_start(&self) -> Option<u32> {
+        *self.inner.cds_start()
+    }
+
+    /// The end of the coding part of the exon, `None` for non-coding exons
+    #[getter]
+    fn cds_end(&self) -> Option<u32> {
+        *self.inner.cds_end()
+    }
+
+    fn is_coding(&self) -> bool {
+        self.inner.is_coding()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Exon {}-{}>", self.inner.start(), self.inner.end())
+    }
+}
+
+/// A transcript with its exons
+#[pyclass(name = "Transcript", module = "atg")]
+#[derive(Clone)]
+struct PyTranscript {
+    inner: Transcript,
+}
+
+#[pymethods]
+impl PyTranscript {
+    #[getter]
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    #[getter]
+    fn gene(&self) -> &str {
+        self.inner.gene()
+    }
+
+    #[getter]
+    fn chrom(&self) -> &str {
+        self.inner.chrom()
+    }
+
+    /// The strand as `+`, `-` or `.`
+    #[getter]
+    fn strand(&self) -> String {
+        self.inner.strand().to_string()
+    }
+
+    #[getter]
+    fn start(&self) -> u32 {
+        self.inner.tx_start()
+    }
+
+    #[getter]
+    fn end(&self) -> u32 {
+        self.inner.tx_end()
+    }
+
+    #[getter]
+    fn cds_start(&self) -> Option<u32> {
+        self.inner.cds_start()
+    }
+
+    #[getter]
+    fn cds_end(&self) -> Option<u32> {
+        self.inner.cds_end()
+    }
+
+    /// The exons, ordered by their genomic position
+    #[getter]
+    fn exons(&self) -> Vec<PyExon> {
+        self.inner
+            .exons()
+            .iter()
+            .map(|exon| PyExon {
+                inner: exon.clone(),
+            })
+            .collect()
+    }
+
+    fn is_coding(&self) -> bool {
+        self.inner.is_coding()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<Transcript {} ({}) {}:{}-{}>",
+            self.inner.name(),
+            self.inner.gene(),
+            self.inner.chrom(),
+            self.inner.tx_start(),
+            self.inner.tx_end()
+        )
+    }
+}
+
+/// A list of transcripts
+#[pyclass(name = "Transcripts", module = "atg")]
+struct PyTranscripts {
+    inner: Vec<Transcript>,
+}
+
+impl PyTranscripts {
+    fn wrap(transcripts: &[&Transcript]) -> Vec<PyTranscript> {
+        transcripts
+            .iter()
+            .map(|tx| PyTranscript {
+                inner: (*tx).clone(),
+            })
+            .collect()
+    }
+}
+
+#[pymethods]
+impl PyTranscripts {
+    /// Creates a list of transcripts, e.g. to write a selection of transcripts
+    #[new]
+    fn new(transcripts: Vec<PyTranscript>) -> Self {
+        Self {
+            inner: transcripts.into_iter().map(|tx| tx.inner).collect(),
+        }
+    }
+
+    /// Returns all transcripts with the name
+    fn by_name(&self, name: &str) -> Vec<PyTranscript> {
+        let found: Vec<&Transcript> = self.inner.iter().filter(|tx| tx.name() == name).collect();
+        Self::wrap(&found)
+    }
+
+    /// Returns all transcripts of the gene
+    fn by_gene(&self, gene: &str) -> Vec<PyTranscript> {
+        let found: Vec<&Transcript> = self.inner.iter().filter(|tx| tx.gene() == gene).collect();
+        Self::wrap(&found)
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn __getitem__(&self, idx: isize) -> PyResult<PyTranscript> {
+        let len = self.inner.len() as isize;
+        let pos = if idx < 0 { idx + len } else { idx };
+        if pos < 0 || pos >= len {
+            return Err(PyIndexError::new_err("transcript index out of range"));
+        }
+        Ok(PyTranscript {
+            inner: self.inner[pos as usize].clone(),
+        })
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> TranscriptIter {
+        TranscriptIter {
+            inner: slf.inner.clone().into_iter(),
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Transcripts ({} transcripts)>", self.inner.len())
+    }
+}
+
+#[pyclass(module = "atg")]
+struct TranscriptIter {
+    inner: std::vec::IntoIter<Transcript>,
+}
+
+#[pymethods]
+impl TranscriptIter {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<'_, Self>) -> Option<PyTranscript> {
+        slf.inner.next().map(|tx| PyTranscript { inner: tx })
+    }
+}
+
+fn read<R: TranscriptRead>(mut reader: R) -> PyResult<PyTranscripts> {
+    let transcripts = reader.transcripts().map_err(io_error)?;
+    Ok(PyTranscripts {
+        inner: transcripts.to_vec(),
+    })
+}
+
+/// Reads all transcripts from a GTF file
+#[pyfunction]
+fn read_gtf(path: &str) -> PyResult<PyTranscripts> {
+    read(gtf::Reader::from_file(path).map_err(io_error)?)
+}
+
+/// Reads all transcripts from a RefGene file
+#[pyfunction]
+fn read_refgene(path: &str) -> PyResult<PyTranscripts> {
+    read(refgene::Reader::from_file(path).map_err(io_error)?)
+}
+
+/// Reads all transcripts from a GenePredExt file
+#[pyfunction]
+fn read_genepredext(path: &str) -> PyResult<PyTranscripts> {
+    read(genepredext::Reader::from_file(path).map_err(io_error)?)
+}
+
+fn write<W: TranscriptWrite>(mut writer: W, transcripts: &PyTranscripts) -> PyResult<()> {
+    writer
+        .write_transcript_vec(&transcripts.inner)
+        .map_err(io_error)
+}
+
+/// Writes the transcripts to a GTF file
+#[pyfunction]
+#[pyo3(signature = (transcripts, path, source = None))]
+fn write_gtf(transcripts: &PyTranscripts, path: &str, source: Option<&str>) -> PyResult<()> {
+    let mut writer = gtf::Writer::from_file(path).map_err(io_error)?;
+    if let Some(source) = source {
+        writer.set_source(source);
+    }
+    write(writer, transcripts)
+}
+
+/// Writes the transcripts to a RefGene file
+#[pyfunction]
+fn write_refgene(transcripts: &PyTranscripts, path: &str) -> PyResult<()> {
+    write(
+        refgene::Writer::from_file(path).map_err(io_error)?,
+        transcripts,
+    )
+}
+
+/// Writes the transcripts to a GenePred file
+#[pyfunction]
+fn write_genepred(transcripts: &PyTranscripts, path: &str) -> PyResult<()> {
+    write(
+        genepred::Writer::from_file(path).map_err(io_error)?,
+        transcripts,
+    )
+}
+
+/// Writes the transcripts to a GenePredExt file
+#[pyfunction]
+fn write_genepredext(transcripts: &PyTranscripts, path: &str) -> PyResult<()> {
+    write(
+        genepredext::Writer::from_file(path).map_err(io_error)?,
+        transcripts,
+    )
+}
+
+/// Writes the transcripts to a Bed file
+#[pyfunction]
+fn write_bed(transcripts: &PyTranscripts, path: &str) -> PyResult<()> {
+    write(bed::Writer::from_file(path).map_err(io_error)?, transcripts)
+}
+
+/// Extracts sequences from an indexed Fasta file
+///
+/// The Fasta index (`.fai`) must be next to the Fasta file.
+#[pyclass(name = "Fasta", module = "atg", unsendable)]
+struct PyFasta {
+    inner: FastaReader<File>,
+}
+
+impl PyFasta {
+    /// Concatenates the sequences of the regions, in the direction of transcription
+    fn spliced(
+        &mut self,
+        tx: &Transcript,
+        regions: impl Iterator<Item = (u32, u32)>,
+    ) -> PyResult<String> {
+        let mut sequence = String::new();
+        for (start, end) in regions {
+            sequence.push_str(&self.sequence(tx.chrom(), start.into(), end.into())?);
+        }
+        if !tx.forward() {
+            sequence = reverse_complement(&sequence);
+        }
+        Ok(sequence)
+    }
+}
+
+fn reverse_complement(sequence: &str) -> String {
+    sequence
+        .chars()
+        .rev()
+        .map(|base| match base {
+            'A' => 'T',
+            'C' => 'G',
+            'G' => 'C',
+            'T' => 'A',
+            'a' => 't',
+            'c' => 'g',
+            'g' => 'c',
+            't' => 'a',
+            other => other,
+        })
+        .collect()
+}
+
+#[pymethods]
+impl PyFasta {
+    #[new]
+    fn new(path: &str) -> PyResult<Self> {
+        Ok(Self {
+            inner: FastaReader::from_file(path).map_err(io_error)?,
+        })
+    }
+
+    /// Returns the sequence of a genomic region on the plus strand
+    fn sequence(&mut self, chrom: &str, start: u64, end: u64) -> PyResult<String> {
+        if start > end {
+            return Err(PyValueError::new_err("the start must not be after the end"));
+        }
+        self.inner
+            .read_sequence(chrom, start, end)
+            .map(|sequence| sequence.to_string())
+            .map_err(io_error)
+    }
+
+    /// Returns the spliced sequence of all exons of the transcript
+    fn transcript_sequence(&mut self, transcript: &PyTranscript) -> PyResult<String> {
+        let tx = &transcript.inner;
+        self.spliced(tx, tx.exons().iter().map(|exon| (exon.start(), exon.end())))
+    }
+
+    /// Returns the coding sequence of the transcript, including the stop codon
+    fn cds_sequence(&mut self, transcript: &PyTranscript) -> PyResult<String> {
+        let tx = &transcript.inner;
+        if !tx.is_coding() {
+            return Err(PyValueError::new_err(format!(
+                "{} is not a coding transcript",
+                tx.name()
+            )));
+        }
+        self.spliced(
+            tx,
+            tx.exons()
+                .iter()
+                .filter_map(|exon| Some(((*exon.cds_start())?, (*exon.cds_end())?))),
+        )
+    }
+}
+
+/// Read, write and convert transcripts
+#[pymodule]
+fn atg(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyExon>()?;
+    m.add_class::<PyTranscript>()?;
+    m.add_class::<PyTranscripts>()?;
+    m.add_class::<PyFasta>()?;
+    m.add_function(wrap_pyfunction!(read_gtf, m)?)?;
+    m.add_function(wrap_pyfunction!(read_refgene, m)?)?;
+    m.add_function(wrap_pyfunction!(read_genepredext, m)?)?;
+    m.add_function(wrap_pyfunction!(write_gtf, m)?)?;
+    m.add_function(wrap_pyfunction!(write_refgene, m)?)?;
+    m.add_function(wrap_pyfunction!(write_genepred, m)?)?;
+    m.add_function(wrap_pyfunction!(write_genepredext, m)?)?;
+    m.add_function(wrap_pyfunction!(write_bed, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,162 @@
+//! Export transcripts as spliced alignments of their cDNA to the genome
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use atglib::fasta::FastaReader;
+use atglib::models::{Exon, Strand, Transcript};
+use atglib::utils::errors::AtgError;
+
+use crate::utils::{exon_len, sorted_exons, tx_len};
+use crate::warnings::warning;
+
+/// Writes every transcript as one PSL alignment record
+///
+/// The alignment is derived from the exon structure: Every exon is one
+/// aligned block without mismatches and every intron is a gap in the target.
+/// The target size is taken from `chrom_sizes` or is 0 if unknown.
+pub fn write_psl<W: Write>(
+    transcripts: &[Transcript],
+    chrom_sizes: Option<&HashMap<String, u64>>,
+    mut out: W,
+) -> Result<(), std::io::Error> {
+    for tx in transcripts {
+        let exons = sorted_exons(tx);
+        if exons.is_empty() {
+            continue;
+        }
+        let block_sizes: Vec<u64> = exons.iter().map(|exon| exon_len(exon)).collect();
+        let query_size: u64 = block_sizes.iter().sum();
+        let target_span = tx_len(tx);
+
+        // For minus strand alignments, PSL uses coordinates on the
+        // reverse complement of the query, so that qStarts always increase
+        let mut q_starts = Vec::with_capacity(exons.len());
+        let mut q_pos = 0;
+        for size in &block_sizes {
+            q_starts.push(q_pos);
+            q_pos += size;
+        }
+
+        writeln!(
+            out,
+            "{}\t0\t0\t0\t0\t0\t{}\t{}\t{}\t{}\t{}\t0\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            query_size,
+            exons.len() - 1,
+            target_span.saturating_sub(query_size),
+            match tx.strand() {
+                Strand::Minus => "-",
+                _ => "+",
+            },
+            tx.name(),
+            query_size,
+            query_size,
+            tx.chrom(),
+            chrom_sizes
+                .and_then(|sizes| sizes.get(tx.chrom()))
+                .unwrap_or(&0),
+            tx.tx_start().saturating_sub(1),
+            tx.tx_end(),
+            exons.len(),
+            comma_list(block_sizes),
+            comma_list(q_starts),
+            comma_list(exons.iter().map(|exon| exon.start().saturating_sub(1))),
+        )?;
+    }
+    out.flush()
+}
+
+/// Returns the values as comma-separated list with trailing comma, as used by UCSC formats
+fn comma_list<T: std::fmt::Display, I: IntoIterator<Item = T>>(values: I) -> String {
+    values
+        .into_iter()
+        .map(|value| format!("{},", value))
+        .collect()
+}
+
+/// Returns the CIGAR string of the exon structure, with exons as `M` and introns as `N`
+///
+/// Returns `None` if exons overlap each other.
+fn cigar(exons: &[&Exon]) -> Option<String> {
+    let mut cigar = String::new();
+    let mut previous_end: Option<u32> = None;
+    for exon in exons {
+        if let Some(end) = previous_end {
+            let intron = exon.start().checked_sub(end)?.checked_sub(1)?;
+            if intron > 0 {
+                cigar.push_str(&format!("{}N", intron));
+            }
+        }
+        cigar.push_str(&format!("{}M", exon_len(exon)));
+        previous_end = Some(exon.end());
+    }
+    Some(cigar)
+}
+
+/// Writes a SAM file with one alignment record per transcript
+///
+/// The header contains all chromosomes of the reference genome (`chroms`).
+/// The sequence of each record is the exon sequence in genomic orientation,
+/// as required by the SAM specs. Minus-strand transcripts are flagged as reverse
+/// complemented and all records contain the `XS` tag with the transcript's strand.
+pub fn write_sam<W: Write, R: std::io::Read + std::io::Seek>(
+    transcripts: &[Transcript],
+    chroms: &[(String, u64)],
+    fastareader: &mut FastaReader<R>,
+    mut out: W,
+) -> Result<(), AtgError> {
+    writeln!(out, "@HD\tVN:1.6\tSO:unsorted")?;
+    for (chrom, len) in chroms {
+        writeln!(out, "@SQ\tSN:{}\tLN:{}", chrom, len)?;
+    }
+    writeln!(
+        out,
+        "@PG\tID:{}\tPN:{}\tVN:{}",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    )?;
+
+    for tx in transcripts {
+        let exons = sorted_exons(tx);
+        let cigar = match cigar(&exons) {
+            Some(cigar) if !exons.is_empty() => cigar,
+            _ => {
+                warning(
+                    "atg::write",
+                    "invalid-exons",
+                    &format!("Skipping {}: no exons or overlapping exons", tx.name()),
+                );
+                continue;
+            }
+        };
+
+        let mut seq = String::new();
+        for exon in &exons {
+            seq.push_str(
+                &fastareader
+                    .read_sequence(tx.chrom(), exon.start().into(), exon.end().into())?
+                    .to_string(),
+            );
+        }
+
+        let (flag, strand) = match tx.strand() {
+            Strand::Minus => (16, '-'),
+            _ => (0, '+'),
+        };
+
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t255\t{}\t*\t0\t0\t{}\t*\tXS:A:{}",
+            tx.name(),
+            flag,
+            tx.chrom(),
+            tx.tx_start(),
+            cigar,
+            seq,
+            strand
+        )?;
+    }
+    out.flush()?;
+    Ok(())
+}
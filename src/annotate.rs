@@ -0,0 +1,240 @@
+//! Annotate genomic positions and variants with the overlapping transcripts
+
+use std::io::{BufRead, BufReader, Write};
+
+use atglib::models::{Strand, Transcript};
+use atglib::utils::errors::AtgError;
+
+use crate::cli::AnnotateArgs;
+use crate::compression::{create_output, open_input};
+use crate::coordinates::{CdsPosition, TranscriptCoordinates};
+use crate::formats::reader_for;
+use crate::lookup::PositionIndex;
+
+/// The feature of a transcript at a genomic position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    FivePrimeUtr,
+    Cds,
+    ThreePrimeUtr,
+    NonCodingExon,
+    Intron,
+}
+
+impl std::fmt::Display for Feature {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let s = match self {
+            Feature::FivePrimeUtr => "5'UTR",
+            Feature::Cds => "CDS",
+            Feature::ThreePrimeUtr => "3'UTR",
+            Feature::NonCodingExon => "exon",
+            Feature::Intron => "intron",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// The annotation of a genomic position in one transcript
+pub struct Annotation<'a> {
+    pub transcript: &'a Transcript,
+    pub feature: Feature,
+    /// Exon number, in the direction of transcription
+    pub exon: Option<usize>,
+    /// Intron number, in the direction of transcription
+    pub intron: Option<usize>,
+    /// 1-based position in the cDNA, only for exonic positions
+    pub cdna: Option<u64>,
+    /// Position in HGVS notation (`c.` or `n.`)
+    pub hgvs: String,
+}
+
+/// Returns the feature and the HGVS position of an exonic position
+fn exonic(coordinates: &TranscriptCoordinates, pos: u32) -> Option<(Feature, String)> {
+    let cdna = coordinates.genomic_to_cdna(pos)?;
+    Some(match coordinates.genomic_to_cds(pos) {
+        Some(cds @ CdsPosition::FivePrimeUtr(_)) => (Feature::FivePrimeUtr, format!("c.{}", cds)),
+        Some(cds @ CdsPosition::Cds(_)) => (Feature::Cds, format!("c.{}", cds)),
+        Some(cds @ CdsPosition::ThreePrimeUtr(_)) => (Feature::ThreePrimeUtr, format!("c.{}", cds)),
+        None => (Feature::NonCodingExon, format!("n.{}", cdna)),
+    })
+}
+
+/// Returns the intron number (1-based) and HGVS position of an intronic position
+fn intronic(coordinates: &TranscriptCoordinates, minus: bool, pos: u32) -> Option<(usize, String)> {
+    for (idx, pair) in coordinates.exons().windows(2).enumerate() {
+        let (upstream, downstream) = (pair[0], pair[1]);
+        // distance to the last base of the upstream and the first base of the downstream exon
+        let distances = match minus {
+            true if downstream.end() < pos && pos < upstream.start() => {
+                Some((upstream.start() - pos, pos - downstream.end()))
+            }
+            false if upstream.end() < pos && pos < downstream.start() => {
+                Some((pos - upstream.end(), downstream.start() - pos))
+            }
+            _ => None,
+        };
+        if let Some((to_upstream, to_downstream)) = distances {
+            // the closest exonic base, the last base of the upstream or the first of the downstream exon
+            let (boundary, sign, distance) = match (to_upstream <= to_downstream, minus) {
+                (true, false) => (upstream.end(), '+', to_upstream),
+                (true, true) => (upstream.start(), '+', to_upstream),
+                (false, false) => (downstream.start(), '-', to_downstream),
+                (false, true) => (downstream.end(), '-', to_downstream),
+            };
+            let (_, hgvs) = exonic(coordinates, boundary)?;
+            return Some((idx + 1, format!("{}{}{}", hgvs, sign, distance)));
+        }
+    }
+    None
+}
+
+/// Returns the annotation of the position in the transcript
+///
+/// Returns `None` if the position is outside of the transcript.
+pub fn annotate_position(tx: &Transcript, pos: u32) -> Option<Annotation<'_>> {
+    if pos < tx.tx_start() || pos > tx.tx_end() {
+        return None;
+    }
+    let coordinates = TranscriptCoordinates::new(tx);
+    if let Some((exon, cdna)) = coordinates.exon_at(pos) {
+        let (feature, hgvs) = exonic(&coordinates, pos)?;
+        return Some(Annotation {
+            transcript: tx,
+            feature,
+            exon: Some(exon),
+            intron: None,
+            cdna: Some(cdna),
+            hgvs,
+        });
+    }
+    let minus = matches!(tx.strand(), Strand::Minus);
+    let (intron, hgvs) = intronic(&coordinates, minus, pos)?;
+    Some(Annotation {
+        transcript: tx,
+        feature: Feature::Intron,
+        exon: None,
+        intron: Some(intron),
+        cdna: None,
+        hgvs,
+    })
+}
+
+/// Finds all transcripts that overlap a genomic position
+pub struct Annotator<'a> {
+    index: PositionIndex<'a>,
+}
+
+impl<'a> Annotator<'a> {
+    pub fn new(transcripts: &'a [Transcript]) -> Self {
+        Self {
+            index: PositionIndex::new(transcripts),
+        }
+    }
+
+    /// Returns the annotations of all transcripts that overlap the position
+    pub fn annotate(&self, chrom: &str, pos: u32) -> Vec<Annotation<'a>> {
+        self.index
+            .overlapping(chrom, pos, pos)
+            .into_iter()
+            .filter_map(|tx| annotate_position(tx, pos))
+            .collect()
+    }
+
+    /// Returns the transcript that is closest to the position
+    pub fn nearest(&self, chrom: &str, pos: u32) -> Option<&'a Transcript> {
+        self.index.nearest(chrom, pos)
+    }
+}
+
+fn opt<T: std::fmt::Display>(value: Option<T>) -> String {
+    value.map_or_else(|| ".".to_string(), |value| value.to_string())
+}
+
+/// Annotates all variants of a VCF file
+///
+/// Every variant is annotated at its `POS`. Variants that don't overlap
+/// any transcript are written with `.` in all transcript columns, or with the
+/// gene and name of the nearest transcript if `nearest` is set.
+pub fn annotate_vcf<R: BufRead, W: Write>(
+    annotator: &Annotator,
+    vcf: R,
+    mut out: W,
+    nearest: bool,
+) -> Result<(), AtgError> {
+    writeln!(
+        out,
+        "Chrom\tPos\tRef\tAlt\tGene\tTranscript\tFeature\tExon\tIntron\tcDNA\tHGVS"
+    )?;
+    for (line_no, line) in vcf.lines().enumerate() {
+        let line = line?;
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        let cols: Vec<&str> = line.split('\t').collect();
+        let (chrom, pos, reference, alt) = match cols.as_slice() {
+            [chrom, pos, _, reference, alt, ..] => match pos.parse::<u32>() {
+                Ok(pos) => (*chrom, pos, *reference, *alt),
+                Err(_) => {
+                    return Err(AtgError::new(format!(
+                        "invalid position in line {} of the VCF file",
+                        line_no + 1
+                    )))
+                }
+            },
+            _ => {
+                return Err(AtgError::new(format!(
+                    "invalid VCF record in line {}",
+                    line_no + 1
+                )))
+            }
+        };
+
+        let annotations = annotator.annotate(chrom, pos);
+        if annotations.is_empty() {
+            let closest = match nearest {
+                true => annotator.nearest(chrom, pos),
+                false => None,
+            };
+            let (gene, transcript) = closest.map_or((".", "."), |tx| (tx.gene(), tx.name()));
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}\t{}\t{}\tintergenic\t.\t.\t.\t.",
+                chrom, pos, reference, alt, gene, transcript
+            )?;
+        }
+        for annotation in annotations {
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                chrom,
+                pos,
+                reference,
+                alt,
+                annotation.transcript.gene(),
+                annotation.transcript.name(),
+                annotation.feature,
+                opt(annotation.exon),
+                opt(annotation.intron),
+                opt(annotation.cdna),
+                annotation.hgvs
+            )?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Reads the transcripts and annotates all variants of the VCF file
+pub fn run_annotate(args: &AnnotateArgs) -> Result<(), AtgError> {
+    let transcripts = reader_for(&args.from, &args.input)?.transcripts()?;
+    debug!(
+        target: "atg::annotate",
+        "Annotating {} with {} transcripts",
+        args.vcf,
+        transcripts.len()
+    );
+    let annotator = Annotator::new(transcripts.as_vec());
+    let vcf = BufReader::new(open_input(&args.vcf)?);
+    let out = create_output(&args.output, None)?;
+    annotate_vcf(&annotator, vcf, out, args.nearest)
+}
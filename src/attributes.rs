@@ -0,0 +1,265 @@
+//! Preserve additional GTF attributes of transcripts, e.g. `gene_type` or `tag`
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+
+use atglib::models::Transcript;
+use atglib::utils::errors::AtgError;
+
+use crate::biotype::{biotype_of, BIOTYPE_KEYS};
+use crate::gff3::escape;
+use crate::gtf_attributes::{self, tokens, unescape};
+use crate::line_filter::{FilterLines, LineFilter};
+use crate::registry::TranscriptRegistry;
+
+/// Attributes that atg writes itself, they are never preserved
+const WRITTEN_KEYS: &[&str] = &[
+    "gene_id",
+    "transcript_id",
+    "gene_name",
+    "exon_number",
+    "exon_id",
+];
+
+/// Key-value pairs, keys can occur multiple times (e.g. `tag`)
+pub type Attributes = Vec<(String, String)>;
+
+static ATTRIBUTES: TranscriptRegistry<Attributes> = TranscriptRegistry::new();
+
+/// Registers the attributes of a transcript, replacing previous ones
+pub fn register_attributes(chrom: &str, transcript: &str, attributes: Attributes) {
+    ATTRIBUTES.insert(chrom, transcript, attributes);
+}
+
+/// Returns the preserved attributes of a transcript
+pub fn attributes_of(chrom: &str, transcript: &str) -> Option<Attributes> {
+    ATTRIBUTES.get(chrom, transcript)
+}
+
+/// Parses the attribute column of a GTF line
+fn parse_gtf_attributes(column: &str) -> Attributes {
+    tokens(column)
+        .map(|(key, value)| (key.to_string(), unescape(value).into_owned()))
+        .collect()
+}
+
+/// Returns the attributes that are present in every GTF record of a transcript
+pub fn shared_attributes(records: &str) -> Attributes {
+    let mut lines = records
+        .lines()
+        .filter_map(|line| line.split('\t').nth(8))
+        .map(parse_gtf_attributes);
+    let mut shared = match lines.next() {
+        Some(first) => first,
+        None => return vec![],
+    };
+    shared.retain(|(key, _)| !WRITTEN_KEYS.contains(&key.as_str()));
+    for attributes in lines {
+        shared.retain(|attribute| attributes.contains(attribute));
+    }
+    shared
+}
+
+/// Returns the gene-level attributes (`gene_*`, e.g. `gene_type`) of a gene
+///
+/// Only attributes that are identical in all transcripts of the gene are returned.
+pub fn gene_attributes<'a, I: IntoIterator<Item = &'a Transcript>>(transcripts: I) -> Attributes {
+    let mut shared: Option<Attributes> = None;
+    for tx in transcripts {
+        let attributes = attributes_of(tx.chrom(), tx.name()).unwrap_or_default();
+        shared = Some(match shared {
+            None => attributes
+                .into_iter()
+                .filter(|(key, _)| key.starts_with("gene_"))
+                .collect(),
+            Some(mut shared) => {
+                shared.retain(|attribute| attributes.contains(attribute));
+                shared
+            }
+        });
+    }
+    shared.unwrap_or_default()
+}
+
+/// Loads preserved attributes from a tab-separated file
+///
+/// Every line contains the transcript name, the key and the value of one attribute.
+/// The file has no chromosomes, so the attributes are only used for unique
+/// transcript names.
+pub fn load_attributes(filename: &str) -> Result<(), AtgError> {
+    let reader = BufReader::new(File::open(filename)?);
+    let mut attributes: HashMap<String, Attributes> = HashMap::new();
+    for (idx, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let cols: Vec<&str> = line.splitn(3, '\t').collect();
+        match cols.as_slice() {
+            [transcript, key, value] => attributes
+                .entry(transcript.to_string())
+                .or_default()
+                .push((key.to_string(), value.to_string())),
+            _ => {
+                return Err(AtgError::new(format!(
+                    "invalid attribute in line {} of {}",
+                    idx + 1,
+                    filename
+                )))
+            }
+        }
+    }
+    debug!(
+        target: "atg::read",
+        "Loaded attributes of {} transcripts from {}",
+        attributes.len(),
+        filename
+    );
+    for (transcript, attributes) in attributes {
+        register_attributes("", &transcript, attributes);
+    }
+    Ok(())
+}
+
+/// Saves all preserved attributes to a tab-separated file
+pub fn save_attributes(filename: &str) -> Result<(), AtgError> {
+    let mut out = BufWriter::new(File::create(filename)?);
+    for (transcript, attributes) in ATTRIBUTES.all() {
+        for (key, value) in attributes {
+            writeln!(out, "{}\t{}\t{}", transcript, key, value)?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// The layout of the attribute column
+#[derive(Clone, Copy, Debug)]
+pub enum Layout {
+    /// `key "value";`, added to every record of the transcript
+    Gtf,
+    /// `key=value`, added to the transcript feature only
+    Gff3,
+}
+
+/// Formats attributes to append them to the attribute column
+pub fn to_column(attributes: Attributes, layout: Layout) -> String {
+    let mut column = String::new();
+    match layout {
+        Layout::Gtf => {
+            for (key, value) in attributes {
+                column.push_str(&format!(" {} \"{}\";", key, gtf_attributes::escape(&value)));
+            }
+        }
+        Layout::Gff3 => {
+            // GFF3 has one entry per key, with comma-separated values
+            let mut keys: Vec<(String, Vec<String>)> = vec![];
+            for (key, value) in attributes {
+                match keys.iter_mut().find(|(existing, _)| *existing == key) {
+                    Some((_, values)) => values.push(escape(&value)),
+                    None => keys.push((key, vec![escape(&value)])),
+                }
+            }
+            for (key, values) in keys {
+                column.push_str(&format!(";{}={}", escape(&key), values.join(",")));
+            }
+        }
+    }
+    column
+}
+
+/// Adds the biotype and the preserved attributes to GTF or GFF3 lines
+///
+/// The transcript of a line is identified by its `transcript_id` attribute.
+/// The biotype is added as `transcript_type`, unless the preserved attributes
+/// already contain it. Attributes that are already present in the line are
+/// not added again.
+pub struct AddAttributes {
+    layout: Layout,
+    preserve: bool,
+}
+
+impl AddAttributes {
+    /// Creates a new writer, `preserve` adds the preserved attributes
+    pub fn new<W: Write>(inner: W, layout: Layout, preserve: bool) -> FilterLines<W, Self> {
+        FilterLines::new(inner, Self { layout, preserve })
+    }
+
+    /// Returns the additional attributes of the line
+    fn additional(&self, line: &str) -> String {
+        let cols: Vec<&str> = line.trim_end().split('\t').collect();
+        let (chrom, column) = match cols.as_slice() {
+            [chrom, _, _, _, _, _, _, _, column, ..] => (*chrom, *column),
+            _ => return String::new(),
+        };
+        let transcript = match self.layout {
+            Layout::Gtf => parse_gtf_attributes(column)
+                .into_iter()
+                .find(|(key, _)| key == "transcript_id")
+                .map(|(_, value)| value),
+            Layout::Gff3 => column
+                .split(';')
+                .find_map(|attr| attr.strip_prefix("transcript_id="))
+                .map(|value| value.to_string()),
+        };
+        let transcript = match transcript {
+            Some(transcript) => transcript,
+            None => return String::new(),
+        };
+        let mut attributes = match self.preserve {
+            true => attributes_of(chrom, &transcript).unwrap_or_default(),
+            false => vec![],
+        };
+        if !attributes
+            .iter()
+            .any(|(key, _)| BIOTYPE_KEYS.contains(&key.as_str()))
+        {
+            if let Some(biotype) = biotype_of(chrom, &transcript) {
+                attributes.push(("transcript_type".to_string(), biotype));
+            }
+        }
+        if attributes.is_empty() {
+            return String::new();
+        }
+
+        let existing: Vec<String> = match self.layout {
+            Layout::Gtf => parse_gtf_attributes(column)
+                .into_iter()
+                .map(|(key, _)| key)
+                .collect(),
+            Layout::Gff3 => column
+                .split(';')
+                .filter_map(|attr| attr.split_once('='))
+                .map(|(key, _)| key.to_string())
+                .collect(),
+        };
+
+        let additional: Attributes = match self.layout {
+            Layout::Gtf => attributes
+                .into_iter()
+                .filter(|(key, _)| !existing.contains(key))
+                .collect(),
+            Layout::Gff3 => attributes
+                .into_iter()
+                .filter(|(key, _)| !existing.contains(&escape(key)))
+                .collect(),
+        };
+        to_column(additional, self.layout)
+    }
+}
+
+impl LineFilter for AddAttributes {
+    /// Writes the line, with the additional attributes
+    fn write_line(&mut self, line: &[u8], out: &mut dyn Write) -> std::io::Result<()> {
+        let text = String::from_utf8_lossy(line);
+        let additional = self.additional(&text);
+        if additional.is_empty() {
+            return out.write_all(line);
+        }
+        let content = text.trim_end_matches(['\n', '\r']);
+        out.write_all(content.as_bytes())?;
+        out.write_all(additional.as_bytes())?;
+        out.write_all(&text.as_bytes()[content.len()..])
+    }
+}
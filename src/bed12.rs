@@ -0,0 +1,172 @@
+//! Reader for BED files with one transcript per line
+
+use std::io::{BufRead, BufReader, Read};
+
+use atglib::models::{
+    CdsStat, Exon, Frame, Strand, Transcript, TranscriptBuilder, TranscriptRead, Transcripts,
+};
+use atglib::utils::errors::ReadWriteError;
+
+use crate::compression::open_input;
+use crate::utils::set_frames;
+
+/// Parses BED data and returns `Transcripts`
+pub struct Reader<R> {
+    inner: BufReader<R>,
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            inner: BufReader::new(reader),
+        }
+    }
+}
+
+impl Reader<Box<dyn Read>> {
+    pub fn from_file(filename: &str) -> Result<Self, ReadWriteError> {
+        Ok(Self::new(open_input(filename)?))
+    }
+}
+
+/// Parses a comma-separated list of numbers, e.g. `blockSizes`
+pub fn parse_list(column: &str) -> Option<Vec<u32>> {
+    column
+        .split(',')
+        .filter(|value| !value.is_empty())
+        .map(|value| value.parse::<u32>().ok())
+        .collect()
+}
+
+/// Returns the 1-based, inclusive exon coordinates of a BED line
+///
+/// `cols` are the `blockCount`, `blockSizes` and `blockStarts` columns. Without
+/// them, the whole interval is one exon.
+fn parse_blocks(start: u32, end: u32, cols: &[&str]) -> Option<Vec<(u32, u32)>> {
+    // blockCount, blockSizes and blockStarts
+    let (sizes, starts) = match cols {
+        [_, sizes, starts, ..] => (parse_list(sizes)?, parse_list(starts)?),
+        _ => return Some(vec![(start + 1, end)]),
+    };
+    if sizes.len() != starts.len() || sizes.is_empty() {
+        return None;
+    }
+    sizes
+        .iter()
+        .zip(starts)
+        .map(|(size, offset)| {
+            let block_start = start.checked_add(offset)?;
+            let block_end = block_start.checked_add(*size)?;
+            match *size > 0 && block_end <= end {
+                true => Some((block_start + 1, block_end)),
+                false => None,
+            }
+        })
+        .collect()
+}
+
+/// Builds the exons and assigns the CDS and frame of all coding exons
+pub fn build_exons(blocks: &[(u32, u32)], cds: Option<(u32, u32)>, strand: &Strand) -> Vec<Exon> {
+    let mut exons: Vec<Exon> = blocks
+        .iter()
+        .map(|(start, end)| {
+            let overlap = cds
+                .map(|(cds_start, cds_end)| ((*start).max(cds_start), (*end).min(cds_end)))
+                .filter(|(cds_start, cds_end)| cds_start <= cds_end);
+            match overlap {
+                Some((cds_start, cds_end)) => {
+                    Exon::new(*start, *end, Some(cds_start), Some(cds_end), Frame::None)
+                }
+                None => Exon::new(*start, *end, None, None, Frame::None),
+            }
+        })
+        .collect();
+    exons.sort_by_key(|exon| exon.start());
+    set_frames(&mut exons, strand);
+    exons
+}
+
+fn parse_line(line: &str) -> Option<Transcript> {
+    let cols: Vec<&str> = line.split('\t').collect();
+    if cols.len() < 3 {
+        return None;
+    }
+    let start = cols[1].parse::<u32>().ok()?;
+    let end = cols[2].parse::<u32>().ok()?;
+    if start >= end {
+        return None;
+    }
+    let name = cols.get(3).copied().unwrap_or(".");
+
+    // the 11-column format of `--to bed` has the strand in the 5th column
+    let (score, rest): (Option<f32>, &[&str]) = match cols.len() {
+        11 if matches!(cols[4], "+" | "-" | ".") => (None, &cols[4..]),
+        len if len > 4 => (cols[4].parse::<f32>().ok(), &cols[5..]),
+        _ => (None, &[]),
+    };
+    let strand = match rest.first() {
+        Some(&"+") => Strand::Plus,
+        Some(&"-") => Strand::Minus,
+        _ => Strand::Unknown,
+    };
+    // thickStart, thickEnd
+    let cds = match rest {
+        [_, thick_start, thick_end, ..] => {
+            let thick_start = thick_start.parse::<u32>().ok()?;
+            let thick_end = thick_end.parse::<u32>().ok()?;
+            match thick_start < thick_end {
+                true => Some((thick_start + 1, thick_end)),
+                false => None,
+            }
+        }
+        _ => None,
+    };
+    // itemRgb is followed by the block columns
+    let blocks = parse_blocks(start, end, rest.get(4..).unwrap_or(&[]))?;
+
+    let (gene, tx_name) = name.split_once(':').unwrap_or((name, name));
+    let cds_stat = match cds {
+        Some(_) => CdsStat::Complete,
+        None => CdsStat::None,
+    };
+    let mut tx = TranscriptBuilder::new()
+        .name(tx_name)
+        .chrom(cols[0])
+        .gene(gene)
+        .strand(strand)
+        .cds_start_codon_stat(cds_stat)
+        .and_then(|builder| builder.cds_stop_codon_stat(cds_stat))
+        .map(|builder| builder.score(score))
+        .and_then(|builder| builder.build())
+        .ok()?;
+    for exon in build_exons(&blocks, cds, &strand) {
+        tx.push_exon(exon);
+    }
+    Some(tx)
+}
+
+impl<R: Read> TranscriptRead for Reader<R> {
+    fn transcripts(&mut self) -> Result<Transcripts, ReadWriteError> {
+        let mut transcripts = Transcripts::new();
+        for (line_no, line) in (&mut self.inner).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty()
+                || line.starts_with('#')
+                || line.starts_with("track")
+                || line.starts_with("browser")
+            {
+                continue;
+            }
+            match parse_line(&line) {
+                Some(tx) => transcripts.push(tx),
+                None => {
+                    return Err(ReadWriteError::new(format!(
+                        "invalid BED record in line {}",
+                        line_no + 1
+                    )))
+                }
+            }
+        }
+        Ok(transcripts)
+    }
+}
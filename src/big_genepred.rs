@@ -0,0 +1,110 @@
+//! UCSC bigGenePred output, the text input of `bedToBigBed`
+
+use std::io::{BufWriter, Write};
+
+use atglib::models::{Frame, Strand, Transcript, TranscriptWrite};
+
+use crate::attributes::attributes_of;
+use crate::utils::{exon_len, sorted_exons};
+
+/// Attributes that contain the transcript type, in order of preference
+const TYPE_KEYS: &[&str] = &["transcript_type", "transcript_biotype"];
+
+/// Attributes that contain the gene type, in order of preference
+const GENE_TYPE_KEYS: &[&str] = &["gene_type", "gene_biotype"];
+
+/// Returns the GenePred frame of an exon: the number of CDS bases before it, modulo 3
+fn genepred_frame(frame: &Frame) -> i8 {
+    match frame {
+        Frame::Zero => 0,
+        Frame::One => 2,
+        Frame::Two => 1,
+        Frame::None => -1,
+    }
+}
+
+/// Returns the value of the first preserved attribute of `keys`, or `none`
+fn attribute(transcript: &Transcript, keys: &[&str]) -> String {
+    let attributes = attributes_of(transcript.chrom(), transcript.name()).unwrap_or_default();
+    keys.iter()
+        .find_map(|key| attributes.iter().find(|(k, _)| k == key))
+        .map_or_else(|| "none".to_string(), |(_, value)| value.clone())
+}
+
+/// Writes one bigGenePred line per transcript
+pub struct Writer<W: Write> {
+    inner: BufWriter<W>,
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            inner: BufWriter::new(writer),
+        }
+    }
+}
+
+impl<W: Write> TranscriptWrite for Writer<W> {
+    fn writeln_single_transcript(&mut self, transcript: &Transcript) -> Result<(), std::io::Error> {
+        self.write_single_transcript(transcript)
+    }
+
+    fn write_single_transcript(&mut self, tx: &Transcript) -> Result<(), std::io::Error> {
+        let chrom_start = tx.tx_start().saturating_sub(1);
+        // non-coding transcripts have an empty CDS at the transcript end, like in GenePred
+        let (thick_start, thick_end) = match (tx.cds_start(), tx.cds_end()) {
+            (Some(start), Some(end)) => (start.saturating_sub(1), end),
+            _ => (tx.tx_end(), tx.tx_end()),
+        };
+        let mut sizes = String::new();
+        let mut starts = String::new();
+        let mut frames = String::new();
+        let exons = sorted_exons(tx);
+        for exon in &exons {
+            sizes.push_str(&format!("{},", exon_len(exon)));
+            starts.push_str(&format!(
+                "{},",
+                exon.start().saturating_sub(1) - chrom_start
+            ));
+            frames.push_str(&format!("{},", genepred_frame(exon.frame_offset())));
+        }
+        // the CDS stats are in genomic order, not in the order of transcription
+        let (cds_start_stat, cds_end_stat) = match tx.strand() {
+            Strand::Minus => (tx.cds_stop_codon_stat(), tx.cds_start_codon_stat()),
+            _ => (tx.cds_start_codon_stat(), tx.cds_stop_codon_stat()),
+        };
+        let score = tx
+            .score()
+            .map_or(0, |score| score.clamp(0.0, 1000.0).round() as u16);
+        writeln!(
+            self.inner,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t0\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            tx.chrom(),
+            chrom_start,
+            tx.tx_end(),
+            tx.name(),
+            score,
+            tx.strand(),
+            thick_start,
+            thick_end,
+            exons.len(),
+            sizes,
+            starts,
+            tx.gene(),
+            cds_start_stat,
+            cds_end_stat,
+            frames,
+            attribute(tx, TYPE_KEYS),
+            tx.gene(),
+            tx.gene(),
+            attribute(tx, GENE_TYPE_KEYS),
+        )
+    }
+
+    fn write_transcript_vec(&mut self, transcripts: &[Transcript]) -> Result<(), std::io::Error> {
+        for tx in transcripts {
+            self.writeln_single_transcript(tx)?;
+        }
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,261 @@
+//! Versioned container for the ATG-specific binary format
+
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom, Write};
+
+use bincode::{deserialize, deserialize_from, serialize, serialize_into};
+
+use atglib::models::{Transcript, TranscriptRead, Transcripts};
+use atglib::utils::errors::{AtgError, ReadWriteError};
+
+use crate::compression::open_input;
+use crate::warnings::warning;
+
+const MAGIC: &[u8; 6] = b"ATGBIN";
+const INDEX_MAGIC: &[u8; 6] = b"ATGIDX";
+
+/// The format version that is written
+pub const FORMAT_VERSION: u16 = 2;
+
+/// Length of the header, the offset of the first record
+const HEADER_LEN: u64 = 8;
+
+/// Length of the footer: the offset of the end marker and `INDEX_MAGIC`
+const FOOTER_LEN: i64 = 14;
+
+/// Gene, transcript and the byte offset of the record
+type IndexEntry = (String, String, u64);
+
+/// Writes transcripts as individual records, followed by the index
+///
+/// The header (`ATGBIN` and the format version) is followed by one record per
+/// transcript: its length (`u32`) and the bincode-serialized transcript. A
+/// record of length 0 ends the records. It is followed by the index and a footer
+/// with the offset of the end marker and `ATGIDX`.
+pub struct BinWriter<W: Write> {
+    inner: W,
+    offset: u64,
+    index: Vec<IndexEntry>,
+}
+
+impl<W: Write> BinWriter<W> {
+    /// Creates the writer and writes the header
+    pub fn new(mut writer: W) -> Result<Self, AtgError> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        Ok(Self {
+            inner: writer,
+            offset: HEADER_LEN,
+            index: vec![],
+        })
+    }
+
+    /// Appends one transcript record
+    pub fn write_transcript(&mut self, tx: &Transcript) -> Result<(), AtgError> {
+        let data = serialize(tx).map_err(AtgError::new)?;
+        let len = u32::try_from(data.len())
+            .map_err(|_| AtgError::new(format!("transcript {} is too large", tx.name())))?;
+        self.inner.write_all(&len.to_le_bytes())?;
+        self.inner.write_all(&data)?;
+        self.index
+            .push((tx.gene().to_string(), tx.name().to_string(), self.offset));
+        self.offset += 4 + u64::from(len);
+        Ok(())
+    }
+
+    /// Writes the end marker, the index and the footer
+    pub fn finish(mut self) -> Result<(), AtgError> {
+        self.inner.write_all(&0u32.to_le_bytes())?;
+        serialize_into(&mut self.inner, &self.index).map_err(AtgError::new)?;
+        self.inner.write_all(&self.offset.to_le_bytes())?;
+        self.inner.write_all(INDEX_MAGIC)?;
+        self.inner.flush()?;
+        Ok(())
+    }
+}
+
+/// Writes all transcripts with the current format version
+pub fn write_bin<W: Write>(transcripts: &Transcripts, out: W) -> Result<(), AtgError> {
+    let mut writer = BinWriter::new(out)?;
+    for tx in transcripts.as_vec() {
+        writer.write_transcript(tx)?;
+    }
+    writer.finish()
+}
+
+/// Fills `buf` as far as possible and returns the number of bytes read
+fn read_up_to<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize, ReadWriteError> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..])? {
+            0 => break,
+            n => len += n,
+        }
+    }
+    Ok(len)
+}
+
+fn read_u16<R: Read>(reader: &mut R) -> Result<u16, ReadWriteError> {
+    let mut buf = [0; 2];
+    reader.read_exact(&mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64, ReadWriteError> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+/// Reads one record, returns `None` at the end of the records
+fn read_record<R: Read>(reader: &mut R) -> Result<Option<Transcript>, ReadWriteError> {
+    let mut len = [0; 4];
+    match read_up_to(reader, &mut len)? {
+        0 => return Ok(None),
+        4 => {}
+        _ => return Err(ReadWriteError::new("incomplete record in the binary file")),
+    }
+    let len = u32::from_le_bytes(len) as usize;
+    if len == 0 {
+        return Ok(None);
+    }
+    let mut data = vec![0; len];
+    reader.read_exact(&mut data)?;
+    deserialize(&data)
+        .map(Some)
+        .map_err(|err| ReadWriteError::new(format!("invalid record in the binary file ({})", err)))
+}
+
+/// Reads transcripts from the ATG-specific binary format
+pub struct BinReader<R: Read> {
+    inner: R,
+}
+
+impl<R: Read> BinReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self { inner: reader }
+    }
+
+    /// Reads a single bincode dump of all transcripts, format version 0 and 1
+    fn read_dump<D: Read>(reader: D, version: u16) -> Result<Transcripts, ReadWriteError> {
+        deserialize_from(reader).map_err(|err| {
+            ReadWriteError::new(format!(
+                "invalid binary file of format version {} ({})",
+                version, err
+            ))
+        })
+    }
+}
+
+impl BinReader<Box<dyn Read>> {
+    pub fn from_file(filename: &str) -> Result<Self, ReadWriteError> {
+        Ok(Self::new(open_input(filename)?))
+    }
+}
+
+impl<R: Read> TranscriptRead for BinReader<R> {
+    fn transcripts(&mut self) -> Result<Transcripts, ReadWriteError> {
+        let mut magic = [0; 6];
+        let len = read_up_to(&mut self.inner, &mut magic)?;
+        if &magic[..len] != MAGIC {
+            debug!(target: "atg::read", "Reading binary file without header (format version 0)");
+            // the bytes are part of the data
+            let reader = Cursor::new(&magic[..len]).chain(&mut self.inner);
+            return Self::read_dump(reader, 0);
+        }
+
+        match read_u16(&mut self.inner)? {
+            1 => {
+                let count = read_u64(&mut self.inner)?;
+                let transcripts = Self::read_dump(&mut self.inner, 1)?;
+                if transcripts.len() as u64 != count {
+                    return Err(ReadWriteError::new(format!(
+                        "the binary file should contain {} transcripts, but contains {}",
+                        count,
+                        transcripts.len()
+                    )));
+                }
+                Ok(transcripts)
+            }
+            FORMAT_VERSION => {
+                let mut transcripts = Transcripts::new();
+                while let Some(tx) = read_record(&mut self.inner)? {
+                    transcripts.push(tx);
+                }
+                Ok(transcripts)
+            }
+            version => Err(ReadWriteError::new(format!(
+                "the binary file has format version {}, but this version of atg reads only up to version {}",
+                version, FORMAT_VERSION
+            ))),
+        }
+    }
+}
+
+/// Reads only the transcripts of selected genes, using the index of the binary format
+pub struct IndexedBinReader<R: Read + Seek> {
+    inner: R,
+    index: Vec<IndexEntry>,
+}
+
+impl<R: Read + Seek> IndexedBinReader<R> {
+    /// Reads the header and the index
+    pub fn new(mut reader: R) -> Result<Self, ReadWriteError> {
+        let mut magic = [0; 6];
+        read_up_to(&mut reader, &mut magic)?;
+        if &magic != MAGIC || read_u16(&mut reader)? != FORMAT_VERSION {
+            return Err(ReadWriteError::new(format!(
+                "reading single genes requires a binary file of format version {}, convert the file with `--from bin --to bin`",
+                FORMAT_VERSION
+            )));
+        }
+
+        reader.seek(SeekFrom::End(-FOOTER_LEN))?;
+        let end_marker = read_u64(&mut reader)?;
+        read_up_to(&mut reader, &mut magic)?;
+        if &magic != INDEX_MAGIC {
+            return Err(ReadWriteError::new("the binary file has no index"));
+        }
+        reader.seek(SeekFrom::Start(end_marker + 4))?;
+        let index = deserialize_from(&mut reader).map_err(|err| {
+            ReadWriteError::new(format!("invalid index of the binary file ({})", err))
+        })?;
+        Ok(Self {
+            inner: reader,
+            index,
+        })
+    }
+
+    /// Returns all transcripts of the genes
+    pub fn genes(&mut self, genes: &[String]) -> Result<Transcripts, ReadWriteError> {
+        let genes: HashSet<&str> = genes.iter().map(String::as_str).collect();
+        let mut found: HashSet<&str> = HashSet::new();
+        let mut transcripts = Transcripts::new();
+        for (gene, name, offset) in &self.index {
+            if let Some(gene) = genes.get(gene.as_str()) {
+                found.insert(gene);
+                self.inner.seek(SeekFrom::Start(*offset))?;
+                let tx = read_record(&mut self.inner)?.ok_or_else(|| {
+                    ReadWriteError::new(format!("the index entry of {} is invalid", name))
+                })?;
+                transcripts.push(tx);
+            }
+        }
+        for gene in genes.difference(&found) {
+            warning(
+                "atg::read",
+                "missing-gene",
+                &format!("Gene {} is not in the binary file", gene),
+            );
+        }
+        Ok(transcripts)
+    }
+}
+
+impl IndexedBinReader<BufReader<File>> {
+    pub fn from_file(filename: &str) -> Result<Self, ReadWriteError> {
+        Self::new(BufReader::new(File::open(filename)?))
+    }
+}
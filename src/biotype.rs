@@ -0,0 +1,28 @@
+//! The biotype of transcripts, e.g. `protein_coding` or `lncRNA`
+
+use crate::gtf_attributes::{attribute, unescape};
+use crate::registry::TranscriptRegistry;
+
+/// Attributes that contain the biotype of a transcript, in order of precedence
+pub const BIOTYPE_KEYS: &[&str] = &["transcript_type", "transcript_biotype", "biotype"];
+
+static BIOTYPES: TranscriptRegistry<String> = TranscriptRegistry::new();
+
+/// Registers the biotype of a transcript, replacing a previous one
+pub fn register_biotype(chrom: &str, transcript: &str, biotype: &str) {
+    BIOTYPES.insert(chrom, transcript, biotype.to_string());
+}
+
+/// Returns the biotype of a transcript
+pub fn biotype_of(chrom: &str, transcript: &str) -> Option<String> {
+    BIOTYPES.get(chrom, transcript)
+}
+
+/// Returns the biotype of the GTF records of a transcript
+pub fn gtf_biotype(records: &str) -> Option<String> {
+    records
+        .lines()
+        .filter_map(|line| line.split('\t').nth(8))
+        .find_map(|column| BIOTYPE_KEYS.iter().find_map(|key| attribute(column, key)))
+        .map(|biotype| unescape(biotype).into_owned())
+}
@@ -0,0 +1,127 @@
+//! LRU block cache for the reference genome
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Error, ErrorKind, Read, Seek, SeekFrom};
+use std::sync::OnceLock;
+
+/// Number of bytes per block
+const BLOCK_SIZE: u64 = 0x10000;
+
+/// Maximum number of cached blocks per reader
+static CACHE_BLOCKS: OnceLock<usize> = OnceLock::new();
+
+/// Sets the size of the cache of every reader, in MB
+///
+/// A size of `0` disables the cache. The size can only be set once.
+pub fn set_cache_size(megabytes: usize) {
+    let blocks = megabytes.saturating_mul(1 << 20) / BLOCK_SIZE as usize;
+    if CACHE_BLOCKS.set(blocks).is_err() {
+        warn!(target: "atg::read", "The reference cache size is already set");
+    }
+}
+
+/// Returns the maximum number of cached blocks per reader, `0` if caching is disabled
+pub fn cache_blocks() -> usize {
+    CACHE_BLOCKS.get().copied().unwrap_or(0)
+}
+
+struct Block {
+    data: Vec<u8>,
+    last_used: u64,
+}
+
+/// Caches the blocks of the inner reader
+pub struct BlockCache<R> {
+    inner: R,
+    capacity: usize,
+    pos: u64,
+    /// The length of the inner reader, only known after seeking from the end
+    len: Option<u64>,
+    blocks: HashMap<u64, Block>,
+    /// The blocks, sorted by their last use
+    usage: BTreeMap<u64, u64>,
+    tick: u64,
+}
+
+impl<R: Read + Seek> BlockCache<R> {
+    pub fn new(inner: R, capacity: usize) -> Self {
+        Self {
+            inner,
+            capacity: capacity.max(1),
+            pos: 0,
+            len: None,
+            blocks: HashMap::new(),
+            usage: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns the block, reading it from the inner reader if it is not cached
+    fn block(&mut self, idx: u64) -> Result<&[u8], Error> {
+        self.tick += 1;
+        match self.blocks.get_mut(&idx) {
+            Some(block) => {
+                self.usage.remove(&block.last_used);
+                block.last_used = self.tick;
+            }
+            None => {
+                if self.blocks.len() >= self.capacity {
+                    if let Some((_, evicted)) = self.usage.pop_first() {
+                        self.blocks.remove(&evicted);
+                    }
+                }
+                self.inner.seek(SeekFrom::Start(idx * BLOCK_SIZE))?;
+                let mut data = Vec::with_capacity(BLOCK_SIZE as usize);
+                (&mut self.inner).take(BLOCK_SIZE).read_to_end(&mut data)?;
+                self.blocks.insert(
+                    idx,
+                    Block {
+                        data,
+                        last_used: self.tick,
+                    },
+                );
+            }
+        }
+        self.usage.insert(self.tick, idx);
+        Ok(&self.blocks[&idx].data)
+    }
+}
+
+impl<R: Read + Seek> Read for BlockCache<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let offset = (self.pos % BLOCK_SIZE) as usize;
+        let block = self.block(self.pos / BLOCK_SIZE)?;
+        // an incomplete block is the end of the file
+        if offset >= block.len() {
+            return Ok(0);
+        }
+        let len = buf.len().min(block.len() - offset);
+        buf[..len].copy_from_slice(&block[offset..offset + len]);
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+
+impl<R: Read + Seek> Seek for BlockCache<R> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Error> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+            SeekFrom::End(offset) => {
+                let len = match self.len {
+                    Some(len) => len,
+                    None => self.inner.seek(SeekFrom::End(0))?,
+                };
+                self.len = Some(len);
+                len.checked_add_signed(offset)
+            }
+        }
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "invalid seek position"))?;
+        self.pos = pos;
+        Ok(pos)
+    }
+}
@@ -0,0 +1,51 @@
+//! Reconcile the start and stop codon stats with the CDS of a transcript
+
+use atglib::models::{CdsStat, Transcript};
+use atglib::qc::{QcCheck, QcResult};
+use atglib::utils::errors::AtgError;
+
+use crate::utils::cds_len;
+
+fn stat_for(result: QcResult) -> CdsStat {
+    match result {
+        QcResult::OK => CdsStat::Complete,
+        _ => CdsStat::Incomplete,
+    }
+}
+
+/// Marks complete start and stop codon stats as incomplete, if the CDS length is not a multiple of 3
+///
+/// Returns `true` if the stats were changed.
+pub fn mark_incomplete(tx: &mut Transcript) -> Result<bool, AtgError> {
+    if !tx.is_coding() || cds_len(tx).is_multiple_of(3) {
+        return Ok(false);
+    }
+    let mut changed = false;
+    if let CdsStat::Complete = tx.cds_start_codon_stat() {
+        tx.set_cds_start_codon_stat(CdsStat::Incomplete)?;
+        changed = true;
+    }
+    if let CdsStat::Complete = tx.cds_stop_codon_stat() {
+        tx.set_cds_stop_codon_stat(CdsStat::Incomplete)?;
+        changed = true;
+    }
+    Ok(changed)
+}
+
+/// Sets the start and stop codon stats based on the codons in the reference sequence
+///
+/// A stat is `cmpl` if the CDS starts with a start codon (or ends with a stop
+/// codon) according to the QC checks, otherwise `incmpl`.
+///
+/// Returns `true` if the stats were changed.
+pub fn recompute(tx: &mut Transcript, qc: &QcCheck) -> Result<bool, AtgError> {
+    if !tx.is_coding() {
+        return Ok(false);
+    }
+    let start = stat_for(qc.correct_start_codon());
+    let stop = stat_for(qc.correct_stop_codon());
+    let changed = start != tx.cds_start_codon_stat() || stop != tx.cds_stop_codon_stat();
+    tx.set_cds_start_codon_stat(start)?;
+    tx.set_cds_stop_codon_stat(stop)?;
+    Ok(changed)
+}
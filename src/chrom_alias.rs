@@ -0,0 +1,206 @@
+//! Renaming of chromosomes, e.g. from RefSeq accessions (`NC_000001.11`) to `chr1`
+
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader};
+
+use atglib::models::{Transcript, Transcripts};
+use atglib::utils::errors::AtgError;
+
+use crate::cli::ChromStyle;
+use crate::contigs::shift_transcript;
+
+/// UCSC, Ensembl and RefSeq names of the GRCh37 primary assembly
+const GRCH37: &[[&str; 3]] = &[
+    ["chr1", "1", "NC_000001.10"],
+    ["chr2", "2", "NC_000002.11"],
+    ["chr3", "3", "NC_000003.11"],
+    ["chr4", "4", "NC_000004.11"],
+    ["chr5", "5", "NC_000005.9"],
+    ["chr6", "6", "NC_000006.11"],
+    ["chr7", "7", "NC_000007.13"],
+    ["chr8", "8", "NC_000008.10"],
+    ["chr9", "9", "NC_000009.11"],
+    ["chr10", "10", "NC_000010.10"],
+    ["chr11", "11", "NC_000011.9"],
+    ["chr12", "12", "NC_000012.11"],
+    ["chr13", "13", "NC_000013.10"],
+    ["chr14", "14", "NC_000014.8"],
+    ["chr15", "15", "NC_000015.9"],
+    ["chr16", "16", "NC_000016.9"],
+    ["chr17", "17", "NC_000017.10"],
+    ["chr18", "18", "NC_000018.9"],
+    ["chr19", "19", "NC_000019.9"],
+    ["chr20", "20", "NC_000020.10"],
+    ["chr21", "21", "NC_000021.8"],
+    ["chr22", "22", "NC_000022.10"],
+    ["chrX", "X", "NC_000023.10"],
+    ["chrY", "Y", "NC_000024.9"],
+    ["chrM", "MT", "NC_012920.1"],
+];
+
+/// UCSC, Ensembl and RefSeq names of the GRCh38 primary assembly
+const GRCH38: &[[&str; 3]] = &[
+    ["chr1", "1", "NC_000001.11"],
+    ["chr2", "2", "NC_000002.12"],
+    ["chr3", "3", "NC_000003.12"],
+    ["chr4", "4", "NC_000004.12"],
+    ["chr5", "5", "NC_000005.10"],
+    ["chr6", "6", "NC_000006.12"],
+    ["chr7", "7", "NC_000007.14"],
+    ["chr8", "8", "NC_000008.11"],
+    ["chr9", "9", "NC_000009.12"],
+    ["chr10", "10", "NC_000010.11"],
+    ["chr11", "11", "NC_000011.10"],
+    ["chr12", "12", "NC_000012.12"],
+    ["chr13", "13", "NC_000013.11"],
+    ["chr14", "14", "NC_000014.9"],
+    ["chr15", "15", "NC_000015.10"],
+    ["chr16", "16", "NC_000016.10"],
+    ["chr17", "17", "NC_000017.11"],
+    ["chr18", "18", "NC_000018.10"],
+    ["chr19", "19", "NC_000019.10"],
+    ["chr20", "20", "NC_000020.11"],
+    ["chr21", "21", "NC_000021.9"],
+    ["chr22", "22", "NC_000022.11"],
+    ["chrX", "X", "NC_000023.11"],
+    ["chrY", "Y", "NC_000024.10"],
+    ["chrM", "MT", "NC_012920.1"],
+];
+
+/// Returns the built-in alias table of an assembly, if there is one
+pub fn builtin_table(name: &str) -> Option<&'static [[&'static str; 3]]> {
+    match name.to_ascii_lowercase().as_str() {
+        "grch37" | "hg19" => Some(GRCH37),
+        "grch38" | "hg38" => Some(GRCH38),
+        _ => None,
+    }
+}
+
+/// Returns the name of a primary chromosome in UCSC (`chr1`, `chrM`) or Ensembl (`1`, `MT`) style
+///
+/// Only the numbered chromosomes, `X`, `Y` and the mitochondrial chromosome are
+/// renamed, other contigs don't have a common naming scheme. Returns `None` if
+/// the name does not change.
+pub fn styled(chrom: &str, style: ChromStyle) -> Option<String> {
+    let base = chrom.strip_prefix("chr").unwrap_or(chrom);
+    let is_primary = matches!(base, "X" | "Y" | "M" | "MT")
+        || (!base.is_empty() && base.bytes().all(|byte| byte.is_ascii_digit()));
+    if !is_primary {
+        return None;
+    }
+    let name = match (style, base) {
+        (ChromStyle::Keep, _) => return None,
+        (ChromStyle::Ucsc, "MT") => "chrM".to_string(),
+        (ChromStyle::Ucsc, base) => format!("chr{}", base),
+        (ChromStyle::Ensembl, "M") => "MT".to_string(),
+        (ChromStyle::Ensembl, base) => base.to_string(),
+    };
+    match name == chrom {
+        true => None,
+        false => Some(name),
+    }
+}
+
+/// Renames the chromosomes of transcripts to their preferred name
+pub struct ChromAliases {
+    reference: Option<HashSet<String>>,
+    // alias => preferred name
+    names: HashMap<String, String>,
+    style: ChromStyle,
+}
+
+impl ChromAliases {
+    /// Creates an empty mapping
+    ///
+    /// With `reference`, the names of the reference genome are preferred over
+    /// the first name of every group.
+    pub fn new(reference: Option<HashSet<String>>) -> Self {
+        Self {
+            reference,
+            names: HashMap::new(),
+            style: ChromStyle::Keep,
+        }
+    }
+
+    /// Adds or removes the `chr` prefix of primary chromosomes, after applying the aliases
+    pub fn set_style(&mut self, style: ChromStyle) {
+        self.style = style;
+    }
+
+    /// Adds a group of names of the same chromosome
+    pub fn add_group<S: AsRef<str>>(&mut self, group: &[S]) {
+        let preferred = self
+            .reference
+            .as_ref()
+            .and_then(|chroms| group.iter().find(|name| chroms.contains(name.as_ref())))
+            .or_else(|| group.first());
+        let preferred = match preferred {
+            Some(name) => name.as_ref().to_string(),
+            None => return,
+        };
+        for name in group {
+            if name.as_ref() != preferred {
+                self.names
+                    .insert(name.as_ref().to_string(), preferred.clone());
+            }
+        }
+    }
+
+    /// Adds a built-in table (`grch37` or `grch38`) or the groups of a file
+    ///
+    /// Every line of the tab-separated file contains all names of one
+    /// chromosome, starting with the preferred name. Lines starting with `#` are
+    /// ignored, so UCSC `chromAlias.txt` files can be used directly.
+    pub fn add_table(&mut self, table: &str) -> Result<(), AtgError> {
+        if let Some(groups) = builtin_table(table) {
+            for group in groups {
+                self.add_group(group);
+            }
+            return Ok(());
+        }
+        let reader = BufReader::new(std::fs::File::open(table)?);
+        for line in reader.lines() {
+            let line = line?;
+            if line.starts_with('#') {
+                continue;
+            }
+            let group: Vec<&str> = line
+                .split('\t')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .collect();
+            self.add_group(&group);
+        }
+        Ok(())
+    }
+
+    /// Returns the new name of a chromosome, or `None` if it keeps its name
+    fn renamed(&self, chrom: &str) -> Option<String> {
+        let alias = self.names.get(chrom).map_or(chrom, String::as_str);
+        let name = styled(alias, self.style).unwrap_or_else(|| alias.to_string());
+        // renaming must be idempotent, the streaming QC renames some transcripts twice
+        (name != chrom).then_some(name)
+    }
+
+    /// Returns the transcript on the preferred chromosome name
+    pub fn rename(&self, tx: Transcript) -> Result<Transcript, AtgError> {
+        match self.renamed(tx.chrom()) {
+            Some(chrom) => shift_transcript(&tx, &chrom, 0),
+            None => Ok(tx),
+        }
+    }
+
+    /// Renames the chromosomes of all transcripts
+    pub fn rename_all(&self, transcripts: Transcripts) -> Result<Transcripts, AtgError> {
+        let mut renamed = Transcripts::new();
+        let mut count = 0;
+        for tx in transcripts {
+            if self.renamed(tx.chrom()).is_some() {
+                count += 1;
+            }
+            renamed.push(self.rename(tx)?);
+        }
+        info!(target: "atg::read", "Renamed the chromosome of {} transcripts", count);
+        Ok(renamed)
+    }
+}
@@ -0,0 +1,46 @@
+//! Chromosome sizes (`chrom.sizes`) for browser tracks and bigBed conversion
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use atglib::models::Transcript;
+
+use crate::chromosomes::chrom_cmp;
+use crate::warnings::warning;
+
+/// Writes the size of every chromosome as `chrom.sizes`
+///
+/// Without a reference index, the size of a chromosome is the highest end
+/// position of all its transcripts, and chromosomes are sorted naturally.
+/// With a reference index (`fai`), all chromosomes of the reference are written
+/// with their true length, in the order of the index. Chromosomes of the
+/// annotation that are missing from the reference are appended, using the
+/// highest end position.
+pub fn write_chrom_sizes<W: Write>(
+    transcripts: &[Transcript],
+    fai: Option<&[(String, u64)]>,
+    mut out: W,
+) -> Result<(), std::io::Error> {
+    let mut observed: HashMap<&str, u32> = HashMap::new();
+    for tx in transcripts {
+        let end = observed.entry(tx.chrom()).or_default();
+        *end = (*end).max(tx.tx_end());
+    }
+
+    if let Some(fai) = fai {
+        for (chrom, len) in fai {
+            writeln!(out, "{}\t{}", chrom, len)?;
+            observed.remove(chrom.as_str());
+        }
+        for chrom in observed.keys() {
+            warning("atg::write", "missing-chromosome", &format!("Chromosome {} is missing from the reference, using the highest transcript position as size", chrom));
+        }
+    }
+
+    let mut observed: Vec<(&str, u32)> = observed.into_iter().collect();
+    observed.sort_by(|a, b| chrom_cmp(a.0, b.0));
+    for (chrom, end) in observed {
+        writeln!(out, "{}\t{}", chrom, end)?;
+    }
+    out.flush()
+}
@@ -0,0 +1,153 @@
+//! Natural sort order of chromosome names
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::sync::{OnceLock, RwLock};
+
+use atglib::models::Transcript;
+use atglib::utils::errors::AtgError;
+
+use crate::registry::{read_lock, write_lock};
+
+static CUSTOM_ORDER: OnceLock<RwLock<HashMap<String, usize>>> = OnceLock::new();
+
+fn registry() -> &'static RwLock<HashMap<String, usize>> {
+    CUSTOM_ORDER.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+/// Registers chromosome names in the given order
+///
+/// Chromosomes that are already registered keep their position.
+pub fn register_chromosomes<I, S>(names: I)
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    let mut order = write_lock(registry());
+    for name in names {
+        let next = order.len();
+        order.entry(name.into()).or_insert(next);
+    }
+}
+
+/// Registers the chromosome order from a file
+///
+/// The chromosome name is the first (tab-separated) column of every
+/// line, so that a fasta index (`.fai`) can be used directly.
+pub fn register_chromosomes_from_file(filename: &str) -> Result<(), AtgError> {
+    let reader = BufReader::new(std::fs::File::open(filename)?);
+    let mut names = vec![];
+    for line in reader.lines() {
+        let line = line?;
+        if let Some(name) = line.split('\t').next().filter(|name| !name.is_empty()) {
+            names.push(name.to_string());
+        }
+    }
+    debug!(
+        target: "atg::sort",
+        "Registered {} chromosomes from {}",
+        names.len(),
+        filename
+    );
+    register_chromosomes(names);
+    Ok(())
+}
+
+/// The rank of the chromosome name, without the `chr` prefix
+///
+/// Numbered chromosomes come first, followed by the sex chromosomes,
+/// mitochondrial DNA and all other contigs.
+fn rank(name: &str) -> (u8, u64) {
+    if let Ok(number) = name.parse::<u64>() {
+        return (0, number);
+    }
+    match name {
+        "X" | "x" => (1, 0),
+        "Y" | "y" => (2, 0),
+        "M" | "m" | "MT" | "Mt" | "mt" => (3, 0),
+        _ => (4, 0),
+    }
+}
+
+fn strip_prefix(name: &str) -> &str {
+    match name.get(..3) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("chr") => &name[3..],
+        _ => name,
+    }
+}
+
+/// Compares two strings, treating runs of digits as numbers
+///
+/// `contig2 < contig10`
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.as_bytes();
+    let mut b = b.as_bytes();
+    loop {
+        match (a.first(), b.first()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let len_a = a.iter().take_while(|c| c.is_ascii_digit()).count();
+                let len_b = b.iter().take_while(|c| c.is_ascii_digit()).count();
+                let (num_a, rest_a) = a.split_at(len_a);
+                let (num_b, rest_b) = b.split_at(len_b);
+                // compare the numbers without leading zeros by length first,
+                // so that arbitrary long numbers can't overflow
+                let trim = |num: &[u8]| -> usize { num.iter().take_while(|c| **c == b'0').count() };
+                let (num_a, num_b) = (&num_a[trim(num_a)..], &num_b[trim(num_b)..]);
+                let ord = num_a.len().cmp(&num_b.len()).then_with(|| num_a.cmp(num_b));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+                a = rest_a;
+                b = rest_b;
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(y);
+                }
+                a = &a[1..];
+                b = &b[1..];
+            }
+        }
+    }
+}
+
+/// Compares two chromosome names by their natural order
+///
+/// Registered chromosomes (see [`register_chromosomes`]) are sorted
+/// before all others, in the order of their registration.
+pub fn chrom_cmp(a: &str, b: &str) -> Ordering {
+    {
+        let order = read_lock(registry());
+        match (order.get(a), order.get(b)) {
+            (Some(x), Some(y)) => return x.cmp(y),
+            (Some(_), None) => return Ordering::Less,
+            (None, Some(_)) => return Ordering::Greater,
+            (None, None) => {}
+        }
+    }
+
+    let (a_name, b_name) = (strip_prefix(a), strip_prefix(b));
+    rank(a_name)
+        .cmp(&rank(b_name))
+        .then_with(|| natural_cmp(a_name, b_name))
+        .then_with(|| a.cmp(b))
+}
+
+/// Compares two transcripts by chromosome (natural order), start and end position
+///
+/// Transcripts at the same position are ordered by their name.
+pub fn position_cmp(a: &Transcript, b: &Transcript) -> Ordering {
+    chrom_cmp(a.chrom(), b.chrom())
+        .then_with(|| a.tx_start().cmp(&b.tx_start()))
+        .then_with(|| a.tx_end().cmp(&b.tx_end()))
+        .then_with(|| a.name().cmp(b.name()))
+}
+
+/// Sorts transcripts by chromosome (natural order), start and end position
+pub fn sort_transcripts(transcripts: &mut [Transcript]) {
+    transcripts.sort_by(position_cmp);
+}
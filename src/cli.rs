@@ -1,45 +1,380 @@
+use atglib::models::Transcript;
 use atglib::qc::QcCheck;
 use atglib::qc::QcResult;
-use clap::{Parser, ValueEnum};
+use atglib::utils::errors::AtgError;
+use clap::{Parser, Subcommand, ValueEnum};
+
+use crate::chrom_alias::builtin_table;
+use crate::compression::output_compression;
+use crate::reader_wrapper::STDIN;
+use crate::region::Region;
+use crate::structure::check_structure;
 
 /// Convert transcript data from and to different file formats
 ///
 /// More detailed usage instructions on Github: <https://github.com/anergictcell/atg>
 #[derive(Parser, Debug)]
-#[command(author, version, about)]
-pub struct Args {
+#[command(author, version, about, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub args: Option<Args>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Convert the input into an intermediate format and back and compare the result with the original input
+    ///
+    /// This is mainly useful for development, to check that no information is lost in a format
+    Roundtrip(RoundtripArgs),
+
+    /// Annotate the variants of a VCF file with the overlapping transcripts
+    ///
+    /// Reports the affected feature, exon or intron number and the cDNA and HGVS position of every variant
+    Annotate(AnnotateArgs),
+
+    /// Map transcript positions in HGVS notation (e.g. `NM_000546.6:c.215+3`) to the genome
+    ///
+    /// Reports the chromosome, position and strand for every transcript with the given name
+    Locate(LocateArgs),
+
+    /// Compare two annotations, e.g. two Gencode releases, and list all differences
+    ///
+    /// Reports added, removed and changed transcripts and every changed, added or removed exon
+    Diff(DiffArgs),
+
+    /// Check the input file and list all problems, with their line numbers
+    ///
+    /// Exits with an error if the file contains at least one error
+    Validate(ValidateArgs),
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ValidateArgs {
     /// Format of input file
     #[arg(short, long, value_name = "FORMAT")]
     pub from: InputFormat,
 
-    /// Output format
+    /// Path to input file
+    ///
+    /// The file is read twice, so it can't be `/dev/stdin`
+    #[arg(short, long, value_name = "FILE")]
+    pub input: String,
+
+    /// Path to output file
+    #[arg(short, long, default_value = "/dev/stdout", value_name = "FILE")]
+    pub output: String,
+
+    /// Sets the level of verbosity
+    #[arg(short, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct AnnotateArgs {
+    /// Format of input file
     #[arg(short, long, value_name = "FORMAT")]
-    pub to: OutputFormat,
+    pub from: InputFormat,
 
     /// Path to input file
     #[arg(short, long, default_value = "/dev/stdin", value_name = "FILE")]
     pub input: String,
 
+    /// Path to the VCF file with the variants
+    #[arg(long, value_name = "FILE")]
+    pub vcf: String,
+
+    /// Report the gene and transcript that are closest to intergenic variants
+    #[arg(long)]
+    pub nearest: bool,
+
     /// Path to output file
     #[arg(short, long, default_value = "/dev/stdout", value_name = "FILE")]
     pub output: String,
 
-    /// The feature source to indicate in GTF files (optional with `--output gtf`)
+    /// Sets the level of verbosity
+    #[arg(short, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct LocateArgs {
+    /// Format of input file
+    #[arg(short, long, value_name = "FORMAT")]
+    pub from: InputFormat,
+
+    /// Path to input file
+    #[arg(short, long, default_value = "/dev/stdin", value_name = "FILE")]
+    pub input: String,
+
+    /// Positions to map, `<transcript>:c.<pos>` or `<transcript>:n.<pos>`
+    #[arg(required = true, value_name = "POSITION")]
+    pub positions: Vec<String>,
+
+    /// Path to output file
+    #[arg(short, long, default_value = "/dev/stdout", value_name = "FILE")]
+    pub output: String,
+
+    /// Sets the level of verbosity
+    #[arg(short, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct DiffArgs {
+    /// Format of both input files
+    #[arg(short, long, value_name = "FORMAT")]
+    pub from: InputFormat,
+
+    /// Path to the first (e.g. previous) input file
+    #[arg(short, long, value_name = "FILE")]
+    pub input: String,
+
+    /// Path to the second (e.g. current) input file
+    #[arg(long, value_name = "FILE")]
+    pub input2: String,
+
+    /// How to match the transcripts of both inputs
+    #[arg(long, default_value = "id", value_name = "MATCH")]
+    pub match_by: MatchBy,
+
+    /// Path to output file
+    #[arg(short, long, default_value = "/dev/stdout", value_name = "FILE")]
+    pub output: String,
+
+    /// Sets the level of verbosity
+    #[arg(short, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct RoundtripArgs {
+    /// Format of input file
+    #[arg(short, long, value_name = "FORMAT")]
+    pub from: InputFormat,
+
+    /// Intermediate format
+    #[arg(long, value_name = "FORMAT")]
+    pub via: InputFormat,
+
+    /// Path to input file
+    #[arg(short, long, default_value = "/dev/stdin", value_name = "FILE")]
+    pub input: String,
+
+    /// The feature source to use in the intermediate GTF data
+    #[arg(short, long, default_value = env!("CARGO_PKG_NAME"), value_name = "SOURCE")]
+    pub gtf_source: String,
+
+    /// Don't write `5UTR` and `3UTR` lines in the intermediate GTF data
+    #[arg(long)]
+    pub no_utr_lines: bool,
+
+    /// Sets the level of verbosity
+    #[arg(short, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+}
+
+/// Options to convert transcripts between formats
+#[derive(clap::Args, Clone, Debug)]
+pub struct Args {
+    /// Format of input file
+    #[arg(short, long, value_name = "FORMAT")]
+    pub from: InputFormat,
+
+    /// Output format
+    ///
+    /// Specify multiple times to write several outputs in a single pass, with one `--output` for every `--to`
+    #[arg(short = 't', long = "to", required = true, action = clap::ArgAction::Append, value_name = "FORMAT")]
+    pub formats: Vec<OutputFormat>,
+
+    /// Path to input file
+    ///
+    /// Specify multiple times or as comma-separated list to merge several input files, see `--merge-policy`.
+    /// Input files in a different format than `--from` are prefixed with their format, e.g. `gtf:custom.gtf`
+    #[arg(short, long, default_value = "/dev/stdin", action = clap::ArgAction::Append, value_delimiter = ',', value_name = "FILE")]
+    pub input: Vec<String>,
+
+    /// Path to output file
+    ///
+    /// You can also specify an S3 Uri (s3://mybucket/myfile.gtf), the output is uploaded once it is complete.
+    /// Specify multiple times together with `--to`, in the same order
+    #[arg(short = 'o', long = "output", default_value = "/dev/stdout", action = clap::ArgAction::Append, value_name = "FILE")]
+    pub outputs: Vec<String>,
+
+    /// Compression of the output file
+    ///
+    /// Defaults to `bgzip` for output files ending in `.gz` or `.bgz`, otherwise to `none`.
+    /// Compressed input files are detected automatically.
+    #[arg(long, value_name = "COMPRESSION")]
+    pub compression: Option<Compression>,
+
+    /// Keep the transcript-level attributes of GTF input (e.g. `gene_type` or `tag`) in GTF and GFF3 output
+    #[arg(long)]
+    pub preserve_attributes: bool,
+
+    /// Save the preserved attributes to a tab-separated file (implies `--preserve-attributes`)
+    ///
+    /// Use this to keep the attributes when converting to a format without attributes, see `--load-attributes`.
+    #[arg(long, value_name = "FILE")]
+    pub save_attributes: Option<String>,
+
+    /// Add the attributes from a file that was written with `--save-attributes` to GTF and GFF3 output
+    #[arg(long, value_name = "FILE")]
+    pub load_attributes: Option<String>,
+
+    /// Sort the output and create a tabix index (`<output>.tbi`)
+    ///
+    /// Only for `gtf` and `bed` output with bgzip compression.
+    #[arg(long)]
+    pub index: bool,
+
+    /// The feature source to indicate in GTF and GFF3 files (optional with `--output gtf` or `--output gff3`)
     #[arg(short, long, default_value = env!("CARGO_PKG_NAME"), value_name = "FILE")]
     pub gtf_source: String,
 
-    /// Path to reference genome fasta file. (required with `--output [fasta | fasta-split | feature-sequence | qc]`)
+    /// Add a `gene` feature line for every gene, spanning all of its transcripts (optional with `--output gtf`)
+    #[arg(long)]
+    pub gtf_gene_lines: bool,
+
+    /// Don't write `5UTR` and `3UTR` lines (optional with `--output gtf`)
+    #[arg(long)]
+    pub no_utr_lines: bool,
+
+    /// Number the exons in genomic order instead of the order of transcription (optional with `--output gtf`)
+    #[arg(long)]
+    pub genomic_exon_numbers: bool,
+
+    /// Write `0` in the `bin` column instead of the UCSC bin of the transcript (optional with `--output refgene`)
+    #[arg(long)]
+    pub no_refgene_bin: bool,
+
+    /// Remove all non-coding transcripts
+    #[arg(long)]
+    pub skip_noncoding: bool,
+
+    /// Only keep transcripts of the biotype, e.g. `protein_coding` (requires GTF or GFF3 input)
+    ///
+    /// Specify multiple times to keep transcripts of any of the biotypes.
+    /// The biotype is the `transcript_type` or `transcript_biotype` attribute of the input.
+    #[arg(long, action = clap::ArgAction::Append, value_name = "BIOTYPE")]
+    pub filter_biotype: Vec<String>,
+
+    /// Only keep transcripts that overlap the region, e.g. `chr7:140,000,000-141,000,000` or `chrM`
+    ///
+    /// Specify multiple times to keep transcripts that overlap any of the regions
+    #[arg(long, action = clap::ArgAction::Append, value_name = "REGION")]
+    pub region: Vec<Region>,
+
+    /// Path to reference genome fasta file. (required with `--output [fasta | fasta-split | feature-sequence | qc | sam]`)
     ///
-    /// You can also specify an S3 Uri (s3://mybucket/myfile.fasta), but reading from S3 is currently quite slow
-    #[arg(short, long, value_name = "FASTA_FILE", required_if_eq_any([("to", "fasta"),("to", "fasta-split"),("to", "feature-sequence"),("to", "qc")]))]
+    /// You can also specify an S3 Uri (s3://mybucket/myfile.fasta), but reading from S3 is currently quite slow.
+    /// Remote files (https://example.org/GRCh38.fa) are read with range requests and require the `remote` feature.
+    /// Use `-` to read the reference from stdin, it is kept in memory and does not require an index
+    #[arg(short, long, value_name = "FASTA_FILE")]
     pub reference: Option<String>,
 
+    /// Size of the block cache of the reference genome, in MB (`0` disables the cache)
+    ///
+    /// Adjacent transcripts reuse the cached parts of the reference, which speeds up
+    /// reading from S3, HTTP and network filesystems. Every thread uses its own cache
+    #[arg(long, default_value_t = 64, value_name = "MB")]
+    pub reference_cache: usize,
+
     /// Which part of the transcript to transcribe
     ///
     /// This option is only needed when generating fasta output.
     #[arg(long, default_value = "cds")]
     pub fasta_format: FastaFormat,
 
+    /// Write the antisense sequence (the reverse complement of the transcript) in fasta output
+    #[arg(long)]
+    pub antisense: bool,
+
+    /// The case of the bases in fasta output, `keep` writes soft-masked (lowercase) bases of the reference in lowercase
+    #[arg(long, default_value = "upper", value_name = "CASE")]
+    pub fasta_case: FastaCase,
+
+    /// Skip transcripts whose reference sequence contains invalid nucleotides, instead of failing (optional with fasta outputs)
+    #[arg(long)]
+    pub skip_invalid_sequences: bool,
+
+    /// Which positions of the codons to include
+    ///
+    /// This option is only needed when generating codon-bed output. Specify multiple
+    /// positions separated by comma (e.g. `--codon-positions 1,2`).
+    #[arg(long, value_delimiter = ',', default_value = "3", value_parser = clap::value_parser!(u8).range(1..=3), value_name = "POSITIONS")]
+    pub codon_positions: Vec<u8>,
+
+    /// Path to a tab-separated file of protein domains (required with `--output domain-bed`)
+    ///
+    /// Every line contains the transcript name, the name of the domain and the
+    /// start and end amino acid position (1-based, inclusive) and, optionally, the chromosome.
+    #[arg(long, value_name = "FILE")]
+    pub domains: Option<String>,
+
+    /// How to handle transcripts that are present in multiple input files with different structures
+    #[arg(long, default_value = "keep-first", value_name = "POLICY")]
+    pub merge_policy: MergePolicy,
+
+    /// Merge transcripts with identical structures, e.g. from RefSeq and Ensembl input files
+    ///
+    /// The first transcript is kept, the names of the merged duplicates are added as
+    /// `merged_transcript_id` attributes to GTF and GFF3 output
+    #[arg(long, value_name = "IDENTITY")]
+    pub deduplicate: Option<DuplicateIdentity>,
+
+    /// Path to the previous release of the input annotation, in the same format as `--input` (required with `--output journal`)
+    #[arg(long, value_name = "FILE")]
+    pub previous: Option<String>,
+
+    /// Write a summary report of the run to FILE
+    #[arg(long, value_name = "FILE")]
+    pub report: Option<String>,
+
+    /// Write every warning about the input data as JSON line to FILE
+    #[arg(long, value_name = "FILE")]
+    pub warnings_file: Option<String>,
+
+    /// Fail if there are more than N warnings about the input data
+    #[arg(long, value_name = "N")]
+    pub max_warnings: Option<usize>,
+
+    /// Show a progress bar with the estimated remaining time on stderr (requires the `progress` feature)
+    #[arg(long)]
+    pub progress: bool,
+
+    /// How to handle transcripts on contigs that are missing from the reference genome
+    ///
+    /// This mostly affects alternate haplotypes (`_alt`), unplaced contigs (`_random`)
+    /// or HLA contigs. Only applies when a `--reference` is specified. Defaults to `error`
+    /// for outputs and options that read the reference sequence, all other outputs
+    /// keep these transcripts. The QC reports them with the `coordinates` check.
+    #[arg(long, value_name = "POLICY")]
+    pub missing_contigs: Option<ContigPolicy>,
+
+    /// How to reconcile the start and stop codon stats with the CDS of coding transcripts
+    ///
+    /// This affects QC checks and the `start_codon` and `stop_codon` records in GTF output.
+    #[arg(long, default_value = "trust-stats", value_name = "POLICY")]
+    pub cds_stat_policy: CdsStatPolicy,
+
+    /// Placement of contigs on the primary chromosomes (required with `--missing-contigs map-to-primary`)
+    ///
+    /// Tab-separated file with the contig name, the name of the primary chromosome and
+    /// the 0-based offset of the contig on the primary chromosome.
+    #[arg(long, value_name = "FILE")]
+    pub contig_map: Option<String>,
+
+    /// Write one row per gene (consensus of all transcripts) or per transcript (optional with `--output spliceai`)
+    #[arg(long, default_value = "gene", value_name = "MODE")]
+    pub spliceai_mode: SpliceaiMode,
+
+    /// Write one BED12 line per transcript or one BED6 line per feature (optional with `--output bed`)
+    #[arg(long, default_value = "transcript", value_name = "FEATURE")]
+    pub bed_format: BedFormat,
+
     /// Sets the level of verbosity
     #[arg(short, action = clap::ArgAction::Count)]
     pub verbose: u8,
@@ -50,7 +385,7 @@ pub struct Args {
     ///
     /// or chromosome specific (e..g `-c "chrM:vertebrate mitochondrial"`).
     ///
-    /// Specify by name or amino acid lookup table (e.g. `FFLLSSSSYY**CC*....`)
+    /// Specify by name, NCBI translation table number (e.g. `2`) or amino acid lookup table (e.g. `FFLLSSSSYY**CC*....`)
     ///
     /// Defaults to the standard genetic code for all transcripts. Suggested use for vertebrates:
     ///
@@ -60,11 +395,475 @@ pub struct Args {
     #[arg(short = 'c', long, action = clap::ArgAction::Append, value_name = "GENETIC CODE")]
     pub genetic_code: Vec<String>,
 
+    /// Remove duplicated exons and exons or CDS with zero or negative length and merge overlapping exons of all transcripts
+    #[arg(long)]
+    pub fix_structure: bool,
+
+    /// Recompute the frames of all exons from the CDS positions and the strand
+    #[arg(long)]
+    pub fix_frames: bool,
+
+    /// How to handle book-ended and overlapping exons of GTF input (optional with `--from gtf`)
+    #[arg(long, default_value = "preserve", value_name = "MODE")]
+    pub exon_merge: ExonMerge,
+
+    /// Skip transcripts that cannot be built or have overlapping exons, instead of failing
+    #[arg(long)]
+    pub lenient: bool,
+
+    /// Read GTF input one transcript at a time, instead of reading the whole file into memory
+    ///
+    /// Requires that all records of every transcript are consecutive, as in GENCODE and
+    /// Ensembl GTF files. Fails otherwise, if the records are less than 10,000 transcripts
+    /// apart. (optional with `--from gtf`)
+    #[arg(long)]
+    pub stream_gtf: bool,
+
+    /// UCSC kgXref table with the gene symbols of the transcripts (optional with `--from knowngene`)
+    ///
+    /// Without it, the transcript name is used as gene symbol.
+    #[arg(long, value_name = "FILE")]
+    pub kgxref: Option<String>,
+
+    /// Download the transcripts of the gene from Ensembl (optional with `--from [ensembl | bin]`)
+    ///
+    /// Specify multiple times to download several genes. All genes that
+    /// overlap a `--region` are downloaded as well. With `--from bin`, only the
+    /// transcripts of the genes are read, using the index of the binary file
+    #[arg(long, action = clap::ArgAction::Append, value_name = "SYMBOL")]
+    pub gene: Vec<String>,
+
+    /// Species to download from Ensembl, e.g. `mus_musculus` (optional with `--from ensembl`)
+    #[arg(long, default_value = "homo_sapiens")]
+    pub species: String,
+
+    /// URL of the Ensembl REST API (optional with `--from ensembl`)
+    ///
+    /// Use https://grch37.rest.ensembl.org for GRCh37 transcripts
+    #[arg(long, default_value = "https://rest.ensembl.org", value_name = "URL")]
+    pub ensembl_server: String,
+
+    /// Extend the CDS of all coding transcripts by the stop codon
+    ///
+    /// Use this for GTF files that exclude the stop codon from the CDS, but don't
+    /// contain `stop_codon` rows. If a `--reference` is specified, the CDS is only
+    /// extended if the following three bases are a stop codon.
+    #[arg(long)]
+    pub assume_stop_excluded: bool,
+
+    /// Write the exon and CDS positions of all transcripts, relative to the gene sequence, as BED12 to this file (optional with `--output gene-fasta`)
+    #[arg(long, value_name = "FILE")]
+    pub gene_annotation: Option<String>,
+
+    /// File with the names of transcripts to flip to the opposite strand, one per line
+    ///
+    /// Use this to correct known mis-annotated transcripts. The coordinates remain unchanged,
+    /// the frames of all coding exons are recomputed.
+    #[arg(long, value_name = "FILE")]
+    pub flip_strand: Option<String>,
+
+    /// Only flip transcripts if the flipped CDS has a correct start and stop codon (requires `--reference`)
+    #[arg(long)]
+    pub verify_flip: bool,
+
     /// Remove all variants from the output that fail QC-checks
     ///
     /// You can specify one or multiple QC-checks. Only `NOK` results will be removed. `OK` and `NA` will remain.
-    #[arg(short = 'q', long = "qc-check", action = clap::ArgAction::Append, value_name = "QC CHECKS", requires = "reference")]
+    #[arg(short = 'q', long = "qc-check", action = clap::ArgAction::Append, value_name = "QC CHECKS")]
     pub qc_check: Vec<QcFilter>,
+
+    /// Accept the minor GC-AG and AT-AC introns in the `splice-sites` QC check
+    #[arg(long)]
+    pub allow_minor_splice_sites: bool,
+
+    /// Accept stop codons on the chromosome that are completed by polyadenylation, e.g. `chrM`
+    ///
+    /// The CDS of these transcripts ends with `T` or `TA`, which is completed
+    /// to `TAA` by the poly(A) tail. They pass the `cds-length` and `stop` QC
+    /// checks (and the `qc` output). Specify multiple times for several chromosomes.
+    #[arg(long, action = clap::ArgAction::Append, value_name = "CHROM")]
+    pub polya_stop: Vec<String>,
+
+    /// Number of threads to use for QC filtering, sorting and for writing
+    ///
+    /// For QC filtering, every thread uses its own reader of the reference genome.
+    /// For sorting, every chromosome is sorted independently and merged afterwards.
+    /// Writing in parallel is supported for `gtf`, `refgene`, `genepred`, `genepredext` and `bed` output.
+    #[arg(long, default_value_t = 1, value_name = "N")]
+    pub threads: usize,
+
+    /// Process QC checks in batches of N transcripts instead of loading all transcripts at once
+    ///
+    /// This keeps memory usage low for very large inputs. Only `refgene` and `genepredext`
+    /// input can be processed lazily, all other formats are still read completely.
+    ///
+    /// (optional with `--output qc`)
+    #[arg(long, value_name = "N")]
+    pub qc_batch_size: Option<usize>,
+
+    /// Extend all transcripts by N bases upstream (strand-aware)
+    ///
+    /// The 5'-most exon is extended, the CDS remains unchanged. The padding
+    /// is limited by the chromosome length, if a `--reference` is specified.
+    /// (mainly useful for `--output [bed | fasta]`)
+    #[arg(long, default_value_t = 0, value_name = "N")]
+    pub upstream: u32,
+
+    /// Extend all transcripts by N bases downstream (strand-aware)
+    ///
+    /// The 3'-most exon is extended, the CDS remains unchanged. The padding
+    /// is limited by the chromosome length, if a `--reference` is specified.
+    /// (mainly useful for `--output [bed | fasta]`)
+    #[arg(long, default_value_t = 0, value_name = "N")]
+    pub downstream: u32,
+
+    /// Sort the output by `position` (default) or by transcript `name`
+    ///
+    /// Positions are sorted by chromosome (natural order: chr1, chr2, …, chr10, chrX, chrY, chrM),
+    /// start and end. Transcripts with the same name are sorted by position
+    #[arg(long, num_args = 0..=1, default_missing_value = "position", value_name = "ORDER")]
+    pub sort: Option<SortOrder>,
+
+    /// Sort chromosomes in the order they are listed in FILE (one name per line, a fasta index works as well)
+    ///
+    /// Chromosomes that are not listed in FILE are sorted naturally after all listed chromosomes.
+    /// (optional with `--sort`)
+    #[arg(long, value_name = "FILE")]
+    pub chrom_order: Option<String>,
+
+    /// Rename chromosomes, e.g. RefSeq accessions (`NC_000001.11`) to `chr1`
+    ///
+    /// Either a built-in table (`grch37` or `grch38`) or a tab-separated FILE, where every
+    /// line contains all names of one chromosome, starting with the preferred name
+    /// (e.g. UCSC `chromAlias.txt`). With `--reference`, chromosomes are renamed to the
+    /// name that is used in the reference genome. Can be specified multiple times.
+    #[arg(long, value_name = "TABLE|FILE")]
+    pub chrom_alias: Vec<String>,
+
+    /// Add (`ucsc`: `chr1`, `chrM`) or remove (`ensembl`: `1`, `MT`) the `chr` prefix of chromosomes
+    ///
+    /// Only applies to the numbered chromosomes, X, Y and the mitochondrial chromosome.
+    /// Chromosomes are renamed directly after reading, after `--chrom-alias`.
+    #[arg(long, default_value = "keep", value_name = "STYLE")]
+    pub chrom_style: ChromStyle,
+
+    /// Keep only one transcript per gene, selected by the given criterion
+    #[arg(long, value_name = "CRITERION")]
+    pub select: Option<SelectionMode>,
+
+    /// Keep at most N transcripts per gene, selected by the diversity of their exon structure
+    ///
+    /// This is useful to create cleaner tracks for genome browsers
+    #[arg(long, value_name = "N")]
+    pub max_isoforms: Option<usize>,
+
+    /// Collapse all transcripts of a gene into a single union model of their exons, named after the gene
+    ///
+    /// The union model is non-coding, e.g. for read counting with `--output gtf` or `--output bed`
+    #[arg(long, value_name = "LEVEL")]
+    pub collapse: Option<CollapseLevel>,
+
+    /// Trim the 5' and 3' UTR of coding transcripts to at most N bases
+    #[arg(long, value_name = "N")]
+    pub max_utr_length: Option<u32>,
+}
+
+impl Args {
+    /// Returns the (first) output format
+    pub fn to(&self) -> &OutputFormat {
+        &self.formats[0]
+    }
+
+    /// Returns the (first) output file
+    pub fn output(&self) -> &str {
+        &self.outputs[0]
+    }
+
+    /// Returns the arguments for every `--to` and `--output` pair
+    ///
+    /// The transcripts are read, filtered and transformed once and then
+    /// written with each of the returned arguments.
+    pub fn split_outputs(&self) -> Result<Vec<Args>, AtgError> {
+        if self.formats.len() != self.outputs.len() {
+            return Err(AtgError::new(format!(
+                "{} output formats (`--to`) but {} output files (`--output`) are specified",
+                self.formats.len(),
+                self.outputs.len()
+            )));
+        }
+        Ok(self
+            .formats
+            .iter()
+            .zip(&self.outputs)
+            .map(|(format, output)| Args {
+                formats: vec![format.clone()],
+                outputs: vec![output.clone()],
+                ..self.clone()
+            })
+            .collect())
+    }
+
+    /// Returns a list of all missing prerequisites for the selected options
+    ///
+    /// This allows to check all requirements before parsing the input,
+    /// which can take a long time for large files.
+    pub fn missing_prerequisites(&self) -> Vec<String> {
+        let mut missing = vec![];
+
+        for input in &self.input {
+            if !is_remote(input) && !std::path::Path::new(input).exists() {
+                missing.push(format!("input file {} does not exist", input));
+            }
+        }
+
+        match &self.contig_map {
+            Some(filename) => {
+                if !std::path::Path::new(filename).exists() {
+                    missing.push(format!("contig map {} does not exist", filename));
+                }
+            }
+            None => {
+                if let Some(ContigPolicy::MapToPrimary) = self.missing_contigs {
+                    missing.push(
+                        "`--missing-contigs map-to-primary` requires `--contig-map`".to_string(),
+                    );
+                }
+            }
+        }
+
+        if self.index && self.output().starts_with("s3://") {
+            missing.push(
+                "`--index` can't write the index to S3, use a local --output file".to_string(),
+            );
+        }
+
+        #[cfg(not(feature = "progress"))]
+        if self.progress {
+            missing.push(
+                "`--progress` requires atg to be built with `--features progress`".to_string(),
+            );
+        }
+
+        if let InputFormat::Ensembl = self.from {
+            if self.gene.is_empty() && self.region.is_empty() {
+                missing.push("`--from ensembl` requires `--gene` or `--region`".to_string());
+            }
+        }
+
+        if let Some(filename) = &self.kgxref {
+            if !is_remote(filename) && !std::path::Path::new(filename).exists() {
+                missing.push(format!("kgXref table {} does not exist", filename));
+            }
+        }
+
+        if let Some(filename) = &self.flip_strand {
+            if !std::path::Path::new(filename).exists() {
+                missing.push(format!("transcript list {} does not exist", filename));
+            }
+        }
+
+        for table in &self.chrom_alias {
+            if builtin_table(table).is_none() && !std::path::Path::new(table).exists() {
+                missing.push(format!("chromosome alias file {} does not exist", table));
+            }
+        }
+
+        if let Some(filename) = &self.chrom_order {
+            if !std::path::Path::new(filename).exists() {
+                missing.push(format!("chromosome order file {} does not exist", filename));
+            }
+        }
+
+        match &self.domains {
+            Some(filename) => {
+                if !std::path::Path::new(filename).exists() {
+                    missing.push(format!("protein domain file {} does not exist", filename));
+                }
+            }
+            None => {
+                if let OutputFormat::DomainBed = self.to() {
+                    missing.push("`--output domain-bed` requires `--domains`".to_string());
+                }
+            }
+        }
+
+        match &self.previous {
+            Some(previous) => {
+                if !is_remote(previous) && !std::path::Path::new(previous).exists() {
+                    missing.push(format!("previous release {} does not exist", previous));
+                }
+            }
+            None => {
+                if let OutputFormat::Journal = self.to() {
+                    missing.push("`--output journal` requires `--previous`".to_string());
+                }
+            }
+        }
+
+        match &self.reference {
+            Some(fasta) if fasta == STDIN => {
+                if self.input.iter().any(|input| is_stdin(input)) {
+                    missing.push(
+                        "`--reference -` reads from stdin, so `--input` must be a file".to_string(),
+                    );
+                }
+            }
+            Some(fasta) => {
+                if !is_remote(fasta) {
+                    if !std::path::Path::new(fasta).exists() {
+                        missing.push(format!("reference file {} does not exist", fasta));
+                    }
+                    let fai = format!("{}.fai", fasta);
+                    if !std::path::Path::new(&fai).exists() {
+                        missing.push(format!(
+                            "reference index {} does not exist (create it with `samtools faidx {}`)",
+                            fai, fasta
+                        ));
+                    }
+                }
+            }
+            None => {
+                if self.to().requires_reference() {
+                    missing.push(format!("`--output {}` requires `--reference`", self.to()));
+                }
+                if !self.qc_check.is_empty() {
+                    missing.push("`--qc-check` requires `--reference`".to_string());
+                }
+                if self.verify_flip {
+                    missing.push("`--verify-flip` requires `--reference`".to_string());
+                }
+                if let CdsStatPolicy::Recompute = self.cds_stat_policy {
+                    missing
+                        .push("`--cds-stat-policy recompute` requires `--reference`".to_string());
+                }
+            }
+        }
+
+        if let Some(filename) = &self.load_attributes {
+            if !std::path::Path::new(filename).exists() {
+                missing.push(format!("attribute file {} does not exist", filename));
+            }
+        }
+
+        if self.index {
+            if !matches!(self.to(), OutputFormat::Gtf | OutputFormat::Bed) {
+                missing.push("`--index` requires `--output gtf` or `--output bed`".to_string());
+            }
+            if !matches!(
+                output_compression(self.output(), self.compression),
+                Compression::Bgzip
+            ) {
+                missing.push(
+                    "`--index` requires bgzip compression (`--compression bgzip` or an output file ending in `.gz`)"
+                        .to_string(),
+                );
+            }
+        }
+
+        if let OutputFormat::FastaSplit = self.to() {
+            if !std::path::Path::new(self.output()).is_dir() {
+                missing
+                    .push("`--output fasta-split` requires a directory as `--output`".to_string());
+            }
+        }
+        missing
+    }
+
+    /// Returns `true` if additional GTF attributes are kept
+    pub fn preserves_attributes(&self) -> bool {
+        self.preserve_attributes
+            || self.save_attributes.is_some()
+            || self.load_attributes.is_some()
+            || self.deduplicate.is_some()
+    }
+
+    /// Returns the policy for transcripts on contigs that are missing from the reference genome
+    ///
+    /// Without an explicit `--missing-contigs`, missing contigs are only checked
+    /// if the reference sequence is read.
+    pub fn contig_policy(&self) -> Option<ContigPolicy> {
+        self.reference.as_ref()?;
+        match self.missing_contigs {
+            Some(policy) => Some(policy),
+            None if self.reads_reference_sequence() => Some(ContigPolicy::Error),
+            None => None,
+        }
+    }
+
+    /// Returns `true` if any output or transformation reads the reference sequence
+    ///
+    /// The QC is not included, it reports missing contigs with the `coordinates` check.
+    fn reads_reference_sequence(&self) -> bool {
+        self.formats
+            .iter()
+            .any(|format| format.requires_reference() && !matches!(format, OutputFormat::Qc))
+            || self.verify_flip
+            || self.assume_stop_excluded
+    }
+
+    /// Returns the format and path of every input file
+    ///
+    /// Input files can be prefixed with their format (e.g. `gtf:custom.gtf`),
+    /// all other input files are in the `--from` format.
+    pub fn inputs(&self) -> Vec<(InputFormat, String)> {
+        self.input
+            .iter()
+            .map(|input| match input.split_once(':') {
+                Some((prefix, path)) => match InputFormat::from_str(prefix, true) {
+                    Ok(format) => (format, path.to_string()),
+                    Err(_) => (self.from.clone(), input.clone()),
+                },
+                None => (self.from.clone(), input.clone()),
+            })
+            .collect()
+    }
+
+    /// Returns `true` if the transcripts can be converted one at a time
+    ///
+    /// This requires a single input in a format that can be parsed lazily and
+    /// an output format that can be written transcript by transcript. All
+    /// options that need to see all transcripts at once (e.g. `--sort`,
+    /// `--select`, `--max-isoforms` or `--collapse`) prevent streaming.
+    pub fn is_streamable(&self) -> bool {
+        let lazy_input = match self.inputs().as_slice() {
+            [(InputFormat::Refgene | InputFormat::Genepredext, _)] => true,
+            [(InputFormat::Gtf, _)] => self.stream_gtf,
+            _ => false,
+        };
+        let per_transcript_output = match self.to() {
+            OutputFormat::Gtf => !self.gtf_gene_lines,
+            OutputFormat::Refgene
+            | OutputFormat::Genepred
+            | OutputFormat::Genepredext
+            | OutputFormat::BigGenepred
+            | OutputFormat::Bed
+            | OutputFormat::Fasta => true,
+            _ => false,
+        };
+        lazy_input
+            && per_transcript_output
+            && self.formats.len() == 1
+            && self.sort.is_none()
+            && self.deduplicate.is_none()
+            && self.select.is_none()
+            && self.max_isoforms.is_none()
+            && self.collapse.is_none()
+            && self.threads <= 1
+            && !self.index
+    }
+}
+
+/// Returns `true` for files that are not on the local filesystem
+fn is_remote(filename: &str) -> bool {
+    filename.starts_with("s3://")
+        || filename.starts_with("http://")
+        || filename.starts_with("https://")
+        || filename == STDIN
+}
+
+/// Returns `true` if the file is read from stdin
+fn is_stdin(filename: &str) -> bool {
+    filename == STDIN || filename == "/dev/stdin"
 }
 
 #[derive(Clone, Debug, ValueEnum)]
@@ -87,16 +886,36 @@ impl FastaFormat {
     }
 }
 
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum FastaCase {
+    /// Keep the case of the reference genome, e.g. lowercase soft-masked repeats
+    Keep,
+    /// Write all bases in uppercase
+    Upper,
+    /// Write all bases in lowercase
+    Lower,
+}
+
 #[derive(Clone, Debug, ValueEnum)]
 pub enum InputFormat {
     /// GTF2.2 format
     Gtf,
+    /// GFF3 format, e.g. from Gencode or Ensembl
+    Gff3,
     /// RefGene format (one transcript per line)
     Refgene,
     /// GenePredExt format (one transcript per line)
     Genepredext,
+    /// Bedfile, BED12 (one transcript per line)
+    Bed,
+    /// UCSC knownGene table (one transcript per line), see --kgxref for gene symbols
+    Knowngene,
+    /// Download from the Ensembl REST API, see --gene and --region (requires the `remote` feature)
+    Ensembl,
     /// ATG-specific binary format
     Bin,
+    /// JSON Lines (one transcript per line, requires the `json` feature)
+    Json,
 }
 
 impl std::fmt::Display for InputFormat {
@@ -109,12 +928,16 @@ impl std::fmt::Display for InputFormat {
 pub enum OutputFormat {
     /// GTF2.2 format
     Gtf,
+    /// GFF3 format, with gene, mRNA, exon, CDS and UTR features
+    Gff3,
     /// RefGene format (one transcript per line)
     Refgene,
     /// GenePred format (one transcript per line)
     Genepred,
     /// GenePredExt format (one transcript per line)
     Genepredext,
+    /// UCSC bigGenePred format (BED12+8), the input of `bedToBigBed -type=bed12+8`
+    BigGenepred,
     /// Bedfile (one transcript per line)
     Bed,
     /// Nucleotide sequence. There are multiple formatting options available, see --fasta-format
@@ -123,24 +946,201 @@ pub enum OutputFormat {
     FastaSplit,
     /// Nucleotide sequence for every 'feature' (UTR, CDS or non-coding exons)
     FeatureSequence,
+    /// Genomic sequence of every gene, including introns. See --gene-annotation for the exon positions
+    GeneFasta,
     /// Custom format, as needed for SpliceAI
     Spliceai,
+    /// Gene boundaries, strand and CDS bounds of every gene, as SpliceAI masking table
+    SpliceaiGenes,
     /// ATG-specific binary format
     Bin,
+    /// JSON Lines (one transcript per line, requires the `json` feature)
+    Json,
     /// Performs QC checks on all Transcripts
     Qc,
+    /// Other genes that overlap each transcript, on the same and opposite strand
+    Overlaps,
+    /// Bedfile of the selected codon positions in the CDS, see --codon-positions
+    CodonBed,
+    /// Bedfile (BED12) of protein domains, projected onto the genome, see --domains
+    DomainBed,
+    /// PSL alignment of the cDNA to the genome (one transcript per line)
+    Psl,
+    /// SAM alignment of the cDNA to the genome (one transcript per line)
+    Sam,
+    /// Changes of every transcript compared to a previous release, see --previous
+    Journal,
+    /// Size of every chromosome, from the reference index if --reference is specified, otherwise the highest transcript position
+    ChromSizes,
+    /// Summary of the annotation: counts of genes, transcripts and exons, CDS lengths and a per-chromosome breakdown
+    Stats,
     /// No output
     None,
     /// This only makes sense for debugging purposes
     Raw,
 }
 
+impl OutputFormat {
+    /// Returns `true` if the output requires a reference genome
+    pub fn requires_reference(&self) -> bool {
+        matches!(
+            self,
+            OutputFormat::Fasta
+                | OutputFormat::FastaSplit
+                | OutputFormat::FeatureSequence
+                | OutputFormat::GeneFasta
+                | OutputFormat::Qc
+                | OutputFormat::Sam
+        )
+    }
+}
+
 impl std::fmt::Display for OutputFormat {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "{:?}", self)
     }
 }
 
+// The variant names are used as CLI values, where the prefix is helpful
+#[allow(clippy::enum_variant_names)]
+#[derive(Clone, Debug, ValueEnum)]
+pub enum MergePolicy {
+    /// Keep the transcript from the first input file
+    KeepFirst,
+    /// Keep the transcript with the longest CDS
+    KeepLongestCds,
+    /// Keep both transcripts, the later one is renamed with the number of its input file as suffix
+    KeepBoth,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum SortOrder {
+    /// Sort by chromosome, start and end position
+    Position,
+    /// Sort by transcript name
+    Name,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum MatchBy {
+    /// The transcript ID without version and the chromosome
+    Id,
+    /// Identical exons and CDS
+    Structure,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum DuplicateIdentity {
+    /// Identical exons and CDS
+    Exons,
+    /// Identical coding parts of the exons, the UTRs can differ
+    Cds,
+}
+
+impl std::fmt::Display for DuplicateIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DuplicateIdentity::Exons => write!(f, "exons"),
+            DuplicateIdentity::Cds => write!(f, "cds"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ContigPolicy {
+    /// Stop with an error
+    Error,
+    /// Remove the transcript from the output, with a warning
+    Skip,
+    /// Move the transcript to the primary chromosome, as specified with `--contig-map`.
+    /// Transcripts on contigs without a mapping are removed, with a warning
+    MapToPrimary,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum SpliceaiMode {
+    /// One row per gene, with the merged exons of all its transcripts
+    Gene,
+    /// One row per transcript
+    Transcript,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum BedFormat {
+    /// One BED12 line per transcript
+    Transcript,
+    /// One BED6 line per exon
+    Exons,
+    /// One BED6 line per coding part of an exon
+    Cds,
+    /// One BED6 line per 5' and 3' UTR part of an exon
+    Utr,
+    /// One BED6 line per intron
+    Introns,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ChromStyle {
+    /// Keep the chromosome names of the input
+    Keep,
+    /// `chr1`, `chrX`, `chrM`
+    Ucsc,
+    /// `1`, `X`, `MT`
+    Ensembl,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Compression {
+    /// Plain text
+    None,
+    /// A single gzip stream
+    Gzip,
+    /// Blocked gzip, as written by `bgzip` (can be indexed with `tabix`)
+    Bgzip,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum CdsStatPolicy {
+    /// Keep the start and stop codon stats of the input
+    TrustStats,
+    /// Set the stats based on the start and stop codons in the `--reference`
+    Recompute,
+    /// Mark complete stats as incomplete if the CDS length is not a multiple of 3
+    MarkIncomplete,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ExonMerge {
+    /// Keep every exon of the input, also book-ended exons
+    Preserve,
+    /// Merge book-ended exons into one exon
+    BookEnded,
+    /// Merge book-ended and overlapping exons into one exon
+    Overlapping,
+}
+
+#[derive(Clone, Debug, ValueEnum)]
+pub enum SelectionMode {
+    /// The transcript with the longest coding sequence
+    LongestCds,
+    /// The transcript with the longest processed mRNA (all exons)
+    LongestTranscript,
+    /// The transcript with the most exons
+    MostExons,
+}
+
+impl std::fmt::Display for SelectionMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum)]
+pub enum CollapseLevel {
+    /// One union model per gene (gene symbol and chromosome)
+    Gene,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, ValueEnum)]
 pub enum QcFilter {
     /// Transcript contains at least one exon
@@ -157,18 +1157,32 @@ pub enum QcFilter {
     UpstreamStop,
     /// The transcript is within the coordinates of the reference genome
     Coordinates,
+    /// The transcript has no duplicated or overlapping exons and no exons or CDS with zero or negative length
+    Structure,
+    /// All introns start with GT and end with AG (see `--allow-minor-splice-sites`)
+    SpliceSites,
 }
 
 impl QcFilter {
-    pub fn remove(&self, qc: &QcCheck) -> bool {
+    /// Returns the result of the QC check for the transcript
+    pub fn result(&self, qc: &QcCheck, tx: &Transcript) -> QcResult {
         match self {
-            QcFilter::Exon => qc.contains_exon() == QcResult::NOK,
-            QcFilter::CdsLength => qc.correct_cds_length() == QcResult::NOK,
-            QcFilter::Start => qc.correct_start_codon() == QcResult::NOK,
-            QcFilter::Stop => qc.correct_stop_codon() == QcResult::NOK,
-            QcFilter::UpstreamStart => qc.no_upstream_start_codon() == QcResult::NOK,
-            QcFilter::UpstreamStop => qc.no_upstream_stop_codon() == QcResult::NOK,
-            QcFilter::Coordinates => qc.correct_coordinates() == QcResult::NOK,
+            QcFilter::Exon => qc.contains_exon(),
+            QcFilter::CdsLength => qc.correct_cds_length(),
+            QcFilter::Start => qc.correct_start_codon(),
+            QcFilter::Stop => qc.correct_stop_codon(),
+            QcFilter::UpstreamStart => qc.no_upstream_start_codon(),
+            QcFilter::UpstreamStop => qc.no_upstream_stop_codon(),
+            QcFilter::Coordinates => qc.correct_coordinates(),
+            QcFilter::Structure => {
+                if check_structure(tx).is_empty() {
+                    QcResult::OK
+                } else {
+                    QcResult::NOK
+                }
+            }
+            // requires the reference sequence of the introns, see `check_splice_sites`
+            QcFilter::SpliceSites => QcResult::NA,
         }
     }
 }
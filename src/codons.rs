@@ -0,0 +1,261 @@
+//! Genomic positions of codon bases within the CDS
+
+use std::fmt;
+use std::io::Write;
+
+use atglib::models::{CdsStat, Frame, Strand, Transcript, TranscriptWrite};
+
+/// Returns every CDS position of the transcript along with its position within the codon (1, 2 or 3)
+///
+/// The positions are sorted in genomic order. The codon position of the
+/// first CDS base is derived from the frame offset of the first coding exon,
+/// so that transcripts with an incomplete 5' CDS are handled correctly.
+pub fn codon_positions(tx: &Transcript) -> Vec<(u32, u8)> {
+    let mut segments: Vec<(u32, u32, Frame)> = tx
+        .exons()
+        .iter()
+        .filter_map(|exon| match (exon.cds_start(), exon.cds_end()) {
+            (Some(start), Some(end)) if start <= end => Some((*start, *end, *exon.frame_offset())),
+            _ => None,
+        })
+        .collect();
+    segments.sort_by_key(|segment| segment.0);
+
+    let minus = matches!(tx.strand(), Strand::Minus);
+    if minus {
+        segments.reverse();
+    }
+
+    // frame is the number of bases that belong to the previous codon
+    let mut codon_pos: u8 = match segments.first().map(|segment| segment.2) {
+        Some(Frame::One) => 3,
+        Some(Frame::Two) => 2,
+        _ => 1,
+    };
+
+    let mut positions = vec![];
+    for (start, end, _) in segments {
+        let bases: Box<dyn Iterator<Item = u32>> = match minus {
+            true => Box::new((start..=end).rev()),
+            false => Box::new(start..=end),
+        };
+        for pos in bases {
+            positions.push((pos, codon_pos));
+            codon_pos = codon_pos % 3 + 1;
+        }
+    }
+    positions.sort_unstable_by_key(|pos| pos.0);
+    positions
+}
+
+/// Writes the positions of the selected codon bases as BED6 intervals
+///
+/// Adjacent positions are merged into one interval, e.g. when selecting
+/// the first and second codon position.
+pub fn write_codon_bed<W: Write>(
+    transcripts: &[Transcript],
+    selected: &[u8],
+    mut out: W,
+) -> Result<(), std::io::Error> {
+    let mut selected = selected.to_vec();
+    selected.sort_unstable();
+    selected.dedup();
+    let name_suffix = selected
+        .iter()
+        .map(|pos| pos.to_string())
+        .collect::<Vec<String>>()
+        .join(",");
+
+    for tx in transcripts {
+        let name = format!("{}:codon_pos{}", tx.name(), name_suffix);
+        let mut interval: Option<(u32, u32)> = None;
+        for (pos, codon_pos) in codon_positions(tx) {
+            if !selected.contains(&codon_pos) {
+                continue;
+            }
+            interval = match interval {
+                Some((start, end)) if end.checked_add(1) == Some(pos) => Some((start, pos)),
+                Some((start, end)) => {
+                    write_bed_line(&mut out, tx, start, end, &name)?;
+                    Some((pos, pos))
+                }
+                None => Some((pos, pos)),
+            };
+        }
+        if let Some((start, end)) = interval {
+            write_bed_line(&mut out, tx, start, end, &name)?;
+        }
+    }
+    out.flush()
+}
+
+/// Writes a BED6 line, converting the 1-based inclusive interval to 0-based half-open
+fn write_bed_line<W: Write>(
+    out: &mut W,
+    tx: &Transcript,
+    start: u32,
+    end: u32,
+    name: &str,
+) -> Result<(), std::io::Error> {
+    writeln!(
+        out,
+        "{}\t{}\t{}\t{}\t0\t{}",
+        tx.chrom(),
+        start.saturating_sub(1),
+        end,
+        name,
+        tx.strand()
+    )
+}
+
+/// The reason why a transcript has no start or stop codon
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Absence {
+    /// The transcript has no CDS
+    NonCoding,
+    /// The CDS stat of the codon is not `cmpl`
+    Stat(CdsStat),
+}
+
+impl fmt::Display for Absence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Absence::NonCoding => write!(f, "the transcript is non-coding"),
+            Absence::Stat(stat) => write!(f, "the CDS stat is {}", stat),
+        }
+    }
+}
+
+/// The start or stop codon of a transcript
+///
+/// The intervals are 1-based, inclusive and in genomic order. A codon can
+/// be split by an intron into several intervals.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Codon {
+    /// All three bases of the codon are within the CDS
+    Complete(Vec<(u32, u32)>),
+    /// The codon is cut off by the CDS boundary, e.g. because the CDS is
+    /// shorter than 3 bases or does not start at the first codon position
+    Partial(Vec<(u32, u32)>),
+    /// The transcript has no such codon
+    Absent(Absence),
+}
+
+impl Codon {
+    pub fn is_complete(&self) -> bool {
+        matches!(self, Codon::Complete(_))
+    }
+}
+
+/// Merges adjacent positions into intervals
+fn intervals(mut positions: Vec<u32>) -> Vec<(u32, u32)> {
+    positions.sort_unstable();
+    let mut intervals: Vec<(u32, u32)> = vec![];
+    for pos in positions {
+        match intervals.last_mut() {
+            Some((_, end)) if end.checked_add(1) == Some(pos) => *end = pos,
+            _ => intervals.push((pos, pos)),
+        }
+    }
+    intervals
+}
+
+/// Returns the first (`start == true`) or last codon of the CDS
+fn checked_codon(tx: &Transcript, start: bool) -> Codon {
+    let stat = match start {
+        true => tx.cds_start_codon_stat(),
+        false => tx.cds_stop_codon_stat(),
+    };
+    let mut positions = codon_positions(tx);
+    if positions.is_empty() {
+        return Codon::Absent(Absence::NonCoding);
+    }
+    if stat != CdsStat::Complete {
+        return Codon::Absent(Absence::Stat(stat));
+    }
+    // the bases of the codon are popped from the end, starting at the CDS boundary
+    if matches!(tx.strand(), Strand::Minus) != start {
+        positions.reverse();
+    }
+    let (first, last) = match start {
+        true => (1, 3),
+        false => (3, 1),
+    };
+    let boundary = positions.last().map(|pos| pos.1);
+    let mut bases = vec![];
+    while let Some((pos, codon_pos)) = positions.pop() {
+        bases.push(pos);
+        if codon_pos == last || bases.len() == 3 {
+            break;
+        }
+    }
+    match bases.len() == 3 && boundary == Some(first) {
+        true => Codon::Complete(intervals(bases)),
+        false => Codon::Partial(intervals(bases)),
+    }
+}
+
+/// Returns the start codon of the transcript, as typed result
+///
+/// Unlike `Transcript::start_codon`, a codon that is cut off by the CDS
+/// boundary is reported as `Partial` and transcripts without a start codon
+/// report the reason.
+pub fn start_codon_checked(tx: &Transcript) -> Codon {
+    checked_codon(tx, true)
+}
+
+/// Returns the stop codon of the transcript, as typed result, see `start_codon_checked`
+pub fn stop_codon_checked(tx: &Transcript) -> Codon {
+    checked_codon(tx, false)
+}
+
+/// Logs why the `start_codon` or `stop_codon` of a transcript is missing or partial in GTF output
+pub struct ExplainCodons<T: TranscriptWrite> {
+    inner: T,
+}
+
+impl<T: TranscriptWrite> ExplainCodons<T> {
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+
+    fn explain(tx: &Transcript) {
+        for (feature, codon) in [
+            ("start_codon", start_codon_checked(tx)),
+            ("stop_codon", stop_codon_checked(tx)),
+        ] {
+            match codon {
+                Codon::Complete(_) | Codon::Absent(Absence::NonCoding) => {}
+                Codon::Partial(intervals) => debug!(
+                    target: "atg::write",
+                    "The {} of {} is partial: {} of 3 bases are within the CDS",
+                    feature,
+                    tx.name(),
+                    intervals
+                        .iter()
+                        .map(|(start, end)| end - start + 1)
+                        .sum::<u32>()
+                ),
+                Codon::Absent(absence) => debug!(
+                    target: "atg::write",
+                    "No {} for {}: {}",
+                    feature,
+                    tx.name(),
+                    absence
+                ),
+            }
+        }
+    }
+}
+
+impl<T: TranscriptWrite> TranscriptWrite for ExplainCodons<T> {
+    fn writeln_single_transcript(&mut self, transcript: &Transcript) -> Result<(), std::io::Error> {
+        Self::explain(transcript);
+        self.inner.writeln_single_transcript(transcript)
+    }
+
+    fn write_single_transcript(&mut self, transcript: &Transcript) -> Result<(), std::io::Error> {
+        Self::explain(transcript);
+        self.inner.write_single_transcript(transcript)
+    }
+}
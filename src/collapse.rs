@@ -0,0 +1,49 @@
+//! Collapse all transcripts of a gene into a single union model
+
+use atglib::models::{CdsStat, Exon, Frame, Transcript, TranscriptBuilder, Transcripts};
+use atglib::utils::errors::BuildTranscriptError;
+
+use crate::genes::genes;
+
+/// Returns the union of the exons of all transcripts, sorted by position
+///
+/// Overlapping and adjacent exons are merged into one interval.
+pub fn exon_union(transcripts: &[&Transcript]) -> Vec<(u32, u32)> {
+    let mut exons: Vec<(u32, u32)> = transcripts
+        .iter()
+        .flat_map(|tx| tx.exons().iter().map(|exon| (exon.start(), exon.end())))
+        .collect();
+    exons.sort_unstable();
+
+    let mut union: Vec<(u32, u32)> = vec![];
+    for (start, end) in exons {
+        match union.last_mut() {
+            Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+            _ => union.push((start, end)),
+        }
+    }
+    union
+}
+
+/// Replaces the transcripts of every gene by the union model of the gene
+///
+/// Genes are identified by their gene symbol and chromosome, like in `genes`.
+/// The order of the genes is preserved.
+pub fn collapse_genes(transcripts: Transcripts) -> Result<Transcripts, BuildTranscriptError> {
+    let mut collapsed = Transcripts::new();
+    for gene in genes(transcripts.as_vec()) {
+        let mut tx = TranscriptBuilder::new()
+            .name(gene.name())
+            .chrom(gene.chrom())
+            .gene(gene.name())
+            .strand(gene.strand())
+            .cds_start_codon_stat(CdsStat::None)?
+            .cds_stop_codon_stat(CdsStat::None)?
+            .build()?;
+        for (start, end) in exon_union(gene.transcripts()) {
+            tx.push_exon(Exon::new(start, end, None, None, Frame::None));
+        }
+        collapsed.push(tx);
+    }
+    Ok(collapsed)
+}
@@ -0,0 +1,255 @@
+//! Compare two annotations, e.g. two Gencode releases
+
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
+use atglib::models::{Exon, Transcript};
+use atglib::utils::errors::AtgError;
+
+use crate::cli::{DiffArgs, DuplicateIdentity, MatchBy};
+use crate::compression::create_output;
+use crate::coordinates::TranscriptCoordinates;
+use crate::formats::reader_for;
+use crate::journal::stable_id;
+use crate::merge::structure_key;
+
+/// The kind of difference between both annotations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Difference {
+    /// The transcript is only present in the second annotation
+    Added,
+    /// The transcript is only present in the first annotation
+    Removed,
+    /// The version suffix of the transcript ID changed (e.g. `ENST01.1` => `ENST01.2`)
+    VersionChanged,
+    /// The transcript has the same structure, but a different ID
+    Renamed,
+    /// The transcript is on the other strand
+    StrandChanged,
+    /// The exon is only present in the second annotation
+    ExonAdded,
+    /// The exon is only present in the first annotation
+    ExonRemoved,
+    /// The start or end of the exon changed
+    ExonChanged,
+    /// The start or end of the CDS changed
+    CdsChanged,
+}
+
+impl std::fmt::Display for Difference {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let s = match self {
+            Difference::Added => "added",
+            Difference::Removed => "removed",
+            Difference::VersionChanged => "version-changed",
+            Difference::Renamed => "renamed",
+            Difference::StrandChanged => "strand-changed",
+            Difference::ExonAdded => "exon-added",
+            Difference::ExonRemoved => "exon-removed",
+            Difference::ExonChanged => "exon-changed",
+            Difference::CdsChanged => "cds-changed",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// One difference of a transcript between both annotations
+pub struct DiffEntry<'a> {
+    pub difference: Difference,
+    pub first: Option<&'a Transcript>,
+    pub second: Option<&'a Transcript>,
+    /// The exon number, for exon differences
+    pub exon: Option<usize>,
+    /// The affected region in the first annotation, e.g. `chr1:100-200`
+    pub before: Option<String>,
+    /// The affected region in the second annotation
+    pub after: Option<String>,
+}
+
+impl<'a> DiffEntry<'a> {
+    fn new(difference: Difference, first: &'a Transcript, second: &'a Transcript) -> Self {
+        Self {
+            difference,
+            first: Some(first),
+            second: Some(second),
+            exon: None,
+            before: None,
+            after: None,
+        }
+    }
+}
+
+fn region(chrom: &str, start: u32, end: u32) -> String {
+    format!("{}:{}-{}", chrom, start, end)
+}
+
+fn exon_region(tx: &Transcript, exon: &Exon) -> String {
+    region(tx.chrom(), exon.start(), exon.end())
+}
+
+fn cds_region(tx: &Transcript) -> Option<String> {
+    Some(region(tx.chrom(), tx.cds_start()?, tx.cds_end()?))
+}
+
+/// Returns the differences between two matched transcripts
+fn compare_transcripts<'a>(first: &'a Transcript, second: &'a Transcript) -> Vec<DiffEntry<'a>> {
+    let mut entries = vec![];
+    if first.name() != second.name() {
+        let difference = match stable_id(first.name()) == stable_id(second.name()) {
+            true => Difference::VersionChanged,
+            false => Difference::Renamed,
+        };
+        entries.push(DiffEntry::new(difference, first, second));
+    }
+    if first.strand() != second.strand() {
+        entries.push(DiffEntry::new(Difference::StrandChanged, first, second));
+    }
+
+    let overlaps = |a: &Exon, b: &Exon| a.start() <= b.end() && b.start() <= a.end();
+    let first_exons = TranscriptCoordinates::new(first);
+    let second_exons = TranscriptCoordinates::new(second);
+    for (idx, exon) in first_exons.exons().iter().enumerate() {
+        let other = second_exons
+            .exons()
+            .iter()
+            .find(|other| overlaps(exon, other));
+        let (difference, after) = match other {
+            Some(other) if other.start() == exon.start() && other.end() == exon.end() => continue,
+            Some(other) => (Difference::ExonChanged, Some(exon_region(second, other))),
+            None => (Difference::ExonRemoved, None),
+        };
+        entries.push(DiffEntry {
+            exon: Some(idx + 1),
+            before: Some(exon_region(first, exon)),
+            after,
+            ..DiffEntry::new(difference, first, second)
+        });
+    }
+    for (idx, exon) in second_exons.exons().iter().enumerate() {
+        if !first_exons
+            .exons()
+            .iter()
+            .any(|other| overlaps(exon, other))
+        {
+            entries.push(DiffEntry {
+                exon: Some(idx + 1),
+                after: Some(exon_region(second, exon)),
+                ..DiffEntry::new(Difference::ExonAdded, first, second)
+            });
+        }
+    }
+
+    if first.cds_start() != second.cds_start() || first.cds_end() != second.cds_end() {
+        entries.push(DiffEntry {
+            before: cds_region(first),
+            after: cds_region(second),
+            ..DiffEntry::new(Difference::CdsChanged, first, second)
+        });
+    }
+    entries
+}
+
+/// Returns the key to match the transcript with, `None` if it can't be matched
+fn match_key(tx: &Transcript, by: MatchBy) -> Option<String> {
+    match by {
+        MatchBy::Id => Some(format!("{}\t{}", stable_id(tx.name()), tx.chrom())),
+        MatchBy::Structure => {
+            structure_key(tx, &DuplicateIdentity::Exons).map(|key| format!("{:?}", key))
+        }
+    }
+}
+
+/// Compares two annotations and returns all differences
+///
+/// The differences are ordered by the transcripts of the first annotation,
+/// followed by the added transcripts in the order of the second annotation.
+pub fn compare<'a>(
+    first: &'a [Transcript],
+    second: &'a [Transcript],
+    by: MatchBy,
+) -> Vec<DiffEntry<'a>> {
+    // key => index of the transcript in `second`
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for (idx, tx) in second.iter().enumerate() {
+        if let Some(key) = match_key(tx, by) {
+            index.entry(key).or_insert(idx);
+        }
+    }
+
+    let mut entries = vec![];
+    let mut matched = vec![false; second.len()];
+    for tx in first {
+        match match_key(tx, by).and_then(|key| index.remove(&key)) {
+            Some(idx) => {
+                matched[idx] = true;
+                entries.append(&mut compare_transcripts(tx, &second[idx]));
+            }
+            None => entries.push(DiffEntry {
+                difference: Difference::Removed,
+                first: Some(tx),
+                second: None,
+                exon: None,
+                before: Some(region(tx.chrom(), tx.tx_start(), tx.tx_end())),
+                after: None,
+            }),
+        }
+    }
+    for (tx, _) in second.iter().zip(matched).filter(|(_, matched)| !matched) {
+        entries.push(DiffEntry {
+            difference: Difference::Added,
+            first: None,
+            second: Some(tx),
+            exon: None,
+            before: None,
+            after: Some(region(tx.chrom(), tx.tx_start(), tx.tx_end())),
+        });
+    }
+    entries
+}
+
+/// Writes all differences as tab-separated table
+pub fn write_diff<W: Write>(entries: &[DiffEntry], mut out: W) -> Result<(), AtgError> {
+    writeln!(
+        out,
+        "Change\tGene\tTranscript1\tTranscript2\tExon\tBefore\tAfter"
+    )?;
+    for entry in entries {
+        let gene = entry.second.or(entry.first).map_or(".", |tx| tx.gene());
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            entry.difference,
+            gene,
+            entry.first.map_or(".", |tx| tx.name()),
+            entry.second.map_or(".", |tx| tx.name()),
+            entry
+                .exon
+                .map_or_else(|| ".".to_string(), |exon| exon.to_string()),
+            entry.before.as_deref().unwrap_or("."),
+            entry.after.as_deref().unwrap_or(".")
+        )?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Reads both annotations and writes all differences
+pub fn run_diff(args: &DiffArgs) -> Result<(), AtgError> {
+    let first = reader_for(&args.from, &args.input)?.transcripts()?;
+    let second = reader_for(&args.from, &args.input2)?.transcripts()?;
+    let entries = compare(first.as_vec(), second.as_vec(), args.match_by);
+    let changed: HashSet<&str> = entries
+        .iter()
+        .filter_map(|entry| entry.first.or(entry.second))
+        .map(|tx| tx.name())
+        .collect();
+    debug!(
+        target: "atg::diff",
+        "{} differences in {} of {} and {} transcripts",
+        entries.len(),
+        changed.len(),
+        first.len(),
+        second.len()
+    );
+    write_diff(&entries, create_output(&args.output, None)?)
+}
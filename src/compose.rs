@@ -0,0 +1,127 @@
+//! Compose the text records of transcripts independently of the output sink
+
+use std::io::Write;
+
+use atglib::bed;
+use atglib::genepred;
+use atglib::genepredext;
+use atglib::gtf;
+use atglib::models::{Transcript, TranscriptWrite};
+use atglib::refgene;
+use atglib::utils::errors::AtgError;
+
+use crate::big_genepred;
+use crate::cli::{Args, OutputFormat};
+use crate::formats::writer_to;
+
+/// Number of transcripts that are composed by one thread at a time
+const CHUNK_SIZE: usize = 1000;
+
+/// Returns `true` if records of the output format can be composed per transcript
+pub fn is_composable(format: &OutputFormat) -> bool {
+    matches!(
+        format,
+        OutputFormat::Refgene
+            | OutputFormat::Genepred
+            | OutputFormat::Genepredext
+            | OutputFormat::BigGenepred
+            | OutputFormat::Gtf
+            | OutputFormat::Bed
+    )
+}
+
+/// Writes all transcripts into the writer
+///
+/// The writer is dropped at the end, which flushes its internal buffer.
+fn write_all<T: TranscriptWrite>(
+    mut writer: T,
+    transcripts: &[Transcript],
+) -> Result<(), std::io::Error> {
+    for tx in transcripts {
+        writer.writeln_single_transcript(tx)?;
+    }
+    Ok(())
+}
+
+/// Returns the formatted records of all transcripts
+pub fn compose(
+    format: &OutputFormat,
+    transcripts: &[Transcript],
+    gtf_source: &str,
+) -> Result<Vec<u8>, AtgError> {
+    let mut buf: Vec<u8> = vec![];
+    match format {
+        OutputFormat::Refgene => write_all(refgene::Writer::new(&mut buf), transcripts)?,
+        OutputFormat::Genepred => write_all(genepred::Writer::new(&mut buf), transcripts)?,
+        OutputFormat::Genepredext => write_all(genepredext::Writer::new(&mut buf), transcripts)?,
+        OutputFormat::BigGenepred => write_all(big_genepred::Writer::new(&mut buf), transcripts)?,
+        OutputFormat::Gtf => {
+            let mut writer = gtf::Writer::new(&mut buf);
+            writer.set_source(gtf_source);
+            write_all(writer, transcripts)?
+        }
+        OutputFormat::Bed => write_all(bed::Writer::new(&mut buf), transcripts)?,
+        _ => {
+            return Err(AtgError::new(format!(
+                "{} records can not be composed per transcript",
+                format
+            )))
+        }
+    }
+    Ok(buf)
+}
+
+/// Returns the formatted records of all transcripts, as written by `writer_to`
+///
+/// Unlike `compose`, the records contain all adjustments of the CLI arguments,
+/// e.g. the UCSC bin of RefGene output or `--genomic-exon-numbers`.
+fn compose_with_args(
+    format: &OutputFormat,
+    transcripts: &[Transcript],
+    args: &Args,
+) -> Result<Vec<u8>, AtgError> {
+    let mut buf: Vec<u8> = vec![];
+    {
+        // the writer is dropped at the end of the block, which flushes all wrappers
+        let mut writer = writer_to(format, Box::new(&mut buf), args)?;
+        for tx in transcripts {
+            writer.writeln_single_transcript(tx)?;
+        }
+    }
+    Ok(buf)
+}
+
+/// Composes the records in parallel and writes them in the original order
+///
+/// Every chunk of transcripts is written by its own `writer_to` writer, so
+/// the output does not depend on `--threads`. Only `threads * CHUNK_SIZE`
+/// composed transcripts are kept in memory before they are written to `out`.
+pub fn write_parallel<W: Write>(
+    format: &OutputFormat,
+    transcripts: &[Transcript],
+    mut out: W,
+    args: &Args,
+) -> Result<(), AtgError> {
+    let threads = args.threads.max(1);
+    for window in transcripts.chunks(CHUNK_SIZE * threads) {
+        let composed: Vec<Result<Vec<u8>, AtgError>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = window
+                .chunks(CHUNK_SIZE)
+                .map(|chunk| scope.spawn(move || compose_with_args(format, chunk, args)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|_| Err(AtgError::new("writer thread panicked")))
+                })
+                .collect()
+        });
+        for records in composed {
+            out.write_all(&records?)?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
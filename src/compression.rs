@@ -0,0 +1,180 @@
+//! Transparent gzip and bgzip compression of input and output files
+
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+
+use flate2::read::MultiGzDecoder;
+use flate2::write::{DeflateEncoder, GzEncoder};
+use flate2::Crc;
+
+use crate::cli::Compression;
+
+/// The first two bytes of every gzip member
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Maximum number of uncompressed bytes per BGZF block
+///
+/// This is the limit that `htslib` uses, to guarantee that every compressed
+/// block fits into 64kB, even for incompressible data.
+const BGZF_BLOCK_SIZE: usize = 0xff00;
+
+/// The empty block that marks the end of a BGZF file
+const BGZF_EOF: [u8; 28] = [
+    0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, 0x42, 0x43, 0x02, 0x00,
+    0x1b, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// Opens a file for reading and decompresses it, if it is gzip or bgzip compressed
+pub fn open_input(filename: &str) -> Result<Box<dyn Read>, std::io::Error> {
+    let mut reader = BufReader::new(input_file(filename)?);
+    if reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+        debug!(target: "atg::read", "Decompressing {}", filename);
+        return Ok(Box::new(MultiGzDecoder::new(reader)));
+    }
+    Ok(Box::new(reader))
+}
+
+/// Returns the compression of an output file
+///
+/// If no compression is specified, it is chosen based on the file extension.
+pub fn output_compression(filename: &str, compression: Option<Compression>) -> Compression {
+    match compression {
+        Some(compression) => compression,
+        None if filename.ends_with(".gz") || filename.ends_with(".bgz") => Compression::Bgzip,
+        None => Compression::None,
+    }
+}
+
+/// Creates a buffered output file, compressed with `compression`
+///
+/// If no compression is specified, it is chosen based on the file extension.
+/// S3 URIs (`s3://bucket/key`) are uploaded once the output is complete.
+pub fn create_output(
+    filename: &str,
+    compression: Option<Compression>,
+) -> Result<Box<dyn Write>, std::io::Error> {
+    let file: Box<dyn Write> = match filename.starts_with("s3://") {
+        true => s3_output(filename)?,
+        false => Box::new(BufWriter::new(File::create(filename)?)),
+    };
+    match output_compression(filename, compression) {
+        Compression::None => Ok(Box::new(file)),
+        Compression::Gzip => Ok(Box::new(GzEncoder::new(
+            file,
+            flate2::Compression::default(),
+        ))),
+        Compression::Bgzip => Ok(Box::new(BgzfWriter::new(file))),
+    }
+}
+
+/// Opens an input file, its bytes are counted in the progress bar
+#[cfg(feature = "progress")]
+fn input_file(filename: &str) -> Result<Box<dyn Read>, std::io::Error> {
+    Ok(Box::new(crate::progress::ProgressReader::new(File::open(
+        filename,
+    )?)))
+}
+
+#[cfg(not(feature = "progress"))]
+fn input_file(filename: &str) -> Result<Box<dyn Read>, std::io::Error> {
+    Ok(Box::new(File::open(filename)?))
+}
+
+#[cfg(feature = "s3")]
+fn s3_output(uri: &str) -> Result<Box<dyn Write>, std::io::Error> {
+    Ok(Box::new(crate::s3_output::S3Writer::new(uri)?))
+}
+
+#[cfg(not(feature = "s3"))]
+fn s3_output(_uri: &str) -> Result<Box<dyn Write>, std::io::Error> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "atg was built without S3 support, rebuild it with `--features s3`",
+    ))
+}
+
+/// Writes blocked gzip (BGZF) data, as specified in the SAM specification
+///
+/// The end-of-file marker is written when the writer is dropped.
+pub struct BgzfWriter<W: Write> {
+    inner: W,
+    buffer: Vec<u8>,
+    /// Number of compressed bytes written so far, i.e. the offset of the current block
+    block_address: u64,
+}
+
+impl<W: Write> BgzfWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            inner: writer,
+            buffer: Vec::with_capacity(BGZF_BLOCK_SIZE),
+            block_address: 0,
+        }
+    }
+
+    /// Returns the virtual offset of the next byte, as used in tabix indexes
+    ///
+    /// The upper 48 bits are the offset of the BGZF block in the compressed
+    /// file, the lower 16 bits the offset within the uncompressed block.
+    pub fn virtual_offset(&self) -> u64 {
+        (self.block_address << 16) | self.buffer.len() as u64
+    }
+
+    /// Compresses the buffered data into a single block
+    fn write_block(&mut self) -> Result<(), std::io::Error> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let mut encoder = DeflateEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(&self.buffer)?;
+        let compressed = encoder.finish()?;
+
+        let mut crc = Crc::new();
+        crc.update(&self.buffer);
+
+        // total block size - 1, including the header (18 bytes) and footer (8 bytes)
+        let block_size = u16::try_from(compressed.len() + 25).map_err(|_| {
+            std::io::Error::other("the compressed BGZF block exceeds the maximum size")
+        })?;
+        self.inner.write_all(&[
+            0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, 0x42, 0x43,
+            0x02, 0x00,
+        ])?;
+        self.inner.write_all(&block_size.to_le_bytes())?;
+        self.inner.write_all(&compressed)?;
+        self.inner.write_all(&crc.sum().to_le_bytes())?;
+        self.inner.write_all(&crc.amount().to_le_bytes())?;
+        self.block_address += u64::from(block_size) + 1;
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for BgzfWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, std::io::Error> {
+        let len = buf.len().min(BGZF_BLOCK_SIZE - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+        if self.buffer.len() == BGZF_BLOCK_SIZE {
+            self.write_block()?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> Result<(), std::io::Error> {
+        self.write_block()?;
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for BgzfWriter<W> {
+    fn drop(&mut self) {
+        let finished = self
+            .write_block()
+            .and_then(|_| self.inner.write_all(&BGZF_EOF))
+            .and_then(|_| self.inner.flush());
+        if let Err(err) = finished {
+            error!(target: "atg::write", "Unable to finish the bgzip output: {}", err);
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! Handling of transcripts on contigs that are missing from the reference genome
+
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader};
+
+use atglib::models::{Exon, Transcript, TranscriptBuilder};
+use atglib::utils::errors::AtgError;
+
+use crate::cli::ContigPolicy;
+use crate::report::{MissingContig, RunReport};
+use crate::warnings::warning;
+
+/// Resolves transcripts on contigs that are missing from the reference genome
+pub struct ContigResolver {
+    chroms: HashSet<String>,
+    policy: ContigPolicy,
+    // contig => (primary chromosome, offset)
+    mapping: HashMap<String, (String, u32)>,
+}
+
+impl ContigResolver {
+    /// Creates a resolver for the chromosomes of the reference genome
+    pub fn new<I: IntoIterator<Item = String>>(chroms: I, policy: ContigPolicy) -> Self {
+        Self {
+            chroms: chroms.into_iter().collect(),
+            policy,
+            mapping: HashMap::new(),
+        }
+    }
+
+    /// Reads the placement of contigs on the primary chromosomes
+    ///
+    /// Every line contains the contig name, the name of the primary chromosome
+    /// and the 0-based offset of the contig on the primary chromosome,
+    /// separated by tab.
+    pub fn add_mapping_from_file(&mut self, filename: &str) -> Result<(), AtgError> {
+        let reader = BufReader::new(std::fs::File::open(filename)?);
+        for (line_no, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let cols: Vec<&str> = line.split('\t').collect();
+            let offset = match cols.as_slice() {
+                [_, _, offset, ..] => offset.parse::<u32>().ok(),
+                _ => None,
+            };
+            match offset {
+                Some(offset) => {
+                    self.mapping
+                        .insert(cols[0].to_string(), (cols[1].to_string(), offset));
+                }
+                None => {
+                    return Err(AtgError::new(format!(
+                        "invalid contig mapping in line {} of {}",
+                        line_no + 1,
+                        filename
+                    )))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the transcript, if its contig is present in the reference genome
+    ///
+    /// Transcripts on missing contigs are handled according to the policy and
+    /// recorded in the report. `None` is returned for skipped transcripts.
+    pub fn resolve(
+        &self,
+        tx: Transcript,
+        report: &mut RunReport,
+    ) -> Result<Option<Transcript>, AtgError> {
+        if self.chroms.contains(tx.chrom()) {
+            return Ok(Some(tx));
+        }
+
+        let mut missing = MissingContig {
+            name: tx.name().to_string(),
+            chrom: tx.chrom().to_string(),
+            action: "skipped".to_string(),
+        };
+
+        let res = match self.policy {
+            ContigPolicy::Error => {
+                return Err(AtgError::new(format!(
+                "contig {} of {} is not present in the reference genome (see `--missing-contigs`)",
+                tx.chrom(),
+                tx.name()
+            )))
+            }
+            ContigPolicy::Skip => None,
+            ContigPolicy::MapToPrimary => match self.mapping.get(tx.chrom()) {
+                Some((chrom, offset)) if self.chroms.contains(chrom) => {
+                    missing.action = format!("mapped to {}", chrom);
+                    Some(shift_transcript(&tx, chrom, *offset)?)
+                }
+                _ => None,
+            },
+        };
+
+        warning(
+            "atg::contigs",
+            "missing-contig",
+            &format!(
+                "{} on {} is {}",
+                missing.name, missing.chrom, missing.action
+            ),
+        );
+        report.add_missing_contig(missing);
+        Ok(res)
+    }
+}
+
+/// Returns a copy of the transcript on another chromosome, shifted by `offset` bases
+pub fn shift_transcript(tx: &Transcript, chrom: &str, offset: u32) -> Result<Transcript, AtgError> {
+    let shift = |pos: u32| -> Result<u32, AtgError> {
+        pos.checked_add(offset).ok_or_else(|| {
+            AtgError::new(format!(
+                "{} can't be shifted by {} bases to {}",
+                tx.name(),
+                offset,
+                chrom
+            ))
+        })
+    };
+
+    // the bin is calculated from the coordinates, so it can't be kept
+    let mut shifted = TranscriptBuilder::new()
+        .bin(None)
+        .name(tx.name())
+        .chrom(chrom)
+        .gene(tx.gene())
+        .strand(*tx.strand())
+        .cds_start_codon_stat(tx.cds_start_codon_stat())?
+        .cds_stop_codon_stat(tx.cds_stop_codon_stat())?
+        .score(*tx.score())
+        .build()?;
+
+    for exon in tx.exons() {
+        shifted.push_exon(Exon::new(
+            shift(exon.start())?,
+            shift(exon.end())?,
+            (*exon.cds_start()).map(shift).transpose()?,
+            (*exon.cds_end()).map(shift).transpose()?,
+            *exon.frame_offset(),
+        ));
+    }
+    Ok(shifted)
+}
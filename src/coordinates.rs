@@ -0,0 +1,141 @@
+//! Conversion between genomic, cDNA and CDS positions of a transcript
+
+use std::fmt;
+
+use atglib::models::{Exon, Strand, Transcript};
+
+use crate::utils::{exon_len, sorted_exons};
+
+/// A position relative to the CDS
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CdsPosition {
+    /// Distance to the first CDS base, in the 5'UTR (`c.-12`)
+    FivePrimeUtr(u64),
+    /// Position within the CDS (`c.123`)
+    Cds(u64),
+    /// Distance to the last CDS base, in the 3'UTR (`c.*45`)
+    ThreePrimeUtr(u64),
+}
+
+impl fmt::Display for CdsPosition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CdsPosition::FivePrimeUtr(pos) => write!(f, "-{}", pos),
+            CdsPosition::Cds(pos) => write!(f, "{}", pos),
+            CdsPosition::ThreePrimeUtr(pos) => write!(f, "*{}", pos),
+        }
+    }
+}
+
+/// The exons of a transcript, in the direction of transcription
+pub struct TranscriptCoordinates<'a> {
+    exons: Vec<&'a Exon>,
+    minus: bool,
+    /// cDNA positions of the first and last CDS base
+    cds: Option<(u64, u64)>,
+}
+
+impl<'a> TranscriptCoordinates<'a> {
+    pub fn new(tx: &'a Transcript) -> Self {
+        let mut exons = sorted_exons(tx);
+        let minus = matches!(tx.strand(), Strand::Minus);
+        if minus {
+            exons.reverse();
+        }
+        let mut coordinates = Self {
+            exons,
+            minus,
+            cds: None,
+        };
+        if let (Some(cds_start), Some(cds_end)) = (tx.cds_start(), tx.cds_end()) {
+            let (first, last) = match minus {
+                true => (cds_end, cds_start),
+                false => (cds_start, cds_end),
+            };
+            coordinates.cds = coordinates
+                .genomic_to_cdna(first)
+                .zip(coordinates.genomic_to_cdna(last));
+        }
+        coordinates
+    }
+
+    /// Returns the exons, in the direction of transcription
+    pub fn exons(&self) -> &[&'a Exon] {
+        &self.exons
+    }
+
+    /// Returns the exon number (1-based) and cDNA position of a genomic position
+    ///
+    /// Returns `None` for positions outside of all exons.
+    pub fn exon_at(&self, pos: u32) -> Option<(usize, u64)> {
+        let mut offset = 0;
+        for (idx, exon) in self.exons.iter().enumerate() {
+            if exon.start() <= pos && pos <= exon.end() {
+                let within = match self.minus {
+                    true => exon.end() - pos,
+                    false => pos - exon.start(),
+                };
+                return Some((idx + 1, offset + u64::from(within) + 1));
+            }
+            offset += exon_len(exon);
+        }
+        None
+    }
+
+    /// Returns the cDNA position of a genomic position
+    pub fn genomic_to_cdna(&self, pos: u32) -> Option<u64> {
+        self.exon_at(pos).map(|(_, cdna)| cdna)
+    }
+
+    /// Returns the genomic position of a cDNA position
+    pub fn cdna_to_genomic(&self, cdna: u64) -> Option<u32> {
+        let mut offset = 0;
+        for exon in &self.exons {
+            let len = exon_len(exon);
+            if cdna > offset && cdna <= offset + len {
+                // the offset within the exon is smaller than the exon length, a u32
+                let within = (cdna - offset - 1) as u32;
+                return Some(match self.minus {
+                    true => exon.end() - within,
+                    false => exon.start() + within,
+                });
+            }
+            offset += len;
+        }
+        None
+    }
+
+    /// Returns the CDS position of a cDNA position, `None` for non-coding transcripts
+    pub fn cdna_to_cds(&self, cdna: u64) -> Option<CdsPosition> {
+        let (first, last) = self.cds?;
+        Some(match cdna {
+            cdna if cdna < first => CdsPosition::FivePrimeUtr(first - cdna),
+            cdna if cdna > last => CdsPosition::ThreePrimeUtr(cdna - last),
+            cdna => CdsPosition::Cds(cdna - first + 1),
+        })
+    }
+
+    /// Returns the cDNA position of a CDS position
+    ///
+    /// Returns `None` for non-coding transcripts and positions outside of the CDS and UTRs.
+    pub fn cds_to_cdna(&self, pos: CdsPosition) -> Option<u64> {
+        let (first, last) = self.cds?;
+        match pos {
+            CdsPosition::FivePrimeUtr(distance) if distance > 0 => first.checked_sub(distance),
+            CdsPosition::Cds(pos) if pos > 0 && pos <= last - first + 1 => Some(first + pos - 1),
+            CdsPosition::ThreePrimeUtr(distance) if distance > 0 => Some(last + distance),
+            _ => None,
+        }
+        .filter(|cdna| *cdna > 0)
+    }
+
+    /// Returns the CDS position of a genomic position
+    pub fn genomic_to_cds(&self, pos: u32) -> Option<CdsPosition> {
+        self.cdna_to_cds(self.genomic_to_cdna(pos)?)
+    }
+
+    /// Returns the genomic position of a CDS position
+    pub fn cds_to_genomic(&self, pos: CdsPosition) -> Option<u32> {
+        self.cdna_to_genomic(self.cds_to_cdna(pos)?)
+    }
+}
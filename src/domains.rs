@@ -0,0 +1,213 @@
+//! Projection of protein domains onto the genome
+
+use std::io::{BufRead, BufReader, Write};
+
+use atglib::models::{Frame, Strand, Transcript};
+use atglib::utils::errors::AtgError;
+
+use crate::lookup::TranscriptIndex;
+use crate::utils::interval_len;
+use crate::warnings::warning;
+
+/// A protein domain of a transcript, in amino acid coordinates (1-based, inclusive)
+pub struct Domain {
+    pub transcript: String,
+    pub name: String,
+    pub start: u32,
+    pub end: u32,
+    /// The chromosome of the transcript, for transcripts that are present on multiple chromosomes
+    pub chrom: Option<String>,
+}
+
+/// Reads protein domains from a tab-separated file
+///
+/// Every line contains the transcript name, the name of the domain and
+/// the start and end amino acid position (1-based, inclusive). An optional
+/// fifth column specifies the chromosome of the transcript.
+/// Empty lines and lines starting with `#` are ignored.
+pub fn read_domains(filename: &str) -> Result<Vec<Domain>, AtgError> {
+    let reader = BufReader::new(std::fs::File::open(filename)?);
+    let mut domains = vec![];
+    for (line_no, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let cols: Vec<&str> = line.split('\t').collect();
+        let domain = match cols.as_slice() {
+            [transcript, name, start, end, rest @ ..] => {
+                match (start.parse::<u32>(), end.parse::<u32>()) {
+                    (Ok(start), Ok(end)) if start >= 1 && start <= end => Some(Domain {
+                        transcript: transcript.to_string(),
+                        name: name.to_string(),
+                        start,
+                        end,
+                        chrom: rest
+                            .first()
+                            .filter(|chrom| !chrom.is_empty())
+                            .map(|chrom| chrom.to_string()),
+                    }),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        match domain {
+            Some(domain) => domains.push(domain),
+            None => {
+                return Err(AtgError::new(format!(
+                    "invalid protein domain in line {} of {}",
+                    line_no + 1,
+                    filename
+                )))
+            }
+        }
+    }
+    Ok(domains)
+}
+
+/// Returns the genomic blocks of the amino acid range, sorted by their position
+///
+/// The amino acid range is mapped to the CDS, following the coding exons in the
+/// direction of transcription. Amino acid 1 is the first complete codon,
+/// so transcripts with an incomplete 5' CDS (a non-zero frame of the first
+/// coding exon) are handled correctly. Ranges that extend beyond the end of the
+/// CDS are truncated.
+///
+/// Returns `None` if the range starts after the end of the CDS.
+pub fn project_domain(tx: &Transcript, aa_start: u32, aa_end: u32) -> Option<Vec<(u32, u32)>> {
+    let mut segments: Vec<(u32, u32, Frame)> = tx
+        .exons()
+        .iter()
+        .filter_map(|exon| match (exon.cds_start(), exon.cds_end()) {
+            (Some(start), Some(end)) if start <= end => Some((*start, *end, *exon.frame_offset())),
+            _ => None,
+        })
+        .collect();
+    segments.sort_by_key(|segment| segment.0);
+
+    let minus = matches!(tx.strand(), Strand::Minus);
+    if minus {
+        segments.reverse();
+    }
+
+    // the bases of an incomplete first codon are not part of any amino acid
+    let skip: u64 = match segments.first().map(|segment| segment.2) {
+        Some(Frame::One) => 1,
+        Some(Frame::Two) => 2,
+        _ => 0,
+    };
+
+    // 0-based, inclusive positions within the CDS
+    let first = skip + (u64::from(aa_start) - 1) * 3;
+    let last = skip + u64::from(aa_end) * 3 - 1;
+
+    let mut blocks = vec![];
+    let mut offset: u64 = 0;
+    for (start, end, _) in segments {
+        let len = interval_len(start, end);
+        let lo = first.max(offset);
+        let hi = last.min(offset + len - 1);
+        if lo <= hi {
+            // both values are smaller than the segment length, which fits into u32
+            let (lo, hi) = ((lo - offset) as u32, (hi - offset) as u32);
+            blocks.push(match minus {
+                true => (end - hi, end - lo),
+                false => (start + lo, start + hi),
+            });
+        }
+        offset += len;
+    }
+
+    if blocks.is_empty() {
+        return None;
+    }
+    blocks.sort_unstable();
+    Some(blocks)
+}
+
+/// Writes the protein domains as BED12 lines, with one block per coding exon
+///
+/// Domains of transcripts that are not present, or that lie outside of
+/// the CDS, are skipped with a warning.
+pub fn write_domain_bed<W: Write>(
+    transcripts: &[Transcript],
+    domains: &[Domain],
+    mut out: W,
+) -> Result<(), std::io::Error> {
+    let index = TranscriptIndex::new(transcripts);
+
+    for domain in domains {
+        let txs: Vec<&Transcript> = match &domain.chrom {
+            Some(chrom) => index
+                .by_name_and_chrom(&domain.transcript, chrom)
+                .into_iter()
+                .collect(),
+            None => index.by_name(&domain.transcript).to_vec(),
+        };
+        if txs.is_empty() {
+            warning(
+                "atg::write",
+                "skipped-domain",
+                &format!(
+                    "Skipping domain {}: transcript {} does not exist on {}",
+                    domain.name,
+                    domain.transcript,
+                    domain.chrom.as_deref().unwrap_or("any chromosome")
+                ),
+            );
+            continue;
+        }
+        for tx in txs {
+            match project_domain(tx, domain.start, domain.end) {
+                Some(blocks) => write_bed12_line(&mut out, tx, domain, &blocks)?,
+                None => warning(
+                    "atg::write",
+                    "skipped-domain",
+                    &format!(
+                        "Skipping domain {}: it is outside of the CDS of {} on {}",
+                        domain.name,
+                        tx.name(),
+                        tx.chrom()
+                    ),
+                ),
+            }
+        }
+    }
+    out.flush()
+}
+
+/// Writes a BED12 line of 1-based, inclusive blocks
+fn write_bed12_line<W: Write>(
+    out: &mut W,
+    tx: &Transcript,
+    domain: &Domain,
+    blocks: &[(u32, u32)],
+) -> Result<(), std::io::Error> {
+    // blocks is never empty
+    let start = blocks[0].0.saturating_sub(1);
+    let end = blocks[blocks.len() - 1].1;
+    let sizes: String = blocks
+        .iter()
+        .map(|block| format!("{},", interval_len(block.0, block.1)))
+        .collect();
+    let starts: String = blocks
+        .iter()
+        .map(|block| format!("{},", block.0.saturating_sub(1) - start))
+        .collect();
+    writeln!(
+        out,
+        "{}\t{}\t{}\t{}:{}\t0\t{}\t{}\t{}\t0\t{}\t{}\t{}",
+        tx.chrom(),
+        start,
+        end,
+        tx.name(),
+        domain.name,
+        tx.strand(),
+        start,
+        end,
+        blocks.len(),
+        sizes,
+        starts
+    )
+}
@@ -0,0 +1,239 @@
+//! Download transcripts from the Ensembl REST API
+
+use std::collections::HashSet;
+use std::convert::TryFrom;
+
+use atglib::models::{CdsStat, Strand, Transcript, TranscriptBuilder, TranscriptRead, Transcripts};
+use atglib::utils::errors::ReadWriteError;
+use serde_json::{json, Value};
+
+use crate::bed12::build_exons;
+use crate::region::Region;
+use crate::warnings::warning;
+
+/// The maximum number of IDs or symbols per POST request
+const MAX_POST_SIZE: usize = 1000;
+
+/// The maximum length of a region for the overlap endpoint
+const MAX_REGION_LEN: u32 = 5_000_000;
+
+/// Downloads all transcripts of genes from Ensembl
+pub struct Reader {
+    server: String,
+    species: String,
+    genes: Vec<String>,
+    regions: Vec<Region>,
+}
+
+impl Reader {
+    pub fn new(server: &str, species: &str) -> Self {
+        Self {
+            server: server.trim_end_matches('/').to_string(),
+            species: species.to_string(),
+            genes: vec![],
+            regions: vec![],
+        }
+    }
+
+    /// Adds genes to download, by their symbol
+    pub fn add_genes(&mut self, genes: &[String]) {
+        self.genes.extend_from_slice(genes);
+    }
+
+    /// Adds regions to download all overlapping genes
+    pub fn add_regions(&mut self, regions: &[Region]) {
+        self.regions.extend_from_slice(regions);
+    }
+
+    fn get(&self, endpoint: &str) -> Result<Value, ReadWriteError> {
+        let url = format!("{}/{}", self.server, endpoint);
+        debug!(target: "atg::ensembl", "GET {}", url);
+        let response = ureq::get(&url)
+            .set("Content-Type", "application/json")
+            .call()
+            .map_err(|err| ReadWriteError::new(format!("Ensembl request failed: {}", err)))?;
+        serde_json::from_reader(response.into_reader()).map_err(ReadWriteError::new)
+    }
+
+    fn post(&self, endpoint: &str, body: &Value) -> Result<Value, ReadWriteError> {
+        let url = format!("{}/{}", self.server, endpoint);
+        debug!(target: "atg::ensembl", "POST {}", url);
+        let response = ureq::post(&url)
+            .set("Content-Type", "application/json")
+            .set("Accept", "application/json")
+            .send_string(&body.to_string())
+            .map_err(|err| ReadWriteError::new(format!("Ensembl request failed: {}", err)))?;
+        serde_json::from_reader(response.into_reader()).map_err(ReadWriteError::new)
+    }
+
+    /// Returns the genes with all transcripts and exons of the gene symbols
+    fn genes_by_symbol(&self) -> Result<Vec<Value>, ReadWriteError> {
+        let mut genes = vec![];
+        for symbols in self.genes.chunks(MAX_POST_SIZE) {
+            let mut found = self.post(
+                &format!("lookup/symbol/{}", self.species),
+                &json!({ "symbols": symbols, "expand": 1 }),
+            )?;
+            for symbol in symbols {
+                match found.get_mut(symbol).map(Value::take) {
+                    Some(gene) if gene.is_object() => genes.push(gene),
+                    _ => warning(
+                        "atg::ensembl",
+                        "missing-gene",
+                        &format!("Gene {} was not found in Ensembl", symbol),
+                    ),
+                }
+            }
+        }
+        Ok(genes)
+    }
+
+    /// Returns the IDs of all genes that overlap the region
+    fn gene_ids_in(&self, region: &Region) -> Result<Vec<String>, ReadWriteError> {
+        let end = match region.end {
+            u32::MAX => self.chrom_len(&region.chrom)?,
+            end => end,
+        };
+        let mut ids = vec![];
+        let mut start = region.start;
+        while start <= end {
+            let window_end = end.min(start.saturating_add(MAX_REGION_LEN - 1));
+            let overlapping = self.get(&format!(
+                "overlap/region/{}/{}:{}-{}?feature=gene",
+                self.species, region.chrom, start, window_end
+            ))?;
+            for gene in overlapping.as_array().into_iter().flatten() {
+                if let Some(id) = gene["id"].as_str() {
+                    ids.push(id.to_string());
+                }
+            }
+            match window_end.checked_add(1) {
+                Some(next) => start = next,
+                None => break,
+            }
+        }
+        Ok(ids)
+    }
+
+    fn chrom_len(&self, chrom: &str) -> Result<u32, ReadWriteError> {
+        let info = self.get(&format!("info/assembly/{}/{}", self.species, chrom))?;
+        info["length"]
+            .as_u64()
+            .map(|len| len.min(u64::from(u32::MAX)) as u32)
+            .ok_or_else(|| ReadWriteError::new(format!("unknown chromosome {} in Ensembl", chrom)))
+    }
+
+    /// Returns the genes with all transcripts and exons of all genes in the regions
+    fn genes_by_region(&self) -> Result<Vec<Value>, ReadWriteError> {
+        let mut ids = vec![];
+        let mut seen = HashSet::new();
+        for region in &self.regions {
+            for id in self.gene_ids_in(region)? {
+                // genes can overlap several regions or windows
+                if seen.insert(id.clone()) {
+                    ids.push(id);
+                }
+            }
+        }
+        let mut genes = vec![];
+        for ids in ids.chunks(MAX_POST_SIZE) {
+            let mut found = self.post("lookup/id", &json!({ "ids": ids, "expand": 1 }))?;
+            for id in ids {
+                match found.get_mut(id).map(Value::take) {
+                    Some(gene) if gene.is_object() => genes.push(gene),
+                    _ => warning(
+                        "atg::ensembl",
+                        "missing-gene",
+                        &format!("Gene {} was not found in Ensembl", id),
+                    ),
+                }
+            }
+        }
+        Ok(genes)
+    }
+}
+
+/// Builds a transcript from the Ensembl JSON data
+fn parse_transcript(gene: &str, data: &Value) -> Option<Transcript> {
+    let name = match (data["id"].as_str()?, data["version"].as_u64()) {
+        (id, Some(version)) => format!("{}.{}", id, version),
+        (id, None) => id.to_string(),
+    };
+    let strand = match data["strand"].as_i64()? {
+        1 => Strand::Plus,
+        -1 => Strand::Minus,
+        _ => return None,
+    };
+    let coordinate = |value: &Value| value.as_u64().and_then(|pos| u32::try_from(pos).ok());
+    let mut blocks: Vec<(u32, u32)> = data["Exon"]
+        .as_array()?
+        .iter()
+        .map(|exon| Some((coordinate(&exon["start"])?, coordinate(&exon["end"])?)))
+        .collect::<Option<_>>()?;
+    if blocks.is_empty() {
+        return None;
+    }
+    blocks.sort_unstable();
+    let cds = match data.get("Translation") {
+        Some(translation) => Some((
+            coordinate(&translation["start"])?,
+            coordinate(&translation["end"])?,
+        )),
+        None => None,
+    };
+    let cds_stat = match cds {
+        Some(_) => CdsStat::Complete,
+        None => CdsStat::None,
+    };
+
+    let mut tx = TranscriptBuilder::new()
+        .name(&name)
+        .chrom(data["seq_region_name"].as_str()?)
+        .gene(gene)
+        .strand(strand)
+        .cds_start_codon_stat(cds_stat)
+        .and_then(|builder| builder.cds_stop_codon_stat(cds_stat))
+        .and_then(|builder| builder.build())
+        .ok()?;
+    for exon in build_exons(&blocks, cds, &strand) {
+        tx.push_exon(exon);
+    }
+    Some(tx)
+}
+
+impl TranscriptRead for Reader {
+    fn transcripts(&mut self) -> Result<Transcripts, ReadWriteError> {
+        let mut genes = self.genes_by_symbol()?;
+        genes.append(&mut self.genes_by_region()?);
+
+        let mut transcripts = Transcripts::new();
+        let mut seen = HashSet::new();
+        for gene in &genes {
+            // genes without a symbol use their Ensembl ID
+            let symbol = gene["display_name"]
+                .as_str()
+                .or_else(|| gene["id"].as_str())
+                .unwrap_or_default();
+            for data in gene["Transcript"].as_array().into_iter().flatten() {
+                let tx = parse_transcript(symbol, data).ok_or_else(|| {
+                    ReadWriteError::new(format!(
+                        "invalid Ensembl transcript {} of gene {}",
+                        data["id"].as_str().unwrap_or("?"),
+                        symbol
+                    ))
+                })?;
+                // a gene can be requested by symbol and region
+                if seen.insert(tx.name().to_string()) {
+                    transcripts.push(tx);
+                }
+            }
+        }
+        debug!(
+            target: "atg::ensembl",
+            "Downloaded {} transcripts of {} genes",
+            transcripts.len(),
+            genes.len()
+        );
+        Ok(transcripts)
+    }
+}
@@ -0,0 +1,95 @@
+//! Exit codes of the CLI for the different categories of errors
+
+use std::fmt;
+
+use atglib::utils::errors::AtgError;
+
+/// The category of an error
+#[derive(Clone, Copy, Debug)]
+pub enum ErrorKind {
+    /// Any other error
+    Other,
+    /// Invalid or missing CLI arguments
+    Usage,
+    /// The input files can't be read or parsed
+    Input,
+    /// The transcripts don't match the reference genome, or it can't be read
+    Reference,
+    /// The output files can't be written
+    Output,
+    /// The QC removed all transcripts
+    EmptyOutput,
+}
+
+impl ErrorKind {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ErrorKind::Other => 1,
+            ErrorKind::Usage => 2,
+            ErrorKind::Input => 3,
+            ErrorKind::Reference => 4,
+            ErrorKind::Output => 5,
+            ErrorKind::EmptyOutput => 6,
+        }
+    }
+}
+
+/// An error of the CLI, with its category and the file that caused it
+#[derive(Debug)]
+pub struct CliError {
+    kind: ErrorKind,
+    file: Option<String>,
+    err: AtgError,
+}
+
+impl CliError {
+    pub fn new<E: Into<AtgError>>(kind: ErrorKind, err: E) -> Self {
+        Self {
+            kind,
+            file: None,
+            err: err.into(),
+        }
+    }
+
+    /// Sets the file that caused the error
+    pub fn in_file(mut self, filename: &str) -> Self {
+        self.file = Some(filename.to_string());
+        self
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        self.kind.exit_code()
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.file {
+            Some(filename) => write!(f, "{}: {}", filename, self.err),
+            None => write!(f, "{}", self.err),
+        }
+    }
+}
+
+impl std::error::Error for CliError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.err)
+    }
+}
+
+impl From<AtgError> for CliError {
+    fn from(err: AtgError) -> Self {
+        Self::new(ErrorKind::Other, err)
+    }
+}
+
+/// Sets the category of the error of a `Result`
+pub trait WithKind<T> {
+    fn with_kind(self, kind: ErrorKind) -> Result<T, CliError>;
+}
+
+impl<T, E: Into<AtgError>> WithKind<T> for Result<T, E> {
+    fn with_kind(self, kind: ErrorKind) -> Result<T, CliError> {
+        self.map_err(|err| CliError::new(kind, err))
+    }
+}
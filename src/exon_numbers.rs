@@ -0,0 +1,107 @@
+//! Number the exons of GTF output in genomic order
+
+use std::io::Write;
+
+use crate::gtf_attributes::attribute;
+use crate::line_filter::{FilterLines, LineFilter};
+
+/// Renumbers the exons of minus strand transcripts in GTF lines
+///
+/// atglib numbers the exons in the order of transcription, so exon 1 of a
+/// minus strand transcript becomes the exon with the lowest position.
+///
+/// The lines of a transcript are held back until the next transcript starts
+/// or the writer is flushed. atglib's writers flush only between transcripts.
+pub struct GenomicOrder {
+    /// The `transcript_id` and the lines of the current transcript
+    transcript: Option<String>,
+    lines: Vec<String>,
+}
+
+/// Returns the column at `idx` of a tab-separated line
+fn column(line: &str, idx: usize) -> Option<&str> {
+    line.split('\t').nth(idx)
+}
+
+/// Returns the line with the value of the attribute replaced
+fn replace_attribute(line: &str, key: &str, old: &str, new: &str) -> String {
+    let pattern = format!("{} \"{}\"", key, old);
+    match line.find(&pattern) {
+        Some(pos) => format!(
+            "{}{} \"{}\"{}",
+            &line[..pos],
+            key,
+            new,
+            &line[pos + pattern.len()..]
+        ),
+        None => line.to_string(),
+    }
+}
+
+impl GenomicOrder {
+    pub fn new<W: Write>(inner: W) -> FilterLines<W, Self> {
+        FilterLines::new(
+            inner,
+            Self {
+                transcript: None,
+                lines: vec![],
+            },
+        )
+    }
+}
+
+impl LineFilter for GenomicOrder {
+    /// Holds the line back with the lines of its transcript
+    fn write_line(&mut self, line: &[u8], out: &mut dyn Write) -> std::io::Result<()> {
+        let line = String::from_utf8_lossy(line).into_owned();
+        let transcript = column(&line, 8)
+            .and_then(|attributes| attribute(attributes, "transcript_id"))
+            .map(str::to_string);
+        if transcript.is_none() || transcript != self.transcript {
+            self.finish(out)?;
+        }
+        match transcript {
+            Some(transcript) => {
+                self.transcript = Some(transcript);
+                self.lines.push(line);
+                Ok(())
+            }
+            // e.g. gene lines and comments
+            None => out.write_all(line.as_bytes()),
+        }
+    }
+
+    /// Writes all lines of the current transcript, with renumbered exons
+    fn finish(&mut self, out: &mut dyn Write) -> std::io::Result<()> {
+        let lines = std::mem::take(&mut self.lines);
+        let transcript = self.transcript.take();
+        let minus = lines
+            .first()
+            .and_then(|line| column(line, 6))
+            .is_some_and(|strand| strand == "-");
+        let number = |line: &str| {
+            column(line, 8)
+                .and_then(|attributes| attribute(attributes, "exon_number"))
+                .and_then(|number| number.parse::<usize>().ok())
+        };
+        let exon_count = lines.iter().filter_map(|line| number(line)).max();
+
+        for line in lines {
+            let renumbered = match (minus, exon_count, number(&line), &transcript) {
+                (true, Some(count), Some(old), Some(transcript)) => {
+                    let new = (count + 1 - old).to_string();
+                    let line = replace_attribute(&line, "exon_number", &old.to_string(), &new);
+                    replace_attribute(
+                        &line,
+                        "exon_id",
+                        &format!("{}.{}", transcript, old),
+                        &format!("{}.{}", transcript, new),
+                    )
+                }
+                _ => line,
+            };
+            out.write_all(renumbered.as_bytes())?;
+        }
+        Ok(())
+    }
+}
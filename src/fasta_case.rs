@@ -0,0 +1,218 @@
+//! Upper- and lowercase bases in Fasta output
+
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::rc::Rc;
+
+use atglib::models::{Strand, Transcript, TranscriptWrite};
+use atglib::utils::errors::AtgError;
+
+use crate::cli::{FastaCase, FastaFormat};
+use crate::line_filter::{FilterLines, LineFilter};
+use crate::reader_wrapper::ReadSeekWrapper;
+use crate::strandedness::segments;
+
+/// The location of a sequence in a Fasta file, see the `.fai` specification
+struct FaiEntry {
+    len: u64,
+    offset: u64,
+    line_bases: u64,
+    line_width: u64,
+}
+
+/// Reads the raw bytes of a Fasta file, without changing their case
+pub struct RawFasta<R: Read + Seek> {
+    reader: R,
+    index: HashMap<String, FaiEntry>,
+}
+
+impl RawFasta<ReadSeekWrapper> {
+    /// Opens the reference genome and its index, like `get_fasta_reader`
+    pub fn from_cli_arg(filename: &Option<&str>) -> Result<Self, AtgError> {
+        let fasta = ReadSeekWrapper::from_cli_arg(filename)?;
+        let fai = ReadSeekWrapper::from_filename(&format!("{}.fai", fasta.filename()))?;
+        Self::from_reader(fasta, fai)
+    }
+}
+
+impl<R: Read + Seek> RawFasta<R> {
+    pub fn from_reader<F: Read>(reader: R, fai: F) -> Result<Self, AtgError> {
+        let mut index = HashMap::new();
+        for line in BufReader::new(fai).lines() {
+            let line = line?;
+            let cols: Vec<&str> = line.split('\t').collect();
+            let numbers: Vec<u64> = cols
+                .iter()
+                .skip(1)
+                .take(4)
+                .filter_map(|col| col.trim().parse().ok())
+                .collect();
+            match (cols.first(), numbers.as_slice()) {
+                (Some(name), [len, offset, line_bases, line_width]) if *line_bases > 0 => {
+                    index.insert(
+                        name.to_string(),
+                        FaiEntry {
+                            len: *len,
+                            offset: *offset,
+                            line_bases: *line_bases,
+                            line_width: *line_width,
+                        },
+                    );
+                }
+                _ => return Err(AtgError::new(format!("invalid Fasta index line: {}", line))),
+            }
+        }
+        Ok(Self { reader, index })
+    }
+
+    /// Returns the bases from `start` to `end` (1-based, inclusive)
+    pub fn read_raw(&mut self, chrom: &str, start: u32, end: u32) -> Result<Vec<u8>, AtgError> {
+        let entry = self
+            .index
+            .get(chrom)
+            .ok_or_else(|| AtgError::new(format!("{} is not in the Fasta index", chrom)))?;
+        if start == 0 || start > end || u64::from(end) > entry.len {
+            return Err(AtgError::new(format!(
+                "{}:{}-{} is outside of the reference sequence",
+                chrom, start, end
+            )));
+        }
+        let byte = |pos: u32| {
+            let idx = u64::from(pos) - 1;
+            entry.offset + idx / entry.line_bases * entry.line_width + idx % entry.line_bases
+        };
+        let (first, last) = (byte(start), byte(end));
+        self.reader.seek(SeekFrom::Start(first))?;
+        let mut bytes = vec![0; (last - first + 1) as usize];
+        self.reader.read_exact(&mut bytes)?;
+        bytes.retain(|byte| !byte.is_ascii_whitespace());
+        Ok(bytes)
+    }
+}
+
+/// Returns for every base of the Fasta output of the transcript if it is lowercase
+///
+/// The mask is in the order of the output: 5' to 3' of the transcript, or of
+/// the antisense sequence.
+fn case_mask<R: Read + Seek>(
+    tx: &Transcript,
+    fasta: &mut RawFasta<R>,
+    format: &FastaFormat,
+    antisense: bool,
+) -> Result<Vec<bool>, AtgError> {
+    let mut mask = vec![];
+    for (start, end) in segments(tx, format) {
+        let bases = fasta.read_raw(tx.chrom(), start, end)?;
+        mask.extend(bases.iter().map(u8::is_ascii_lowercase));
+    }
+    if matches!(tx.strand(), Strand::Minus) != antisense {
+        mask.reverse();
+    }
+    Ok(mask)
+}
+
+/// The case masks of the transcripts that are not written yet
+pub type Masks = Rc<RefCell<VecDeque<Vec<bool>>>>;
+
+/// Sets the case of the bases of Fasta lines
+///
+/// With `FastaCase::Keep`, every record (starting with `>`) gets the next
+/// mask from `masks`.
+pub struct CaseLines {
+    case: FastaCase,
+    masks: Masks,
+    current: VecDeque<bool>,
+}
+
+impl CaseLines {
+    pub fn new<W: Write>(inner: W, case: FastaCase, masks: Masks) -> FilterLines<W, Self> {
+        FilterLines::new(
+            inner,
+            Self {
+                case,
+                masks,
+                current: VecDeque::new(),
+            },
+        )
+    }
+}
+
+impl LineFilter for CaseLines {
+    fn write_line(&mut self, line: &[u8], out: &mut dyn Write) -> std::io::Result<()> {
+        if line.starts_with(b">") {
+            self.current = self
+                .masks
+                .borrow_mut()
+                .pop_front()
+                .unwrap_or_default()
+                .into();
+            return out.write_all(line);
+        }
+        let mut line = line.to_vec();
+        for byte in line.iter_mut() {
+            if byte.is_ascii_whitespace() {
+                continue;
+            }
+            let lowercase = match self.case {
+                FastaCase::Upper => false,
+                FastaCase::Lower => true,
+                FastaCase::Keep => self.current.pop_front().unwrap_or_default(),
+            };
+            if lowercase {
+                byte.make_ascii_lowercase();
+            }
+        }
+        out.write_all(&line)
+    }
+}
+
+/// Wraps a Fasta writer whose output is written into `CaseLines`
+///
+/// The case mask of every transcript is read from the reference before the
+/// transcript is written.
+pub struct SoftMasked<'a, R: Read + Seek> {
+    inner: Box<dyn TranscriptWrite + 'a>,
+    fasta: RawFasta<R>,
+    format: FastaFormat,
+    antisense: bool,
+    masks: Masks,
+}
+
+impl<'a, R: Read + Seek> SoftMasked<'a, R> {
+    /// Creates a new writer, `masks` must be shared with the `CaseLines` of the output of `inner`
+    pub fn new(
+        inner: Box<dyn TranscriptWrite + 'a>,
+        fasta: RawFasta<R>,
+        format: FastaFormat,
+        antisense: bool,
+        masks: Masks,
+    ) -> Self {
+        Self {
+            inner,
+            fasta,
+            format,
+            antisense,
+            masks,
+        }
+    }
+
+    fn push_mask(&mut self, tx: &Transcript) -> Result<(), std::io::Error> {
+        let mask = case_mask(tx, &mut self.fasta, &self.format, self.antisense)
+            .map_err(|err| std::io::Error::other(err.to_string()))?;
+        self.masks.borrow_mut().push_back(mask);
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> TranscriptWrite for SoftMasked<'_, R> {
+    fn writeln_single_transcript(&mut self, transcript: &Transcript) -> Result<(), std::io::Error> {
+        self.push_mask(transcript)?;
+        self.inner.writeln_single_transcript(transcript)
+    }
+
+    fn write_single_transcript(&mut self, transcript: &Transcript) -> Result<(), std::io::Error> {
+        self.push_mask(transcript)?;
+        self.inner.write_single_transcript(transcript)
+    }
+}
@@ -0,0 +1,86 @@
+use std::sync::{Condvar, Mutex};
+
+use atglib::fasta::FastaReader;
+use atglib::utils::errors::AtgError;
+
+use crate::get_fasta_reader;
+use crate::reader_wrapper::ReadSeekWrapper;
+
+/// Idle readers and the total number of readers handed out by the pool
+struct PoolState {
+    idle: Vec<FastaReader<ReadSeekWrapper>>,
+    opened: usize,
+}
+
+/// FastaReaderPool shares a limited number of `FastaReader`s of the same reference across threads
+///
+/// Readers are opened lazily, once all existing readers are in use.
+/// The number of readers is capped, so that S3-backed references don't
+/// open more connections than necessary. If all readers are in use, the
+/// calling thread blocks until one is returned to the pool.
+pub struct FastaReaderPool {
+    filename: String,
+    max_readers: usize,
+    state: Mutex<PoolState>,
+    available: Condvar,
+}
+
+impl FastaReaderPool {
+    pub fn new(filename: &str, max_readers: usize) -> Self {
+        Self {
+            filename: filename.to_string(),
+            max_readers: max_readers.max(1),
+            state: Mutex::new(PoolState {
+                idle: vec![],
+                opened: 0,
+            }),
+            available: Condvar::new(),
+        }
+    }
+
+    /// Runs `func` with exclusive access to one of the pool's readers
+    pub fn with_reader<T, F>(&self, func: F) -> Result<T, AtgError>
+    where
+        F: FnOnce(&mut FastaReader<ReadSeekWrapper>) -> T,
+    {
+        let mut reader = self.acquire()?;
+        let res = func(&mut reader);
+        self.release(reader);
+        Ok(res)
+    }
+
+    fn acquire(&self) -> Result<FastaReader<ReadSeekWrapper>, AtgError> {
+        let mut state = self.lock();
+        loop {
+            if let Some(reader) = state.idle.pop() {
+                return Ok(reader);
+            }
+            if state.opened < self.max_readers {
+                state.opened += 1;
+                // don't block other threads while opening the file
+                drop(state);
+                debug!(target: "atg::fasta", "Opening a new FastaReader for {}", self.filename);
+                return get_fasta_reader(&Some(&self.filename)).inspect_err(|_| {
+                    self.lock().opened -= 1;
+                    self.available.notify_one();
+                });
+            }
+            state = self
+                .available
+                .wait(state)
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+
+    fn release(&self, reader: FastaReader<ReadSeekWrapper>) {
+        self.lock().idle.push(reader);
+        self.available.notify_one();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, PoolState> {
+        // The state remains consistent even if another thread panicked
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
@@ -0,0 +1,93 @@
+//! BED6 output with one line per exon, CDS, UTR or intron
+
+use std::io::{BufWriter, Write};
+
+use atglib::models::{Strand, Transcript, TranscriptWrite};
+
+use crate::cli::BedFormat;
+use crate::utils::{intron_coordinates, numbered_exons};
+
+/// Returns the features of a transcript, as 1-based, inclusive intervals
+fn features(tx: &Transcript, format: BedFormat) -> Vec<(u32, u32, String)> {
+    let cds = tx.cds_start().zip(tx.cds_end());
+    let (left_utr, right_utr) = match tx.strand() {
+        Strand::Minus => ("3utr", "5utr"),
+        _ => ("5utr", "3utr"),
+    };
+
+    let mut features = vec![];
+    for (number, exon) in numbered_exons(tx) {
+        let (start, end) = (exon.start(), exon.end());
+        match (format, cds) {
+            (BedFormat::Exons, _) => features.push((start, end, format!("exon{}", number))),
+            (BedFormat::Cds, Some((cds_start, cds_end)))
+                if start <= cds_end && cds_start <= end =>
+            {
+                let segment = (start.max(cds_start), end.min(cds_end));
+                features.push((segment.0, segment.1, format!("cds{}", number)));
+            }
+            (BedFormat::Utr, Some((cds_start, cds_end))) => {
+                if start < cds_start {
+                    let segment_end = end.min(cds_start - 1);
+                    features.push((start, segment_end, format!("{}{}", left_utr, number)));
+                }
+                if cds_end < end {
+                    let segment_start = start.max(cds_end + 1);
+                    features.push((segment_start, end, format!("{}{}", right_utr, number)));
+                }
+            }
+            _ => {}
+        }
+    }
+    if let BedFormat::Introns = format {
+        for intron in intron_coordinates(tx) {
+            let name = format!("intron{}", intron.number);
+            features.push((intron.start, intron.end, name));
+        }
+    }
+    features
+}
+
+/// Writes one BED6 line per feature of every transcript
+pub struct Writer<W: Write> {
+    inner: BufWriter<W>,
+    format: BedFormat,
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(writer: W, format: BedFormat) -> Self {
+        Self {
+            inner: BufWriter::new(writer),
+            format,
+        }
+    }
+}
+
+impl<W: Write> TranscriptWrite for Writer<W> {
+    fn writeln_single_transcript(&mut self, transcript: &Transcript) -> Result<(), std::io::Error> {
+        self.write_single_transcript(transcript)
+    }
+
+    fn write_single_transcript(&mut self, tx: &Transcript) -> Result<(), std::io::Error> {
+        for (start, end, feature) in features(tx, self.format) {
+            writeln!(
+                self.inner,
+                "{}\t{}\t{}\t{}_{}\t0\t{}",
+                tx.chrom(),
+                start - 1,
+                end,
+                tx.name(),
+                feature,
+                tx.strand()
+            )?;
+        }
+        Ok(())
+    }
+
+    fn write_transcript_vec(&mut self, transcripts: &[Transcript]) -> Result<(), std::io::Error> {
+        for tx in transcripts {
+            self.writeln_single_transcript(tx)?;
+        }
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,35 @@
+//! Remove lines of certain feature types from GTF output
+
+use std::io::Write;
+
+use crate::line_filter::{FilterLines, LineFilter};
+
+/// UTR feature types of GTF files
+pub const UTR_FEATURES: &[&str] = &["5UTR", "3UTR", "UTR"];
+
+/// Drops all GTF lines of the given feature types
+///
+/// All other lines are passed to the inner writer unchanged.
+pub struct SkipFeatures {
+    features: &'static [&'static str],
+}
+
+impl SkipFeatures {
+    pub fn new<W: Write>(inner: W, features: &'static [&'static str]) -> FilterLines<W, Self> {
+        FilterLines::new(inner, Self { features })
+    }
+}
+
+impl LineFilter for SkipFeatures {
+    /// Writes the line, unless it is one of the skipped features
+    fn write_line(&mut self, line: &[u8], out: &mut dyn Write) -> std::io::Result<()> {
+        let feature = line
+            .split(|byte| *byte == b'\t')
+            .nth(2)
+            .and_then(|feature| std::str::from_utf8(feature).ok());
+        match feature.is_some_and(|feature| self.features.contains(&feature)) {
+            true => Ok(()),
+            false => out.write_all(line),
+        }
+    }
+}
@@ -0,0 +1,57 @@
+//! Flip the strand of mis-annotated transcripts
+
+use std::collections::HashSet;
+use std::io::{BufRead, BufReader};
+
+use atglib::models::{Exon, Strand, Transcript, TranscriptBuilder};
+use atglib::utils::errors::AtgError;
+
+use crate::utils::set_frames;
+
+/// Reads the names of the transcripts to flip, one name per line
+///
+/// Empty lines and lines starting with `#` are ignored.
+pub fn read_transcript_names(filename: &str) -> Result<HashSet<String>, AtgError> {
+    let reader = BufReader::new(std::fs::File::open(filename)?);
+    let mut names = HashSet::new();
+    for line in reader.lines() {
+        let line = line?;
+        let name = line.trim();
+        if !name.is_empty() && !name.starts_with('#') {
+            names.insert(name.to_string());
+        }
+    }
+    Ok(names)
+}
+
+/// Returns a copy of the transcript on the opposite strand
+///
+/// The exon and CDS coordinates remain unchanged, but the direction of
+/// transcription is reversed. The start and stop codon stats are swapped
+/// and the frames of all coding exons are recomputed from the new 5' end
+/// of the CDS.
+///
+/// Returns `None` for transcripts with an unknown strand.
+pub fn flip_strand(tx: &Transcript) -> Result<Option<Transcript>, AtgError> {
+    let strand = match tx.strand() {
+        Strand::Plus => Strand::Minus,
+        Strand::Minus => Strand::Plus,
+        Strand::Unknown => return Ok(None),
+    };
+    let mut flipped = TranscriptBuilder::new()
+        .bin(*tx.bin())
+        .name(tx.name())
+        .chrom(tx.chrom())
+        .gene(tx.gene())
+        .strand(strand)
+        .cds_start_codon_stat(tx.cds_stop_codon_stat())?
+        .cds_stop_codon_stat(tx.cds_start_codon_stat())?
+        .score(*tx.score())
+        .build()?;
+
+    let mut exons: Vec<Exon> = tx.exons().to_vec();
+    exons.sort_by_key(|exon| exon.start());
+    set_frames(&mut exons, &strand);
+    flipped.append_exons(&mut exons);
+    Ok(Some(flipped))
+}
@@ -0,0 +1,219 @@
+//! Factory functions to create boxed readers and writers for all supported file formats
+
+use std::io::Write;
+
+use atglib::bed;
+use atglib::fasta;
+use atglib::genepred;
+use atglib::genepredext;
+use atglib::gtf;
+use atglib::models::{TranscriptRead, TranscriptWrite};
+use atglib::refgene;
+use atglib::spliceai;
+use atglib::utils::errors::AtgError;
+
+use crate::attributes::{AddAttributes, Layout};
+use crate::bed12;
+use crate::big_genepred;
+#[cfg(feature = "bin")]
+use crate::bin_format::BinReader;
+use crate::cli::{Args, BedFormat, FastaCase, InputFormat, OutputFormat, SpliceaiMode};
+use crate::codons::ExplainCodons;
+use crate::compression::create_output;
+use crate::exon_numbers::GenomicOrder;
+use crate::fasta_case::{CaseLines, Masks, RawFasta, SoftMasked};
+use crate::feature_bed;
+use crate::feature_filter::{SkipFeatures, UTR_FEATURES};
+use crate::gff3;
+use crate::gtf_groups::GroupedGtfReader;
+#[cfg(feature = "json")]
+use crate::json;
+use crate::knowngene;
+use crate::line_numbers::{LineFormat, NumberedReader};
+use crate::nucleotides::{CheckedSequences, SequenceCheck};
+use crate::qc_writer::QcWriter;
+use crate::spliceai_table::TranscriptWriter;
+use crate::strandedness::AntisenseWriter;
+use crate::ucsc_bin::RefgeneBin;
+use crate::{add_genetic_code, get_fasta_reader};
+
+#[cfg(not(feature = "bin"))]
+pub const BIN_DISABLED: &str =
+    "atg was built without support for the binary format, rebuild it with `--features bin`";
+
+#[cfg(not(feature = "json"))]
+pub const JSON_DISABLED: &str =
+    "atg was built without support for JSON, rebuild it with `--features json`";
+
+#[cfg(not(feature = "remote"))]
+pub const REMOTE_DISABLED: &str =
+    "atg was built without support for downloading from Ensembl, rebuild it with `--features remote`";
+
+/// Returns a boxed `TranscriptRead` for the given input format
+pub fn reader_for(
+    input_format: &InputFormat,
+    source: &str,
+) -> Result<Box<dyn TranscriptRead>, AtgError> {
+    let reader: Box<dyn TranscriptRead> = match input_format {
+        InputFormat::Refgene => Box::new(NumberedReader::from_file(source, LineFormat::Refgene)?),
+        InputFormat::Genepredext => {
+            Box::new(NumberedReader::from_file(source, LineFormat::Genepredext)?)
+        }
+        InputFormat::Gtf => Box::new(GroupedGtfReader::from_file(source, false)?),
+        InputFormat::Gff3 => Box::new(gff3::Reader::from_file(source)?),
+        InputFormat::Bed => Box::new(bed12::Reader::from_file(source)?),
+        InputFormat::Knowngene => Box::new(knowngene::Reader::from_file(source)?),
+        InputFormat::Ensembl => return Err(AtgError::new(
+            "Ensembl transcripts can only be downloaded for conversions, use `atg --from ensembl`",
+        )),
+        #[cfg(feature = "bin")]
+        InputFormat::Bin => Box::new(BinReader::from_file(source)?),
+        #[cfg(not(feature = "bin"))]
+        InputFormat::Bin => return Err(AtgError::new(BIN_DISABLED)),
+        #[cfg(feature = "json")]
+        InputFormat::Json => Box::new(json::Reader::from_file(source)?),
+        #[cfg(not(feature = "json"))]
+        InputFormat::Json => return Err(AtgError::new(JSON_DISABLED)),
+    };
+    Ok(reader)
+}
+
+/// Returns a boxed `TranscriptWrite` for the given output format
+///
+/// Writers that need additional configuration, e.g. a Fasta reference or
+/// genetic codes, are configured based on the CLI arguments.
+///
+/// Some output formats cannot be written transcript by transcript
+/// (e.g. `bin` or `fasta-split`), they return an error.
+pub fn writer_for(
+    output_format: &OutputFormat,
+    output: &str,
+    args: &Args,
+) -> Result<Box<dyn TranscriptWrite>, AtgError> {
+    writer_to(
+        output_format,
+        create_output(output, args.compression)?,
+        args,
+    )
+}
+
+/// Adjusts the attributes of GTF and GFF3 output and the bin of RefGene output
+///
+/// Adds the biotype and the preserved attributes with `--preserve-attributes` and numbers
+/// the exons of GTF output in genomic order with `--genomic-exon-numbers`. RefGene output gets the UCSC bin of
+/// every transcript, or `0` with `--no-refgene-bin`.
+pub fn adjust_attributes<'a>(
+    output_format: &OutputFormat,
+    file: Box<dyn Write + 'a>,
+    args: &Args,
+) -> Box<dyn Write + 'a> {
+    let preserve = args.preserves_attributes();
+    let file: Box<dyn Write + 'a> = match output_format {
+        OutputFormat::Gtf => Box::new(AddAttributes::new(file, Layout::Gtf, preserve)),
+        OutputFormat::Gff3 => Box::new(AddAttributes::new(file, Layout::Gff3, preserve)),
+        _ => file,
+    };
+    match (output_format, args.genomic_exon_numbers) {
+        (OutputFormat::Gtf, true) => Box::new(GenomicOrder::new(file)),
+        (OutputFormat::Refgene, _) => Box::new(RefgeneBin::new(file, !args.no_refgene_bin)),
+        _ => file,
+    }
+}
+
+/// Returns a boxed `TranscriptWrite` for the given output format that writes into `file`
+pub fn writer_to<'a>(
+    output_format: &OutputFormat,
+    file: Box<dyn Write + 'a>,
+    args: &Args,
+) -> Result<Box<dyn TranscriptWrite + 'a>, AtgError> {
+    let fasta_reference = args.reference.as_deref();
+    let file = adjust_attributes(output_format, file, args);
+    let writer: Box<dyn TranscriptWrite + 'a> = match output_format {
+        OutputFormat::Refgene => Box::new(refgene::Writer::new(file)),
+        OutputFormat::Genepred => Box::new(genepred::Writer::new(file)),
+        OutputFormat::Genepredext => Box::new(genepredext::Writer::new(file)),
+        OutputFormat::BigGenepred => Box::new(big_genepred::Writer::new(file)),
+        OutputFormat::Gtf if args.no_utr_lines => {
+            let mut writer = gtf::Writer::new(SkipFeatures::new(file, UTR_FEATURES));
+            writer.set_source(&args.gtf_source);
+            Box::new(ExplainCodons::new(writer))
+        }
+        OutputFormat::Gtf => {
+            let mut writer = gtf::Writer::new(file);
+            writer.set_source(&args.gtf_source);
+            Box::new(ExplainCodons::new(writer))
+        }
+        OutputFormat::Gff3 => {
+            let mut writer = gff3::Writer::new(file);
+            writer.set_source(&args.gtf_source);
+            Box::new(writer)
+        }
+        OutputFormat::Bed => match args.bed_format {
+            BedFormat::Transcript => Box::new(bed::Writer::new(file)),
+            format => Box::new(feature_bed::Writer::new(file, format)),
+        },
+        OutputFormat::Fasta => {
+            let masks = Masks::default();
+            let file = match args.fasta_case {
+                FastaCase::Upper => file,
+                case => Box::new(CaseLines::new(file, case, masks.clone())),
+            };
+            let writer: Box<dyn TranscriptWrite + 'a> = match args.antisense {
+                true => Box::new(AntisenseWriter::new(
+                    file,
+                    get_fasta_reader(&fasta_reference)?,
+                    args.fasta_format.clone(),
+                )),
+                false => {
+                    let mut writer = fasta::Writer::new(file);
+                    writer.fasta_reader(get_fasta_reader(&fasta_reference)?);
+                    writer.fasta_format(args.fasta_format.as_str());
+                    Box::new(writer)
+                }
+            };
+            let writer: Box<dyn TranscriptWrite + 'a> = match args.fasta_case {
+                FastaCase::Keep => Box::new(SoftMasked::new(
+                    writer,
+                    RawFasta::from_cli_arg(&fasta_reference)?,
+                    args.fasta_format.clone(),
+                    args.antisense,
+                    masks,
+                )),
+                _ => writer,
+            };
+            Box::new(CheckedSequences::new(
+                writer,
+                SequenceCheck::new(
+                    RawFasta::from_cli_arg(&fasta_reference)?,
+                    args.skip_invalid_sequences,
+                ),
+                args.fasta_format.clone(),
+            ))
+        }
+        OutputFormat::Spliceai => match args.spliceai_mode {
+            SpliceaiMode::Gene => Box::new(spliceai::Writer::new(file)),
+            SpliceaiMode::Transcript => Box::new(TranscriptWriter::new(file)),
+        },
+        #[cfg(feature = "json")]
+        OutputFormat::Json => Box::new(json::Writer::new(file)),
+        #[cfg(not(feature = "json"))]
+        OutputFormat::Json => return Err(AtgError::new(JSON_DISABLED)),
+        OutputFormat::Qc => {
+            let mut writer =
+                QcWriter::new(file, get_fasta_reader(&fasta_reference)?, &args.polya_stop);
+            add_genetic_code(&args.genetic_code, writer.qc_writer_mut())?;
+            writer
+                .qc_writer_mut()
+                .fasta_reader(get_fasta_reader(&fasta_reference)?);
+            writer.write_header()?;
+            Box::new(writer)
+        }
+        _ => {
+            return Err(AtgError::new(format!(
+                "{} output can not be written transcript by transcript",
+                output_format
+            )))
+        }
+    };
+    Ok(writer)
+}
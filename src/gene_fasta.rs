@@ -0,0 +1,120 @@
+//! Genomic sequence of whole genes, with the exon positions of their transcripts
+
+use std::io::{Read, Seek, Write};
+
+use atglib::fasta::FastaReader;
+use atglib::models::{Strand, Transcript};
+use atglib::utils::errors::AtgError;
+
+use crate::genes::genes;
+use crate::utils::sorted_exons;
+
+/// Number of bases per line in the Fasta output
+const LINE_WIDTH: usize = 60;
+
+/// Returns the 0-based, half-open position of a genomic interval within the gene sequence
+///
+/// The gene sequence is reverse complemented for genes on the minus strand.
+fn relative(start: u32, end: u32, gene: (u32, u32), strand: Strand) -> (u32, u32) {
+    match strand {
+        Strand::Minus => (gene.1 - end, gene.1 - start + 1),
+        _ => (start - gene.0, end - gene.0 + 1),
+    }
+}
+
+/// Writes the exons of the transcript as BED12 line, relative to the gene sequence
+fn write_bed_line<W: Write>(
+    out: &mut W,
+    seq_name: &str,
+    tx: &Transcript,
+    gene: (u32, u32),
+    strand: Strand,
+) -> Result<(), std::io::Error> {
+    let mut blocks: Vec<(u32, u32)> = sorted_exons(tx)
+        .into_iter()
+        .map(|exon| relative(exon.start(), exon.end(), gene, strand))
+        .collect();
+    blocks.sort_unstable();
+    // blocks is never empty, genes only contain transcripts with exons
+    let start = blocks[0].0;
+    let end = blocks[blocks.len() - 1].1;
+    let (thick_start, thick_end) = match (tx.cds_start(), tx.cds_end()) {
+        (Some(cds_start), Some(cds_end)) => relative(cds_start, cds_end, gene, strand),
+        _ => (start, start),
+    };
+    let sizes: String = blocks
+        .iter()
+        .map(|block| format!("{},", block.1 - block.0))
+        .collect();
+    let starts: String = blocks
+        .iter()
+        .map(|block| format!("{},", block.0 - start))
+        .collect();
+    writeln!(
+        out,
+        "{}\t{}\t{}\t{}:{}\t0\t+\t{}\t{}\t0\t{}\t{}\t{}",
+        seq_name,
+        start,
+        end,
+        tx.gene(),
+        tx.name(),
+        thick_start,
+        thick_end,
+        blocks.len(),
+        sizes,
+        starts
+    )
+}
+
+/// Writes the genomic sequence of every gene, including introns, as Fasta
+///
+/// Every gene spans from the lowest start to the highest end position of all
+/// its transcripts. The sequence of genes on the minus strand is reverse
+/// complemented. If `annotation` is given, the exons and CDS of all transcripts
+/// are written to it as BED12, relative to the gene sequence.
+pub fn write_gene_fasta<R: Read + Seek, W: Write, B: Write>(
+    transcripts: &[Transcript],
+    fastareader: &mut FastaReader<R>,
+    mut out: W,
+    mut annotation: Option<B>,
+) -> Result<(), AtgError> {
+    for gene in genes(transcripts) {
+        let bounds = gene.bounds();
+        let strand = gene.strand();
+        let mut seq =
+            fastareader.read_sequence(gene.chrom(), u64::from(bounds.0), u64::from(bounds.1))?;
+        if let Strand::Minus = strand {
+            seq.reverse_complement();
+        }
+        let seq = seq.to_string();
+
+        // gene symbols are only unique per chromosome
+        let seq_name = format!("{}_{}", gene.name(), gene.chrom());
+        writeln!(
+            out,
+            ">{} {}:{}-{}:{}",
+            seq_name,
+            gene.chrom(),
+            bounds.0,
+            bounds.1,
+            strand
+        )?;
+        for line in seq.as_bytes().chunks(LINE_WIDTH) {
+            out.write_all(line)?;
+            out.write_all(b"\n")?;
+        }
+
+        if let Some(annotation) = annotation.as_mut() {
+            for tx in gene.transcripts() {
+                if !tx.exons().is_empty() {
+                    write_bed_line(annotation, &seq_name, tx, bounds, strand)?;
+                }
+            }
+        }
+    }
+    out.flush()?;
+    if let Some(mut annotation) = annotation {
+        annotation.flush()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,260 @@
+//! Aggregate transcripts into genes
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use atglib::gtf;
+use atglib::models::{Strand, Transcript, TranscriptWrite};
+
+use crate::attributes::{gene_attributes, to_column, Attributes, Layout};
+use crate::biotype::biotype_of;
+
+/// Attributes that contain the biotype of a gene
+const GENE_BIOTYPE_KEYS: &[&str] = &["gene_type", "gene_biotype"];
+
+/// A Gene groups all transcripts with the same gene symbol on the same chromosome
+///
+/// The gene spans from the lowest start to the highest end position
+/// of all its transcripts.
+pub struct Gene<'a> {
+    name: &'a str,
+    chrom: &'a str,
+    strand: Strand,
+    start: u32,
+    end: u32,
+    cds_start: Option<u32>,
+    cds_end: Option<u32>,
+    transcripts: Vec<&'a Transcript>,
+}
+
+impl<'a> Gene<'a> {
+    fn new(tx: &'a Transcript) -> Self {
+        Self {
+            name: tx.gene(),
+            chrom: tx.chrom(),
+            strand: *tx.strand(),
+            start: tx.tx_start(),
+            end: tx.tx_end(),
+            cds_start: tx.cds_start(),
+            cds_end: tx.cds_end(),
+            transcripts: vec![tx],
+        }
+    }
+
+    fn add(&mut self, tx: &'a Transcript) {
+        self.start = self.start.min(tx.tx_start());
+        self.end = self.end.max(tx.tx_end());
+        self.cds_start = min_option(self.cds_start, tx.cds_start());
+        self.cds_end = self.cds_end.max(tx.cds_end());
+        self.transcripts.push(tx);
+    }
+
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    pub fn chrom(&self) -> &'a str {
+        self.chrom
+    }
+
+    pub fn strand(&self) -> Strand {
+        self.strand
+    }
+
+    /// Returns the lowest start and highest end position of all transcripts
+    pub fn bounds(&self) -> (u32, u32) {
+        (self.start, self.end)
+    }
+
+    pub fn transcripts(&self) -> &[&'a Transcript] {
+        &self.transcripts
+    }
+
+    /// Returns the most common biotype of the transcripts
+    ///
+    /// On a tie, the biotype of the first transcript wins.
+    pub fn biotype(&self) -> Option<String> {
+        let mut counts: Vec<(String, usize)> = vec![];
+        for tx in &self.transcripts {
+            let biotype = match biotype_of(tx.chrom(), tx.name()) {
+                Some(biotype) => biotype,
+                None => continue,
+            };
+            match counts.iter_mut().find(|(b, _)| *b == biotype) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((biotype, 1)),
+            }
+        }
+        // `max_by_key` returns the last maximum, so the counts are reversed
+        counts
+            .into_iter()
+            .rev()
+            .max_by_key(|(_, count)| *count)
+            .map(|(biotype, _)| biotype)
+    }
+
+    /// Returns the gene-level attributes
+    ///
+    /// These are the preserved attributes (see `--preserve-attributes`), e.g.
+    /// `gene_type`, and otherwise the most common biotype of the transcripts as `gene_type`.
+    pub fn attributes(&self) -> Attributes {
+        let mut attributes = gene_attributes(self.transcripts.iter().copied());
+        if !attributes
+            .iter()
+            .any(|(key, _)| GENE_BIOTYPE_KEYS.contains(&key.as_str()))
+        {
+            if let Some(biotype) = self.biotype() {
+                attributes.push(("gene_type".to_string(), biotype));
+            }
+        }
+        attributes
+    }
+
+    /// Returns the `gene` feature line of the gene in GTF format
+    pub fn to_gtf(&self, source: &str) -> String {
+        format!(
+            "{}\t{}\tgene\t{}\t{}\t.\t{}\t.\tgene_id \"{}\"; gene_name \"{}\"; transcript_count \"{}\";{}",
+            self.chrom,
+            source,
+            self.start,
+            self.end,
+            self.strand,
+            self.name,
+            self.name,
+            self.transcripts.len(),
+            to_column(self.attributes(), Layout::Gtf)
+        )
+    }
+
+    /// Returns the gene boundaries as line of the SpliceAI masking table
+    ///
+    /// Start positions are 0-based, like in the `spliceai` output. The CDS
+    /// spans from the lowest CDS start to the highest CDS end of all
+    /// coding transcripts, non-coding genes have no CDS.
+    pub fn to_spliceai(&self) -> String {
+        let (cds_start, cds_end) = match (self.cds_start, self.cds_end) {
+            (Some(start), Some(end)) => ((start - 1).to_string(), end.to_string()),
+            _ => (".".to_string(), ".".to_string()),
+        };
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            self.name,
+            self.chrom,
+            self.strand,
+            self.start - 1,
+            self.end,
+            cds_start,
+            cds_end
+        )
+    }
+}
+
+fn min_option(a: Option<u32>, b: Option<u32>) -> Option<u32> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Groups transcripts into genes
+///
+/// The genes are returned in the order of their first transcript.
+pub fn genes(transcripts: &[Transcript]) -> Vec<Gene<'_>> {
+    let mut genes: Vec<Gene> = vec![];
+    let mut index: HashMap<(&str, &str), usize> = HashMap::new();
+    for tx in transcripts {
+        match index.get(&(tx.gene(), tx.chrom())) {
+            Some(idx) => genes[*idx].add(tx),
+            None => {
+                index.insert((tx.gene(), tx.chrom()), genes.len());
+                genes.push(Gene::new(tx));
+            }
+        }
+    }
+    genes
+}
+
+/// Writes transcripts as GTF, with a `gene` line before the transcripts of every gene
+///
+/// Transcripts are grouped by their gene, so the order of the transcripts
+/// might differ from the input.
+pub fn write_gtf_with_genes<W: Write>(
+    transcripts: &[Transcript],
+    mut out: W,
+    source: &str,
+) -> Result<(), std::io::Error> {
+    for gene in genes(transcripts) {
+        writeln!(out, "{}", gene.to_gtf(source))?;
+        let mut writer = gtf::Writer::new(&mut out);
+        writer.set_source(source);
+        for tx in gene.transcripts() {
+            writer.writeln_single_transcript(tx)?;
+        }
+        writer.flush()?;
+    }
+    out.flush()
+}
+
+/// Writes the boundaries, strand and CDS bounds of every gene as SpliceAI masking table
+pub fn write_spliceai_genes<W: Write>(
+    transcripts: &[Transcript],
+    mut out: W,
+) -> Result<(), std::io::Error> {
+    writeln!(
+        out,
+        "#NAME\tCHROM\tSTRAND\tTX_START\tTX_END\tCDS_START\tCDS_END"
+    )?;
+    for gene in genes(transcripts) {
+        writeln!(out, "{}", gene.to_spliceai())?;
+    }
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::biotype::register_biotype;
+    use atglib::models::{Exon, Frame, TranscriptBuilder};
+
+    fn transcript(name: &str, start: u32, end: u32) -> Transcript {
+        let mut tx = TranscriptBuilder::new()
+            .name(name)
+            .chrom("chr21")
+            .gene("Gene-Biotype")
+            .strand(Strand::Plus)
+            .build()
+            .unwrap();
+        tx.push_exon(Exon::new(start, end, None, None, Frame::None));
+        tx
+    }
+
+    #[test]
+    fn test_gene_biotype() {
+        let transcripts = vec![
+            transcript("Biotype-1", 100, 200),
+            transcript("Biotype-2", 150, 300),
+            transcript("Biotype-3", 120, 180),
+            transcript("Biotype-4", 120, 180),
+        ];
+        register_biotype("chr21", "Biotype-1", "retained_intron");
+        register_biotype("chr21", "Biotype-2", "protein_coding");
+        register_biotype("chr21", "Biotype-3", "protein_coding");
+
+        let grouped = genes(&transcripts);
+        assert_eq!(grouped.len(), 1);
+        assert_eq!(grouped[0].biotype(), Some("protein_coding".to_string()));
+        assert_eq!(
+            grouped[0].to_gtf("test"),
+            "chr21\ttest\tgene\t100\t300\t.\t+\t.\tgene_id \"Gene-Biotype\"; gene_name \"Gene-Biotype\"; transcript_count \"4\"; gene_type \"protein_coding\";"
+        );
+
+        // a tie is resolved by the first transcript
+        let grouped = genes(&transcripts[..2]);
+        assert_eq!(grouped[0].biotype(), Some("retained_intron".to_string()));
+
+        // transcripts without biotype
+        let grouped = genes(&transcripts[3..]);
+        assert_eq!(grouped[0].biotype(), None);
+        assert!(grouped[0].attributes().is_empty());
+    }
+}
@@ -0,0 +1,503 @@
+//! Reader and writer for GFF3 files, e.g. from Gencode or Ensembl
+
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+
+use atglib::models::{
+    CdsStat, Exon, Frame, Strand, Transcript, TranscriptBuilder, TranscriptRead, TranscriptWrite,
+    Transcripts,
+};
+use atglib::utils::errors::ReadWriteError;
+
+use crate::attributes::{gene_attributes, to_column, Layout};
+use crate::biotype::{register_biotype, BIOTYPE_KEYS};
+use crate::compression::open_input;
+use crate::genes::genes;
+use crate::warnings::warning;
+
+/// Exon or CDS coordinates and the phase of CDS features
+type Segment = (u32, u32, Option<u32>);
+
+/// A feature that can be the parent of other features, e.g. a gene or transcript
+struct Parent {
+    seqid: String,
+    strand: Strand,
+    parent: Option<String>,
+    attributes: HashMap<String, String>,
+}
+
+impl Parent {
+    /// Returns the value of the first attribute that is present
+    fn attribute(&self, keys: &[&str]) -> Option<&str> {
+        keys.iter()
+            .find_map(|key| self.attributes.get(*key))
+            .map(|value| value.as_str())
+    }
+}
+
+/// Parses GFF3 data and returns `Transcripts`
+pub struct Reader<R> {
+    inner: BufReader<R>,
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            inner: BufReader::new(reader),
+        }
+    }
+}
+
+impl Reader<Box<dyn Read>> {
+    pub fn from_file(filename: &str) -> Result<Self, ReadWriteError> {
+        Ok(Self::new(open_input(filename)?))
+    }
+}
+
+/// Decodes the percent-encoded characters of GFF3 attribute values
+fn unescape(value: &str) -> String {
+    if !value.contains('%') {
+        return value.to_string();
+    }
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let hex = bytes
+            .get(idx + 1..idx + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[idx], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                idx += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                idx += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn parse_attributes(column: &str) -> HashMap<String, String> {
+    column
+        .split(';')
+        .filter_map(|pair| pair.trim().split_once('='))
+        .map(|(key, value)| (key.to_string(), unescape(value)))
+        .collect()
+}
+
+fn parse_strand(strand: &str) -> Strand {
+    match strand {
+        "+" => Strand::Plus,
+        "-" => Strand::Minus,
+        _ => Strand::Unknown,
+    }
+}
+
+/// Builds the exons from the exon and CDS segments
+///
+/// Transcripts without `exon` features use their CDS segments as exons.
+fn build_exons(mut exons: Vec<Segment>, mut cds: Vec<Segment>) -> Vec<Exon> {
+    if exons.is_empty() {
+        exons.clone_from(&cds);
+    }
+    exons.sort_unstable();
+    cds.sort_unstable();
+    exons
+        .into_iter()
+        .map(|(start, end, _)| {
+            match cds
+                .iter()
+                .find(|segment| segment.0 <= end && segment.1 >= start)
+            {
+                Some((cds_start, cds_end, phase)) => {
+                    let frame = phase
+                        .and_then(|phase| Frame::from_int(phase).ok())
+                        .unwrap_or(Frame::None);
+                    Exon::new(
+                        start,
+                        end,
+                        Some((*cds_start).max(start)),
+                        Some((*cds_end).min(end)),
+                        frame,
+                    )
+                }
+                None => Exon::new(start, end, None, None, Frame::None),
+            }
+        })
+        .collect()
+}
+
+impl<R: Read> TranscriptRead for Reader<R> {
+    fn transcripts(&mut self) -> Result<Transcripts, ReadWriteError> {
+        // features with an `ID`, in the order of the input
+        let mut parents: HashMap<String, Parent> = HashMap::new();
+        let mut order: Vec<String> = vec![];
+        let mut exons: HashMap<String, Vec<Segment>> = HashMap::new();
+        let mut cds: HashMap<String, Vec<Segment>> = HashMap::new();
+
+        for (line_no, line) in (&mut self.inner).lines().enumerate() {
+            let line = line?;
+            if line.starts_with("##FASTA") {
+                break;
+            }
+            if line.starts_with('#') || line.trim().is_empty() {
+                continue;
+            }
+            let cols: Vec<&str> = line.split('\t').collect();
+            if cols.len() != 9 {
+                return Err(ReadWriteError::new(format!(
+                    "invalid GFF3 record in line {}: expected 9 columns",
+                    line_no + 1
+                )));
+            }
+            let (start, end) = match (cols[3].parse::<u32>(), cols[4].parse::<u32>()) {
+                (Ok(start), Ok(end)) => (start, end),
+                _ => {
+                    return Err(ReadWriteError::new(format!(
+                        "invalid coordinates in line {} of the GFF3 file",
+                        line_no + 1
+                    )))
+                }
+            };
+            let attributes = parse_attributes(cols[8]);
+            let parent_ids: Vec<String> = attributes
+                .get("Parent")
+                .map(|parents| parents.split(',').map(|id| id.to_string()).collect())
+                .unwrap_or_default();
+
+            match cols[2] {
+                "exon" => {
+                    for id in parent_ids {
+                        exons.entry(id).or_default().push((start, end, None));
+                    }
+                }
+                "CDS" => {
+                    let phase = cols[7].parse::<u32>().ok();
+                    for id in parent_ids {
+                        cds.entry(id).or_default().push((start, end, phase));
+                    }
+                }
+                _ => {
+                    if let Some(id) = attributes.get("ID").cloned() {
+                        order.push(id.clone());
+                        parents.insert(
+                            id,
+                            Parent {
+                                seqid: cols[0].to_string(),
+                                strand: parse_strand(cols[6]),
+                                parent: parent_ids.into_iter().next(),
+                                attributes,
+                            },
+                        );
+                    }
+                }
+            }
+        }
+
+        let mut transcripts = Transcripts::new();
+        for id in order {
+            let tx_exons = exons.remove(&id).unwrap_or_default();
+            let tx_cds = cds.remove(&id).unwrap_or_default();
+            if tx_exons.is_empty() && tx_cds.is_empty() {
+                // genes and other features without exons
+                continue;
+            }
+            // `order` only contains IDs of `parents`
+            let record = &parents[&id];
+            let gene_record = record.parent.as_ref().and_then(|gene| parents.get(gene));
+            let name = record.attribute(&["transcript_id", "Name"]).unwrap_or(&id);
+            let gene = record
+                .attribute(&["gene_name", "gene"])
+                .or_else(|| gene_record.and_then(|gene| gene.attribute(&["Name", "gene_name"])))
+                .or(record.parent.as_deref())
+                .unwrap_or(name);
+            let cds_stat = match tx_cds.is_empty() {
+                true => CdsStat::None,
+                false => CdsStat::Complete,
+            };
+
+            let mut tx = TranscriptBuilder::new()
+                .name(name)
+                .chrom(&record.seqid)
+                .gene(gene)
+                .strand(record.strand)
+                .cds_start_codon_stat(cds_stat)
+                .and_then(|builder| builder.cds_stop_codon_stat(cds_stat))
+                .and_then(|builder| builder.build())
+                .map_err(|err| {
+                    ReadWriteError::new(format!("invalid transcript {}: {}", id, err))
+                })?;
+            for exon in build_exons(tx_exons, tx_cds) {
+                tx.push_exon(exon);
+            }
+            if let Some(biotype) = record.attribute(BIOTYPE_KEYS) {
+                register_biotype(&record.seqid, name, biotype);
+            }
+            transcripts.push(tx);
+        }
+
+        for id in exons.keys().chain(cds.keys()) {
+            warning(
+                "atg::read",
+                "missing-parent",
+                &format!(
+                    "Skipping exon or CDS features of {}: the parent feature is missing",
+                    id
+                ),
+            );
+        }
+        Ok(transcripts)
+    }
+}
+
+/// Escapes the characters with a special meaning in GFF3 attribute values
+pub fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            ';' | '=' | '&' | ',' | '%' | '\t' | '\n' => {
+                escaped.push_str(&format!("%{:02X}", c as u32))
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Returns the 5' and 3' UTR segments of a coding exon
+fn utr_segments(exon: &Exon, strand: &Strand) -> Vec<(&'static str, u32, u32)> {
+    let (cds_start, cds_end) = match (exon.cds_start(), exon.cds_end()) {
+        (Some(start), Some(end)) => (*start, *end),
+        _ => return vec![],
+    };
+    let (left, right) = match strand {
+        Strand::Minus => ("three_prime_UTR", "five_prime_UTR"),
+        _ => ("five_prime_UTR", "three_prime_UTR"),
+    };
+    let mut segments = vec![];
+    if exon.start() < cds_start {
+        segments.push((left, exon.start(), cds_start - 1));
+    }
+    if cds_end < exon.end() {
+        segments.push((right, cds_end + 1, exon.end()));
+    }
+    segments
+}
+
+/// Writes transcripts in GFF3 format
+///
+/// Every gene and transcript gets a unique `ID`. Genes and transcripts that
+/// are present on multiple chromosomes (e.g. in the pseudoautosomal regions)
+/// get the chromosome appended to their `ID`.
+pub struct Writer<W: Write> {
+    inner: BufWriter<W>,
+    source: String,
+    header_written: bool,
+    ids: HashMap<(String, String), String>,
+    used_ids: HashSet<String>,
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            inner: BufWriter::new(writer),
+            source: env!("CARGO_PKG_NAME").to_string(),
+            header_written: false,
+            ids: HashMap::new(),
+            used_ids: HashSet::new(),
+        }
+    }
+
+    /// Sets the value of the `source` column
+    pub fn set_source(&mut self, source: &str) {
+        self.source = source.to_string();
+    }
+
+    pub fn flush(&mut self) -> Result<(), std::io::Error> {
+        self.inner.flush()
+    }
+
+    fn write_header(&mut self) -> Result<(), std::io::Error> {
+        if !self.header_written {
+            writeln!(self.inner, "##gff-version 3")?;
+            self.header_written = true;
+        }
+        Ok(())
+    }
+
+    /// Returns the unique ID of a feature, or `None` if it was already written
+    fn new_id(&mut self, prefix: &str, name: &str, chrom: &str) -> Option<String> {
+        let key = (format!("{}:{}", prefix, name), chrom.to_string());
+        if self.ids.contains_key(&key) {
+            return None;
+        }
+        let mut id = key.0.clone();
+        if self.used_ids.contains(&id) {
+            id = format!("{}_{}", id, chrom);
+        }
+        self.used_ids.insert(id.clone());
+        self.ids.insert(key, id.clone());
+        Some(id)
+    }
+
+    fn id(&self, prefix: &str, name: &str, chrom: &str) -> String {
+        let key = (format!("{}:{}", prefix, name), chrom.to_string());
+        self.ids.get(&key).cloned().unwrap_or(key.0)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn write_line(
+        &mut self,
+        chrom: &str,
+        feature: &str,
+        start: u32,
+        end: u32,
+        strand: &Strand,
+        phase: &str,
+        attributes: &str,
+    ) -> Result<(), std::io::Error> {
+        writeln!(
+            self.inner,
+            "{}\t{}\t{}\t{}\t{}\t.\t{}\t{}\t{}",
+            chrom, self.source, feature, start, end, strand, phase, attributes
+        )
+    }
+
+    /// Writes the `gene` line, unless the gene was written before
+    ///
+    /// `extra` contains the preserved gene-level attributes, already formatted.
+    fn write_gene(
+        &mut self,
+        name: &str,
+        chrom: &str,
+        strand: &Strand,
+        (start, end): (u32, u32),
+        extra: &str,
+    ) -> Result<(), std::io::Error> {
+        if let Some(id) = self.new_id("gene", name, chrom) {
+            let attributes = format!("ID={};Name={}{}", escape(&id), escape(name), extra);
+            self.write_line(chrom, "gene", start, end, strand, ".", &attributes)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the transcript, exon, CDS and UTR lines of the transcript
+    fn write_transcript(&mut self, tx: &Transcript) -> Result<(), std::io::Error> {
+        let gene_id = self.id("gene", tx.gene(), tx.chrom());
+        let tx_id = match self.new_id("transcript", tx.name(), tx.chrom()) {
+            Some(id) => id,
+            None => {
+                warning(
+                    "atg::write",
+                    "duplicate-transcript",
+                    &format!(
+                        "Skipping duplicate transcript {} on {}",
+                        tx.name(),
+                        tx.chrom()
+                    ),
+                );
+                return Ok(());
+            }
+        };
+        let feature = match tx.is_coding() {
+            true => "mRNA",
+            false => "transcript",
+        };
+        let attributes = format!(
+            "ID={};Parent={};Name={};transcript_id={};gene_name={}",
+            escape(&tx_id),
+            escape(&gene_id),
+            escape(tx.name()),
+            escape(tx.name()),
+            escape(tx.gene())
+        );
+        self.write_line(
+            tx.chrom(),
+            feature,
+            tx.tx_start(),
+            tx.tx_end(),
+            tx.strand(),
+            ".",
+            &attributes,
+        )?;
+
+        let parent = format!("Parent={}", escape(&tx_id));
+        let cds_attributes = format!("ID={};{}", escape(&format!("cds:{}", tx_id)), parent);
+        let mut exons: Vec<&Exon> = tx.exons().iter().collect();
+        exons.sort_by_key(|exon| exon.start());
+        for exon in exons {
+            self.write_line(
+                tx.chrom(),
+                "exon",
+                exon.start(),
+                exon.end(),
+                tx.strand(),
+                ".",
+                &parent,
+            )?;
+            for (utr, start, end) in utr_segments(exon, tx.strand()) {
+                self.write_line(tx.chrom(), utr, start, end, tx.strand(), ".", &parent)?;
+            }
+            if let (Some(start), Some(end)) = (exon.cds_start(), exon.cds_end()) {
+                let phase = match exon.frame_offset() {
+                    Frame::None => "0".to_string(),
+                    frame => frame.to_gtf(),
+                };
+                self.write_line(
+                    tx.chrom(),
+                    "CDS",
+                    *start,
+                    *end,
+                    tx.strand(),
+                    &phase,
+                    &cds_attributes,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> TranscriptWrite for Writer<W> {
+    /// Writes the transcript, preceded by a `gene` line that spans only this transcript
+    fn writeln_single_transcript(&mut self, transcript: &Transcript) -> Result<(), std::io::Error> {
+        self.write_single_transcript(transcript)
+    }
+
+    fn write_single_transcript(&mut self, transcript: &Transcript) -> Result<(), std::io::Error> {
+        self.write_header()?;
+        self.write_gene(
+            transcript.gene(),
+            transcript.chrom(),
+            transcript.strand(),
+            (transcript.tx_start(), transcript.tx_end()),
+            &to_column(gene_attributes([transcript]), Layout::Gff3),
+        )?;
+        self.write_transcript(transcript)
+    }
+
+    /// Writes all transcripts, grouped by their gene
+    ///
+    /// The `gene` line spans all transcripts of the gene, so the order
+    /// of the transcripts might differ from the input.
+    fn write_transcript_vec(&mut self, transcripts: &[Transcript]) -> Result<(), std::io::Error> {
+        self.write_header()?;
+        for gene in genes(transcripts) {
+            self.write_gene(
+                gene.name(),
+                gene.chrom(),
+                &gene.strand(),
+                gene.bounds(),
+                &to_column(gene.attributes(), Layout::Gff3),
+            )?;
+            for tx in gene.transcripts() {
+                self.write_transcript(tx)?;
+            }
+        }
+        self.flush()
+    }
+}
@@ -0,0 +1,287 @@
+//! Quote-aware parsing of the attribute column of GTF records
+
+use std::borrow::Cow;
+
+use atglib::models::Transcript;
+use atglib::utils::errors::AtgError;
+
+use crate::utils::rebuild_transcript;
+
+/// Replaces `;` in values that are passed to atglib
+const SEMICOLON: char = '\u{1f}';
+
+/// Replaces `"` in values that are passed to atglib
+const QUOTE: char = '\u{1e}';
+
+/// Iterator over the key-value pairs of a GTF attribute column
+///
+/// The values are returned as written, without the surrounding quotes
+/// but with escape sequences (see `unescape`). Keys without value are skipped.
+pub struct Tokens<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = (&'a str, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let rest = self
+                .rest
+                .trim_start_matches(|c: char| c == ';' || c.is_whitespace());
+            if rest.is_empty() {
+                self.rest = rest;
+                return None;
+            }
+            let key_end = rest
+                .find(|c: char| c == ';' || c.is_whitespace())
+                .unwrap_or(rest.len());
+            let key = &rest[..key_end];
+            let value = rest[key_end..].trim_start_matches(char::is_whitespace);
+
+            if let Some(quoted) = value.strip_prefix('"') {
+                let end = closing_quote(quoted);
+                // anything between the closing quote and the next `;` is ignored
+                let tail = &quoted[(end + 1).min(quoted.len())..];
+                self.rest = tail.find(';').map_or("", |pos| &tail[pos..]);
+                return Some((key, &quoted[..end]));
+            }
+            let end = value.find(';').unwrap_or(value.len());
+            self.rest = &value[end..];
+            if end > 0 {
+                return Some((key, value[..end].trim_end()));
+            }
+        }
+    }
+}
+
+/// Returns the position of the first unescaped `"`, or the length of the value
+fn closing_quote(value: &str) -> usize {
+    let mut escaped = false;
+    for (idx, byte) in value.bytes().enumerate() {
+        match byte {
+            _ if escaped => escaped = false,
+            b'\\' => escaped = true,
+            b'"' => return idx,
+            _ => {}
+        }
+    }
+    value.len()
+}
+
+/// Returns the key-value pairs of a GTF attribute column
+pub fn tokens(column: &str) -> Tokens<'_> {
+    Tokens { rest: column }
+}
+
+/// Returns the (escaped) value of an attribute
+pub fn attribute<'a>(column: &'a str, key: &str) -> Option<&'a str> {
+    tokens(column)
+        .find(|(k, _)| *k == key)
+        .map(|(_, value)| value)
+}
+
+/// Resolves the escape sequences `\"` and `\\` of a value
+pub fn unescape(value: &str) -> Cow<'_, str> {
+    if !value.contains('\\') {
+        return Cow::Borrowed(value);
+    }
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some(next @ ('"' | '\\'))) => {
+                unescaped.push(next);
+                chars.next();
+            }
+            _ => unescaped.push(c),
+        }
+    }
+    Cow::Owned(unescaped)
+}
+
+/// Escapes `"` and `\` of a value, the reverse of `unescape`
+pub fn escape(value: &str) -> Cow<'_, str> {
+    if !value.contains(['"', '\\']) {
+        return Cow::Borrowed(value);
+    }
+    Cow::Owned(value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Returns the GTF line in a form that atglib's parser reads correctly
+///
+/// Values with `;` or escaped quotes are unescaped and those characters are
+/// replaced by control characters. All other lines are returned unchanged.
+pub fn protect(line: &str) -> Cow<'_, str> {
+    let start = match line.match_indices('\t').nth(7) {
+        Some((pos, _)) => pos + 1,
+        None => return Cow::Borrowed(line),
+    };
+    let column = line[start..].trim_end_matches(['\n', '\r']);
+    if !tokens(column).any(|(_, value)| value.contains([';', '\\'])) {
+        return Cow::Borrowed(line);
+    }
+    let mut protected = line[..start].to_string();
+    for (key, value) in tokens(column) {
+        let value: String = unescape(value)
+            .chars()
+            .map(|c| match c {
+                ';' => SEMICOLON,
+                '"' => QUOTE,
+                c => c,
+            })
+            .collect();
+        protected.push_str(&format!("{} \"{}\"; ", key, value));
+    }
+    protected.truncate(protected.trim_end().len());
+    protected.push_str(&line[start + column.len()..]);
+    Cow::Owned(protected)
+}
+
+/// Restores the characters that were replaced by `protect`
+fn restored(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            SEMICOLON => ';',
+            QUOTE => '"',
+            c => c,
+        })
+        .collect()
+}
+
+/// Restores the transcript and gene name of a transcript that was built from `protect`ed records
+pub fn restore(tx: Transcript) -> Result<Transcript, AtgError> {
+    let is_protected = |name: &str| name.contains([SEMICOLON, QUOTE]);
+    if !is_protected(tx.name()) && !is_protected(tx.gene()) {
+        return Ok(tx);
+    }
+    rebuild_transcript(&tx, &restored(tx.name()), tx.chrom(), &restored(tx.gene()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use atglib::models::{Strand, TranscriptBuilder};
+
+    // Ensembl style, all values are quoted
+    const ENSEMBL: &str = "14\tensembl_havana\texon\t36519683\t36520232\t.\t-\t.\tgene_id \"ENSG00000136352\"; transcript_id \"ENST00000354822\"; gene_name \"NKX2-1;variant\"; note \"the \\\"long\\\" isoform\";";
+
+    // Gencode style, with unquoted numeric values and repeated keys
+    const GENCODE: &str = "chr14\tHAVANA\texon\t36519683\t36520232\t.\t-\t.\tgene_id \"ENSG00000136352.18\"; transcript_id \"ENST00000498187.6\"; transcript_name \"NKX2-1;201\"; exon_number 1; tag \"basic\"; tag \"a \\\\ b\";";
+
+    fn column(line: &str) -> &str {
+        line.split('\t').nth(8).unwrap()
+    }
+
+    #[test]
+    fn test_tokens_ensembl() {
+        let pairs: Vec<(&str, &str)> = tokens(column(ENSEMBL)).collect();
+        assert_eq!(
+            pairs,
+            vec![
+                ("gene_id", "ENSG00000136352"),
+                ("transcript_id", "ENST00000354822"),
+                ("gene_name", "NKX2-1;variant"),
+                ("note", "the \\\"long\\\" isoform"),
+            ]
+        );
+        assert_eq!(
+            unescape(attribute(column(ENSEMBL), "note").unwrap()),
+            "the \"long\" isoform"
+        );
+    }
+
+    #[test]
+    fn test_tokens_gencode() {
+        let pairs: Vec<(&str, &str)> = tokens(column(GENCODE)).collect();
+        assert_eq!(
+            pairs,
+            vec![
+                ("gene_id", "ENSG00000136352.18"),
+                ("transcript_id", "ENST00000498187.6"),
+                ("transcript_name", "NKX2-1;201"),
+                ("exon_number", "1"),
+                ("tag", "basic"),
+                ("tag", "a \\\\ b"),
+            ]
+        );
+        assert_eq!(attribute(column(GENCODE), "tag"), Some("basic"));
+        assert_eq!(attribute(column(GENCODE), "gene_name"), None);
+    }
+
+    #[test]
+    fn test_tokens_ignore_text_after_quote() {
+        let pairs: Vec<(&str, &str)> = tokens("gene_id \"A\" x; transcript_id \"B\"").collect();
+        assert_eq!(pairs, vec![("gene_id", "A"), ("transcript_id", "B")]);
+
+        // an unterminated value ends at the end of the column
+        let pairs: Vec<(&str, &str)> = tokens("gene_id \"A;B").collect();
+        assert_eq!(pairs, vec![("gene_id", "A;B")]);
+    }
+
+    #[test]
+    fn test_escape_roundtrip() {
+        for value in ["plain", "the \"long\" isoform", "a \\ b", "\\\""] {
+            assert_eq!(unescape(&escape(value)), value);
+        }
+        assert!(matches!(escape("plain"), Cow::Borrowed(_)));
+        assert!(matches!(unescape("plain"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_protect() {
+        let protected = protect(ENSEMBL);
+        let cols: Vec<&str> = protected.split('\t').collect();
+        assert_eq!(cols.len(), 9);
+        assert_eq!(cols[..8], ENSEMBL.split('\t').collect::<Vec<&str>>()[..8]);
+        // a naive split on `;` finds every attribute again
+        let attributes: Vec<&str> = cols[8]
+            .split(';')
+            .map(str::trim)
+            .filter(|attr| !attr.is_empty())
+            .collect();
+        assert_eq!(
+            attributes,
+            vec![
+                "gene_id \"ENSG00000136352\"",
+                "transcript_id \"ENST00000354822\"",
+                "gene_name \"NKX2-1\u{1f}variant\"",
+                "note \"the \u{1e}long\u{1e} isoform\"",
+            ]
+        );
+
+        let protected = protect(GENCODE);
+        assert_eq!(
+            attribute(column(&protected), "transcript_name"),
+            Some("NKX2-1\u{1f}201")
+        );
+        assert_eq!(attribute(column(&protected), "exon_number"), Some("1"));
+        assert_eq!(
+            attribute(column(&protected), "gene_id"),
+            Some("ENSG00000136352.18")
+        );
+    }
+
+    #[test]
+    fn test_protect_keeps_plain_lines() {
+        let line = "chr1\tHAVANA\texon\t1\t10\t.\t+\t.\tgene_id \"A\"; transcript_id \"B\";";
+        assert!(matches!(protect(line), Cow::Borrowed(_)));
+        assert!(matches!(protect("chr1\tHAVANA"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_restore() {
+        let tx = TranscriptBuilder::new()
+            .name("ENST00000354822\u{1f}x")
+            .chrom("chr14")
+            .gene("NKX2-1\u{1f}variant \u{1e}A\u{1e}")
+            .strand(Strand::Minus)
+            .build()
+            .unwrap();
+        let tx = restore(tx).unwrap();
+        assert_eq!(tx.name(), "ENST00000354822;x");
+        assert_eq!(tx.gene(), "NKX2-1;variant \"A\"");
+        assert_eq!(tx.chrom(), "chr14");
+    }
+}
@@ -0,0 +1,514 @@
+//! Build transcripts from GTF data one transcript at a time
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{BufRead, BufReader, Cursor, Read};
+use std::rc::Rc;
+
+use atglib::gtf;
+use atglib::models::{Transcript, TranscriptRead, Transcripts};
+use atglib::utils::errors::{AtgError, ReadWriteError};
+
+use crate::attributes::{register_attributes, shared_attributes};
+use crate::biotype::{gtf_biotype, register_biotype};
+use crate::cli::ExonMerge;
+use crate::compression::open_input;
+use crate::gtf_attributes::{attribute, protect, restore, unescape};
+use crate::selenocysteine::{gtf_selenocysteines, register_selenocysteines};
+use crate::stream::TranscriptIter;
+use crate::structure::{merge_exons, split_exons};
+use crate::warnings::warning;
+
+/// Number of transcripts that are built together
+const BATCH_SIZE: usize = 1000;
+
+/// Number of finished transcript IDs that `--stream-gtf` remembers to detect non-consecutive records
+const RECENT_IDS: usize = 10_000;
+
+/// The ID, the records and the line numbers of the records of a transcript
+type Group = (String, String, Vec<usize>);
+
+/// A transcript of the input that could not be built
+pub struct BuildFailure {
+    pub transcript: String,
+    pub reason: String,
+}
+
+/// Features that belong to a single transcript and require a `transcript_id`
+const TRANSCRIPT_FEATURES: &[&str] = &[
+    "transcript",
+    "exon",
+    "CDS",
+    "UTR",
+    "5UTR",
+    "3UTR",
+    "five_prime_utr",
+    "three_prime_utr",
+    "start_codon",
+    "stop_codon",
+];
+
+/// Feature names of Ensembl and other GTF files and the names that atglib's parser reads
+///
+/// The names are compared case-insensitively.
+const FEATURE_ALIASES: &[(&str, &str)] = &[
+    ("five_prime_utr", "5UTR"),
+    ("three_prime_utr", "3UTR"),
+    ("selenocysteine", "Selenocysteine"),
+];
+
+/// Returns the GTF line with the feature name that atglib's parser reads
+fn normalize_feature(line: &str) -> Cow<'_, str> {
+    let mut tabs = line.match_indices('\t').map(|(pos, _)| pos);
+    let (start, end) = match (tabs.nth(1), tabs.next()) {
+        (Some(start), Some(end)) => (start + 1, end),
+        _ => return Cow::Borrowed(line),
+    };
+    let feature = &line[start..end];
+    match FEATURE_ALIASES
+        .iter()
+        .find(|(alias, name)| feature.eq_ignore_ascii_case(alias) && feature != *name)
+    {
+        Some((_, name)) => Cow::Owned(format!("{}{}{}", &line[..start], name, &line[end..])),
+        None => Cow::Borrowed(line),
+    }
+}
+
+/// Handles a GTF record without `transcript_id`
+///
+/// Gene-level records (e.g. `gene` in GENCODE) are skipped. Transcript-level
+/// records (e.g. `exon`) can't be assigned to a transcript, they are skipped
+/// with a warning in lenient mode and fail otherwise.
+fn skip_record(line: &str, line_no: usize, lenient: bool) -> Result<(), ReadWriteError> {
+    let feature = line.split('\t').nth(2).unwrap_or_default();
+    if !TRANSCRIPT_FEATURES.contains(&feature) {
+        return Ok(());
+    }
+    let record = line.trim_end_matches(['\n', '\r']);
+    match lenient {
+        true => {
+            warning(
+                "atg::read",
+                "missing-transcript-id",
+                &format!(
+                    "Skipping {} record without transcript_id in line {}: {}",
+                    feature, line_no, record
+                ),
+            );
+            Ok(())
+        }
+        false => Err(ReadWriteError::new(format!(
+            "{} record without transcript_id in line {}, use `--lenient` to skip it: {}",
+            feature, line_no, record
+        ))),
+    }
+}
+
+/// Returns the (escaped) value of the `transcript_id` attribute of a GTF line
+fn transcript_id(line: &str) -> Option<&str> {
+    attribute(line.split('\t').nth(8)?, "transcript_id")
+}
+
+/// Returns the start and end positions of all `exon` records
+fn exon_bounds(records: &str) -> Vec<(u32, u32)> {
+    records
+        .lines()
+        .map(|line| line.split('\t').collect::<Vec<&str>>())
+        .filter(|cols| cols.len() > 4 && cols[2] == "exon")
+        .filter_map(|cols| Some((cols[3].parse().ok()?, cols[4].parse().ok()?)))
+        .collect()
+}
+
+/// Returns why the GTF record is invalid, or `None` if no problem is found
+fn check_record(record: &str) -> Option<String> {
+    let cols: Vec<&str> = record.split('\t').collect();
+    if cols.len() != 9 {
+        return Some(format!("expected 9 columns, found {}", cols.len()));
+    }
+    let (start, end) = match (cols[3].parse::<u32>(), cols[4].parse::<u32>()) {
+        (Ok(start), Ok(end)) => (start, end),
+        (Err(_), _) => return Some(format!("invalid start position '{}'", cols[3])),
+        (_, Err(_)) => return Some(format!("invalid end position '{}'", cols[4])),
+    };
+    if start == 0 || start > end {
+        return Some(format!("invalid coordinates {}-{}", start, end));
+    }
+    if !["+", "-", "."].contains(&cols[6]) {
+        return Some(format!("invalid strand '{}'", cols[6]));
+    }
+    if !["0", "1", "2", "."].contains(&cols[7]) {
+        return Some(format!("invalid frame '{}'", cols[7]));
+    }
+    None
+}
+
+/// Returns the reason why the records could not be built, with the line of the invalid record
+///
+/// If no record is obviously invalid, the error refers to the first line of the transcript.
+fn describe_failure(records: &str, lines: &[usize], err: &ReadWriteError) -> String {
+    for (record, line_no) in records.lines().zip(lines) {
+        if let Some(reason) = check_record(record) {
+            return format!("line {}: {}: {}", line_no, reason, record);
+        }
+    }
+    match lines.first() {
+        Some(line_no) => format!("line {}: {}", line_no, err),
+        None => err.to_string(),
+    }
+}
+
+/// Builds the transcripts of GTF records with atglib's GTF reader
+///
+/// Attribute values with `;` or escaped quotes are protected from atglib's parser,
+/// Ensembl feature names (e.g. `five_prime_utr`) are renamed.
+fn parse(records: &str) -> Result<Vec<Transcript>, ReadWriteError> {
+    let records: String = records
+        .lines()
+        .map(|line| protect(&normalize_feature(line)).into_owned() + "\n")
+        .collect();
+    gtf::Reader::new(Cursor::new(records.as_bytes()))
+        .transcripts()?
+        .into_iter()
+        .map(|tx| restore(tx).map_err(ReadWriteError::new))
+        .collect()
+}
+
+/// Reads GTF data and builds every transcript separately
+pub struct GroupedGtfReader<R> {
+    inner: BufReader<R>,
+    lenient: bool,
+    preserve_attributes: bool,
+    exon_merge: ExonMerge,
+    failures: Vec<BuildFailure>,
+    // state of `next_transcript`
+    line: String,
+    line_no: usize,
+    pending: Option<Group>,
+    // only the last `RECENT_IDS` transcripts are remembered, so that the memory
+    // usage does not grow with the input. Records that are further apart are
+    // not detected and build two separate transcripts.
+    finished: HashSet<String>,
+    finished_order: VecDeque<String>,
+    ready: VecDeque<Transcript>,
+}
+
+impl<R: Read> GroupedGtfReader<R> {
+    /// Creates a new reader
+    ///
+    /// In lenient mode, transcripts that cannot be built are skipped.
+    /// Otherwise, reading fails with a summary of all failing transcripts.
+    pub fn new(reader: R, lenient: bool) -> Self {
+        Self {
+            inner: BufReader::new(reader),
+            lenient,
+            preserve_attributes: false,
+            exon_merge: ExonMerge::Preserve,
+            failures: vec![],
+            line: String::new(),
+            line_no: 0,
+            pending: None,
+            finished: HashSet::new(),
+            finished_order: VecDeque::new(),
+            ready: VecDeque::new(),
+        }
+    }
+
+    /// Registers the transcript-level attributes of every transcript, see `attributes`
+    pub fn set_preserve_attributes(&mut self, preserve: bool) {
+        self.preserve_attributes = preserve;
+    }
+
+    /// Sets how book-ended and overlapping exons are handled
+    pub fn set_exon_merge(&mut self, exon_merge: ExonMerge) {
+        self.exon_merge = exon_merge;
+    }
+
+    /// Returns all transcripts that could not be built
+    pub fn failures(&self) -> &[BuildFailure] {
+        &self.failures
+    }
+
+    /// Remembers a transcript ID, returns `false` if it was seen recently
+    fn mark_finished(&mut self, id: &str) -> bool {
+        if !self.finished.insert(id.to_string()) {
+            return false;
+        }
+        self.finished_order.push_back(id.to_string());
+        if self.finished_order.len() > RECENT_IDS {
+            if let Some(oldest) = self.finished_order.pop_front() {
+                self.finished.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    /// Returns the ID, the records and their line numbers of the next transcript
+    ///
+    /// The records of every transcript must be consecutive, a transcript
+    /// is complete as soon as a record of another transcript is read.
+    /// Non-consecutive records are detected within the last `RECENT_IDS` transcripts.
+    fn next_group(&mut self) -> Result<Option<Group>, ReadWriteError> {
+        loop {
+            self.line.clear();
+            if self.inner.read_line(&mut self.line)? == 0 {
+                return Ok(self.pending.take());
+            }
+            self.line_no += 1;
+            if self.line.starts_with('#') {
+                continue;
+            }
+            let id = match transcript_id(&self.line) {
+                Some(id) => id,
+                None => {
+                    skip_record(&self.line, self.line_no, self.lenient)?;
+                    continue;
+                }
+            };
+            let record = self.line.trim_end_matches(['\n', '\r']);
+            if let Some((pending_id, records, lines)) = &mut self.pending {
+                if pending_id == id {
+                    records.push_str(record);
+                    records.push('\n');
+                    lines.push(self.line_no);
+                    continue;
+                }
+            }
+            let id = id.to_string();
+            if !self.mark_finished(&id) {
+                return Err(ReadWriteError::new(format!(
+                    "the records of transcript {} are not consecutive, \
+                    the GTF input must be read completely (without `--stream-gtf`)",
+                    id
+                )));
+            }
+            let record = self.line.trim_end_matches(['\n', '\r']);
+            let next = (id, format!("{}\n", record), vec![self.line_no]);
+            if let Some(group) = self.pending.replace(next) {
+                return Ok(Some(group));
+            }
+        }
+    }
+
+    /// Merges or splits the exons of a transcript that was built from the records
+    ///
+    /// atglib merges book-ended exon records into one exon, they are split
+    /// again with `ExonMerge::Preserve`.
+    fn normalize_exons(&self, tx: &mut Transcript, records: &str) {
+        let changed = match self.exon_merge {
+            ExonMerge::Preserve => split_exons(tx, &exon_bounds(records)),
+            ExonMerge::BookEnded => merge_exons(tx, false),
+            ExonMerge::Overlapping => merge_exons(tx, true),
+        };
+        if changed > 0 {
+            debug!(
+                target: "atg::read",
+                "Normalized {} exons of {} ({:?})",
+                changed,
+                tx.name(),
+                self.exon_merge
+            );
+        }
+    }
+
+    /// Registers the biotype, selenocysteines and preserved attributes of a transcript
+    ///
+    /// A transcript ID on several chromosomes (e.g. in the pseudoautosomal
+    /// regions of chrX and chrY) is registered separately for every chromosome.
+    fn register(&self, id: &str, records: &str) {
+        let id = unescape(id);
+        let mut chroms: Vec<&str> = records
+            .lines()
+            .filter_map(|line| line.split('\t').next())
+            .collect();
+        chroms.sort_unstable();
+        chroms.dedup();
+        for chrom in &chroms {
+            let records: Cow<str> = match chroms.len() {
+                1 => Cow::Borrowed(records),
+                _ => records
+                    .lines()
+                    .filter(|line| line.split('\t').next() == Some(chrom))
+                    .map(|line| line.to_string() + "\n")
+                    .collect(),
+            };
+            if let Some(biotype) = gtf_biotype(&records) {
+                register_biotype(chrom, &id, &biotype);
+            }
+            let selenocysteines = gtf_selenocysteines(&records);
+            if !selenocysteines.is_empty() {
+                register_selenocysteines(chrom, &id, selenocysteines);
+            }
+            if self.preserve_attributes {
+                let attributes = shared_attributes(&records);
+                if !attributes.is_empty() {
+                    register_attributes(chrom, &id, attributes);
+                }
+            }
+        }
+    }
+
+    /// Builds the transcript of one group of records and queues it in `ready`
+    ///
+    /// In lenient mode, failures are only recorded.
+    fn build(&mut self, id: &str, records: &str, lines: &[usize]) -> Result<(), ReadWriteError> {
+        self.register(id, records);
+        match parse(records) {
+            Ok(txs) => {
+                for mut tx in txs {
+                    self.normalize_exons(&mut tx, records);
+                    self.ready.push_back(tx);
+                }
+                Ok(())
+            }
+            Err(err) => {
+                let reason = describe_failure(records, lines, &err);
+                warning(
+                    "atg::read",
+                    "unbuildable-transcript",
+                    &format!("Unable to build transcript {}: {}", id, reason),
+                );
+                self.failures.push(BuildFailure {
+                    transcript: id.to_string(),
+                    reason: reason.clone(),
+                });
+                match self.lenient {
+                    true => Ok(()),
+                    false => Err(ReadWriteError::new(format!(
+                        "transcript {} could not be built, use `--lenient` to skip it: {}",
+                        id, reason
+                    ))),
+                }
+            }
+        }
+    }
+}
+
+impl<R: Read> TranscriptIter for GroupedGtfReader<R> {
+    fn next_transcript(&mut self) -> Option<Result<Transcript, AtgError>> {
+        loop {
+            if let Some(tx) = self.ready.pop_front() {
+                return Some(Ok(tx));
+            }
+            let res = match self.next_group() {
+                Ok(Some((id, records, lines))) => self.build(&id, &records, &lines),
+                Ok(None) => return None,
+                Err(err) => Err(err),
+            };
+            if let Err(err) = res {
+                return Some(Err(AtgError::from(err)));
+            }
+        }
+    }
+}
+
+impl GroupedGtfReader<Box<dyn Read>> {
+    pub fn from_file(filename: &str, lenient: bool) -> Result<Self, ReadWriteError> {
+        Ok(Self::new(open_input(filename)?, lenient))
+    }
+}
+
+impl<R: Read> TranscriptRead for GroupedGtfReader<R> {
+    fn transcripts(&mut self) -> Result<Transcripts, ReadWriteError> {
+        // records of every transcript, in the order of their first record
+        let mut groups: Vec<(Rc<str>, String, Vec<usize>)> = vec![];
+        // every transcript ID is allocated only once and shared with `groups`
+        let mut index: HashMap<Rc<str>, usize> = HashMap::new();
+        let mut line = String::new();
+        let mut line_no = 0;
+        loop {
+            line.clear();
+            if self.inner.read_line(&mut line)? == 0 {
+                break;
+            }
+            line_no += 1;
+            if line.starts_with('#') {
+                continue;
+            }
+            // e.g. `gene` records don't belong to a transcript
+            let id = match transcript_id(&line) {
+                Some(id) => id,
+                None => {
+                    skip_record(&line, line_no, self.lenient)?;
+                    continue;
+                }
+            };
+            let idx = match index.get(id) {
+                Some(idx) => *idx,
+                None => {
+                    let id: Rc<str> = Rc::from(id);
+                    index.insert(Rc::clone(&id), groups.len());
+                    groups.push((id, String::new(), vec![]));
+                    groups.len() - 1
+                }
+            };
+            let (_, records, lines) = &mut groups[idx];
+            records.push_str(line.trim_end_matches(['\n', '\r']));
+            records.push('\n');
+            lines.push(line_no);
+        }
+        drop(index);
+
+        for (id, records, _) in &groups {
+            self.register(id, records);
+        }
+
+        let mut transcripts = Transcripts::new();
+        for batch in groups.chunks(BATCH_SIZE) {
+            // most transcripts are valid, so they are built in batches and
+            // only a failing batch is built again transcript by transcript
+            let records: String = batch.iter().map(|group| group.1.as_str()).collect();
+            if let Ok(mut txs) = parse(&records) {
+                let by_id: HashMap<Cow<str>, (usize, &str)> = batch
+                    .iter()
+                    .enumerate()
+                    .map(|(pos, (id, records, _))| (unescape(id), (pos, records.as_str())))
+                    .collect();
+                // atglib groups the records by hash, so the transcripts are
+                // returned in the order of their first record again
+                txs.sort_by_key(|tx| by_id.get(tx.name()).map_or(usize::MAX, |group| group.0));
+                for mut tx in txs {
+                    let records = by_id.get(tx.name()).map_or("", |group| group.1);
+                    self.normalize_exons(&mut tx, records);
+                    transcripts.push(tx);
+                }
+                continue;
+            }
+            for (id, records, lines) in batch {
+                match parse(records) {
+                    Ok(txs) => {
+                        for mut tx in txs {
+                            self.normalize_exons(&mut tx, records);
+                            transcripts.push(tx);
+                        }
+                    }
+                    Err(err) => {
+                        let reason = describe_failure(records, lines, &err);
+                        warning(
+                            "atg::read",
+                            "unbuildable-transcript",
+                            &format!("Unable to build transcript {}: {}", id, reason),
+                        );
+                        self.failures.push(BuildFailure {
+                            transcript: id.to_string(),
+                            reason,
+                        });
+                    }
+                }
+            }
+        }
+
+        if !self.lenient && !self.failures.is_empty() {
+            let summary: Vec<String> = self
+                .failures
+                .iter()
+                .map(|failure| format!(" - {}: {}", failure.transcript, failure.reason))
+                .collect();
+            return Err(ReadWriteError::new(format!(
+                "{} transcripts could not be built, use `--lenient` to skip them:\n{}",
+                self.failures.len(),
+                summary.join("\n")
+            )));
+        }
+        Ok(transcripts)
+    }
+}
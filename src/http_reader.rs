@@ -0,0 +1,97 @@
+//! Read remote files over HTTP(S) with byte-range requests
+
+use std::cmp::min;
+use std::io::{Error, ErrorKind, Read, Seek, SeekFrom};
+
+/// Minimum number of bytes to download per request
+const BLOCK_SIZE: u64 = 0x10000;
+
+/// `Read + Seek` access to a remote file
+pub struct HttpReader {
+    url: String,
+    len: u64,
+    pos: u64,
+    /// The last downloaded block and its offset in the file
+    block: Vec<u8>,
+    block_start: u64,
+}
+
+fn request_error(err: ureq::Error) -> Error {
+    Error::other(err.to_string())
+}
+
+impl HttpReader {
+    /// Opens a remote file, its size is requested from the server
+    pub fn open(url: &str) -> Result<Self, Error> {
+        let response = ureq::head(url).call().map_err(request_error)?;
+        let len = response
+            .header("Content-Length")
+            .and_then(|len| len.parse::<u64>().ok())
+            .ok_or_else(|| {
+                Error::other(format!("the server does not report the size of {}", url))
+            })?;
+        debug!(target: "atg::read", "Opened {} ({} bytes)", url, len);
+        Ok(Self {
+            url: url.to_string(),
+            len,
+            pos: 0,
+            block: vec![],
+            block_start: 0,
+        })
+    }
+
+    /// Downloads at least `size` bytes, starting at `start`
+    fn fetch(&mut self, start: u64, size: u64) -> Result<(), Error> {
+        let end = min(start + size.max(BLOCK_SIZE), self.len);
+        let response = ureq::get(&self.url)
+            .set("Range", &format!("bytes={}-{}", start, end - 1))
+            .call()
+            .map_err(request_error)?;
+        // servers without range support return the complete file
+        if response.status() != 206 {
+            return Err(Error::other(format!(
+                "the server does not support range requests for {}",
+                self.url
+            )));
+        }
+        self.block.clear();
+        response
+            .into_reader()
+            .take(end - start)
+            .read_to_end(&mut self.block)?;
+        self.block_start = start;
+        Ok(())
+    }
+}
+
+impl Read for HttpReader {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if self.pos >= self.len || buf.is_empty() {
+            return Ok(0);
+        }
+        let block_end = self.block_start + self.block.len() as u64;
+        if self.pos < self.block_start || self.pos >= block_end {
+            self.fetch(self.pos, buf.len() as u64)?;
+        }
+        // the position is within the block, so the offset fits into usize
+        let offset = (self.pos - self.block_start) as usize;
+        let available = &self.block[offset..];
+        let len = min(buf.len(), available.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+
+impl Seek for HttpReader {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Error> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        }
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "invalid seek position"))?;
+        self.pos = pos;
+        Ok(pos)
+    }
+}
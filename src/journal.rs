@@ -0,0 +1,182 @@
+//! Track changes of transcripts between two releases of an annotation
+
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
+use atglib::models::Transcript;
+use atglib::utils::errors::AtgError;
+
+use crate::merge::same_structure;
+
+/// The kind of change of a transcript between two releases
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Change {
+    /// The transcript is only present in the current release
+    Added,
+    /// The transcript is only present in the previous release
+    Removed,
+    /// The version suffix of the transcript ID changed (e.g. `NM_001.1` => `NM_001.2`)
+    VersionChanged,
+    /// The transcript has the same structure, but a different ID
+    Renamed,
+    /// The start or end of at least one exon changed
+    ExonsChanged,
+    /// The start or end of the CDS changed
+    CdsChanged,
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let s = match self {
+            Change::Added => "added",
+            Change::Removed => "removed",
+            Change::VersionChanged => "version-changed",
+            Change::Renamed => "renamed",
+            Change::ExonsChanged => "exons-changed",
+            Change::CdsChanged => "cds-changed",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// One change of a transcript between two releases
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct JournalEntry {
+    /// The stable (unversioned) gene ID
+    pub gene: String,
+    /// The transcript ID in the current release, `.` for removed transcripts
+    pub transcript: String,
+    /// The transcript ID in the previous release, `.` for added transcripts
+    pub previous: String,
+    pub change: Change,
+}
+
+impl JournalEntry {
+    fn new(previous: Option<&Transcript>, current: Option<&Transcript>, change: Change) -> Self {
+        let gene = current.or(previous).map(|tx| tx.gene()).unwrap_or(".");
+        Self {
+            gene: stable_id(gene).to_string(),
+            transcript: current.map_or(".", |tx| tx.name()).to_string(),
+            previous: previous.map_or(".", |tx| tx.name()).to_string(),
+            change,
+        }
+    }
+}
+
+/// Returns the ID without its version suffix
+///
+/// `NM_001101.5` => `NM_001101`, IDs without a numeric suffix are returned unchanged.
+pub fn stable_id(id: &str) -> &str {
+    match id.rsplit_once('.') {
+        Some((stable, version))
+            if !stable.is_empty()
+                && !version.is_empty()
+                && version.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            stable
+        }
+        _ => id,
+    }
+}
+
+fn same_exons(a: &Transcript, b: &Transcript) -> bool {
+    a.exon_count() == b.exon_count()
+        && a.exons()
+            .iter()
+            .zip(b.exons())
+            .all(|(x, y)| x.start() == y.start() && x.end() == y.end())
+}
+
+/// Compares two releases of an annotation and returns all changes
+///
+/// Transcripts are matched by their stable ID (without version) and chromosome.
+/// Transcripts that can't be matched by their ID, but have an identical structure
+/// as a transcript of the same gene in the other release, are reported as renamed.
+///
+/// The entries are sorted by gene and transcript ID.
+pub fn journal(previous: &[Transcript], current: &[Transcript]) -> Vec<JournalEntry> {
+    let mut entries = vec![];
+
+    let mut previous_index: HashMap<(&str, &str), &Transcript> = HashMap::new();
+    for tx in previous {
+        previous_index
+            .entry((stable_id(tx.name()), tx.chrom()))
+            .or_insert(tx);
+    }
+
+    let mut matched: HashSet<(&str, &str)> = HashSet::new();
+    let mut added: Vec<&Transcript> = vec![];
+
+    for tx in current {
+        let key = (stable_id(tx.name()), tx.chrom());
+        let prev = match previous_index.get(&key) {
+            Some(prev) if matched.insert(key) => *prev,
+            _ => {
+                added.push(tx);
+                continue;
+            }
+        };
+
+        if prev.name() != tx.name() {
+            entries.push(JournalEntry::new(
+                Some(prev),
+                Some(tx),
+                Change::VersionChanged,
+            ));
+        }
+        if !same_exons(prev, tx) || prev.strand() != tx.strand() {
+            entries.push(JournalEntry::new(
+                Some(prev),
+                Some(tx),
+                Change::ExonsChanged,
+            ));
+        }
+        if prev.cds_start() != tx.cds_start() || prev.cds_end() != tx.cds_end() {
+            entries.push(JournalEntry::new(Some(prev), Some(tx), Change::CdsChanged));
+        }
+    }
+
+    // unmatched transcripts of the previous release, grouped by gene
+    let mut removed: HashMap<&str, Vec<&Transcript>> = HashMap::new();
+    for tx in previous {
+        if !matched.contains(&(stable_id(tx.name()), tx.chrom())) {
+            removed.entry(stable_id(tx.gene())).or_default().push(tx);
+        }
+    }
+
+    for tx in added {
+        let candidates = removed.entry(stable_id(tx.gene())).or_default();
+        match candidates.iter().position(|prev| same_structure(prev, tx)) {
+            Some(idx) => {
+                let prev = candidates.swap_remove(idx);
+                entries.push(JournalEntry::new(Some(prev), Some(tx), Change::Renamed));
+            }
+            None => entries.push(JournalEntry::new(None, Some(tx), Change::Added)),
+        }
+    }
+
+    for prev in removed.into_values().flatten() {
+        entries.push(JournalEntry::new(Some(prev), None, Change::Removed));
+    }
+
+    entries.sort();
+    entries
+}
+
+/// Writes the change journal between two releases as tab-separated table
+pub fn write_journal<W: Write>(
+    previous: &[Transcript],
+    current: &[Transcript],
+    mut out: W,
+) -> Result<(), AtgError> {
+    writeln!(out, "Gene\tTranscript\tPrevious transcript\tChange")?;
+    for entry in journal(previous, current) {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}",
+            entry.gene, entry.transcript, entry.previous, entry.change
+        )?;
+    }
+    out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,184 @@
+//! JSON Lines input and output
+
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+
+use serde::{Deserialize, Serialize};
+
+use atglib::models::{
+    CdsStat, Exon, Frame, Strand, Transcript, TranscriptBuilder, TranscriptRead, TranscriptWrite,
+    Transcripts,
+};
+use atglib::utils::errors::ReadWriteError;
+
+use crate::compression::open_input;
+
+#[derive(Serialize, Deserialize)]
+struct JsonExon {
+    start: u32,
+    end: u32,
+    cds_start: Option<u32>,
+    cds_end: Option<u32>,
+    frame: Option<u8>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonTranscript {
+    name: String,
+    gene: String,
+    chrom: String,
+    strand: String,
+    start: u32,
+    end: u32,
+    cds_start: Option<u32>,
+    cds_end: Option<u32>,
+    cds_start_stat: String,
+    cds_end_stat: String,
+    score: Option<f32>,
+    exons: Vec<JsonExon>,
+}
+
+fn frame_to_int(frame: &Frame) -> Option<u8> {
+    match frame {
+        Frame::Zero => Some(0),
+        Frame::One => Some(1),
+        Frame::Two => Some(2),
+        Frame::None => None,
+    }
+}
+
+fn frame_from_int(frame: Option<u8>) -> Option<Frame> {
+    match frame {
+        Some(0) => Some(Frame::Zero),
+        Some(1) => Some(Frame::One),
+        Some(2) => Some(Frame::Two),
+        None => Some(Frame::None),
+        Some(_) => None,
+    }
+}
+
+impl From<&Transcript> for JsonTranscript {
+    fn from(tx: &Transcript) -> Self {
+        Self {
+            name: tx.name().to_string(),
+            gene: tx.gene().to_string(),
+            chrom: tx.chrom().to_string(),
+            strand: tx.strand().to_string(),
+            start: tx.tx_start(),
+            end: tx.tx_end(),
+            cds_start: tx.cds_start(),
+            cds_end: tx.cds_end(),
+            cds_start_stat: tx.cds_start_codon_stat().to_string(),
+            cds_end_stat: tx.cds_stop_codon_stat().to_string(),
+            score: *tx.score(),
+            exons: tx
+                .exons()
+                .iter()
+                .map(|exon| JsonExon {
+                    start: exon.start(),
+                    end: exon.end(),
+                    cds_start: *exon.cds_start(),
+                    cds_end: *exon.cds_end(),
+                    frame: frame_to_int(exon.frame_offset()),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl JsonTranscript {
+    fn build(&self) -> Result<Transcript, String> {
+        let strand: Strand = self.strand.parse()?;
+        let cds_start_stat: CdsStat = self.cds_start_stat.parse()?;
+        let cds_end_stat: CdsStat = self.cds_end_stat.parse()?;
+        let mut tx = TranscriptBuilder::new()
+            .name(&self.name)
+            .chrom(&self.chrom)
+            .gene(&self.gene)
+            .strand(strand)
+            .score(self.score)
+            .cds_start_codon_stat(cds_start_stat)
+            .and_then(|builder| builder.cds_stop_codon_stat(cds_end_stat))
+            .and_then(|builder| builder.build())
+            .map_err(|err| err.to_string())?;
+        for exon in &self.exons {
+            let frame = frame_from_int(exon.frame)
+                .ok_or_else(|| format!("invalid frame {:?}", exon.frame))?;
+            tx.push_exon(Exon::new(
+                exon.start,
+                exon.end,
+                exon.cds_start,
+                exon.cds_end,
+                frame,
+            ));
+        }
+        Ok(tx)
+    }
+}
+
+/// Parses JSON Lines and returns `Transcripts`
+pub struct Reader<R> {
+    inner: BufReader<R>,
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            inner: BufReader::new(reader),
+        }
+    }
+}
+
+impl Reader<Box<dyn Read>> {
+    pub fn from_file(filename: &str) -> Result<Self, ReadWriteError> {
+        Ok(Self::new(open_input(filename)?))
+    }
+}
+
+impl<R: Read> TranscriptRead for Reader<R> {
+    fn transcripts(&mut self) -> Result<Transcripts, ReadWriteError> {
+        let mut transcripts = Transcripts::new();
+        for (idx, line) in (&mut self.inner).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let tx = serde_json::from_str::<JsonTranscript>(&line)
+                .map_err(|err| err.to_string())
+                .and_then(|tx| tx.build())
+                .map_err(|err| {
+                    ReadWriteError::new(format!(
+                        "invalid JSON transcript in line {}: {}",
+                        idx + 1,
+                        err
+                    ))
+                })?;
+            transcripts.push(tx);
+        }
+        Ok(transcripts)
+    }
+}
+
+/// Writes every transcript as JSON object on its own line
+pub struct Writer<W: Write> {
+    inner: BufWriter<W>,
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            inner: BufWriter::new(writer),
+        }
+    }
+}
+
+impl<W: Write> TranscriptWrite for Writer<W> {
+    fn writeln_single_transcript(&mut self, transcript: &Transcript) -> Result<(), std::io::Error> {
+        self.write_single_transcript(transcript)?;
+        self.inner.write_all(b"\n")
+    }
+
+    fn write_single_transcript(&mut self, transcript: &Transcript) -> Result<(), std::io::Error> {
+        serde_json::to_writer(&mut self.inner, &JsonTranscript::from(transcript))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+//! Merge multiple position-sorted transcript streams into one sorted stream
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use atglib::models::Transcript;
+
+use crate::chromosomes::{position_cmp, sort_transcripts};
+
+/// The next transcript of one of the streams
+struct Head {
+    tx: Transcript,
+    stream: usize,
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // BinaryHeap is a max-heap, so the order is reversed.
+        // Ties are resolved by the stream order to keep the merge stable.
+        position_cmp(&other.tx, &self.tx).then_with(|| other.stream.cmp(&self.stream))
+    }
+}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Head {}
+
+/// Iterator over the transcripts of multiple sorted streams, in sorted order
+///
+/// Every stream must already be sorted by [`position_cmp`], otherwise the
+/// output is not sorted either. Only one transcript per stream is kept
+/// in memory at any time.
+pub struct KMerge<I: Iterator<Item = Transcript>> {
+    streams: Vec<I>,
+    heap: BinaryHeap<Head>,
+}
+
+impl<I: Iterator<Item = Transcript>> KMerge<I> {
+    pub fn new(streams: Vec<I>) -> Self {
+        let mut streams = streams;
+        let mut heap = BinaryHeap::with_capacity(streams.len());
+        for (idx, stream) in streams.iter_mut().enumerate() {
+            if let Some(tx) = stream.next() {
+                heap.push(Head { tx, stream: idx });
+            }
+        }
+        Self { streams, heap }
+    }
+}
+
+impl<I: Iterator<Item = Transcript>> Iterator for KMerge<I> {
+    type Item = Transcript;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let head = self.heap.pop()?;
+        if let Some(tx) = self.streams[head.stream].next() {
+            self.heap.push(Head {
+                tx,
+                stream: head.stream,
+            });
+        }
+        Some(head.tx)
+    }
+}
+
+/// Sorts transcripts in parallel, using one shard per chromosome
+///
+/// The shards are sorted by up to `threads` threads and then merged
+/// into one sorted list.
+pub fn sort_parallel(transcripts: Vec<Transcript>, threads: usize) -> Vec<Transcript> {
+    let mut shards: HashMap<String, Vec<Transcript>> = HashMap::new();
+    for tx in transcripts {
+        shards.entry(tx.chrom().to_string()).or_default().push(tx);
+    }
+    let mut shards: Vec<Vec<Transcript>> = shards.into_values().collect();
+
+    let threads = threads.max(1);
+    let shards_per_thread = shards.len().div_ceil(threads).max(1);
+    std::thread::scope(|scope| {
+        for chunk in shards.chunks_mut(shards_per_thread) {
+            scope.spawn(move || {
+                for shard in chunk {
+                    sort_transcripts(shard);
+                }
+            });
+        }
+    });
+
+    KMerge::new(shards.into_iter().map(|shard| shard.into_iter()).collect()).collect()
+}
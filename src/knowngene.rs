@@ -0,0 +1,154 @@
+//! Reader for UCSC knownGene tables
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read};
+
+use atglib::models::{CdsStat, Strand, Transcript, TranscriptBuilder, TranscriptRead, Transcripts};
+use atglib::utils::errors::{AtgError, ReadWriteError};
+
+use crate::bed12::{build_exons, parse_list};
+use crate::compression::open_input;
+
+/// Parses knownGene data and returns `Transcripts`
+pub struct Reader<R> {
+    inner: BufReader<R>,
+    // kgID => geneSymbol
+    gene_symbols: HashMap<String, String>,
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            inner: BufReader::new(reader),
+            gene_symbols: HashMap::new(),
+        }
+    }
+
+    /// Uses the gene symbols of a kgXref table, see `load_kgxref`
+    pub fn set_gene_symbols(&mut self, gene_symbols: HashMap<String, String>) {
+        self.gene_symbols = gene_symbols;
+    }
+
+    fn parse_line(&self, line: &str) -> Option<Transcript> {
+        let cols: Vec<&str> = line.split('\t').collect();
+        // proteinID and alignID are not used, GenePred data is read as well
+        if cols.len() < 10 {
+            return None;
+        }
+        let strand = match cols[2] {
+            "+" => Strand::Plus,
+            "-" => Strand::Minus,
+            _ => return None,
+        };
+        let cds_start = cols[5].parse::<u32>().ok()?;
+        let cds_end = cols[6].parse::<u32>().ok()?;
+        let starts = parse_list(cols[8])?;
+        let ends = parse_list(cols[9])?;
+        if starts.is_empty() || starts.len() != ends.len() {
+            return None;
+        }
+        let blocks: Vec<(u32, u32)> = starts
+            .iter()
+            .zip(&ends)
+            .map(|(start, end)| match start < end {
+                true => Some((start + 1, *end)),
+                false => None,
+            })
+            .collect::<Option<_>>()?;
+        // non-coding transcripts have `cdsStart == cdsEnd`
+        let cds = match cds_start < cds_end {
+            true => Some((cds_start + 1, cds_end)),
+            false => None,
+        };
+
+        let name = cols[0];
+        let gene = self.gene_symbols.get(name).map_or(name, String::as_str);
+        let cds_stat = match cds {
+            Some(_) => CdsStat::Complete,
+            None => CdsStat::None,
+        };
+        let mut tx = TranscriptBuilder::new()
+            .name(name)
+            .chrom(cols[1])
+            .gene(gene)
+            .strand(strand)
+            .cds_start_codon_stat(cds_stat)
+            .and_then(|builder| builder.cds_stop_codon_stat(cds_stat))
+            .and_then(|builder| builder.build())
+            .ok()?;
+        for exon in build_exons(&blocks, cds, &strand) {
+            tx.push_exon(exon);
+        }
+        Some(tx)
+    }
+}
+
+impl Reader<Box<dyn Read>> {
+    pub fn from_file(filename: &str) -> Result<Self, ReadWriteError> {
+        Ok(Self::new(open_input(filename)?))
+    }
+}
+
+impl<R: Read> TranscriptRead for Reader<R> {
+    fn transcripts(&mut self) -> Result<Transcripts, ReadWriteError> {
+        let mut transcripts = Transcripts::new();
+        let mut line = String::new();
+        let mut line_no = 0;
+        loop {
+            line.clear();
+            if self.inner.read_line(&mut line)? == 0 {
+                break;
+            }
+            line_no += 1;
+            let record = line.trim_end_matches(['\n', '\r']);
+            if record.trim().is_empty() || record.starts_with('#') {
+                continue;
+            }
+            match self.parse_line(record) {
+                Some(tx) => transcripts.push(tx),
+                None => {
+                    return Err(ReadWriteError::new(format!(
+                        "invalid knownGene record in line {}",
+                        line_no
+                    )))
+                }
+            }
+        }
+        Ok(transcripts)
+    }
+}
+
+/// Returns the gene symbol of every transcript of a kgXref table
+///
+/// The first column contains the transcript (`kgID`), the fifth column the `geneSymbol`.
+pub fn load_kgxref(filename: &str) -> Result<HashMap<String, String>, AtgError> {
+    let reader = BufReader::new(open_input(filename)?);
+    let mut gene_symbols = HashMap::new();
+    for (idx, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        let cols: Vec<&str> = line.split('\t').collect();
+        match (cols.first(), cols.get(4)) {
+            (Some(transcript), Some(symbol)) if !symbol.is_empty() => {
+                gene_symbols.insert(transcript.to_string(), symbol.to_string());
+            }
+            (Some(_), Some(_)) => {}
+            _ => {
+                return Err(AtgError::new(format!(
+                    "invalid kgXref record in line {} of {}",
+                    idx + 1,
+                    filename
+                )))
+            }
+        }
+    }
+    debug!(
+        target: "atg::read",
+        "Loaded {} gene symbols from {}",
+        gene_symbols.len(),
+        filename
+    );
+    Ok(gene_symbols)
+}
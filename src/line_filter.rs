@@ -0,0 +1,108 @@
+//! Writers that modify or drop the lines of the output of atglib's writers
+
+use std::io::Write;
+
+/// Handles one complete line of output at a time, see `FilterLines`
+pub trait LineFilter {
+    /// Writes the line (including its line break) to `out`, modified or not at all
+    fn write_line(&mut self, line: &[u8], out: &mut dyn Write) -> std::io::Result<()>;
+
+    /// Writes all lines that are held back, on `flush` and when the writer is dropped
+    fn finish(&mut self, _out: &mut dyn Write) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A writer that passes every line to a `LineFilter`
+///
+/// Incomplete lines are buffered until they are complete or the writer is dropped.
+pub struct FilterLines<W: Write, F: LineFilter> {
+    inner: W,
+    filter: F,
+    line: Vec<u8>,
+}
+
+impl<W: Write, F: LineFilter> FilterLines<W, F> {
+    pub fn new(inner: W, filter: F) -> Self {
+        Self {
+            inner,
+            filter,
+            line: vec![],
+        }
+    }
+
+    fn write_line(&mut self) -> std::io::Result<()> {
+        let result = self.filter.write_line(&self.line, &mut self.inner);
+        self.line.clear();
+        result
+    }
+}
+
+impl<W: Write, F: LineFilter> Write for FilterLines<W, F> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut rest = buf;
+        while let Some(pos) = rest.iter().position(|byte| *byte == b'\n') {
+            self.line.extend_from_slice(&rest[..=pos]);
+            self.write_line()?;
+            rest = &rest[pos + 1..];
+        }
+        self.line.extend_from_slice(rest);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.filter.finish(&mut self.inner)?;
+        self.inner.flush()
+    }
+}
+
+impl<W: Write, F: LineFilter> Drop for FilterLines<W, F> {
+    fn drop(&mut self) {
+        // errors can't be returned from `drop`, like in `BufWriter`
+        if !self.line.is_empty() {
+            let _ = self.write_line();
+        }
+        let _ = self.filter.finish(&mut self.inner);
+        let _ = self.inner.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Drops comment lines and upper-cases the others
+    struct Upper;
+
+    impl LineFilter for Upper {
+        fn write_line(&mut self, line: &[u8], out: &mut dyn Write) -> std::io::Result<()> {
+            if line.starts_with(b"#") {
+                return Ok(());
+            }
+            out.write_all(&line.to_ascii_uppercase())
+        }
+    }
+
+    #[test]
+    fn test_lines_across_writes() {
+        let mut out: Vec<u8> = vec![];
+        {
+            let mut writer = FilterLines::new(&mut out, Upper);
+            writer.write_all(b"ab").unwrap();
+            writer.write_all(b"c\n# comment\nd").unwrap();
+            writer.write_all(b"ef\n#").unwrap();
+        }
+        assert_eq!(out, b"ABC\nDEF\n");
+    }
+
+    #[test]
+    fn test_incomplete_line_on_drop() {
+        let mut out: Vec<u8> = vec![];
+        {
+            let mut writer = FilterLines::new(&mut out, Upper);
+            writer.write_all(b"abc\nxyz").unwrap();
+            writer.flush().unwrap();
+        }
+        assert_eq!(out, b"ABC\nXYZ");
+    }
+}
@@ -0,0 +1,106 @@
+//! Line numbers in the errors of RefGene and GenePredExt input
+
+use std::io::{BufRead, BufReader, Read};
+
+use atglib::genepredext;
+use atglib::models::{Transcript, TranscriptRead, Transcripts};
+use atglib::refgene;
+use atglib::utils::errors::{AtgError, ReadWriteError};
+
+use crate::compression::open_input;
+use crate::stream::TranscriptIter;
+
+/// The line-based formats that can be read with line numbers
+#[derive(Clone, Copy)]
+pub enum LineFormat {
+    Refgene,
+    Genepredext,
+}
+
+impl LineFormat {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Refgene => "RefGene",
+            Self::Genepredext => "GenePredExt",
+        }
+    }
+
+    fn parse(&self, line: &str) -> Option<Result<Transcript, ReadWriteError>> {
+        match self {
+            Self::Refgene => refgene::Reader::new(line.as_bytes()).line(),
+            Self::Genepredext => genepredext::Reader::new(line.as_bytes()).line(),
+        }
+    }
+}
+
+/// Reads RefGene or GenePredExt lines and keeps track of the line number
+pub struct NumberedReader<R: Read> {
+    inner: BufReader<R>,
+    format: LineFormat,
+    line: String,
+    line_no: usize,
+}
+
+impl<R: Read> NumberedReader<R> {
+    pub fn new(inner: R, format: LineFormat) -> Self {
+        Self {
+            inner: BufReader::new(inner),
+            format,
+            line: String::new(),
+            line_no: 0,
+        }
+    }
+
+    fn next_line(&mut self) -> Option<Result<Transcript, ReadWriteError>> {
+        loop {
+            self.line.clear();
+            match self.inner.read_line(&mut self.line) {
+                Ok(0) => return None,
+                Ok(_) => self.line_no += 1,
+                Err(err) => return Some(Err(err.into())),
+            }
+            let record = self.line.trim_end_matches(['\n', '\r']);
+            if record.is_empty() || record.starts_with('#') {
+                continue;
+            }
+            return match self.format.parse(record) {
+                Some(Ok(tx)) => Some(Ok(tx)),
+                Some(Err(err)) => Some(Err(ReadWriteError::new(format!(
+                    "invalid {} record in line {}: {}: {}",
+                    self.format.name(),
+                    self.line_no,
+                    err,
+                    record
+                )))),
+                None => Some(Err(ReadWriteError::new(format!(
+                    "invalid {} record in line {}: {}",
+                    self.format.name(),
+                    self.line_no,
+                    record
+                )))),
+            };
+        }
+    }
+}
+
+impl NumberedReader<Box<dyn Read>> {
+    pub fn from_file(filename: &str, format: LineFormat) -> Result<Self, ReadWriteError> {
+        Ok(Self::new(open_input(filename)?, format))
+    }
+}
+
+impl<R: Read> TranscriptIter for NumberedReader<R> {
+    fn next_transcript(&mut self) -> Option<Result<Transcript, AtgError>> {
+        self.next_line().map(|res| res.map_err(AtgError::from))
+    }
+}
+
+impl<R: Read> TranscriptRead for NumberedReader<R> {
+    fn transcripts(&mut self) -> Result<Transcripts, ReadWriteError> {
+        let mut transcripts = Transcripts::new();
+        while let Some(tx) = self.next_line() {
+            transcripts.push(tx?);
+        }
+        Ok(transcripts)
+    }
+}
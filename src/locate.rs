@@ -0,0 +1,120 @@
+//! Map transcript positions in HGVS notation to the genome
+
+use std::convert::TryFrom;
+use std::io::Write;
+
+use atglib::models::{Strand, Transcript};
+use atglib::utils::errors::AtgError;
+
+use crate::cli::LocateArgs;
+use crate::compression::create_output;
+use crate::coordinates::{CdsPosition, TranscriptCoordinates};
+use crate::formats::reader_for;
+
+/// The position within the transcript, without the intronic offset
+#[derive(Debug, Clone, Copy)]
+enum Position {
+    Cdna(u64),
+    Cds(CdsPosition),
+}
+
+/// A position in HGVS notation, e.g. `NM_000546.6:c.215+3`
+#[derive(Debug)]
+struct Query<'a> {
+    transcript: &'a str,
+    position: Position,
+    /// Intronic offset, in the direction of transcription
+    offset: i64,
+}
+
+impl<'a> Query<'a> {
+    fn parse(query: &'a str) -> Option<Self> {
+        let (transcript, notation) = query.rsplit_once(':')?;
+        let (kind, rest) = notation.split_at(notation.find('.')? + 1);
+        // the first character can be the `-` of a 5'UTR position
+        let (base, offset) = match rest[1.min(rest.len())..].find(['+', '-']) {
+            Some(idx) => {
+                let (base, offset) = rest.split_at(idx + 1);
+                let offset = offset.strip_prefix('+').unwrap_or(offset);
+                (base, offset.parse::<i64>().ok()?)
+            }
+            None => (rest, 0),
+        };
+        let position = match (kind, base.as_bytes().first()?) {
+            ("n.", _) => Position::Cdna(base.parse().ok()?),
+            ("c.", b'-') => Position::Cds(CdsPosition::FivePrimeUtr(base[1..].parse().ok()?)),
+            ("c.", b'*') => Position::Cds(CdsPosition::ThreePrimeUtr(base[1..].parse().ok()?)),
+            ("c.", _) => Position::Cds(CdsPosition::Cds(base.parse().ok()?)),
+            _ => return None,
+        };
+        Some(Self {
+            transcript,
+            position,
+            offset,
+        })
+    }
+
+    /// Returns the genomic position of the query in the transcript
+    fn locate(&self, tx: &Transcript) -> Option<u32> {
+        let coordinates = TranscriptCoordinates::new(tx);
+        let pos = match self.position {
+            Position::Cdna(cdna) => coordinates.cdna_to_genomic(cdna)?,
+            Position::Cds(cds) => coordinates.cds_to_genomic(cds)?,
+        };
+        let offset = match tx.strand() {
+            Strand::Minus => -self.offset,
+            _ => self.offset,
+        };
+        u32::try_from(i64::from(pos) + offset).ok()
+    }
+}
+
+/// Writes the genomic position of every query in every matching transcript
+///
+/// Queries that can't be mapped are written with `.` in the position columns.
+pub fn locate<W: Write>(
+    transcripts: &[Transcript],
+    queries: &[String],
+    mut out: W,
+) -> Result<(), AtgError> {
+    writeln!(out, "Query\tGene\tTranscript\tChrom\tPos\tStrand")?;
+    for query in queries {
+        let parsed = Query::parse(query).ok_or_else(|| {
+            AtgError::new(format!(
+                "invalid position {}, expected e.g. NM_000546.6:c.215 or NM_000546.6:n.80+2",
+                query
+            ))
+        })?;
+        let mut found = false;
+        for tx in transcripts
+            .iter()
+            .filter(|tx| tx.name() == parsed.transcript)
+        {
+            if let Some(pos) = parsed.locate(tx) {
+                found = true;
+                writeln!(
+                    out,
+                    "{}\t{}\t{}\t{}\t{}\t{}",
+                    query,
+                    tx.gene(),
+                    tx.name(),
+                    tx.chrom(),
+                    pos,
+                    tx.strand()
+                )?;
+            }
+        }
+        if !found {
+            writeln!(out, "{}\t.\t{}\t.\t.\t.", query, parsed.transcript)?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Reads the transcripts and maps all positions to the genome
+pub fn run_locate(args: &LocateArgs) -> Result<(), AtgError> {
+    let transcripts = reader_for(&args.from, &args.input)?.transcripts()?;
+    let out = create_output(&args.output, None)?;
+    locate(transcripts.as_vec(), &args.positions, out)
+}
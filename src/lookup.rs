@@ -0,0 +1,227 @@
+//! Lookup of transcripts by their name and chromosome or by their position
+
+use std::collections::HashMap;
+
+use atglib::models::Transcript;
+
+/// Index of transcripts by their name
+///
+/// Some transcripts are annotated on multiple chromosomes with the same
+/// name, e.g. genes in the pseudoautosomal regions of chrX and chrY.
+/// They can be disambiguated by their chromosome.
+pub struct TranscriptIndex<'a> {
+    by_name: HashMap<&'a str, Vec<&'a Transcript>>,
+}
+
+impl<'a> TranscriptIndex<'a> {
+    pub fn new(transcripts: &'a [Transcript]) -> Self {
+        let mut by_name: HashMap<&str, Vec<&Transcript>> = HashMap::new();
+        for tx in transcripts {
+            by_name.entry(tx.name()).or_default().push(tx);
+        }
+        Self { by_name }
+    }
+
+    /// Returns all transcripts with the name, on any chromosome
+    pub fn by_name(&self, name: &str) -> &[&'a Transcript] {
+        self.by_name.get(name).map_or(&[], |txs| txs.as_slice())
+    }
+
+    /// Returns the transcript with the name on the chromosome
+    ///
+    /// If the same transcript is present multiple times on the
+    /// chromosome, the first one is returned.
+    pub fn by_name_and_chrom(&self, name: &str, chrom: &str) -> Option<&'a Transcript> {
+        self.by_name(name)
+            .iter()
+            .find(|tx| tx.chrom() == chrom)
+            .copied()
+    }
+}
+
+/// Index of transcripts by their genomic position
+///
+/// The transcripts of every chromosome are sorted by their start position.
+/// Together with the length of the longest transcript, this limits the
+/// transcripts that must be checked for an overlap to a small window.
+pub struct PositionIndex<'a> {
+    transcripts: &'a [Transcript],
+    /// Indices of the transcripts of every chromosome, sorted by start position
+    by_chrom: HashMap<&'a str, Vec<usize>>,
+    /// The length of the longest transcript of every chromosome
+    max_len: HashMap<&'a str, u32>,
+}
+
+impl<'a> PositionIndex<'a> {
+    pub fn new(transcripts: &'a [Transcript]) -> Self {
+        let mut by_chrom: HashMap<&str, Vec<usize>> = HashMap::new();
+        let mut max_len: HashMap<&str, u32> = HashMap::new();
+        for (idx, tx) in transcripts.iter().enumerate() {
+            by_chrom.entry(tx.chrom()).or_default().push(idx);
+            let len = tx.tx_end().saturating_sub(tx.tx_start());
+            let max = max_len.entry(tx.chrom()).or_default();
+            *max = (*max).max(len);
+        }
+        for indices in by_chrom.values_mut() {
+            indices.sort_by_key(|idx| transcripts[*idx].tx_start());
+        }
+        Self {
+            transcripts,
+            by_chrom,
+            max_len,
+        }
+    }
+
+    /// Returns the indices of all transcripts that overlap the interval (1-based, inclusive)
+    pub fn overlapping_indices(&self, chrom: &str, start: u32, end: u32) -> Vec<usize> {
+        let indices = match self.by_chrom.get(chrom) {
+            Some(indices) => indices,
+            None => return vec![],
+        };
+        // no transcript that starts before `min_start` can reach the interval
+        let min_start = start.saturating_sub(self.max_len.get(chrom).copied().unwrap_or(0));
+        let first = indices.partition_point(|idx| self.transcripts[*idx].tx_start() < min_start);
+        indices[first..]
+            .iter()
+            .take_while(|idx| self.transcripts[**idx].tx_start() <= end)
+            .filter(|idx| self.transcripts[**idx].tx_end() >= start)
+            .copied()
+            .collect()
+    }
+
+    /// Returns all transcripts that overlap the interval (1-based, inclusive)
+    pub fn overlapping(&self, chrom: &str, start: u32, end: u32) -> Vec<&'a Transcript> {
+        self.overlapping_indices(chrom, start, end)
+            .into_iter()
+            .map(|idx| &self.transcripts[idx])
+            .collect()
+    }
+
+    /// Returns the transcript that is closest to the position
+    ///
+    /// The distance is measured to the genomic span of the transcripts, overlapping
+    /// transcripts have a distance of 0. Returns `None` if the chromosome has no transcripts.
+    ///
+    /// On a tie, the first transcript downstream of the position wins, unless a
+    /// transcript that starts at or before the position is strictly closer. Of
+    /// those, the one with the highest start position wins.
+    pub fn nearest(&self, chrom: &str, pos: u32) -> Option<&'a Transcript> {
+        let indices = self.by_chrom.get(chrom)?;
+        let max_len = self.max_len.get(chrom).copied().unwrap_or(0);
+        let split = indices.partition_point(|idx| self.transcripts[*idx].tx_start() <= pos);
+        // the first transcript that starts after the position
+        let mut best = indices
+            .get(split)
+            .map(|idx| (self.transcripts[*idx].tx_start() - pos, *idx));
+        // transcripts that start before the position, in reverse order. A
+        // transcript can't end closer to the position than a previous one
+        // once it starts more than `max_len` before that one's end.
+        let mut max_end = 0;
+        for idx in indices[..split].iter().rev() {
+            let tx = &self.transcripts[*idx];
+            if tx.tx_start().saturating_add(max_len) < max_end {
+                break;
+            }
+            max_end = max_end.max(tx.tx_end());
+            let distance = pos.saturating_sub(tx.tx_end());
+            if best.is_none_or(|(best_distance, _)| distance < best_distance) {
+                best = Some((distance, *idx));
+            }
+        }
+        best.map(|(_, idx)| &self.transcripts[idx])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use atglib::models::{Exon, Frame, Strand, TranscriptBuilder};
+
+    fn transcript(name: &str, chrom: &str, start: u32, end: u32) -> Transcript {
+        let mut tx = TranscriptBuilder::new()
+            .name(name)
+            .chrom(chrom)
+            .gene("Test-Gene")
+            .strand(Strand::Plus)
+            .build()
+            .unwrap();
+        tx.push_exon(Exon::new(start, end, None, None, Frame::None));
+        tx
+    }
+
+    fn nearest(index: &PositionIndex, chrom: &str, pos: u32) -> Option<String> {
+        index.nearest(chrom, pos).map(|tx| tx.name().to_string())
+    }
+
+    #[test]
+    fn test_nearest_before_and_after_all_transcripts() {
+        let transcripts = vec![
+            transcript("B", "chr1", 500, 600),
+            transcript("A", "chr1", 100, 200),
+        ];
+        let index = PositionIndex::new(&transcripts);
+        assert_eq!(nearest(&index, "chr1", 1), Some("A".to_string()));
+        assert_eq!(nearest(&index, "chr1", 100), Some("A".to_string()));
+        assert_eq!(nearest(&index, "chr1", 601), Some("B".to_string()));
+        assert_eq!(nearest(&index, "chr1", u32::MAX), Some("B".to_string()));
+    }
+
+    #[test]
+    fn test_nearest_in_gap() {
+        let transcripts = vec![
+            transcript("A", "chr1", 100, 200),
+            transcript("B", "chr1", 500, 600),
+        ];
+        let index = PositionIndex::new(&transcripts);
+        assert_eq!(nearest(&index, "chr1", 150), Some("A".to_string()));
+        assert_eq!(nearest(&index, "chr1", 300), Some("A".to_string()));
+        assert_eq!(nearest(&index, "chr1", 400), Some("B".to_string()));
+    }
+
+    #[test]
+    fn test_nearest_tie() {
+        let transcripts = vec![
+            transcript("A", "chr1", 100, 200),
+            transcript("B", "chr1", 300, 400),
+        ];
+        let index = PositionIndex::new(&transcripts);
+        // 50 bases to both transcripts, the downstream one wins
+        assert_eq!(nearest(&index, "chr1", 250), Some("B".to_string()));
+        assert_eq!(nearest(&index, "chr1", 249), Some("A".to_string()));
+
+        // two transcripts that overlap the position
+        let transcripts = vec![
+            transcript("A", "chr1", 100, 200),
+            transcript("B", "chr1", 150, 250),
+        ];
+        let index = PositionIndex::new(&transcripts);
+        assert_eq!(nearest(&index, "chr1", 175), Some("B".to_string()));
+    }
+
+    #[test]
+    fn test_nearest_long_transcript() {
+        // the long transcript overlaps the position, although
+        // several shorter transcripts start closer to it
+        let transcripts = vec![
+            transcript("Long", "chr1", 100, 10_000),
+            transcript("A", "chr1", 200, 300),
+            transcript("B", "chr1", 400, 500),
+            transcript("C", "chr1", 20_000, 20_100),
+        ];
+        let index = PositionIndex::new(&transcripts);
+        assert_eq!(nearest(&index, "chr1", 5_000), Some("Long".to_string()));
+        assert_eq!(nearest(&index, "chr1", 10_001), Some("Long".to_string()));
+        assert_eq!(nearest(&index, "chr1", 19_000), Some("C".to_string()));
+    }
+
+    #[test]
+    fn test_nearest_unknown_chromosome() {
+        let transcripts = vec![transcript("A", "chr1", 100, 200)];
+        let index = PositionIndex::new(&transcripts);
+        assert_eq!(nearest(&index, "chr2", 150), None);
+        assert_eq!(nearest(&index, "1", 150), None);
+
+        let index = PositionIndex::new(&[]);
+        assert_eq!(nearest(&index, "chr1", 150), None);
+    }
+}
@@ -1,93 +1,421 @@
 #[macro_use]
 extern crate log;
+use std::collections::HashMap;
 use std::fs::File;
 use std::process;
+use std::time::Instant;
 
-use bincode::{deserialize_from, serialize_into};
-use clap::Parser;
+use clap::{CommandFactory, Parser};
 
-use atglib::bed;
 use atglib::fasta;
 use atglib::fasta::FastaReader;
-use atglib::genepred;
-use atglib::genepredext;
-use atglib::gtf;
-use atglib::models::{GeneticCode, TranscriptWrite, Transcripts};
+use atglib::models::{GeneticCode, Transcript, TranscriptRead, TranscriptWrite, Transcripts};
 use atglib::qc;
-use atglib::qc::QcCheck;
-use atglib::read_transcripts;
-use atglib::refgene;
-use atglib::spliceai;
+use atglib::qc::{QcCheck, QcResult};
 use atglib::utils::errors::AtgError;
 
 mod cli;
-use cli::{Args, InputFormat, OutputFormat};
+use cli::{
+    Args, BedFormat, CdsStatPolicy, ChromStyle, Cli, CollapseLevel, Command, FastaFormat,
+    InputFormat, OutputFormat, SortOrder,
+};
+
+mod alignment;
+use alignment::{write_psl, write_sam};
+
+mod annotate;
+use annotate::run_annotate;
+
+mod attributes;
+use attributes::{load_attributes, save_attributes};
+
+mod bed12;
+
+mod big_genepred;
+
+#[cfg(feature = "bin")]
+mod bin_format;
+#[cfg(feature = "bin")]
+use bin_format::{write_bin, IndexedBinReader};
+
+mod biotype;
+use biotype::biotype_of;
+
+mod block_cache;
+use block_cache::set_cache_size;
+
+mod cds_stats;
+
+mod chrom_alias;
+use chrom_alias::ChromAliases;
+
+mod chrom_sizes;
+use chrom_sizes::write_chrom_sizes;
+
+mod chromosomes;
+use chromosomes::register_chromosomes_from_file;
+
+mod codons;
+use codons::write_codon_bed;
+
+mod collapse;
+use collapse::collapse_genes;
+
+mod compare;
+use compare::run_diff;
+
+mod compose;
+use compose::{is_composable, write_parallel};
+
+mod compression;
+use compression::create_output;
+
+mod contigs;
+use contigs::ContigResolver;
+
+mod coordinates;
+
+mod domains;
+use domains::{read_domains, write_domain_bed};
+
+mod exit_codes;
+use exit_codes::{CliError, ErrorKind, WithKind};
+
+mod fasta_case;
+use fasta_case::RawFasta;
+
+mod fasta_pool;
+use fasta_pool::FastaReaderPool;
+
+#[cfg(feature = "remote")]
+mod ensembl;
+
+mod exon_numbers;
+
+mod feature_bed;
+
+mod feature_filter;
+use feature_filter::{SkipFeatures, UTR_FEATURES};
+
+mod flip;
+use flip::{flip_strand, read_transcript_names};
+
+mod formats;
+use formats::{adjust_attributes, reader_for, writer_for, writer_to};
+
+#[cfg(feature = "remote")]
+mod http_reader;
+
+mod journal;
+use journal::write_journal;
+
+#[cfg(feature = "json")]
+mod json;
+
+mod kmerge;
+
+mod knowngene;
+use knowngene::load_kgxref;
+
+mod line_filter;
+
+mod line_numbers;
+
+mod memory_fasta;
+
+mod locate;
+use locate::run_locate;
+
+mod lookup;
+
+mod merge;
+use merge::{deduplicate, merge};
+
+mod nucleotides;
+use nucleotides::SequenceCheck;
+
+mod overlap;
+use overlap::write_overlaps;
+
+#[cfg(feature = "progress")]
+mod progress;
+
+mod polya;
+
+mod qc_report;
+use qc_report::QcReport;
+
+mod qc_writer;
+use qc_writer::QcWriter;
 
 mod reader_wrapper;
 use reader_wrapper::ReadSeekWrapper;
 
-fn read_input_file(args: &Args) -> Result<Transcripts, AtgError> {
-    let input_format = &args.from;
-    let input_fd = &args.input;
-    debug!("Reading {} transcripts from {}", input_format, input_fd);
+#[cfg(feature = "s3")]
+mod s3_output;
+
+mod sort;
+use sort::SortTranscripts;
+
+mod spliceai_table;
+
+mod splice_sites;
+
+mod stats;
+use stats::write_stats;
+
+mod stop_codon;
+use stop_codon::extend_cds_by_stop_codon;
+
+mod strandedness;
+use strandedness::AntisenseWriter;
+
+mod stream;
+use stream::{TranscriptIter, TranscriptStream};
+
+mod gff3;
+
+mod gtf_attributes;
+
+mod gtf_groups;
+use gtf_groups::{BuildFailure, GroupedGtfReader};
+
+mod gene_fasta;
+use gene_fasta::write_gene_fasta;
+
+mod genes;
+use genes::{write_gtf_with_genes, write_spliceai_genes};
+
+mod genetic_codes;
+use genetic_codes::parse_genetic_code;
+
+mod region;
+use region::select_regions;
+
+mod registry;
+
+mod report;
+use report::RunReport;
+
+mod roundtrip;
+use roundtrip::run_roundtrip;
+
+mod select;
+use select::select_per_gene;
+
+mod selenocysteine;
+
+mod sequence;
 
+mod simplify;
+use simplify::{limit_isoforms, trim_utrs};
+
+mod structure;
+use structure::{fix_structure, recompute_frames, validate_exons};
+
+mod tabix;
+use tabix::{write_indexed, Preset};
+
+mod ucsc_bin;
+
+mod utils;
+use utils::{chrom_sizes_from_fai, fai_entries, pad_transcript};
+
+mod validate;
+use validate::run_validate;
+
+mod warnings;
+use warnings::{finish_warnings, set_warnings_file, warning};
+
+#[cfg(test)]
+mod tests;
+
+fn read_input_file(args: &Args, report: &mut RunReport) -> Result<Transcripts, CliError> {
+    let aliases = chrom_aliases(args).with_kind(ErrorKind::Input)?;
+    // Ensembl transcripts are downloaded once, instead of reading the input files
+    let sources = match args.from {
+        InputFormat::Ensembl => vec![(InputFormat::Ensembl, args.ensembl_server.clone())],
+        _ => args.inputs(),
+    };
+    let has_knowngene = sources
+        .iter()
+        .any(|(input_format, _)| matches!(input_format, InputFormat::Knowngene));
+    let gene_symbols = match (has_knowngene, &args.kgxref) {
+        (true, Some(filename)) => Some(
+            load_kgxref(filename)
+                .map_err(|err| CliError::new(ErrorKind::Input, err).in_file(filename))?,
+        ),
+        _ => None,
+    };
+    let mut inputs = vec![];
+
+    for (input_format, input_fd) in &sources {
+        debug!(target: "atg::read", "Reading {} transcripts from {}", input_format, input_fd);
+        let transcripts = read_transcripts(args, input_format, input_fd, &gene_symbols, report)
+            .map_err(|err| CliError::new(ErrorKind::Input, err).in_file(input_fd))?;
+        let transcripts = match &aliases {
+            Some(aliases) => aliases
+                .rename_all(transcripts)
+                .map_err(|err| CliError::new(ErrorKind::Input, err).in_file(input_fd))?,
+            None => transcripts,
+        };
+        debug!(
+            target: "atg::read",
+            "Finished parsing input data. Found {} transcripts",
+            transcripts.len()
+        );
+        report.add_input(input_fd, transcripts.len());
+        inputs.push((input_fd.to_string(), transcripts));
+    }
+
+    if inputs.len() == 1 {
+        return Ok(inputs.remove(0).1);
+    }
+
+    let transcripts = merge(inputs, &args.merge_policy, report).with_kind(ErrorKind::Input)?;
+    debug!(target: "atg::merge", "Merged input data into {} transcripts", transcripts.len());
+    Ok(transcripts)
+}
+
+/// Reads the transcripts of a single input file
+fn read_transcripts(
+    args: &Args,
+    input_format: &InputFormat,
+    input_fd: &str,
+    gene_symbols: &Option<HashMap<String, String>>,
+    report: &mut RunReport,
+) -> Result<Transcripts, AtgError> {
     let transcripts = match input_format {
-        InputFormat::Refgene => read_transcripts(refgene::Reader::from_file(input_fd))?,
-        InputFormat::Genepredext => read_transcripts(genepredext::Reader::from_file(input_fd))?,
-        InputFormat::Gtf => read_transcripts(gtf::Reader::from_file(input_fd))?,
-        InputFormat::Bin => {
-            let reader = File::open(input_fd)?;
-            match deserialize_from(reader) {
-                Ok(res) => res,
-                Err(err) => return Err(AtgError::new(err)),
+        InputFormat::Gtf => {
+            let mut reader = GroupedGtfReader::from_file(input_fd, args.lenient)?;
+            reader.set_preserve_attributes(args.preserves_attributes());
+            reader.set_exon_merge(args.exon_merge);
+            let transcripts = reader.transcripts()?;
+            for failure in reader.failures() {
+                report.add_build_failure(input_fd, failure);
             }
+            transcripts
+        }
+        InputFormat::Knowngene => {
+            let mut reader = knowngene::Reader::from_file(input_fd)?;
+            if let Some(gene_symbols) = gene_symbols {
+                reader.set_gene_symbols(gene_symbols.clone());
+            }
+            reader.transcripts()?
+        }
+        #[cfg(feature = "bin")]
+        InputFormat::Bin if !args.gene.is_empty() => {
+            IndexedBinReader::from_file(input_fd)?.genes(&args.gene)?
         }
+        #[cfg(feature = "remote")]
+        InputFormat::Ensembl => {
+            let mut reader = ensembl::Reader::new(input_fd, &args.species);
+            reader.add_genes(&args.gene);
+            reader.add_regions(&args.region);
+            reader.transcripts()?
+        }
+        #[cfg(not(feature = "remote"))]
+        InputFormat::Ensembl => return Err(AtgError::new(formats::REMOTE_DISABLED)),
+        _ => reader_for(input_format, input_fd)?.transcripts()?,
     };
+    let mut validated = Transcripts::new();
+    for mut tx in transcripts {
+        if validate_transcript(&mut tx, args, input_fd, report)? {
+            validated.push(tx);
+        }
+    }
+    Ok(validated)
+}
 
-    debug!(
-        "Finished parsing input data. Found {} transcripts",
-        transcripts.len()
+/// Sorts the exons of the transcript and rejects overlapping exons, see `validate_exons`
+///
+/// Returns `false` if the transcript is rejected with `--lenient`. It is
+/// added to the build failures of the report instead.
+fn validate_transcript(
+    tx: &mut Transcript,
+    args: &Args,
+    filename: &str,
+    report: &mut RunReport,
+) -> Result<bool, AtgError> {
+    match validate_exons(tx, args.fix_structure) {
+        Ok(true) => debug!(target: "atg::read", "Sorted the exons of {}", tx.name()),
+        Ok(false) => {}
+        Err(err) if args.lenient => {
+            warning(
+                "atg::read",
+                "unbuildable-transcript",
+                &format!("Unable to build transcript {}: {}", tx.name(), err),
+            );
+            report.add_build_failure(
+                filename,
+                &BuildFailure {
+                    transcript: tx.name().to_string(),
+                    reason: err.to_string(),
+                },
+            );
+            return Ok(false);
+        }
+        Err(err) => return Err(err),
+    }
+    Ok(true)
+}
+
+/// Returns the buffered and, if requested, compressed `--output` file
+///
+/// UTR lines are removed from GTF output with `--no-utr-lines`.
+fn output_file(args: &Args) -> Result<Box<dyn std::io::Write>, AtgError> {
+    let file = adjust_attributes(
+        args.to(),
+        create_output(args.output(), args.compression)?,
+        args,
     );
-    Ok(transcripts)
+    match (args.to(), args.no_utr_lines) {
+        (OutputFormat::Gtf, true) => Ok(Box::new(SkipFeatures::new(file, UTR_FEATURES))),
+        _ => Ok(Box::new(file)),
+    }
+}
+
+/// Writes the sorted `gtf` or `bed` output with bgzip compression and creates a tabix index
+fn write_indexed_output(args: &Args, transcripts: Transcripts) -> Result<(), AtgError> {
+    let preset = match args.to() {
+        OutputFormat::Gtf => Preset::Gtf,
+        OutputFormat::Bed => Preset::Bed,
+        _ => {
+            return Err(AtgError::new(
+                "`--index` requires `--output gtf` or `--output bed`",
+            ))
+        }
+    };
+    let mut data: Vec<u8> = vec![];
+    if let (OutputFormat::Gtf, true) = (args.to(), args.gtf_gene_lines) {
+        let file = adjust_attributes(args.to(), Box::new(&mut data), args);
+        let out: Box<dyn std::io::Write> = match args.no_utr_lines {
+            true => Box::new(SkipFeatures::new(file, UTR_FEATURES)),
+            false => file,
+        };
+        write_gtf_with_genes(transcripts.as_vec(), out, &args.gtf_source)?;
+    } else {
+        writer_to(args.to(), Box::new(&mut data), args)?.write_transcripts(&transcripts)?;
+    }
+    let data = String::from_utf8(data).map_err(AtgError::new)?;
+    write_indexed(&data, preset, args.output())
 }
 
 fn write_output(args: &Args, transcripts: Transcripts) -> Result<(), AtgError> {
-    let output_fd = &args.output;
-    let output_format = &args.to;
+    if args.index {
+        return write_indexed_output(args, transcripts);
+    }
+    let output_fd = args.output();
+    let output_format = args.to();
 
     let fasta_format = &args.fasta_format;
     let fasta_reference = &args.reference;
-    let fastareader = get_fasta_reader(&fasta_reference.as_deref());
 
-    debug!("Writing transcripts as {} to {}", output_format, output_fd);
+    debug!(target: "atg::write", "Writing transcripts as {} to {}", output_format, output_fd);
 
     match output_format {
-        OutputFormat::Refgene => {
-            let mut writer = refgene::Writer::from_file(output_fd)?;
-            writer.write_transcripts(&transcripts)?
-        }
-        OutputFormat::Genepred => {
-            let mut writer = genepred::Writer::from_file(output_fd)?;
-            writer.write_transcripts(&transcripts)?
-        }
-        OutputFormat::Genepredext => {
-            let mut writer = genepredext::Writer::from_file(output_fd)?;
-            writer.write_transcripts(&transcripts)?
-        }
-        OutputFormat::Gtf => {
-            let mut writer = gtf::Writer::from_file(output_fd)?;
-            writer.set_source(&args.gtf_source);
-            writer.write_transcripts(&transcripts)?
-        }
-        OutputFormat::Bed => {
-            let mut writer = bed::Writer::from_file(output_fd)?;
-            writer.write_transcripts(&transcripts)?
-        }
-        OutputFormat::Fasta => {
-            let mut writer = fasta::Writer::from_file(output_fd)?;
-            writer.fasta_reader(fastareader?);
-            writer.fasta_format(fasta_format.as_str());
-            writer.write_transcripts(&transcripts)?
+        OutputFormat::Gtf if args.gtf_gene_lines => {
+            write_gtf_with_genes(transcripts.as_vec(), output_file(args)?, &args.gtf_source)?
         }
         OutputFormat::FastaSplit => {
             let outdir = std::path::Path::new(&output_fd);
@@ -96,40 +424,127 @@ fn write_output(args: &Args, transcripts: Transcripts) -> Result<(), AtgError> {
                     "fasta-split requires a directory as --output option",
                 ));
             }
-            let mut writer = fasta::Writer::from_file("/dev/null")?;
-            writer.fasta_reader(fastareader?);
-            writer.fasta_format(fasta_format.as_str());
+            let mut check = SequenceCheck::new(
+                RawFasta::from_cli_arg(&fasta_reference.as_deref())?,
+                args.skip_invalid_sequences,
+            );
+            if args.antisense {
+                let mut writer = AntisenseWriter::from_file(
+                    "/dev/null",
+                    get_fasta_reader(&fasta_reference.as_deref())?,
+                    fasta_format.clone(),
+                )?;
+                for tx in transcripts {
+                    if !check.check(&tx, fasta_format)? {
+                        continue;
+                    }
+                    let outfile = outdir.join(format!("{}.fasta", tx.name()));
+                    *writer.inner_mut() = std::io::BufWriter::new(File::create(outfile)?);
+                    writer.writeln_single_transcript(&tx)?;
+                }
+            } else {
+                let mut writer = fasta::Writer::from_file("/dev/null")?;
+                writer.fasta_reader(get_fasta_reader(&fasta_reference.as_deref())?);
+                writer.fasta_format(fasta_format.as_str());
 
-            for tx in transcripts {
-                let outfile = outdir.join(format!("{}.fasta", tx.name()));
-                *writer.inner_mut() = std::io::BufWriter::new(File::create(outfile)?);
-                writer.writeln_single_transcript(&tx)?;
+                for tx in transcripts {
+                    if !check.check(&tx, fasta_format)? {
+                        continue;
+                    }
+                    let outfile = outdir.join(format!("{}.fasta", tx.name()));
+                    *writer.inner_mut() = std::io::BufWriter::new(File::create(outfile)?);
+                    writer.writeln_single_transcript(&tx)?;
+                }
             }
         }
         OutputFormat::FeatureSequence => {
-            let mut writer = fasta::Writer::from_file(output_fd)?;
-            writer.fasta_reader(fastareader?);
+            let mut writer = fasta::Writer::new(output_file(args)?);
+            writer.fasta_reader(get_fasta_reader(&fasta_reference.as_deref())?);
+            let mut check = SequenceCheck::new(
+                RawFasta::from_cli_arg(&fasta_reference.as_deref())?,
+                args.skip_invalid_sequences,
+            );
             for tx in transcripts {
-                writer.write_features(&tx)?
+                if check.check(&tx, &FastaFormat::Exons)? {
+                    writer.write_features(&tx)?
+                }
             }
         }
-        OutputFormat::Spliceai => {
-            let mut writer = spliceai::Writer::from_file(output_fd)?;
-            writer.write_transcripts(&transcripts)?
+        OutputFormat::GeneFasta => {
+            let mut fastareader = get_fasta_reader(&fasta_reference.as_deref())?;
+            let writer = output_file(args)?;
+            let annotation = match &args.gene_annotation {
+                Some(filename) => Some(std::io::BufWriter::new(File::create(filename)?)),
+                None => None,
+            };
+            write_gene_fasta(transcripts.as_vec(), &mut fastareader, writer, annotation)?
+        }
+        OutputFormat::ChromSizes => {
+            let fai = match fasta_reference {
+                Some(fasta) => Some(fai_entries(&format!("{}.fai", fasta))?),
+                None => None,
+            };
+            let writer = output_file(args)?;
+            write_chrom_sizes(transcripts.as_vec(), fai.as_deref(), writer)?
+        }
+        OutputFormat::Overlaps => {
+            let writer = output_file(args)?;
+            write_overlaps(transcripts.as_vec(), writer)?
         }
-        OutputFormat::Qc => {
-            let mut writer = qc::Writer::from_file(output_fd)?;
-            add_genetic_code(&args.genetic_code, &mut writer)?;
-            writer.fasta_reader(fastareader?);
-            writer.write_header()?;
-            writer.write_transcripts(&transcripts)?
+        OutputFormat::Stats => {
+            let writer = output_file(args)?;
+            write_stats(transcripts.as_vec(), writer)?
         }
+        OutputFormat::SpliceaiGenes => {
+            let writer = output_file(args)?;
+            write_spliceai_genes(transcripts.as_vec(), writer)?
+        }
+        OutputFormat::CodonBed => {
+            let writer = output_file(args)?;
+            write_codon_bed(transcripts.as_vec(), &args.codon_positions, writer)?
+        }
+        OutputFormat::DomainBed => {
+            let filename = args
+                .domains
+                .as_deref()
+                .ok_or_else(|| AtgError::new("`--output domain-bed` requires `--domains`"))?;
+            let domains = read_domains(filename)?;
+            let writer = output_file(args)?;
+            write_domain_bed(transcripts.as_vec(), &domains, writer)?
+        }
+        OutputFormat::Psl => {
+            let chrom_sizes = match fasta_reference {
+                Some(fasta) => Some(chrom_sizes_from_fai(&format!("{}.fai", fasta))?),
+                None => None,
+            };
+            let writer = output_file(args)?;
+            write_psl(transcripts.as_vec(), chrom_sizes.as_ref(), writer)?
+        }
+        OutputFormat::Sam => {
+            let fasta = fasta_reference
+                .as_deref()
+                .ok_or_else(|| AtgError::new("no Fasta filename specified"))?;
+            let chroms = fai_entries(&format!("{}.fai", fasta))?;
+            let mut fastareader = get_fasta_reader(&Some(fasta))?;
+            let writer = output_file(args)?;
+            write_sam(transcripts.as_vec(), &chroms, &mut fastareader, writer)?
+        }
+        OutputFormat::Journal => {
+            let filename = args
+                .previous
+                .as_deref()
+                .ok_or_else(|| AtgError::new("`--output journal` requires `--previous`"))?;
+            debug!(target: "atg::read", "Reading previous release from {}", filename);
+            let previous = reader_for(&args.from, filename)?.transcripts()?;
+            let writer = output_file(args)?;
+            write_journal(previous.as_vec(), transcripts.as_vec(), writer)?
+        }
+        #[cfg(not(feature = "bin"))]
+        OutputFormat::Bin => return Err(AtgError::new(formats::BIN_DISABLED)),
+        #[cfg(feature = "bin")]
         OutputFormat::Bin => {
-            let writer = File::create(output_fd)?;
-            match serialize_into(&writer, &transcripts) {
-                Ok(res) => res,
-                Err(err) => return Err(AtgError::new(err)),
-            }
+            let writer = create_output(output_fd, Some(cli::Compression::None))?;
+            write_bin(&transcripts, writer)?
         }
         OutputFormat::Raw => {
             for t in transcripts {
@@ -140,6 +555,16 @@ fn write_output(args: &Args, transcripts: Transcripts) -> Result<(), AtgError> {
             }
         }
         OutputFormat::None => {}
+        // feature-level BED lines are not composed in parallel
+        _ if args.threads > 1
+            && is_composable(output_format)
+            && !(matches!(output_format, OutputFormat::Bed)
+                && args.bed_format != BedFormat::Transcript) =>
+        {
+            let writer = output_file(args)?;
+            write_parallel(output_format, transcripts.as_vec(), writer, args)?
+        }
+        _ => writer_for(output_format, output_fd, args)?.write_transcripts(&transcripts)?,
     };
 
     Ok(())
@@ -192,13 +617,13 @@ impl GeneticCodeSelecter {
                 // if the value contains a `:`, it is a key:value pair
                 // for chromosome:genetic_code.
                 Some((chrom, seq)) => {
-                    let gen_code = GeneticCode::guess(seq)?;
+                    let gen_code = parse_genetic_code(seq)?;
                     debug!("Specified custom genetic code {} for {}", gen_code, chrom);
                     code.custom.push((chrom.to_string(), gen_code));
                 }
                 // Without `:` the genetic code is used as default
                 None => {
-                    let gen_code = GeneticCode::guess(genetic_code_value)?;
+                    let gen_code = parse_genetic_code(genetic_code_value)?;
                     debug!("Specified default genetic code {}", gen_code);
                     code.default = gen_code;
                 }
@@ -206,6 +631,15 @@ impl GeneticCodeSelecter {
         }
         Ok(code)
     }
+
+    /// Returns the genetic code for the chromosome of the transcript
+    fn for_transcript(&self, tx: &Transcript) -> &GeneticCode {
+        self.custom
+            .iter()
+            .find(|cc| cc.0 == tx.chrom())
+            .map(|cc| &cc.1)
+            .unwrap_or(&self.default)
+    }
 }
 
 /// Returns a filtered `Transcript`s object based on CLI-provided filter criteria
@@ -219,83 +653,944 @@ impl GeneticCodeSelecter {
 /// would add a lot more logic complexity.
 /// The performance hit does not impact the most frequent use cases, where Fasta
 /// data is needed anyway
-fn filter_transcripts(transcripts: Transcripts, args: &Args) -> Result<Transcripts, AtgError> {
+fn filter_transcripts(
+    transcripts: Transcripts,
+    args: &Args,
+    report: &mut RunReport,
+) -> Result<Transcripts, AtgError> {
     let len_start = transcripts.len();
 
-    let fasta_reference = &args.reference;
-    let mut fastareader = get_fasta_reader(&fasta_reference.as_deref())?;
+    let fasta_reference = match &args.reference {
+        Some(fasta) => fasta,
+        None => return Err(AtgError::new("no Fasta filename specified")),
+    };
+    let threads = args.threads.max(1);
+    let pool = FastaReaderPool::new(fasta_reference, threads);
+
+    let codes = GeneticCodeSelecter::from_cli(&args.genetic_code)?;
+
+    let transcripts = transcripts.to_vec();
+    let chunk_size = transcripts.len().div_ceil(threads).max(1);
+
+    // Every thread checks a consecutive chunk of transcripts, so that
+    // the results can be joined in the original order
+    let results: Vec<Result<Vec<QcReport>, AtgError>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = transcripts
+            .chunks(chunk_size)
+            .map(|chunk| {
+                let pool = &pool;
+                let codes = &codes;
+                scope.spawn(move || {
+                    pool.with_reader(|fastareader| {
+                        chunk
+                            .iter()
+                            .map(|tx| {
+                                QcReport::run_all(tx, fastareader, codes.for_transcript(tx), args)
+                            })
+                            .collect::<Vec<QcReport>>()
+                    })
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|_| Err(AtgError::new("QC check thread panicked")))
+            })
+            .collect()
+    });
+
+    let mut qc_reports = Vec::with_capacity(len_start);
+    for res in results {
+        qc_reports.append(&mut res?);
+    }
 
     // To collect all transcripts that pass the filter
     let mut filtered_transcripts = Transcripts::new();
+    for (tx, qc_report) in transcripts.into_iter().zip(qc_reports) {
+        let failed = qc_report.failed();
+        // only keep transcripts that did not fail any QC test
+        if failed.is_empty() {
+            filtered_transcripts.push(tx)
+        } else {
+            debug!(
+                target: "atg::qc",
+                "Removing {} ({}) for failing QC filter {}",
+                qc_report.transcript,
+                qc_report.gene,
+                failed
+                    .iter()
+                    .map(|check| check.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            );
+            report.add_qc_failures(&failed);
+        }
+    }
+    info!(
+        target: "atg::qc",
+        "Filtered out {} transcripts.",
+        len_start - filtered_transcripts.len()
+    );
+    Ok(filtered_transcripts)
+}
+
+/// The number of transcripts of a streaming conversion or QC
+#[derive(Default)]
+struct StreamCounts {
+    /// Transcripts that were checked by `--qc-check`
+    qc_checked: usize,
+    /// Transcripts that failed `--qc-check` and were removed
+    qc_failed: usize,
+    /// Transcripts that were written to the output
+    written: usize,
+}
+
+impl StreamCounts {
+    /// Returns `true` if `--qc-check` removed every transcript
+    fn qc_removed_all(&self) -> bool {
+        self.qc_checked > 0 && self.qc_failed == self.qc_checked
+    }
+}
+
+/// Performs QC checks on a stream of transcripts and writes the results in batches
+///
+/// At most `batch_size` transcripts are kept in memory at any time. This
+/// only applies to input formats that can be parsed lazily, all others
+/// must be loaded completely before the QC can start.
+fn write_qc_streaming(
+    args: &Args,
+    batch_size: usize,
+    report: &mut RunReport,
+) -> Result<StreamCounts, CliError> {
+    let inputs = args.inputs();
+    let lazy_stream = match inputs.as_slice() {
+        [(input_format, input_fd)] => TranscriptStream::open(args, input_format, input_fd),
+        _ => None,
+    };
+    let mut stream = match lazy_stream {
+        Some(stream) => stream?,
+        None => {
+            warn!(
+                "{} input can not be streamed, all transcripts are read into memory",
+                args.from
+            );
+            TranscriptStream::from_transcripts(read_input_file(args, report)?)
+        }
+    };
+
+    let fasta_reference = &args.reference;
+    let mut writer = QcWriter::new(
+        output_file(args)?,
+        get_fasta_reader(&fasta_reference.as_deref())?,
+        &args.polya_stop,
+    );
+    add_genetic_code(&args.genetic_code, writer.qc_writer_mut())?;
+    writer
+        .qc_writer_mut()
+        .fasta_reader(get_fasta_reader(&fasta_reference.as_deref())?);
+    writer.write_header().with_kind(ErrorKind::Output)?;
+
+    // QC filtering requires its own FastaReader, since the writer
+    // takes ownership of the first one
+    let mut filter = match args.qc_check.is_empty() {
+        true => None,
+        false => Some((
+            get_fasta_reader(&fasta_reference.as_deref())?,
+            GeneticCodeSelecter::from_cli(&args.genetic_code)?,
+        )),
+    };
+
+    let aliases = chrom_aliases(args)?;
+    let resolver = contig_resolver(args)?;
+
+    let batch_size = batch_size.max(1);
+    let mut batch: Vec<Transcript> = Vec::with_capacity(batch_size);
+    let mut counts = StreamCounts::default();
+
+    for tx in stream.iter_transcripts() {
+        let mut tx = tx?;
+        if !validate_transcript(&mut tx, args, &inputs[0].1, report)? {
+            continue;
+        }
+        // renaming is a no-op for transcripts that were renamed in `read_input_file`
+        let tx = match &aliases {
+            Some(aliases) => aliases.rename(tx)?,
+            None => tx,
+        };
+        let tx = match &resolver {
+            Some(resolver) => match resolver.resolve(tx, report)? {
+                Some(tx) => tx,
+                None => continue,
+            },
+            None => tx,
+        };
+        if let Some((fastareader, codes)) = &mut filter {
+            counts.qc_checked += 1;
+            let failed =
+                QcReport::run_all(&tx, fastareader, codes.for_transcript(&tx), args).failed();
+            if !failed.is_empty() {
+                counts.qc_failed += 1;
+                report.add_qc_failures(&failed);
+                continue;
+            }
+        }
+        batch.push(tx);
+        if batch.len() == batch_size {
+            counts.written += write_qc_batch(&mut writer, &mut batch)?;
+            trace!(target: "atg::qc", "Processed {} transcripts", counts.written);
+            #[cfg(feature = "progress")]
+            progress::set_transcripts(counts.written);
+        }
+    }
+    counts.written += write_qc_batch(&mut writer, &mut batch)?;
+    for failure in stream.failures() {
+        report.add_build_failure(&inputs[0].1, failure);
+    }
+    debug!(target: "atg::qc", "Finished QC of {} transcripts", counts.written);
+    Ok(counts)
+}
 
+/// Converts the transcripts one at a time, without reading all of them into memory
+///
+/// This is only used if `Args::is_streamable` allows it. All per-transcript
+/// transformations are applied in the same order as in the non-streaming mode,
+/// so the output is identical.
+fn convert_streaming(args: &Args, report: &mut RunReport) -> Result<StreamCounts, AtgError> {
+    let inputs = args.inputs();
+    let (input_format, input_fd) = &inputs[0];
+    let mut stream = TranscriptStream::open(args, input_format, input_fd)
+        .ok_or_else(|| AtgError::new(format!("{} input can not be streamed", input_format)))??;
+
+    let aliases = chrom_aliases(args)?;
+    let resolver = contig_resolver(args)?;
     let codes = GeneticCodeSelecter::from_cli(&args.genetic_code)?;
-    let mut custom_code: Option<&GeneticCode>;
-
-    'tx_loop: for tx in transcripts.to_vec() {
-        let qc = match codes.custom.is_empty() {
-            true => QcCheck::new(&tx, &mut fastareader, &codes.default),
-            false => {
-                custom_code = None;
-                for cc in &codes.custom {
-                    if cc.0 == tx.chrom() {
-                        custom_code = Some(&cc.1);
-                        break;
-                    }
-                }
-                QcCheck::new(&tx, &mut fastareader, custom_code.unwrap_or(&codes.default))
+    let flip_names = match &args.flip_strand {
+        Some(filename) => Some(read_transcript_names(filename)?),
+        None => None,
+    };
+    let needs_reference =
+        args.verify_flip || args.assume_stop_excluded || !args.qc_check.is_empty();
+    let mut fastareader = match (&args.reference, needs_reference) {
+        (Some(fasta), true) => Some(get_fasta_reader(&Some(fasta))?),
+        (None, true) if !args.qc_check.is_empty() => {
+            return Err(AtgError::new("no Fasta filename specified"))
+        }
+        _ => None,
+    };
+    let chrom_sizes = match (&args.reference, args.upstream > 0 || args.downstream > 0) {
+        (Some(fasta), true) => Some(chrom_sizes_from_fai(&format!("{}.fai", fasta))?),
+        _ => None,
+    };
+
+    let mut writer = writer_for(args.to(), args.output(), args)?;
+    let mut read = 0;
+    let mut counts = StreamCounts::default();
+    for tx in stream.iter_transcripts() {
+        let mut tx = tx?;
+        read += 1;
+        if !validate_transcript(&mut tx, args, input_fd, report)? {
+            continue;
+        }
+        if let Some(aliases) = &aliases {
+            tx = aliases.rename(tx)?;
+        }
+        if !args.region.is_empty() && !args.region.iter().any(|region| region.overlaps(&tx)) {
+            continue;
+        }
+        if args.fix_structure {
+            for issue in fix_structure(&mut tx) {
+                warning(
+                    "atg::transform",
+                    "fixed-structure",
+                    &format!("Fixed {} in {}", issue, tx.name()),
+                );
             }
-        };
+        }
+        if args.fix_frames {
+            fix_frames(&mut tx);
+        }
+        if let Some(resolver) = &resolver {
+            tx = match resolver.resolve(tx, report)? {
+                Some(tx) => tx,
+                None => continue,
+            };
+        }
+        if flip_names
+            .as_ref()
+            .is_some_and(|names| names.contains(tx.name()))
+        {
+            let verify = fastareader.as_mut().filter(|_| args.verify_flip);
+            tx = flip_transcript(tx, verify, &codes)?.0;
+        }
+        if args.assume_stop_excluded {
+            extend_cds_by_stop_codon(&mut tx, fastareader.as_mut())?;
+        }
+        reconcile_cds_stats(&mut tx, args.cds_stat_policy, fastareader.as_mut(), &codes)?;
+        if !args.qc_check.is_empty() {
+            let fastareader = fastareader
+                .as_mut()
+                .ok_or_else(|| AtgError::new("no Fasta filename specified"))?;
+            counts.qc_checked += 1;
+            let failed =
+                QcReport::run_all(&tx, fastareader, codes.for_transcript(&tx), args).failed();
+            if !failed.is_empty() {
+                counts.qc_failed += 1;
+                report.add_qc_failures(&failed);
+                continue;
+            }
+        }
+        if args.skip_noncoding && !tx.is_coding() {
+            continue;
+        }
+        if !args.filter_biotype.is_empty() && !has_biotype(&tx, &args.filter_biotype) {
+            continue;
+        }
+        if let Some(max_len) = args.max_utr_length {
+            trim_utrs(&mut tx, max_len);
+        }
+        if args.upstream > 0 || args.downstream > 0 {
+            let chrom_len = chrom_sizes
+                .as_ref()
+                .and_then(|sizes| sizes.get(tx.chrom()).copied());
+            pad_transcript(&mut tx, args.upstream, args.downstream, chrom_len);
+        }
+
+        writer.writeln_single_transcript(&tx)?;
+        report.add_output(std::slice::from_ref(&tx));
+        counts.written += 1;
+        #[cfg(feature = "progress")]
+        progress::set_transcripts(counts.written);
+    }
+    for failure in stream.failures() {
+        report.add_build_failure(input_fd, failure);
+    }
+    report.add_input(input_fd, read);
+    Ok(counts)
+}
+
+/// Writes the QC results of all transcripts and clears the batch
+fn write_qc_batch<W: std::io::Write, R: std::io::Read + std::io::Seek>(
+    writer: &mut QcWriter<W, R>,
+    batch: &mut Vec<Transcript>,
+) -> Result<usize, AtgError> {
+    for tx in batch.iter() {
+        writer.writeln_single_transcript(tx)?;
+    }
+    writer.flush()?;
+    let len = batch.len();
+    batch.clear();
+    Ok(len)
+}
+
+/// Returns `true` if the biotype of the transcript is one of `biotypes`
+///
+/// Transcripts without biotype, e.g. from RefGene input, have none of the biotypes.
+fn has_biotype(tx: &Transcript, biotypes: &[String]) -> bool {
+    biotype_of(tx.chrom(), tx.name()).is_some_and(|biotype| biotypes.contains(&biotype))
+}
 
-        for check in &args.qc_check {
-            if check.remove(&qc) {
-                debug!("Removing {} for failing QC filter {}", tx.name(), check);
-                // Transcript fails the QC check, move on to the next transcript
-                continue 'tx_loop;
+/// Applies all CLI-specified modifications to the transcripts
+fn transform_transcripts(
+    mut transcripts: Transcripts,
+    args: &Args,
+) -> Result<Transcripts, AtgError> {
+    if args.skip_noncoding {
+        let len_start = transcripts.len();
+        let mut coding = Transcripts::new();
+        for tx in transcripts {
+            if tx.is_coding() {
+                coding.push(tx);
             }
         }
+        transcripts = coding;
+        info!(
+            target: "atg::transform",
+            "Removed {} non-coding transcripts",
+            len_start - transcripts.len()
+        );
+    }
 
-        // only keep transcripts that did not fail any QC test
-        filtered_transcripts.push(tx)
+    if !args.filter_biotype.is_empty() {
+        let len_start = transcripts.len();
+        let mut kept = Transcripts::new();
+        for tx in transcripts {
+            if has_biotype(&tx, &args.filter_biotype) {
+                kept.push(tx);
+            }
+        }
+        transcripts = kept;
+        info!(
+            target: "atg::transform",
+            "Removed {} transcripts of other biotypes than {}",
+            len_start - transcripts.len(),
+            args.filter_biotype.join(", ")
+        );
+    }
+
+    if let Some(identity) = &args.deduplicate {
+        let (unique, removed) = deduplicate(transcripts, identity);
+        transcripts = unique;
+        info!(
+            target: "atg::transform",
+            "Merged {} duplicate transcripts by {}",
+            removed,
+            identity
+        );
+    }
+
+    if let Some(mode) = &args.select {
+        let len_start = transcripts.len();
+        transcripts = select_per_gene(transcripts, mode);
+        info!(
+            target: "atg::transform",
+            "Selected {} of {} transcripts by {}",
+            transcripts.len(),
+            len_start,
+            mode
+        );
+    }
+
+    if let Some(max) = args.max_isoforms {
+        transcripts = limit_isoforms(transcripts, max);
+        debug!(
+            target: "atg::transform",
+            "Kept {} transcripts with diverse exon structure",
+            transcripts.len()
+        );
+    }
+
+    if let Some(CollapseLevel::Gene) = args.collapse {
+        let len_start = transcripts.len();
+        transcripts = collapse_genes(transcripts)?;
+        info!(
+            target: "atg::transform",
+            "Collapsed {} transcripts into {} gene models",
+            len_start,
+            transcripts.len()
+        );
+    }
+
+    if let Some(max_len) = args.max_utr_length {
+        debug!(target: "atg::transform", "Trimming UTRs to at most {} bases", max_len);
+        let mut trimmed = Transcripts::new();
+        for mut tx in transcripts {
+            trim_utrs(&mut tx, max_len);
+            trimmed.push(tx);
+        }
+        transcripts = trimmed;
+    }
+
+    if args.upstream > 0 || args.downstream > 0 {
+        transcripts = pad_transcripts(transcripts, args)?;
+    }
+
+    match args.sort {
+        Some(SortOrder::Position) => transcripts.sort_by_position(args.threads),
+        Some(SortOrder::Name) => transcripts.sort_by_name(),
+        None => {}
+    }
+
+    Ok(transcripts)
+}
+
+/// Flips the strand of all transcripts listed in the `--flip-strand` file
+///
+/// With `--verify-flip`, a transcript is only flipped if the flipped CDS
+/// starts with a start codon and ends with a stop codon.
+fn flip_transcripts(
+    transcripts: Transcripts,
+    filename: &str,
+    args: &Args,
+) -> Result<Transcripts, AtgError> {
+    let names = read_transcript_names(filename)?;
+    let mut fastareader = match (&args.reference, args.verify_flip) {
+        (Some(fasta), true) => Some(get_fasta_reader(&Some(fasta))?),
+        _ => None,
+    };
+    let codes = GeneticCodeSelecter::from_cli(&args.genetic_code)?;
+
+    let mut count = 0;
+    let mut result = Transcripts::new();
+    for tx in transcripts {
+        if !names.contains(tx.name()) {
+            result.push(tx);
+            continue;
+        }
+        let (tx, flipped) = flip_transcript(tx, fastareader.as_mut(), &codes)?;
+        if flipped {
+            count += 1;
+        }
+        result.push(tx);
+    }
+    info!(target: "atg::transform", "Flipped the strand of {} transcripts", count);
+    Ok(result)
+}
+
+/// Returns the transcript on the opposite strand, or the original transcript if it can't be flipped
+///
+/// If a `fastareader` is given, the transcript is only flipped if the flipped
+/// CDS starts with a start codon and ends with a stop codon. The returned
+/// bool indicates if the transcript was flipped.
+fn flip_transcript<R: std::io::Read + std::io::Seek>(
+    tx: Transcript,
+    fastareader: Option<&mut FastaReader<R>>,
+    codes: &GeneticCodeSelecter,
+) -> Result<(Transcript, bool), AtgError> {
+    let flipped = match flip_strand(&tx)? {
+        Some(flipped) => flipped,
+        None => {
+            warning(
+                "atg::transform",
+                "unflipped-transcript",
+                &format!("Unable to flip {}: the strand is unknown", tx.name()),
+            );
+            return Ok((tx, false));
+        }
+    };
+    if let Some(fastareader) = fastareader {
+        let qc = QcCheck::new(&flipped, fastareader, codes.for_transcript(&flipped));
+        if !matches!(qc.correct_start_codon(), QcResult::OK)
+            || !matches!(qc.correct_stop_codon(), QcResult::OK)
+        {
+            warning(
+                "atg::transform",
+                "unflipped-transcript",
+                &format!(
+                    "Not flipping {}: the flipped CDS has no correct start and stop codon",
+                    tx.name()
+                ),
+            );
+            return Ok((tx, false));
+        }
+    }
+    Ok((flipped, true))
+}
+
+/// Reconciles the start and stop codon stats of the transcript, according to `--cds-stat-policy`
+///
+/// Returns `true` if the stats were changed.
+fn reconcile_cds_stats<R: std::io::Read + std::io::Seek>(
+    tx: &mut Transcript,
+    policy: CdsStatPolicy,
+    fastareader: Option<&mut FastaReader<R>>,
+    codes: &GeneticCodeSelecter,
+) -> Result<bool, AtgError> {
+    let changed = match (policy, fastareader) {
+        (CdsStatPolicy::TrustStats, _) => false,
+        (CdsStatPolicy::MarkIncomplete, _) => cds_stats::mark_incomplete(tx)?,
+        (CdsStatPolicy::Recompute, Some(fastareader)) => {
+            let qc = QcCheck::new(tx, fastareader, codes.for_transcript(tx));
+            cds_stats::recompute(tx, &qc)?
+        }
+        (CdsStatPolicy::Recompute, None) => {
+            return Err(AtgError::new(
+                "`--cds-stat-policy recompute` requires `--reference`",
+            ))
+        }
+    };
+    if changed {
+        debug!(
+            target: "atg::transform",
+            "Changed the CDS stats of {} to {} / {}",
+            tx.name(),
+            tx.cds_start_codon_stat(),
+            tx.cds_stop_codon_stat()
+        );
+    }
+    Ok(changed)
+}
+
+/// Reconciles the start and stop codon stats of all transcripts
+fn reconcile_all_cds_stats(transcripts: Transcripts, args: &Args) -> Result<Transcripts, AtgError> {
+    let mut fastareader = match (&args.reference, args.cds_stat_policy) {
+        (Some(fasta), CdsStatPolicy::Recompute) => Some(get_fasta_reader(&Some(fasta))?),
+        _ => None,
+    };
+    let codes = GeneticCodeSelecter::from_cli(&args.genetic_code)?;
+    let mut changed = 0;
+    let mut reconciled = Transcripts::new();
+    for mut tx in transcripts {
+        if reconcile_cds_stats(&mut tx, args.cds_stat_policy, fastareader.as_mut(), &codes)? {
+            changed += 1;
+        }
+        reconciled.push(tx);
+    }
+    info!(target: "atg::transform", "Changed the CDS stats of {} transcripts", changed);
+    Ok(reconciled)
+}
+
+/// Extends the CDS of all transcripts by the stop codon
+fn restore_stop_codons(transcripts: Transcripts, args: &Args) -> Result<Transcripts, AtgError> {
+    let mut fastareader = match &args.reference {
+        Some(fasta) => Some(get_fasta_reader(&Some(fasta))?),
+        None => None,
+    };
+    let mut extended = 0;
+    let mut restored = Transcripts::new();
+    for mut tx in transcripts {
+        if extend_cds_by_stop_codon(&mut tx, fastareader.as_mut())? {
+            extended += 1;
+        }
+        restored.push(tx);
     }
     info!(
-        "Filtered out {} transcripts.",
-        len_start - filtered_transcripts.len()
+        target: "atg::transform",
+        "Extended the CDS of {} transcripts by the stop codon",
+        extended
     );
-    Ok(filtered_transcripts)
+    Ok(restored)
 }
 
-fn main() {
-    let cli_commands = Args::parse();
+/// Returns the chromosome aliases of all `--chrom-alias` tables and the `--chrom-style`, if specified
+fn chrom_aliases(args: &Args) -> Result<Option<ChromAliases>, AtgError> {
+    if args.chrom_alias.is_empty() && matches!(args.chrom_style, ChromStyle::Keep) {
+        return Ok(None);
+    }
+    let reference = match &args.reference {
+        Some(fasta) => Some(
+            fai_entries(&format!("{}.fai", fasta))?
+                .into_iter()
+                .map(|(chrom, _)| chrom)
+                .collect(),
+        ),
+        None => None,
+    };
+    let mut aliases = ChromAliases::new(reference);
+    for table in &args.chrom_alias {
+        debug!(target: "atg::read", "Adding chromosome aliases from {}", table);
+        aliases.add_table(table)?;
+    }
+    aliases.set_style(args.chrom_style);
+    Ok(Some(aliases))
+}
 
-    loggerv::init_with_verbosity(cli_commands.verbose.into()).unwrap();
+/// Returns a `ContigResolver` for the chromosomes of the reference genome, if needed
+fn contig_resolver(args: &Args) -> Result<Option<ContigResolver>, AtgError> {
+    let (fasta, policy) = match (&args.reference, args.contig_policy()) {
+        (Some(fasta), Some(policy)) => (fasta, policy),
+        _ => return Ok(None),
+    };
+    let chroms = fai_entries(&format!("{}.fai", fasta))?
+        .into_iter()
+        .map(|(chrom, _)| chrom);
+    let mut resolver = ContigResolver::new(chroms, policy);
+    if let Some(filename) = &args.contig_map {
+        resolver.add_mapping_from_file(filename)?;
+    }
+    Ok(Some(resolver))
+}
 
-    let mut transcripts = match read_input_file(&cli_commands) {
-        Ok(x) => x,
-        Err(err) => {
-            println!("\x1b[1;31mError:\x1b[0m {}", err);
-            println!("\nPlease check `atg --help` for more options\n");
-            process::exit(1);
+/// Handles all transcripts on contigs that are missing from the reference genome
+fn resolve_contigs(
+    transcripts: Transcripts,
+    resolver: &ContigResolver,
+    report: &mut RunReport,
+) -> Result<Transcripts, AtgError> {
+    let mut resolved = Transcripts::new();
+    for tx in transcripts {
+        if let Some(tx) = resolver.resolve(tx, report)? {
+            resolved.push(tx);
         }
+    }
+    Ok(resolved)
+}
+
+/// Pads all transcripts by the CLI-specified up- and downstream bases
+fn pad_transcripts(transcripts: Transcripts, args: &Args) -> Result<Transcripts, AtgError> {
+    let chrom_sizes = match &args.reference {
+        Some(fasta) => Some(chrom_sizes_from_fai(&format!("{}.fai", fasta))?),
+        None => None,
     };
 
-    if !cli_commands.qc_check.is_empty() {
-        debug!("Filtering transcripts");
-        transcripts = match filter_transcripts(transcripts, &cli_commands) {
-            Ok(t) => t,
-            Err(err) => {
-                println!("\x1b[1;31mError:\x1b[0m {}", err);
-                println!("\nPlease check `atg --help` for more options\n");
-                process::exit(1);
+    debug!(
+        target: "atg::transform",
+        "Padding transcripts by {} bases upstream and {} bases downstream",
+        args.upstream, args.downstream
+    );
+    let mut padded = Transcripts::new();
+    for mut tx in transcripts {
+        let chrom_len = chrom_sizes
+            .as_ref()
+            .and_then(|sizes| sizes.get(tx.chrom()).copied());
+        pad_transcript(&mut tx, args.upstream, args.downstream, chrom_len);
+        padded.push(tx);
+    }
+    Ok(padded)
+}
+
+/// Fixes structural issues of all transcripts and logs every fix as warning
+fn fix_transcript_structures(transcripts: Transcripts) -> Transcripts {
+    let mut fixed = Transcripts::new();
+    for mut tx in transcripts {
+        for issue in fix_structure(&mut tx) {
+            warning(
+                "atg::transform",
+                "fixed-structure",
+                &format!("Fixed {} in {}", issue, tx.name()),
+            );
+        }
+        fixed.push(tx);
+    }
+    fixed
+}
+
+/// Recomputes the frames of the transcript and logs changed frames as warning
+fn fix_frames(tx: &mut Transcript) {
+    let changed = recompute_frames(tx);
+    if changed > 0 {
+        warning(
+            "atg::transform",
+            "fixed-frames",
+            &format!("Fixed the frames of {} exons in {}", changed, tx.name()),
+        );
+    }
+}
+
+/// Recomputes the frames of all transcripts, see `fix_frames`
+fn fix_transcript_frames(transcripts: Transcripts) -> Transcripts {
+    let mut fixed = Transcripts::new();
+    for mut tx in transcripts {
+        fix_frames(&mut tx);
+        fixed.push(tx);
+    }
+    fixed
+}
+
+/// Runs the full conversion: reading, filtering, transforming and writing
+fn run(args: &Args) -> Result<(), CliError> {
+    let outputs = args.split_outputs().with_kind(ErrorKind::Usage)?;
+    let mut missing: Vec<String> = vec![];
+    for output_args in &outputs {
+        for message in output_args.missing_prerequisites() {
+            if !missing.contains(&message) {
+                missing.push(message);
             }
-        };
+        }
+    }
+    if !missing.is_empty() {
+        return Err(CliError::new(
+            ErrorKind::Usage,
+            AtgError::new(format!(
+                "missing prerequisites:\n - {}",
+                missing.join("\n - ")
+            )),
+        ));
+    }
+
+    set_cache_size(args.reference_cache);
+
+    if let Some(filename) = &args.chrom_order {
+        register_chromosomes_from_file(filename).with_kind(ErrorKind::Input)?;
+    }
+
+    if let Some(filename) = &args.load_attributes {
+        load_attributes(filename).with_kind(ErrorKind::Input)?;
+    }
+
+    if let Some(filename) = &args.warnings_file {
+        set_warnings_file(filename).with_kind(ErrorKind::Output)?;
+    }
+
+    #[cfg(feature = "progress")]
+    if args.progress {
+        let files: Vec<String> = args.inputs().into_iter().map(|(_, file)| file).collect();
+        progress::start_progress(&files);
+    }
+
+    let mut report = RunReport::new();
+    let mut qc_removed_all = false;
+
+    if let ([OutputFormat::Qc], Some(batch_size)) = (args.formats.as_slice(), args.qc_batch_size) {
+        debug!(target: "atg::qc", "Running QC in batches of {} transcripts", batch_size);
+        let start = Instant::now();
+        let counts = write_qc_streaming(args, batch_size, &mut report)?;
+        debug!(target: "atg::qc", "Finished streaming QC in {:.2?}", start.elapsed());
+        qc_removed_all = counts.qc_removed_all();
+        report.add_timing("qc", start.elapsed());
+    } else if args.is_streamable() {
+        debug!(target: "atg::read", "Converting {} transcripts one at a time", args.from);
+        let start = Instant::now();
+        let counts = convert_streaming(args, &mut report)?;
+        debug!(
+            target: "atg::write",
+            "Converted {} transcripts in {:.2?}",
+            counts.written,
+            start.elapsed()
+        );
+        qc_removed_all = counts.qc_removed_all();
+        report.add_timing("convert", start.elapsed());
+    } else {
+        #[cfg(feature = "progress")]
+        progress::set_stage("reading");
+        let start = Instant::now();
+        let mut transcripts = read_input_file(args, &mut report)?;
+        debug!(
+            target: "atg::read",
+            "Read {} transcripts in {:.2?}",
+            transcripts.len(),
+            start.elapsed()
+        );
+        report.add_timing("read", start.elapsed());
+
+        if !args.region.is_empty() {
+            let len_start = transcripts.len();
+            transcripts = select_regions(transcripts, &args.region);
+            info!(
+                target: "atg::transform",
+                "Selected {} of {} transcripts in {} regions",
+                transcripts.len(),
+                len_start,
+                args.region.len()
+            );
+        }
+
+        if args.fix_structure {
+            transcripts = fix_transcript_structures(transcripts);
+        }
+
+        if args.fix_frames {
+            transcripts = fix_transcript_frames(transcripts);
+        }
+
+        if let Some(resolver) = contig_resolver(args)? {
+            transcripts = resolve_contigs(transcripts, &resolver, &mut report)
+                .with_kind(ErrorKind::Reference)?;
+        }
+
+        if let Some(filename) = &args.flip_strand {
+            transcripts =
+                flip_transcripts(transcripts, filename, args).with_kind(ErrorKind::Reference)?;
+        }
+
+        if args.assume_stop_excluded {
+            transcripts = restore_stop_codons(transcripts, args).with_kind(ErrorKind::Reference)?;
+        }
+
+        if !matches!(args.cds_stat_policy, CdsStatPolicy::TrustStats) {
+            transcripts =
+                reconcile_all_cds_stats(transcripts, args).with_kind(ErrorKind::Reference)?;
+        }
+
+        if !args.qc_check.is_empty() {
+            #[cfg(feature = "progress")]
+            progress::set_stage("filtering");
+            let start = Instant::now();
+            let len_start = transcripts.len();
+            transcripts = filter_transcripts(transcripts, args, &mut report)
+                .with_kind(ErrorKind::Reference)?;
+            debug!(target: "atg::qc", "Filtered transcripts in {:.2?}", start.elapsed());
+            report.add_timing("qc", start.elapsed());
+            qc_removed_all = len_start > 0 && transcripts.is_empty();
+        }
+
+        #[cfg(feature = "progress")]
+        progress::set_stage("transforming");
+        let start = Instant::now();
+        transcripts = transform_transcripts(transcripts, args)?;
+        debug!(
+            target: "atg::transform",
+            "Transformed transcripts in {:.2?}",
+            start.elapsed()
+        );
+        report.add_timing("transform", start.elapsed());
+        report.add_output(transcripts.as_vec());
+
+        #[cfg(feature = "progress")]
+        progress::set_stage("writing");
+        let start = Instant::now();
+        let count = transcripts.len();
+        let (last, others) = outputs
+            .split_last()
+            .expect("at least one output is required");
+        for output_args in others {
+            let mut copy = Transcripts::new();
+            for tx in transcripts.as_vec() {
+                copy.push(tx.clone());
+            }
+            write_output(output_args, copy).with_kind(ErrorKind::Output)?;
+        }
+        write_output(last, transcripts).with_kind(ErrorKind::Output)?;
+        debug!(
+            target: "atg::write",
+            "Wrote {} transcripts in {:.2?}",
+            count,
+            start.elapsed()
+        );
+        report.add_timing("write", start.elapsed());
+    }
+
+    #[cfg(feature = "progress")]
+    progress::finish_progress();
+    report.log_timings();
+
+    if let Some(filename) = &args.save_attributes {
+        debug!(target: "atg::write", "Saving attributes to {}", filename);
+        save_attributes(filename).with_kind(ErrorKind::Output)?;
     }
 
-    match write_output(&cli_commands, transcripts) {
+    if let Some(filename) = &args.report {
+        debug!("Writing run report to {}", filename);
+        report
+            .write_to_file(filename)
+            .with_kind(ErrorKind::Output)?;
+    }
+
+    finish_warnings(args.max_warnings).with_kind(ErrorKind::Input)?;
+
+    if qc_removed_all {
+        return Err(CliError::new(
+            ErrorKind::EmptyOutput,
+            AtgError::new("the QC removed all transcripts"),
+        ));
+    }
+    Ok(())
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    let res = match (&cli.command, &cli.args) {
+        (Some(Command::Roundtrip(args)), _) => {
+            loggerv::init_with_verbosity(args.verbose.into()).unwrap();
+            run_roundtrip(args).map_err(CliError::from)
+        }
+        (Some(Command::Annotate(args)), _) => {
+            loggerv::init_with_verbosity(args.verbose.into()).unwrap();
+            run_annotate(args).map_err(CliError::from)
+        }
+        (Some(Command::Diff(args)), _) => {
+            loggerv::init_with_verbosity(args.verbose.into()).unwrap();
+            run_diff(args).map_err(CliError::from)
+        }
+        (Some(Command::Locate(args)), _) => {
+            loggerv::init_with_verbosity(args.verbose.into()).unwrap();
+            run_locate(args).map_err(CliError::from)
+        }
+        (Some(Command::Validate(args)), _) => {
+            loggerv::init_with_verbosity(args.verbose.into()).unwrap();
+            run_validate(args).with_kind(ErrorKind::Input)
+        }
+        (None, Some(args)) => {
+            loggerv::init_with_verbosity(args.verbose.into()).unwrap();
+            run(args)
+        }
+        (None, None) => Cli::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "the following arguments are required: --from <FORMAT> --to <FORMAT>",
+            )
+            .exit(),
+    };
+
+    match res {
         Ok(_) => debug!("All done here."),
         Err(err) => {
             println!("\x1b[1;31mError:\x1b[0m {}", err);
             println!("\nPlease check `atg --help` for more options\n");
-            process::exit(1);
+            process::exit(err.exit_code());
         }
     }
 }
@@ -0,0 +1,97 @@
+//! Reference genome that is kept completely in memory
+
+use std::io::{BufRead, Cursor};
+use std::sync::{Arc, OnceLock};
+
+use atglib::utils::errors::AtgError;
+
+/// The number of bases per line in the generated fasta data
+const LINE_WIDTH: usize = 60;
+
+static STDIN_REFERENCE: OnceLock<Result<MemoryFasta, String>> = OnceLock::new();
+
+/// Fasta data and the matching fasta index, both in memory
+pub struct MemoryFasta {
+    fasta: Arc<[u8]>,
+    fai: Arc<[u8]>,
+}
+
+impl MemoryFasta {
+    /// Creates the fasta data and index from (name, sequence) pairs
+    pub fn from_sequences<I, N, S>(sequences: I) -> Self
+    where
+        I: IntoIterator<Item = (N, S)>,
+        N: AsRef<str>,
+        S: AsRef<[u8]>,
+    {
+        let mut fasta: Vec<u8> = vec![];
+        let mut fai = String::new();
+        for (name, sequence) in sequences {
+            let name = name.as_ref();
+            let sequence = sequence.as_ref();
+            fasta.push(b'>');
+            fasta.extend_from_slice(name.as_bytes());
+            fasta.push(b'\n');
+            fai.push_str(&format!(
+                "{}\t{}\t{}\t{}\t{}\n",
+                name,
+                sequence.len(),
+                fasta.len(),
+                LINE_WIDTH,
+                LINE_WIDTH + 1
+            ));
+            for line in sequence.chunks(LINE_WIDTH) {
+                fasta.extend_from_slice(line);
+                fasta.push(b'\n');
+            }
+        }
+        Self {
+            fasta: fasta.into(),
+            fai: fai.into_bytes().into(),
+        }
+    }
+
+    /// Parses fasta data with arbitrary line lengths
+    pub fn from_fasta<R: BufRead>(reader: R) -> Result<Self, AtgError> {
+        let mut sequences: Vec<(String, Vec<u8>)> = vec![];
+        for line in reader.lines() {
+            let line = line?;
+            if let Some(header) = line.strip_prefix('>') {
+                // the name ends at the first whitespace, like in samtools faidx
+                let name = header.split_whitespace().next().unwrap_or_default();
+                sequences.push((name.to_string(), vec![]));
+            } else {
+                match sequences.last_mut() {
+                    Some((_, sequence)) => sequence.extend_from_slice(line.trim_end().as_bytes()),
+                    None if line.trim().is_empty() => {}
+                    None => return Err(AtgError::new("fasta data must start with a header line")),
+                }
+            }
+        }
+        Ok(Self::from_sequences(sequences))
+    }
+
+    /// Returns a reader of the fasta data
+    pub fn fasta(&self) -> Cursor<Arc<[u8]>> {
+        Cursor::new(Arc::clone(&self.fasta))
+    }
+
+    /// Returns a reader of the fasta index
+    pub fn fai(&self) -> Cursor<Arc<[u8]>> {
+        Cursor::new(Arc::clone(&self.fai))
+    }
+}
+
+/// Returns the reference genome from stdin
+///
+/// Stdin can only be read once, so the reference is read on first use
+/// and kept in memory for the rest of the run.
+pub fn stdin_reference() -> Result<&'static MemoryFasta, AtgError> {
+    STDIN_REFERENCE
+        .get_or_init(|| {
+            debug!(target: "atg::fasta", "Reading reference genome from stdin");
+            MemoryFasta::from_fasta(std::io::stdin().lock()).map_err(|err| err.to_string())
+        })
+        .as_ref()
+        .map_err(AtgError::new)
+}
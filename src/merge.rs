@@ -0,0 +1,160 @@
+//! Merge transcripts from multiple inputs
+
+use std::collections::HashMap;
+
+use atglib::models::{Transcript, Transcripts};
+use atglib::utils::errors::AtgError;
+
+use crate::attributes::{attributes_of, register_attributes};
+use crate::cli::{DuplicateIdentity, MergePolicy};
+use crate::report::{MergeConflict, RunReport};
+use crate::utils::cds_len;
+use crate::utils::rebuild_transcript;
+
+/// Returns `true` if both transcripts have the same exon and CDS structure
+///
+/// Exon frames are not compared, since they are derived from the CDS.
+pub fn same_structure(a: &Transcript, b: &Transcript) -> bool {
+    a.chrom() == b.chrom()
+        && a.strand() == b.strand()
+        && a.exon_count() == b.exon_count()
+        && a.exons().iter().zip(b.exons()).all(|(x, y)| {
+            x.start() == y.start()
+                && x.end() == y.end()
+                && x.cds_start() == y.cds_start()
+                && x.cds_end() == y.cds_end()
+        })
+}
+
+/// The chromosome, strand and exon or CDS chain of a transcript
+pub type StructureKey = (String, String, Vec<(u32, u32)>, Option<(u32, u32)>);
+
+/// Returns the key that is identical for duplicate transcripts
+///
+/// Non-coding transcripts have no key with `DuplicateIdentity::Cds`, they are never duplicates.
+pub fn structure_key(tx: &Transcript, identity: &DuplicateIdentity) -> Option<StructureKey> {
+    let mut chain: Vec<(u32, u32)> = match identity {
+        DuplicateIdentity::Exons => tx
+            .exons()
+            .iter()
+            .map(|exon| (exon.start(), exon.end()))
+            .collect(),
+        DuplicateIdentity::Cds => tx
+            .exons()
+            .iter()
+            .filter_map(|exon| exon.cds_start().zip(*exon.cds_end()))
+            .collect(),
+    };
+    let cds = tx.cds_start().zip(tx.cds_end());
+    if let (DuplicateIdentity::Cds, None) = (identity, cds) {
+        return None;
+    }
+    chain.sort_unstable();
+    Some((tx.chrom().to_string(), tx.strand().to_string(), chain, cds))
+}
+
+/// Removes transcripts with the same structure as a previous transcript
+///
+/// With `DuplicateIdentity::Exons`, duplicates have identical exons and CDS,
+/// with `DuplicateIdentity::Cds` only the coding parts of the exons must be
+/// identical. The first transcript is kept and the names of all removed
+/// duplicates are added as `merged_transcript_id` attributes. Returns the
+/// remaining transcripts and the number of removed duplicates.
+pub fn deduplicate(transcripts: Transcripts, identity: &DuplicateIdentity) -> (Transcripts, usize) {
+    let mut unique = Transcripts::new();
+    // structure => name of the first transcript
+    let mut seen: HashMap<StructureKey, String> = HashMap::new();
+    let mut removed = 0;
+    for tx in transcripts {
+        let key = match structure_key(&tx, identity) {
+            Some(key) => key,
+            None => {
+                unique.push(tx);
+                continue;
+            }
+        };
+        match seen.get(&key) {
+            Some(kept) => {
+                debug!(target: "atg::merge", "Merging duplicate {} into {}", tx.name(), kept);
+                let mut attributes = attributes_of(tx.chrom(), kept).unwrap_or_default();
+                attributes.push(("merged_transcript_id".to_string(), tx.name().to_string()));
+                register_attributes(tx.chrom(), kept, attributes);
+                removed += 1;
+            }
+            None => {
+                seen.insert(key, tx.name().to_string());
+                unique.push(tx);
+            }
+        }
+    }
+    (unique, removed)
+}
+
+/// Merges the transcripts of multiple inputs into one `Transcripts` object
+///
+/// Transcripts are identified by their name and chromosome. If a transcript
+/// is present in more than one input with identical structure, only the
+/// first one is kept. Transcripts with the same identifier but different
+/// structures are resolved according to the `policy` and reported as conflict.
+///
+/// Duplicate transcripts within one input are always kept.
+pub fn merge(
+    inputs: Vec<(String, Transcripts)>,
+    policy: &MergePolicy,
+    report: &mut RunReport,
+) -> Result<Transcripts, AtgError> {
+    let mut merged: Vec<Transcript> = vec![];
+    // (name, chrom) => (index in merged, input number)
+    let mut index: HashMap<(String, String), (usize, usize)> = HashMap::new();
+    let filenames: Vec<String> = inputs.iter().map(|input| input.0.clone()).collect();
+
+    for (input_no, (_, transcripts)) in inputs.into_iter().enumerate() {
+        for tx in transcripts {
+            let key = (tx.name().to_string(), tx.chrom().to_string());
+            let (idx, first_input) = match index.get(&key) {
+                Some(existing) if existing.1 != input_no => *existing,
+                _ => {
+                    index.insert(key, (merged.len(), input_no));
+                    merged.push(tx);
+                    continue;
+                }
+            };
+
+            if same_structure(&merged[idx], &tx) {
+                continue;
+            }
+
+            let resolution = match policy {
+                MergePolicy::KeepFirst => "kept first".to_string(),
+                MergePolicy::KeepLongestCds => {
+                    if cds_len(&tx) > cds_len(&merged[idx]) {
+                        merged[idx] = tx;
+                        "kept second (longer CDS)".to_string()
+                    } else {
+                        "kept first (longer CDS)".to_string()
+                    }
+                }
+                MergePolicy::KeepBoth => {
+                    let name = format!("{}_{}", tx.name(), input_no + 1);
+                    merged.push(rebuild_transcript(&tx, &name, tx.chrom(), tx.gene())?);
+                    format!("kept both, renamed second to {}", name)
+                }
+            };
+            debug!(target: "atg::merge", "Merge conflict for {}: {}", key.0, resolution);
+
+            report.add_conflict(MergeConflict {
+                name: key.0,
+                chrom: key.1,
+                first_input: filenames[first_input].clone(),
+                second_input: filenames[input_no].clone(),
+                resolution,
+            });
+        }
+    }
+
+    let mut res = Transcripts::new();
+    for tx in merged {
+        res.push(tx)
+    }
+    Ok(res)
+}
@@ -0,0 +1,119 @@
+//! Invalid nucleotides in the reference genome
+
+use std::io::{Read, Seek};
+
+use atglib::models::{Transcript, TranscriptWrite};
+use atglib::utils::errors::AtgError;
+
+use crate::cli::FastaFormat;
+use crate::fasta_case::RawFasta;
+use crate::strandedness::segments;
+use crate::warnings::warning;
+
+/// The bases that atglib can read, in upper- and lowercase
+const NUCLEOTIDES: &[u8] = b"ACGTNacgtn";
+
+/// Checks the reference sequence of transcripts for invalid nucleotides
+pub struct SequenceCheck<R: Read + Seek> {
+    fasta: RawFasta<R>,
+    skip: bool,
+    skipped: usize,
+}
+
+impl<R: Read + Seek> SequenceCheck<R> {
+    /// Creates a new check, transcripts with invalid nucleotides are skipped if `skip` is set
+    pub fn new(fasta: RawFasta<R>, skip: bool) -> Self {
+        Self {
+            fasta,
+            skip,
+            skipped: 0,
+        }
+    }
+
+    /// Returns `true` if the sequence of the transcript can be written
+    ///
+    /// Returns an error for invalid nucleotides, or `false` if the
+    /// transcript is skipped.
+    pub fn check(&mut self, tx: &Transcript, format: &FastaFormat) -> Result<bool, AtgError> {
+        for (start, end) in segments(tx, format) {
+            let bases = self.fasta.read_raw(tx.chrom(), start, end)?;
+            let invalid = match bases.iter().position(|base| !NUCLEOTIDES.contains(base)) {
+                Some(idx) => idx,
+                None => continue,
+            };
+            let message = format!(
+                "invalid nucleotide {:?} at {}:{} in the sequence of {}",
+                char::from(bases[invalid]),
+                tx.chrom(),
+                start as usize + invalid,
+                tx.name()
+            );
+            if !self.skip {
+                return Err(AtgError::new(message));
+            }
+            warning(
+                "atg::write",
+                "invalid-nucleotides",
+                &format!("Skipping transcript: {}", message),
+            );
+            self.skipped += 1;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+}
+
+impl<R: Read + Seek> Drop for SequenceCheck<R> {
+    fn drop(&mut self) {
+        if self.skipped > 0 {
+            warn!(
+                target: "atg::write",
+                "Skipped {} transcripts with invalid nucleotides",
+                self.skipped
+            );
+        }
+    }
+}
+
+/// Wraps a Fasta writer and checks every transcript before it is written
+pub struct CheckedSequences<'a, R: Read + Seek> {
+    inner: Box<dyn TranscriptWrite + 'a>,
+    check: SequenceCheck<R>,
+    format: FastaFormat,
+}
+
+impl<'a, R: Read + Seek> CheckedSequences<'a, R> {
+    pub fn new(
+        inner: Box<dyn TranscriptWrite + 'a>,
+        check: SequenceCheck<R>,
+        format: FastaFormat,
+    ) -> Self {
+        Self {
+            inner,
+            check,
+            format,
+        }
+    }
+
+    fn is_valid(&mut self, tx: &Transcript) -> Result<bool, std::io::Error> {
+        self.check
+            .check(tx, &self.format)
+            .map_err(|err| std::io::Error::other(err.to_string()))
+    }
+}
+
+impl<R: Read + Seek> TranscriptWrite for CheckedSequences<'_, R> {
+    fn writeln_single_transcript(&mut self, transcript: &Transcript) -> Result<(), std::io::Error> {
+        match self.is_valid(transcript)? {
+            true => self.inner.writeln_single_transcript(transcript),
+            false => Ok(()),
+        }
+    }
+
+    fn write_single_transcript(&mut self, transcript: &Transcript) -> Result<(), std::io::Error> {
+        match self.is_valid(transcript)? {
+            true => self.inner.write_single_transcript(transcript),
+            false => Ok(()),
+        }
+    }
+}
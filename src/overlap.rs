@@ -0,0 +1,93 @@
+//! Find genes that overlap each transcript
+
+use std::collections::{BTreeSet, HashMap};
+use std::io::Write;
+
+use atglib::models::Transcript;
+
+/// The genes that overlap a transcript, split by their orientation
+#[derive(Default)]
+pub struct OverlapContext<'a> {
+    pub same_strand: BTreeSet<&'a str>,
+    pub opposite_strand: BTreeSet<&'a str>,
+}
+
+impl<'a> OverlapContext<'a> {
+    fn add(&mut self, tx: &Transcript, other: &'a Transcript) {
+        if tx.gene() == other.gene() {
+            return;
+        }
+        if tx.strand() == other.strand() {
+            self.same_strand.insert(other.gene());
+        } else {
+            self.opposite_strand.insert(other.gene());
+        }
+    }
+}
+
+/// Returns the overlapping genes for every transcript
+///
+/// Two transcripts overlap if their genomic spans (including introns) share
+/// at least one base. The result has the same order as the input.
+pub fn overlapping_genes(transcripts: &[Transcript]) -> Vec<OverlapContext<'_>> {
+    let mut contexts: Vec<OverlapContext> = transcripts
+        .iter()
+        .map(|_| OverlapContext::default())
+        .collect();
+
+    let mut by_chrom: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (idx, tx) in transcripts.iter().enumerate() {
+        by_chrom.entry(tx.chrom()).or_default().push(idx);
+    }
+
+    for mut indices in by_chrom.into_values() {
+        indices.sort_by_key(|idx| transcripts[*idx].tx_start());
+        // sweep through the chromosome, keeping all transcripts that
+        // still overlap the current position
+        let mut active: Vec<usize> = vec![];
+        for idx in indices {
+            let tx = &transcripts[idx];
+            active.retain(|other| transcripts[*other].tx_end() >= tx.tx_start());
+            for other in &active {
+                contexts[idx].add(tx, &transcripts[*other]);
+                contexts[*other].add(&transcripts[*other], tx);
+            }
+            active.push(idx);
+        }
+    }
+    contexts
+}
+
+/// Joins the gene names, or returns `.` if there are none
+fn join(genes: &BTreeSet<&str>) -> String {
+    if genes.is_empty() {
+        return ".".to_string();
+    }
+    genes.iter().copied().collect::<Vec<&str>>().join(",")
+}
+
+/// Writes one row per transcript with the overlapping genes on the same and on the opposite strand
+pub fn write_overlaps<W: Write>(
+    transcripts: &[Transcript],
+    mut out: W,
+) -> Result<(), std::io::Error> {
+    writeln!(
+        out,
+        "Gene\tTranscript\tChrom\tStart\tEnd\tStrand\tSame strand\tOpposite strand"
+    )?;
+    for (tx, context) in transcripts.iter().zip(overlapping_genes(transcripts)) {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            tx.gene(),
+            tx.name(),
+            tx.chrom(),
+            tx.tx_start(),
+            tx.tx_end(),
+            tx.strand(),
+            join(&context.same_strand),
+            join(&context.opposite_strand)
+        )?;
+    }
+    out.flush()
+}
@@ -0,0 +1,42 @@
+//! Stop codons that are completed by polyadenylation
+
+use std::io::{Read, Seek};
+
+use atglib::fasta::FastaReader;
+use atglib::models::Transcript;
+use atglib::qc::QcResult;
+
+use crate::stop_codon::{last_cds_positions, read_codon, STOP_CODONS};
+use crate::utils::cds_len;
+
+/// Returns `OK` if the CDS ends with an incomplete stop codon that is completed by `A`s
+///
+/// Transcripts whose CDS length is divisible by 3 are `NA`, as well as
+/// non-coding transcripts and transcripts whose CDS can't be read from the
+/// reference genome.
+pub fn check_polya_stop<R: Read + Seek>(tx: &Transcript, fasta: &mut FastaReader<R>) -> QcResult {
+    let incomplete = (cds_len(tx) % 3) as usize;
+    if incomplete == 0 {
+        return QcResult::NA;
+    }
+    let bases = match read_codon(fasta, tx, &last_cds_positions(tx, incomplete)) {
+        Ok(bases) => bases,
+        Err(err) => {
+            debug!(target: "atg::qc", "Unable to read the CDS of {}: {}", tx.name(), err);
+            return QcResult::NA;
+        }
+    };
+    let codon = format!("{:A<3}", bases);
+    match STOP_CODONS.contains(&codon.as_str()) {
+        true => {
+            debug!(
+                target: "atg::qc",
+                "The stop codon {} of {} is completed by polyadenylation",
+                codon,
+                tx.name()
+            );
+            QcResult::OK
+        }
+        false => QcResult::NOK,
+    }
+}
@@ -0,0 +1,80 @@
+//! Progress bar for long conversions (feature `progress`)
+
+use std::io::Read;
+use std::sync::OnceLock;
+
+use indicatif::{ProgressBar, ProgressStyle};
+
+/// Template of the progress bar, if the size of the input files is known
+const BAR_TEMPLATE: &str =
+    "{spinner} [{elapsed_precise}] {wide_bar} {bytes}/{total_bytes} (ETA {eta}) {msg}";
+
+/// Template of the progress bar for input from pipes or downloads
+const SPINNER_TEMPLATE: &str = "{spinner} [{elapsed_precise}] {bytes} {msg}";
+
+static PROGRESS: OnceLock<ProgressBar> = OnceLock::new();
+
+/// Starts the progress bar for the input files
+///
+/// The total size is the size of all regular files, the progress bar
+/// shows a spinner if it is unknown (e.g. for `/dev/stdin`).
+pub fn start_progress(files: &[String]) {
+    let total: u64 = files
+        .iter()
+        .filter_map(|file| std::fs::metadata(file).ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum();
+    let (bar, template) = match total {
+        0 => (ProgressBar::new_spinner(), SPINNER_TEMPLATE),
+        total => (ProgressBar::new(total), BAR_TEMPLATE),
+    };
+    if let Ok(style) = ProgressStyle::with_template(template) {
+        bar.set_style(style);
+    }
+    if PROGRESS.set(bar).is_err() {
+        warn!(target: "atg::read", "The progress bar is already started");
+    }
+}
+
+/// Shows the current stage, e.g. `reading` or `writing`
+pub fn set_stage(stage: &str) {
+    if let Some(bar) = PROGRESS.get() {
+        bar.set_message(stage.to_string());
+    }
+}
+
+/// Shows the number of converted transcripts
+pub fn set_transcripts(count: usize) {
+    if let Some(bar) = PROGRESS.get() {
+        bar.set_message(format!("{} transcripts", count));
+    }
+}
+
+/// Removes the progress bar
+pub fn finish_progress() {
+    if let Some(bar) = PROGRESS.get() {
+        bar.finish_and_clear();
+    }
+}
+
+/// Counts the bytes that are read from the inner reader in the progress bar
+pub struct ProgressReader<R: Read> {
+    inner: R,
+}
+
+impl<R: Read> ProgressReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self { inner }
+    }
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        if let Some(bar) = PROGRESS.get() {
+            bar.inc(len as u64);
+        }
+        Ok(len)
+    }
+}
@@ -0,0 +1,157 @@
+//! Typed results of QC checks of a single transcript
+
+use std::io::{Read, Seek};
+
+use atglib::fasta::FastaReader;
+use atglib::models::{GeneticCode, Transcript};
+use atglib::qc::{QcCheck, QcResult};
+use clap::ValueEnum;
+
+use crate::cli::{Args, QcFilter};
+use crate::codons::{start_codon_checked, stop_codon_checked};
+use crate::polya::check_polya_stop;
+use crate::selenocysteine::check_upstream_stop;
+use crate::splice_sites::check_splice_sites;
+
+/// The outcome of a single QC check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QcStatus {
+    Ok,
+    Nok,
+    Na,
+}
+
+impl From<QcResult> for QcStatus {
+    fn from(result: QcResult) -> Self {
+        match result {
+            QcResult::OK => QcStatus::Ok,
+            QcResult::NOK => QcStatus::Nok,
+            QcResult::NA => QcStatus::Na,
+        }
+    }
+}
+
+/// The results of all QC checks that were performed on one transcript
+///
+/// Every field is `None` if the check was not performed.
+#[derive(Debug, Default, Clone)]
+pub struct QcReport {
+    pub transcript: String,
+    pub gene: String,
+    pub exon: Option<QcStatus>,
+    pub cds_length: Option<QcStatus>,
+    pub start_codon: Option<QcStatus>,
+    pub stop_codon: Option<QcStatus>,
+    pub upstream_start: Option<QcStatus>,
+    pub upstream_stop: Option<QcStatus>,
+    pub coordinates: Option<QcStatus>,
+    pub structure: Option<QcStatus>,
+    pub splice_sites: Option<QcStatus>,
+}
+
+impl QcReport {
+    /// Performs the specified QC checks and returns their results
+    ///
+    /// The `splice-sites` check requires the reference sequence, it must be set with `set`.
+    pub fn run_subset(qc: &QcCheck, tx: &Transcript, checks: &[QcFilter]) -> Self {
+        let mut report = Self {
+            transcript: tx.name().to_string(),
+            gene: tx.gene().to_string(),
+            ..Self::default()
+        };
+        for check in checks {
+            // a partial or absent codon can't be checked
+            let codon = match check {
+                QcFilter::Start => Some(start_codon_checked(tx)),
+                QcFilter::Stop => Some(stop_codon_checked(tx)),
+                _ => None,
+            };
+            let status = match codon {
+                Some(codon) if !codon.is_complete() => QcStatus::Na,
+                _ => check.result(qc, tx).into(),
+            };
+            *report.field_mut(check) = Some(status);
+        }
+        report
+    }
+
+    /// Performs the QC checks of `--qc-check`, including those that atglib doesn't provide
+    ///
+    /// The results of atglib are corrected for the selenocysteine codons of
+    /// selenoproteins and for stop codons that are completed by the poly(A) tail
+    /// (see `--polya-stop`).
+    pub fn run_all<R: Read + Seek>(
+        tx: &Transcript,
+        fastareader: &mut FastaReader<R>,
+        code: &GeneticCode,
+        args: &Args,
+    ) -> Self {
+        let qc = QcCheck::new(tx, fastareader, code);
+        let mut report = Self::run_subset(&qc, tx, &args.qc_check);
+        if args.qc_check.contains(&QcFilter::SpliceSites) {
+            let result = check_splice_sites(tx, fastareader, args.allow_minor_splice_sites);
+            report.set(&QcFilter::SpliceSites, result);
+        }
+        // atglib treats the selenocysteine codons of selenoproteins as upstream stop codons
+        if report.get(&QcFilter::UpstreamStop) == Some(QcStatus::Nok)
+            && matches!(check_upstream_stop(tx, fastareader), QcResult::OK)
+        {
+            report.set(&QcFilter::UpstreamStop, QcResult::OK);
+        }
+        if args.polya_stop.iter().any(|chrom| chrom == tx.chrom())
+            && matches!(check_polya_stop(tx, fastareader), QcResult::OK)
+        {
+            for check in [QcFilter::CdsLength, QcFilter::Stop] {
+                if report.get(&check).is_some() {
+                    report.set(&check, QcResult::OK);
+                }
+            }
+        }
+        report
+    }
+
+    /// Sets the result of a QC check that was performed separately
+    fn set(&mut self, check: &QcFilter, result: QcResult) {
+        *self.field_mut(check) = Some(result.into());
+    }
+
+    /// Returns the result of the QC check, `None` if it was not performed
+    pub fn get(&self, check: &QcFilter) -> Option<QcStatus> {
+        match check {
+            QcFilter::Exon => self.exon,
+            QcFilter::CdsLength => self.cds_length,
+            QcFilter::Start => self.start_codon,
+            QcFilter::Stop => self.stop_codon,
+            QcFilter::UpstreamStart => self.upstream_start,
+            QcFilter::UpstreamStop => self.upstream_stop,
+            QcFilter::Coordinates => self.coordinates,
+            QcFilter::Structure => self.structure,
+            QcFilter::SpliceSites => self.splice_sites,
+        }
+    }
+
+    fn field_mut(&mut self, check: &QcFilter) -> &mut Option<QcStatus> {
+        match check {
+            QcFilter::Exon => &mut self.exon,
+            QcFilter::CdsLength => &mut self.cds_length,
+            QcFilter::Start => &mut self.start_codon,
+            QcFilter::Stop => &mut self.stop_codon,
+            QcFilter::UpstreamStart => &mut self.upstream_start,
+            QcFilter::UpstreamStop => &mut self.upstream_stop,
+            QcFilter::Coordinates => &mut self.coordinates,
+            QcFilter::Structure => &mut self.structure,
+            QcFilter::SpliceSites => &mut self.splice_sites,
+        }
+    }
+
+    /// Returns all checks with a `NOK` result
+    ///
+    /// Checks that were not performed or are `NA` never fail.
+    pub fn failed(&self) -> Vec<QcFilter> {
+        QcFilter::value_variants()
+            .iter()
+            .filter(|check| self.get(check) == Some(QcStatus::Nok))
+            .cloned()
+            .collect()
+    }
+}
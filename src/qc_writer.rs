@@ -0,0 +1,261 @@
+//! atglib's QC output, with corrections for selenoproteins and polyadenylated stop codons
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::{Read, Seek, Write};
+use std::rc::Rc;
+
+use atglib::fasta::FastaReader;
+use atglib::models::{Transcript, TranscriptWrite};
+use atglib::qc::{self, QcResult};
+
+use crate::line_filter::{FilterLines, LineFilter};
+use crate::polya::check_polya_stop;
+use crate::selenocysteine::check_upstream_stop;
+
+/// The columns of the QC output
+const CDS_LENGTH_COLUMN: usize = 3;
+const STOP_COLUMN: usize = 5;
+const UPSTREAM_STOP_COLUMN: usize = 7;
+
+/// The transcript name and the columns that are `OK` instead of `NOK`, for every line
+///
+/// atglib buffers its output, so the entries are queued in the order of the
+/// lines and removed once the line is written. The QC lines only contain the
+/// gene and transcript name, so transcripts with the same name are told apart
+/// by their position in the queue.
+type Corrections = Rc<RefCell<VecDeque<(String, Vec<usize>)>>>;
+
+/// Sets the corrected columns of QC lines to `OK`
+pub struct CorrectedColumns {
+    corrections: Corrections,
+}
+
+impl LineFilter for CorrectedColumns {
+    fn write_line(&mut self, line: &[u8], out: &mut dyn Write) -> std::io::Result<()> {
+        let text = String::from_utf8_lossy(line);
+        let mut cols: Vec<&str> = text.split('\t').collect();
+        let columns = match self.corrections.borrow_mut().pop_front() {
+            Some((name, columns)) if cols.get(1) == Some(&name.as_str()) => columns,
+            _ => vec![],
+        };
+        let mut corrected = false;
+        for column in columns {
+            if cols.get(column) == Some(&"NOK") {
+                cols[column] = "OK";
+                corrected = true;
+            }
+        }
+        match corrected {
+            true => out.write_all(cols.join("\t").as_bytes()),
+            false => out.write_all(line),
+        }
+    }
+}
+
+/// atglib's QC writer, with corrected results
+pub struct QcWriter<W: Write, R: Read + Seek> {
+    inner: qc::Writer<FilterLines<W, CorrectedColumns>, R>,
+    fasta: FastaReader<R>,
+    polya_chroms: Vec<String>,
+    corrections: Corrections,
+}
+
+impl<W: Write, R: Read + Seek> QcWriter<W, R> {
+    /// Creates a new writer, `fasta` is only used for the corrections
+    ///
+    /// Stop codons that are completed by polyadenylation are accepted on `polya_chroms`.
+    pub fn new(writer: W, fasta: FastaReader<R>, polya_chroms: &[String]) -> Self {
+        let corrections = Rc::new(RefCell::new(VecDeque::new()));
+        Self {
+            inner: qc::Writer::new(FilterLines::new(
+                writer,
+                CorrectedColumns {
+                    corrections: corrections.clone(),
+                },
+            )),
+            fasta,
+            polya_chroms: polya_chroms.to_vec(),
+            corrections,
+        }
+    }
+
+    /// Returns the QC writer, e.g. to set its reference genome and genetic code
+    pub fn qc_writer_mut(&mut self) -> &mut qc::Writer<FilterLines<W, CorrectedColumns>, R> {
+        &mut self.inner
+    }
+
+    pub fn write_header(&mut self) -> Result<(), std::io::Error> {
+        // the header line is never corrected
+        self.corrections
+            .borrow_mut()
+            .push_back((String::new(), vec![]));
+        self.inner.write_header()
+    }
+
+    pub fn flush(&mut self) -> Result<(), std::io::Error> {
+        self.inner.flush()
+    }
+
+    /// Registers the corrected columns of the next QC line
+    fn check(&mut self, tx: &Transcript) {
+        let columns = corrected_columns(tx, &mut self.fasta, &self.polya_chroms);
+        self.corrections
+            .borrow_mut()
+            .push_back((tx.name().to_string(), columns));
+    }
+}
+
+/// Returns the columns of the QC output that are `OK`, even if atglib's check fails
+///
+/// Upstream stop codons are accepted if they encode selenocysteine, incomplete
+/// stop codons if they are completed by polyadenylation on `polya_chroms`.
+fn corrected_columns<R: Read + Seek>(
+    tx: &Transcript,
+    fasta: &mut FastaReader<R>,
+    polya_chroms: &[String],
+) -> Vec<usize> {
+    let mut columns = vec![];
+    if matches!(check_upstream_stop(tx, fasta), QcResult::OK) {
+        columns.push(UPSTREAM_STOP_COLUMN);
+    }
+    if polya_chroms.iter().any(|chrom| chrom == tx.chrom())
+        && matches!(check_polya_stop(tx, fasta), QcResult::OK)
+    {
+        columns.extend([CDS_LENGTH_COLUMN, STOP_COLUMN]);
+    }
+    columns
+}
+
+impl<W: Write, R: Read + Seek> TranscriptWrite for QcWriter<W, R> {
+    fn writeln_single_transcript(&mut self, transcript: &Transcript) -> Result<(), std::io::Error> {
+        self.check(transcript);
+        self.inner.writeln_single_transcript(transcript)
+    }
+
+    fn write_single_transcript(&mut self, transcript: &Transcript) -> Result<(), std::io::Error> {
+        self.check(transcript);
+        self.inner.write_single_transcript(transcript)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use atglib::models::{CdsStat, Exon, Frame, Strand, TranscriptBuilder};
+
+    use super::*;
+    use crate::memory_fasta::MemoryFasta;
+    use crate::selenocysteine::register_selenocysteines;
+
+    type Reader = FastaReader<std::io::Cursor<Arc<[u8]>>>;
+
+    /// Returns a single-exon transcript on the plus strand, from position 1 to `end`
+    fn transcript(name: &str, chrom: &str, cds: (u32, u32), end: u32) -> Transcript {
+        let mut tx = TranscriptBuilder::new()
+            .name(name)
+            .chrom(chrom)
+            .gene("Gene")
+            .strand(Strand::Plus)
+            .cds_start_codon_stat(CdsStat::Complete)
+            .unwrap()
+            .cds_stop_codon_stat(CdsStat::Complete)
+            .unwrap()
+            .build()
+            .unwrap();
+        tx.push_exon(Exon::new(1, end, Some(cds.0), Some(cds.1), Frame::Zero));
+        tx
+    }
+
+    fn fasta_reader(chrom: &str, sequence: &str) -> Reader {
+        let fasta = MemoryFasta::from_sequences([(chrom, sequence)]);
+        FastaReader::from_reader(fasta.fasta(), fasta.fai()).unwrap()
+    }
+
+    /// Passes the lines through `CorrectedColumns` and returns the output
+    fn corrected(corrections: Vec<(&str, Vec<usize>)>, lines: &str) -> String {
+        let queue: Corrections = Rc::new(RefCell::new(
+            corrections
+                .into_iter()
+                .map(|(name, columns)| (name.to_string(), columns))
+                .collect(),
+        ));
+        let mut out: Vec<u8> = vec![];
+        {
+            let mut writer = FilterLines::new(
+                &mut out,
+                CorrectedColumns {
+                    corrections: queue.clone(),
+                },
+            );
+            writer.write_all(lines.as_bytes()).unwrap();
+        }
+        assert!(queue.borrow().is_empty());
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_upstream_selenocysteine() {
+        // ATG TGA GCT TAA, with TGA at positions 6-8
+        let mut fasta = fasta_reader("chr1", "CCATGTGAGCTTAACC");
+        let tx = transcript("QC-Sec", "chr1", (3, 14), 16);
+        assert!(corrected_columns(&tx, &mut fasta, &[]).is_empty());
+
+        register_selenocysteines("chr1", "QC-Sec", vec![(6, 8)]);
+        assert_eq!(
+            corrected_columns(&tx, &mut fasta, &[]),
+            vec![UPSTREAM_STOP_COLUMN]
+        );
+
+        register_selenocysteines("chr1", "QC-Sec", vec![(9, 11)]);
+        assert!(corrected_columns(&tx, &mut fasta, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_polyadenylated_stop() {
+        // ATG GCT TA, completed to TAA by the poly(A) tail
+        let mut fasta = fasta_reader("chrM", "CCATGGCTTACCC");
+        let tx = transcript("QC-PolyA", "chrM", (3, 10), 13);
+        assert_eq!(
+            corrected_columns(&tx, &mut fasta, &["chrM".to_string()]),
+            vec![CDS_LENGTH_COLUMN, STOP_COLUMN]
+        );
+        // only on the listed chromosomes
+        assert!(corrected_columns(&tx, &mut fasta, &[]).is_empty());
+        assert!(corrected_columns(&tx, &mut fasta, &["chr1".to_string()]).is_empty());
+
+        // ATG GCT TC can't be completed to a stop codon
+        let mut fasta = fasta_reader("chrM", "CCATGGCTTCCCC");
+        assert!(corrected_columns(&tx, &mut fasta, &["chrM".to_string()]).is_empty());
+    }
+
+    #[test]
+    fn test_corrected_columns() {
+        let lines = "Gene\tTranscript\tExon\tCDS Length\tStart\tStop\tUpstream Start\tUpstream Stop\tCoordinates\n\
+            G\tTx1\tOK\tNOK\tOK\tNOK\tOK\tNOK\tOK\n";
+        let output = corrected(
+            vec![("", vec![]), ("Tx1", vec![CDS_LENGTH_COLUMN, STOP_COLUMN])],
+            lines,
+        );
+        assert_eq!(
+            output.lines().nth(1),
+            Some("G\tTx1\tOK\tOK\tOK\tOK\tOK\tNOK\tOK")
+        );
+    }
+
+    #[test]
+    fn test_repeated_transcript_name() {
+        // e.g. the copies of a transcript in the PAR of chrX and chrY
+        let lines = "G\tTx1\tOK\tOK\tOK\tOK\tOK\tNOK\tOK\n\
+            G\tTx1\tOK\tOK\tOK\tOK\tOK\tNOK\tOK\n";
+        let output = corrected(
+            vec![("Tx1", vec![UPSTREAM_STOP_COLUMN]), ("Tx1", vec![])],
+            lines,
+        );
+        assert_eq!(
+            output,
+            "G\tTx1\tOK\tOK\tOK\tOK\tOK\tOK\tOK\nG\tTx1\tOK\tOK\tOK\tOK\tOK\tNOK\tOK\n"
+        );
+    }
+}
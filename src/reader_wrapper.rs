@@ -1,30 +1,92 @@
 use std::fs::File;
+use std::io::Cursor;
+use std::sync::Arc;
 
+#[cfg(feature = "s3")]
 use s3reader::{S3ObjectUri, S3Reader};
 
+#[cfg(feature = "remote")]
+use crate::http_reader::HttpReader;
+
 use atglib::utils::errors::AtgError;
 
+use crate::block_cache::{cache_blocks, BlockCache};
+use crate::memory_fasta::stdin_reference;
+
+/// The filename to read the reference genome from stdin
+pub const STDIN: &str = "-";
+
 // There will be only a single instance of this enum
 // so we can allow a large variant
 #[allow(clippy::large_enum_variant)]
-/// ReadSeekWrapper is an enum to allow dynamic assignment of either File or S3 Readers
+/// ReadSeekWrapper is an enum to allow dynamic assignment of either File, S3, HTTP or in-memory Readers
 /// to be used in the Reader objects of Atglib.
 pub enum ReadSeekWrapper {
     File(File, String),
+    #[cfg(feature = "s3")]
     S3(S3Reader, String),
+    #[cfg(feature = "remote")]
+    Http(HttpReader, String),
+    Memory(Cursor<Arc<[u8]>>, String),
+    Cached(Box<BlockCache<ReadSeekWrapper>>),
 }
 
 impl ReadSeekWrapper {
+    /// Opens the file, all but in-memory files are cached, see `block_cache`
     pub fn from_filename(filename: &str) -> Result<Self, AtgError> {
+        let reader = Self::open(filename)?;
+        match (&reader, cache_blocks()) {
+            (ReadSeekWrapper::Memory(..), _) | (_, 0) => Ok(reader),
+            (_, blocks) => Ok(Self::Cached(Box::new(BlockCache::new(reader, blocks)))),
+        }
+    }
+
+    fn open(filename: &str) -> Result<Self, AtgError> {
         if filename.starts_with("s3://") {
-            let uri = S3ObjectUri::new(filename).map_err(AtgError::new)?;
-            let s3obj = S3Reader::open(uri).map_err(AtgError::new)?;
-            Ok(Self::S3(s3obj, filename.to_string()))
+            Self::from_s3(filename)
+        } else if filename.starts_with("http://") || filename.starts_with("https://") {
+            Self::from_http(filename)
+        } else if filename == STDIN {
+            Ok(Self::Memory(
+                stdin_reference()?.fasta(),
+                filename.to_string(),
+            ))
+        } else if filename == format!("{}.fai", STDIN) {
+            Ok(Self::Memory(stdin_reference()?.fai(), filename.to_string()))
         } else {
             Ok(Self::File(File::open(filename)?, filename.to_string()))
         }
     }
 
+    #[cfg(feature = "s3")]
+    fn from_s3(filename: &str) -> Result<Self, AtgError> {
+        let uri = S3ObjectUri::new(filename).map_err(AtgError::new)?;
+        let s3obj = S3Reader::open(uri).map_err(AtgError::new)?;
+        Ok(Self::S3(s3obj, filename.to_string()))
+    }
+
+    #[cfg(not(feature = "s3"))]
+    fn from_s3(_filename: &str) -> Result<Self, AtgError> {
+        Err(AtgError::new(
+            "atg was built without S3 support, rebuild it with `--features s3`",
+        ))
+    }
+
+    #[cfg(feature = "remote")]
+    fn from_http(filename: &str) -> Result<Self, AtgError> {
+        Ok(Self::Http(
+            HttpReader::open(filename)?,
+            filename.to_string(),
+        ))
+    }
+
+    #[cfg(not(feature = "remote"))]
+    fn from_http(_filename: &str) -> Result<Self, AtgError> {
+        Err(AtgError::new(
+            "atg was built without HTTP support, rebuild it with `--features remote`",
+        ))
+    }
+
     pub fn from_cli_arg(filename: &Option<&str>) -> Result<ReadSeekWrapper, AtgError> {
         if let Some(filename) = filename {
             Ok(ReadSeekWrapper::from_filename(filename)?)
@@ -36,7 +98,12 @@ impl ReadSeekWrapper {
     pub fn filename(&self) -> &str {
         match self {
             ReadSeekWrapper::File(_, fname) => fname,
+            #[cfg(feature = "s3")]
             ReadSeekWrapper::S3(_, fname) => fname,
+            #[cfg(feature = "remote")]
+            ReadSeekWrapper::Http(_, fname) => fname,
+            ReadSeekWrapper::Memory(_, fname) => fname,
+            ReadSeekWrapper::Cached(r) => r.get_ref().filename(),
         }
     }
 }
@@ -45,22 +112,37 @@ impl ReadSeekWrapper {
 impl std::io::Read for ReadSeekWrapper {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
         match self {
+            #[cfg(feature = "s3")]
             ReadSeekWrapper::S3(r, _) => r.read(buf),
+            #[cfg(feature = "remote")]
+            ReadSeekWrapper::Http(r, _) => r.read(buf),
             ReadSeekWrapper::File(r, _) => r.read(buf),
+            ReadSeekWrapper::Memory(r, _) => r.read(buf),
+            ReadSeekWrapper::Cached(r) => r.read(buf),
         }
     }
 
     fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize, std::io::Error> {
         match self {
+            #[cfg(feature = "s3")]
             ReadSeekWrapper::S3(r, _) => r.read_to_end(buf),
+            #[cfg(feature = "remote")]
+            ReadSeekWrapper::Http(r, _) => r.read_to_end(buf),
             ReadSeekWrapper::File(r, _) => r.read_to_end(buf),
+            ReadSeekWrapper::Memory(r, _) => r.read_to_end(buf),
+            ReadSeekWrapper::Cached(r) => r.read_to_end(buf),
         }
     }
 
     fn read_to_string(&mut self, buf: &mut String) -> Result<usize, std::io::Error> {
         match self {
+            #[cfg(feature = "s3")]
             ReadSeekWrapper::S3(r, _) => r.read_to_string(buf),
+            #[cfg(feature = "remote")]
+            ReadSeekWrapper::Http(r, _) => r.read_to_string(buf),
             ReadSeekWrapper::File(r, _) => r.read_to_string(buf),
+            ReadSeekWrapper::Memory(r, _) => r.read_to_string(buf),
+            ReadSeekWrapper::Cached(r) => r.read_to_string(buf),
         }
     }
 }
@@ -68,8 +150,13 @@ impl std::io::Read for ReadSeekWrapper {
 impl std::io::Seek for ReadSeekWrapper {
     fn seek(&mut self, pos: std::io::SeekFrom) -> Result<u64, std::io::Error> {
         match self {
+            #[cfg(feature = "s3")]
             ReadSeekWrapper::S3(r, _) => r.seek(pos),
+            #[cfg(feature = "remote")]
+            ReadSeekWrapper::Http(r, _) => r.seek(pos),
             ReadSeekWrapper::File(r, _) => r.seek(pos),
+            ReadSeekWrapper::Memory(r, _) => r.seek(pos),
+            ReadSeekWrapper::Cached(r) => r.seek(pos),
         }
     }
 }
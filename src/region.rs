@@ -0,0 +1,84 @@
+//! Genomic regions to select transcripts, e.g. `chr7:140,000,000-141,000,000`
+
+use atglib::models::{Transcript, Transcripts};
+
+use crate::lookup::PositionIndex;
+
+/// A genomic region (1-based, inclusive)
+#[derive(Clone, Debug)]
+pub struct Region {
+    pub chrom: String,
+    pub start: u32,
+    pub end: u32,
+}
+
+impl std::str::FromStr for Region {
+    type Err = String;
+
+    /// Parses `chrom`, `chrom:pos` or `chrom:start-end`
+    ///
+    /// Positions can contain `,` or `_` as thousands separators.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |pos: &str| {
+            pos.replace([',', '_'], "")
+                .parse::<u32>()
+                .map_err(|_| format!("invalid position `{}` in region {}", pos, s))
+        };
+        let (chrom, start, end) = match s.rsplit_once(':') {
+            None => (s, 1, u32::MAX),
+            Some((chrom, range)) => match range.split_once('-') {
+                Some((start, end)) => (chrom, parse(start)?, parse(end)?),
+                None => {
+                    let pos = parse(range)?;
+                    (chrom, pos, pos)
+                }
+            },
+        };
+        if chrom.is_empty() {
+            return Err(format!("missing chromosome in region {}", s));
+        }
+        if start == 0 || start > end {
+            return Err(format!("invalid interval in region {}", s));
+        }
+        Ok(Self {
+            chrom: chrom.to_string(),
+            start,
+            end,
+        })
+    }
+}
+
+impl Region {
+    /// Returns `true` if the transcript overlaps the region
+    pub fn overlaps(&self, tx: &Transcript) -> bool {
+        tx.chrom() == self.chrom && tx.tx_start() <= self.end && tx.tx_end() >= self.start
+    }
+}
+
+impl std::fmt::Display for Region {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}-{}", self.chrom, self.start, self.end)
+    }
+}
+
+/// Returns all transcripts that overlap at least one of the regions
+///
+/// The transcripts keep their original order.
+pub fn select_regions(transcripts: Transcripts, regions: &[Region]) -> Transcripts {
+    let transcripts = transcripts.to_vec();
+    let mut keep = vec![false; transcripts.len()];
+    let index = PositionIndex::new(&transcripts);
+    for region in regions {
+        for idx in index.overlapping_indices(&region.chrom, region.start, region.end) {
+            keep[idx] = true;
+        }
+    }
+
+    let mut selected = Transcripts::new();
+    for (tx, keep) in transcripts.into_iter().zip(keep) {
+        if keep {
+            selected.push(tx);
+        }
+    }
+    selected
+}
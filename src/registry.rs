@@ -0,0 +1,114 @@
+//! Global registries of transcript data that atglib's transcripts don't hold
+
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// Locks a registry for reading
+///
+/// A poisoned lock still contains a valid map, since insert can't fail halfway,
+/// so the poison is ignored.
+pub fn read_lock<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Locks a registry for writing, see `read_lock`
+pub fn write_lock<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(|err| err.into_inner())
+}
+
+/// transcript name => [(chromosome, value)]
+type Entries<V> = HashMap<String, Vec<(String, V)>>;
+
+/// Values of transcripts, keyed by chromosome and transcript name
+///
+/// Transcripts with the same name on different chromosomes, e.g. in the
+/// pseudoautosomal regions of chrX and chrY, have separate values. If no
+/// value is registered for the chromosome (e.g. after `--chrom-style`
+/// renamed it), the value is returned if the transcript name is unique.
+pub struct TranscriptRegistry<V> {
+    entries: OnceLock<RwLock<Entries<V>>>,
+}
+
+impl<V: Clone> TranscriptRegistry<V> {
+    pub const fn new() -> Self {
+        Self {
+            entries: OnceLock::new(),
+        }
+    }
+
+    fn entries(&self) -> &RwLock<Entries<V>> {
+        self.entries.get_or_init(|| RwLock::new(HashMap::new()))
+    }
+
+    /// Registers the value of a transcript, replacing a previous one on the same chromosome
+    pub fn insert(&self, chrom: &str, transcript: &str, value: V) {
+        let mut entries = write_lock(self.entries());
+        let values = entries.entry(transcript.to_string()).or_default();
+        match values.iter_mut().find(|(c, _)| c == chrom) {
+            Some((_, existing)) => *existing = value,
+            None => values.push((chrom.to_string(), value)),
+        }
+    }
+
+    /// Returns the value of a transcript
+    pub fn get(&self, chrom: &str, transcript: &str) -> Option<V> {
+        let entries = read_lock(self.entries());
+        match entries.get(transcript)?.as_slice() {
+            [(_, value)] => Some(value.clone()),
+            values => values
+                .iter()
+                .find(|(c, _)| c == chrom)
+                .map(|(_, value)| value.clone()),
+        }
+    }
+
+    /// Returns all transcript names and values, sorted by transcript name and chromosome
+    pub fn all(&self) -> Vec<(String, V)> {
+        let entries = read_lock(self.entries());
+        let mut all: Vec<(&String, &String, &V)> = entries
+            .iter()
+            .flat_map(|(transcript, values)| {
+                values
+                    .iter()
+                    .map(move |(chrom, value)| (transcript, chrom, value))
+            })
+            .collect();
+        all.sort_unstable_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+        all.into_iter()
+            .map(|(transcript, _, value)| (transcript.clone(), value.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_name_on_different_chromosomes() {
+        let registry: TranscriptRegistry<u32> = TranscriptRegistry::new();
+        registry.insert("chrX", "ENST00000381192", 1);
+        registry.insert("chrY", "ENST00000381192", 2);
+        assert_eq!(registry.get("chrX", "ENST00000381192"), Some(1));
+        assert_eq!(registry.get("chrY", "ENST00000381192"), Some(2));
+        assert_eq!(registry.get("X", "ENST00000381192"), None);
+
+        registry.insert("chrX", "ENST00000381192", 3);
+        assert_eq!(registry.get("chrX", "ENST00000381192"), Some(3));
+        assert_eq!(
+            registry.all(),
+            vec![
+                ("ENST00000381192".to_string(), 3),
+                ("ENST00000381192".to_string(), 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unique_name_on_renamed_chromosome() {
+        let registry: TranscriptRegistry<u32> = TranscriptRegistry::new();
+        registry.insert("chr1", "NM_001101.5", 1);
+        assert_eq!(registry.get("1", "NM_001101.5"), Some(1));
+        assert_eq!(registry.get("1", "NM_001101.4"), None);
+    }
+}
@@ -0,0 +1,205 @@
+//! Summary report of a single atg run
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::time::Duration;
+
+use atglib::models::Transcript;
+use atglib::utils::errors::AtgError;
+
+use crate::chromosomes::chrom_cmp;
+use crate::cli::QcFilter;
+use crate::gtf_groups::BuildFailure;
+
+/// A transcript that was present in multiple inputs with different structures
+pub struct MergeConflict {
+    pub name: String,
+    pub chrom: String,
+    pub first_input: String,
+    pub second_input: String,
+    pub resolution: String,
+}
+
+/// A transcript on a contig that is missing from the reference genome
+pub struct MissingContig {
+    pub name: String,
+    pub chrom: String,
+    pub action: String,
+}
+
+/// RunReport collects statistics and issues during processing
+///
+/// The report is written to a file at the end of the run, if requested
+/// with `--report`.
+#[derive(Default)]
+pub struct RunReport {
+    inputs: Vec<(String, usize)>,
+    build_failures: Vec<(String, String, String)>,
+    conflicts: Vec<MergeConflict>,
+    qc_failures: BTreeMap<String, usize>,
+    missing_contigs: Vec<MissingContig>,
+    timings: Vec<(String, Duration)>,
+    chroms: HashMap<String, usize>,
+    strands: BTreeMap<String, usize>,
+}
+
+impl RunReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the number of transcripts read from an input file
+    pub fn add_input(&mut self, filename: &str, transcripts: usize) {
+        self.inputs.push((filename.to_string(), transcripts))
+    }
+
+    /// Records a transcript that could not be built and was skipped
+    pub fn add_build_failure(&mut self, filename: &str, failure: &BuildFailure) {
+        self.build_failures.push((
+            filename.to_string(),
+            failure.transcript.clone(),
+            failure.reason.clone(),
+        ))
+    }
+
+    pub fn add_conflict(&mut self, conflict: MergeConflict) {
+        self.conflicts.push(conflict)
+    }
+
+    pub fn add_missing_contig(&mut self, missing: MissingContig) {
+        self.missing_contigs.push(missing)
+    }
+
+    /// Records the failed QC checks of a transcript that was removed
+    pub fn add_qc_failures(&mut self, checks: &[QcFilter]) {
+        for check in checks {
+            *self.qc_failures.entry(check.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    /// Records the wall-clock time of a processing stage
+    pub fn add_timing(&mut self, stage: &str, duration: Duration) {
+        self.timings.push((stage.to_string(), duration))
+    }
+
+    /// Logs the duration of every stage
+    pub fn log_timings(&self) {
+        if self.timings.is_empty() {
+            return;
+        }
+        let timings: Vec<String> = self
+            .timings
+            .iter()
+            .map(|(stage, duration)| format!("{} {:.2?}", stage, duration))
+            .collect();
+        info!("Timing: {}", timings.join(", "));
+    }
+
+    /// Records the number of output transcripts per chromosome and strand
+    pub fn add_output(&mut self, transcripts: &[Transcript]) {
+        for tx in transcripts {
+            *self.chroms.entry(tx.chrom().to_string()).or_insert(0) += 1;
+            *self.strands.entry(tx.strand().to_string()).or_insert(0) += 1;
+        }
+    }
+
+    pub fn write<W: Write>(&self, mut out: W) -> Result<(), std::io::Error> {
+        writeln!(out, "## Inputs")?;
+        for (filename, count) in &self.inputs {
+            writeln!(out, "{}\t{} transcripts", filename, count)?;
+        }
+
+        if !self.build_failures.is_empty() {
+            writeln!(
+                out,
+                "\n## Skipped transcripts that could not be built: {}",
+                self.build_failures.len()
+            )?;
+            for (filename, transcript, reason) in &self.build_failures {
+                writeln!(out, "{}\t{}\t{}", transcript, filename, reason)?;
+            }
+        }
+
+        writeln!(out, "\n## Merge conflicts: {}", self.conflicts.len())?;
+        for conflict in &self.conflicts {
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}\t{}",
+                conflict.name,
+                conflict.chrom,
+                conflict.first_input,
+                conflict.second_input,
+                conflict.resolution
+            )?;
+        }
+
+        if !self.missing_contigs.is_empty() {
+            writeln!(
+                out,
+                "\n## Transcripts on contigs missing from the reference: {}",
+                self.missing_contigs.len()
+            )?;
+            for missing in &self.missing_contigs {
+                writeln!(
+                    out,
+                    "{}\t{}\t{}",
+                    missing.name, missing.chrom, missing.action
+                )?;
+            }
+        }
+
+        if !self.qc_failures.is_empty() {
+            writeln!(out, "\n## QC failures")?;
+            for (check, count) in &self.qc_failures {
+                writeln!(out, "{}\t{} transcripts", check, count)?;
+            }
+        }
+
+        if !self.chroms.is_empty() {
+            let mut chroms: Vec<(&String, &usize)> = self.chroms.iter().collect();
+            chroms.sort_by(|a, b| chrom_cmp(a.0, b.0));
+            writeln!(out, "\n## Output transcripts per chromosome")?;
+            for (chrom, count) in chroms {
+                writeln!(out, "{}\t{}", chrom, count)?;
+            }
+
+            writeln!(out, "\n## Output transcripts per strand")?;
+            for (strand, count) in &self.strands {
+                writeln!(out, "{}\t{}", strand, count)?;
+            }
+        }
+
+        if !self.timings.is_empty() {
+            writeln!(out, "\n## Timing")?;
+            for (stage, duration) in &self.timings {
+                writeln!(out, "{}\t{:.2?}", stage, duration)?;
+            }
+        }
+
+        if let Some(peak) = peak_memory() {
+            writeln!(
+                out,
+                "\n## Peak memory: {:.1} MB",
+                peak as f64 / 1024.0 / 1024.0
+            )?;
+        }
+        out.flush()
+    }
+
+    pub fn write_to_file(&self, filename: &str) -> Result<(), AtgError> {
+        self.write(BufWriter::new(File::create(filename)?))?;
+        Ok(())
+    }
+}
+
+/// Returns the peak resident memory of the process in bytes, if available
+///
+/// This is only supported on Linux, where it is read from `/proc/self/status`.
+fn peak_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    // e.g. `VmHWM:     12345 kB`
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
@@ -0,0 +1,218 @@
+//! Convert transcripts into an intermediate format and back, to find information that is lost
+
+use std::collections::BTreeMap;
+use std::io::{Cursor, Write};
+
+use atglib::genepredext;
+use atglib::gtf;
+use atglib::models::{Transcript, TranscriptRead, TranscriptWrite, Transcripts};
+use atglib::refgene;
+use atglib::utils::errors::AtgError;
+
+use crate::bed12;
+use crate::cli::{InputFormat, OutputFormat, RoundtripArgs};
+use crate::compose::compose;
+use crate::feature_filter::{SkipFeatures, UTR_FEATURES};
+use crate::formats::reader_for;
+use crate::gff3;
+use crate::utils::sorted_exons;
+
+/// Exon coordinates: start, end, CDS start, CDS end
+type CanonicalExon = (u32, u32, Option<u32>, Option<u32>);
+
+/// Transcripts with the same name and chromosome, before and after the round-trip
+type Pair<'a> = (Vec<&'a Transcript>, Vec<&'a Transcript>);
+
+/// The structure of a transcript, independent of the order of exons
+#[derive(PartialEq)]
+struct Canonical {
+    gene: String,
+    strand: String,
+    exons: Vec<CanonicalExon>,
+}
+
+impl Canonical {
+    fn new(tx: &Transcript) -> Self {
+        Self {
+            gene: tx.gene().to_string(),
+            strand: tx.strand().to_string(),
+            exons: sorted_exons(tx)
+                .into_iter()
+                .map(|exon| (exon.start(), exon.end(), *exon.cds_start(), *exon.cds_end()))
+                .collect(),
+        }
+    }
+
+    /// Returns a description of every difference to the other transcript
+    fn differences(&self, other: &Canonical) -> Vec<String> {
+        let mut diffs = vec![];
+        if self.gene != other.gene {
+            diffs.push(format!("gene {} != {}", self.gene, other.gene));
+        }
+        if self.strand != other.strand {
+            diffs.push(format!("strand {} != {}", self.strand, other.strand));
+        }
+        if self.exons.len() != other.exons.len() {
+            diffs.push(format!(
+                "exon count {} != {}",
+                self.exons.len(),
+                other.exons.len()
+            ));
+        } else {
+            for (idx, (a, b)) in self.exons.iter().zip(&other.exons).enumerate() {
+                if a != b {
+                    diffs.push(format!(
+                        "exon {} {} != {}",
+                        idx + 1,
+                        format_exon(a),
+                        format_exon(b)
+                    ));
+                }
+            }
+        }
+        diffs
+    }
+}
+
+fn format_exon(exon: &CanonicalExon) -> String {
+    match (exon.2, exon.3) {
+        (Some(cds_start), Some(cds_end)) => {
+            format!("{}-{} (CDS {}-{})", exon.0, exon.1, cds_start, cds_end)
+        }
+        _ => format!("{}-{}", exon.0, exon.1),
+    }
+}
+
+/// Returns the transcripts after writing them in the intermediate format and reading them back
+///
+/// `no_utr_lines` removes all UTR lines from intermediate GTF data.
+pub fn roundtrip(
+    transcripts: &Transcripts,
+    via: &InputFormat,
+    gtf_source: &str,
+    no_utr_lines: bool,
+) -> Result<Transcripts, AtgError> {
+    let reread = match via {
+        InputFormat::Gtf => {
+            let mut data = compose(&OutputFormat::Gtf, transcripts.as_vec(), gtf_source)?;
+            if no_utr_lines {
+                let mut filtered = vec![];
+                SkipFeatures::new(&mut filtered, UTR_FEATURES).write_all(&data)?;
+                data = filtered;
+            }
+            gtf::Reader::new(Cursor::new(data)).transcripts()?
+        }
+        InputFormat::Refgene => {
+            let data = compose(&OutputFormat::Refgene, transcripts.as_vec(), gtf_source)?;
+            refgene::Reader::new(Cursor::new(data)).transcripts()?
+        }
+        InputFormat::Genepredext => {
+            let data = compose(&OutputFormat::Genepredext, transcripts.as_vec(), gtf_source)?;
+            genepredext::Reader::new(Cursor::new(data)).transcripts()?
+        }
+        InputFormat::Bed => {
+            let data = compose(&OutputFormat::Bed, transcripts.as_vec(), gtf_source)?;
+            bed12::Reader::new(Cursor::new(data)).transcripts()?
+        }
+        InputFormat::Gff3 => {
+            let mut data = vec![];
+            let mut writer = gff3::Writer::new(&mut data);
+            writer.set_source(gtf_source);
+            writer.write_transcripts(transcripts)?;
+            drop(writer);
+            gff3::Reader::new(Cursor::new(data)).transcripts()?
+        }
+        InputFormat::Knowngene | InputFormat::Ensembl => {
+            return Err(AtgError::new(format!(
+                "{} data can't be written, use a different --via format",
+                via
+            )))
+        }
+        #[cfg(feature = "bin")]
+        InputFormat::Bin => {
+            let mut data = vec![];
+            crate::bin_format::write_bin(transcripts, &mut data)?;
+            crate::bin_format::BinReader::new(Cursor::new(data)).transcripts()?
+        }
+        #[cfg(not(feature = "bin"))]
+        InputFormat::Bin => return Err(AtgError::new(crate::formats::BIN_DISABLED)),
+        #[cfg(feature = "json")]
+        InputFormat::Json => {
+            let mut data = vec![];
+            crate::json::Writer::new(&mut data).write_transcripts(transcripts)?;
+            crate::json::Reader::new(Cursor::new(data)).transcripts()?
+        }
+        #[cfg(not(feature = "json"))]
+        InputFormat::Json => return Err(AtgError::new(crate::formats::JSON_DISABLED)),
+    };
+    Ok(reread)
+}
+
+/// Compares the original transcripts to the transcripts after the round-trip
+///
+/// Transcripts are matched by their name and chromosome. Returns a
+/// description of every difference, sorted by transcript name.
+pub fn differences(original: &[Transcript], reread: &[Transcript]) -> Vec<String> {
+    let mut pairs: BTreeMap<(&str, &str), Pair> = BTreeMap::new();
+    for tx in original {
+        pairs.entry((tx.name(), tx.chrom())).or_default().0.push(tx);
+    }
+    for tx in reread {
+        pairs.entry((tx.name(), tx.chrom())).or_default().1.push(tx);
+    }
+
+    let mut diffs = vec![];
+    for ((name, chrom), (before, after)) in pairs {
+        if before.len() != after.len() {
+            diffs.push(format!(
+                "{} ({}): {} transcripts before and {} after the round-trip",
+                name,
+                chrom,
+                before.len(),
+                after.len()
+            ));
+            continue;
+        }
+        for (a, b) in before.iter().zip(after) {
+            for diff in Canonical::new(a).differences(&Canonical::new(b)) {
+                diffs.push(format!("{} ({}): {}", name, chrom, diff));
+            }
+        }
+    }
+    diffs
+}
+
+/// Runs the round-trip and prints all differences
+///
+/// Returns an error if the transcripts changed, so that the round-trip
+/// can be used in scripts.
+pub fn run_roundtrip(args: &RoundtripArgs) -> Result<(), AtgError> {
+    let original = reader_for(&args.from, &args.input)?.transcripts()?;
+    debug!(
+        target: "atg::roundtrip",
+        "Converting {} transcripts from {} via {}",
+        original.len(),
+        args.from,
+        args.via
+    );
+    let reread = roundtrip(&original, &args.via, &args.gtf_source, args.no_utr_lines)?;
+
+    let diffs = differences(original.as_vec(), reread.as_vec());
+    for diff in &diffs {
+        println!("{}", diff);
+    }
+    if diffs.is_empty() {
+        println!(
+            "All {} transcripts are identical after the round-trip via {}",
+            original.len(),
+            args.via
+        );
+        Ok(())
+    } else {
+        Err(AtgError::new(format!(
+            "{} differences after the round-trip via {}",
+            diffs.len(),
+            args.via
+        )))
+    }
+}
@@ -0,0 +1,85 @@
+//! Write output files to S3
+
+use std::io::{Error, ErrorKind, Write};
+
+use aws_config::BehaviorVersion;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::Client;
+
+/// Uploads all written data to an S3 object
+pub struct S3Writer {
+    bucket: String,
+    key: String,
+    buffer: Vec<u8>,
+}
+
+impl S3Writer {
+    /// Creates a writer for an S3 URI, e.g. `s3://bucket/path/to/output.gtf`
+    pub fn new(uri: &str) -> Result<Self, Error> {
+        let (bucket, key) = uri
+            .strip_prefix("s3://")
+            .and_then(|path| path.split_once('/'))
+            .filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty())
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("invalid S3 URI {}, expected s3://bucket/key", uri),
+                )
+            })?;
+        Ok(Self {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            buffer: vec![],
+        })
+    }
+
+    fn upload(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let body = ByteStream::from(std::mem::take(&mut self.buffer));
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        runtime.block_on(async {
+            let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
+            Client::new(&config)
+                .put_object()
+                .bucket(&self.bucket)
+                .key(&self.key)
+                .body(body)
+                .send()
+                .await
+        })?;
+        Ok(())
+    }
+}
+
+impl Write for S3Writer {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl Drop for S3Writer {
+    /// Uploads the data, atg exits with an error if the upload fails
+    fn drop(&mut self) {
+        debug!(
+            target: "atg::write",
+            "Uploading {} bytes to s3://{}/{}",
+            self.buffer.len(),
+            self.bucket,
+            self.key
+        );
+        if let Err(err) = self.upload() {
+            error!(
+                target: "atg::write",
+                "Uploading the output to s3://{}/{} failed: {}",
+                self.bucket, self.key, err
+            );
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,52 @@
+//! Select one representative transcript per gene
+
+use std::collections::HashMap;
+
+use atglib::models::{Transcript, Transcripts};
+
+use crate::cli::SelectionMode;
+use crate::utils::{cds_len, exonic_len};
+
+/// Returns the ranking score of the transcript for the given selection mode
+fn score(tx: &Transcript, mode: &SelectionMode) -> u64 {
+    match mode {
+        SelectionMode::LongestCds => cds_len(tx),
+        SelectionMode::LongestTranscript => exonic_len(tx),
+        SelectionMode::MostExons => tx.exon_count() as u64,
+    }
+}
+
+/// Keeps only the highest-ranking transcript of every gene
+///
+/// Genes are identified by their gene symbol and chromosome, so that
+/// genes in the pseudoautosomal regions keep one transcript on each
+/// sex chromosome. If multiple transcripts have the same score, the one that
+/// comes first in the input is kept.
+///
+/// The order of the genes is preserved.
+pub fn select_per_gene(transcripts: Transcripts, mode: &SelectionMode) -> Transcripts {
+    let mut selected: Vec<(u64, Transcript)> = vec![];
+    let mut genes: HashMap<(String, String), usize> = HashMap::new();
+
+    for tx in transcripts {
+        let tx_score = score(&tx, mode);
+        let key = (tx.gene().to_string(), tx.chrom().to_string());
+        match genes.get(&key) {
+            Some(idx) => {
+                if tx_score > selected[*idx].0 {
+                    selected[*idx] = (tx_score, tx);
+                }
+            }
+            None => {
+                genes.insert(key, selected.len());
+                selected.push((tx_score, tx));
+            }
+        }
+    }
+
+    let mut res = Transcripts::new();
+    for (_, tx) in selected {
+        res.push(tx)
+    }
+    res
+}
@@ -0,0 +1,120 @@
+//! Selenocysteine codons of selenoproteins, e.g. SELENON or GPX1
+
+use std::io::{Read, Seek};
+
+use atglib::fasta::FastaReader;
+use atglib::models::{Strand, Transcript};
+use atglib::qc::QcResult;
+use atglib::utils::errors::AtgError;
+
+use crate::registry::TranscriptRegistry;
+use crate::sequence::{codons, reverse_complement};
+use crate::stop_codon::STOP_CODONS;
+
+/// The codon that is translated to selenocysteine at the annotated positions
+const SEC_CODON: &str = "TGA";
+
+/// The start and end of the selenocysteine codons of every transcript
+static SELENOCYSTEINES: TranscriptRegistry<Vec<(u32, u32)>> = TranscriptRegistry::new();
+
+/// Registers the genomic positions of the selenocysteine codons of a transcript
+pub fn register_selenocysteines(chrom: &str, transcript: &str, positions: Vec<(u32, u32)>) {
+    SELENOCYSTEINES.insert(chrom, transcript, positions);
+}
+
+/// Returns the genomic positions of the selenocysteine codons of a transcript
+pub fn selenocysteines_of(tx: &Transcript) -> Vec<(u32, u32)> {
+    SELENOCYSTEINES
+        .get(tx.chrom(), tx.name())
+        .unwrap_or_default()
+}
+
+/// Returns the start and end of all `Selenocysteine` features of the GTF records of a transcript
+pub fn gtf_selenocysteines(records: &str) -> Vec<(u32, u32)> {
+    records
+        .lines()
+        .map(|line| line.split('\t').collect::<Vec<&str>>())
+        .filter(|cols| cols.len() > 4 && cols[2].eq_ignore_ascii_case("selenocysteine"))
+        .filter_map(|cols| Some((cols[3].parse().ok()?, cols[4].parse().ok()?)))
+        .collect()
+}
+
+/// Returns the codons of the CDS with the genomic position of their bases, in the order of transcription
+fn cds_codons<R: Read + Seek>(
+    tx: &Transcript,
+    fasta: &mut FastaReader<R>,
+) -> Result<Vec<(String, Vec<u32>)>, AtgError> {
+    let mut segments: Vec<(u32, u32)> = tx
+        .exons()
+        .iter()
+        .filter_map(|exon| match (exon.cds_start(), exon.cds_end()) {
+            (Some(start), Some(end)) if start <= end => Some((*start, *end)),
+            _ => None,
+        })
+        .collect();
+    segments.sort_unstable();
+
+    let mut sequence = String::new();
+    let mut positions = vec![];
+    for (start, end) in segments {
+        let bases = fasta.read_sequence(tx.chrom(), start.into(), end.into())?;
+        sequence.push_str(&bases.to_string().to_uppercase());
+        positions.extend(start..=end);
+    }
+    if let Strand::Minus = tx.strand() {
+        sequence = reverse_complement(&sequence);
+        positions.reverse();
+    }
+
+    Ok(codons(&sequence)
+        .zip(positions.chunks_exact(3))
+        .map(|(codon, positions)| (codon.to_string(), positions.to_vec()))
+        .collect())
+}
+
+/// Returns `OK` if all in-frame stop codons before the last codon are selenocysteines
+///
+/// Only the stop codons of the standard genetic code are checked. Transcripts
+/// without selenocysteines and transcripts whose CDS can't be read from the
+/// reference genome are `NA`.
+pub fn check_upstream_stop<R: Read + Seek>(
+    tx: &Transcript,
+    fasta: &mut FastaReader<R>,
+) -> QcResult {
+    let selenocysteines = selenocysteines_of(tx);
+    if selenocysteines.is_empty() {
+        return QcResult::NA;
+    }
+    let codons = match cds_codons(tx, fasta) {
+        Ok(codons) => codons,
+        Err(err) => {
+            debug!(target: "atg::qc", "Unable to read the CDS of {}: {}", tx.name(), err);
+            return QcResult::NA;
+        }
+    };
+    let is_sec = |positions: &[u32]| {
+        positions.iter().any(|pos| {
+            selenocysteines
+                .iter()
+                .any(|(start, end)| start <= pos && pos <= end)
+        })
+    };
+    let upstream = &codons[..codons.len().saturating_sub(1)];
+    for (codon, positions) in upstream {
+        if !STOP_CODONS.contains(&codon.as_str()) {
+            continue;
+        }
+        if codon != SEC_CODON || !is_sec(positions) {
+            return QcResult::NOK;
+        }
+        debug!(
+            target: "atg::qc",
+            "Treating {} at {}:{} of {} as selenocysteine",
+            codon,
+            tx.chrom(),
+            positions[0],
+            tx.name()
+        );
+    }
+    QcResult::OK
+}
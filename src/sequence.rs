@@ -0,0 +1,25 @@
+//! Helpers for nucleotide sequences
+
+/// Returns the complementary base, other characters (e.g. `N`) are unchanged
+pub fn complement(base: char) -> char {
+    match base {
+        'A' => 'T',
+        'C' => 'G',
+        'G' => 'C',
+        'T' => 'A',
+        other => other,
+    }
+}
+
+/// Returns the reverse complement of the sequence
+pub fn reverse_complement(sequence: &str) -> String {
+    sequence.chars().rev().map(complement).collect()
+}
+
+/// Returns the complete codons of the sequence, an incomplete last codon is ignored
+pub fn codons(sequence: &str) -> impl Iterator<Item = &str> {
+    sequence
+        .as_bytes()
+        .chunks_exact(3)
+        .filter_map(|codon| std::str::from_utf8(codon).ok())
+}
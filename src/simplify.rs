@@ -0,0 +1,145 @@
+//! Simplify gene models for display purposes
+
+use std::collections::{HashMap, HashSet};
+
+use atglib::models::{Transcript, Transcripts};
+
+/// Returns the Jaccard distance between the exon sets of two transcripts
+///
+/// Exons are only considered identical if both start and end match.
+fn exon_distance(a: &Transcript, b: &Transcript) -> f64 {
+    let exons_a: HashSet<(u32, u32)> = a.exons().iter().map(|e| (e.start(), e.end())).collect();
+    let exons_b: HashSet<(u32, u32)> = b.exons().iter().map(|e| (e.start(), e.end())).collect();
+    let union = exons_a.union(&exons_b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    1.0 - exons_a.intersection(&exons_b).count() as f64 / union as f64
+}
+
+/// Selects up to `max` transcripts with the most diverse exon structures
+///
+/// The transcript with the most exons is selected first. Every further
+/// transcript is the one with the largest distance to its most similar
+/// already selected transcript. Returns the indices of the selected transcripts.
+fn most_diverse(transcripts: &[&Transcript], max: usize) -> Vec<usize> {
+    let mut selected: Vec<usize> = vec![];
+    let first = (0..transcripts.len()).max_by_key(|idx| {
+        // prefer the first transcript if multiple have the same exon count
+        (transcripts[*idx].exon_count(), std::cmp::Reverse(*idx))
+    });
+    if let Some(first) = first {
+        selected.push(first);
+    }
+
+    while selected.len() < max.min(transcripts.len()) {
+        let mut best: Option<(usize, f64)> = None;
+        for idx in 0..transcripts.len() {
+            if selected.contains(&idx) {
+                continue;
+            }
+            let distance = selected
+                .iter()
+                .map(|sel| exon_distance(transcripts[idx], transcripts[*sel]))
+                .fold(f64::MAX, f64::min);
+            if best.is_none_or(|(_, best_distance)| distance > best_distance) {
+                best = Some((idx, distance));
+            }
+        }
+        match best {
+            Some((idx, _)) => selected.push(idx),
+            None => break,
+        }
+    }
+    selected
+}
+
+/// Keeps at most `max` transcripts per gene, chosen by the diversity of their exon structure
+///
+/// The transcripts remain in their original order.
+pub fn limit_isoforms(transcripts: Transcripts, max: usize) -> Transcripts {
+    let all = transcripts.to_vec();
+
+    let mut genes: HashMap<(&str, &str), Vec<usize>> = HashMap::new();
+    for (idx, tx) in all.iter().enumerate() {
+        genes.entry((tx.gene(), tx.chrom())).or_default().push(idx);
+    }
+
+    let mut keep = vec![false; all.len()];
+    for indices in genes.values() {
+        let gene_transcripts: Vec<&Transcript> = indices.iter().map(|idx| &all[*idx]).collect();
+        for selected in most_diverse(&gene_transcripts, max) {
+            keep[indices[selected]] = true;
+        }
+    }
+
+    let mut res = Transcripts::new();
+    for (tx, keep) in all.into_iter().zip(keep) {
+        if keep {
+            res.push(tx)
+        }
+    }
+    res
+}
+
+/// Trims the UTRs on both ends of a coding transcript to at most `max_len` bases
+///
+/// Exons that are completely beyond the allowed UTR length are removed,
+/// the outermost remaining UTR exon is shortened. Non-coding transcripts
+/// remain unchanged.
+pub fn trim_utrs(tx: &mut Transcript, max_len: u32) {
+    let (cds_start, cds_end) = match (tx.cds_start(), tx.cds_end()) {
+        (Some(start), Some(end)) => (start, end),
+        _ => return,
+    };
+
+    // indices of the exons, sorted by genomic position
+    let mut order: Vec<usize> = (0..tx.exons().len()).collect();
+    order.sort_by_key(|idx| tx.exons()[*idx].start());
+
+    let mut remove: Vec<usize> = vec![];
+    let exons = tx.exons_mut();
+
+    // UTR upstream of the CDS in genomic orientation
+    let mut budget = max_len;
+    for idx in order.iter().rev() {
+        let exon = &mut exons[*idx];
+        if exon.start() >= cds_start {
+            continue;
+        }
+        let utr_end = exon.end().min(cds_start - 1);
+        let utr_len = utr_end - exon.start() + 1;
+        if budget == 0 && exon.end() < cds_start {
+            remove.push(*idx);
+        } else if utr_len > budget {
+            *exon.start_mut() = utr_end - budget + 1;
+            budget = 0;
+        } else {
+            budget -= utr_len;
+        }
+    }
+
+    // UTR downstream of the CDS in genomic orientation
+    let mut budget = max_len;
+    for idx in order.iter() {
+        let exon = &mut exons[*idx];
+        if exon.end() <= cds_end {
+            continue;
+        }
+        let utr_start = exon.start().max(cds_end + 1);
+        let utr_len = exon.end() - utr_start + 1;
+        if budget == 0 && exon.start() > cds_end {
+            remove.push(*idx);
+        } else if utr_len > budget {
+            *exon.end_mut() = utr_start + budget - 1;
+            budget = 0;
+        } else {
+            budget -= utr_len;
+        }
+    }
+
+    remove.sort_unstable();
+    for idx in remove.into_iter().rev() {
+        exons.remove(idx);
+    }
+}
@@ -0,0 +1,44 @@
+//! Sort `Transcripts` by position or by name
+
+use atglib::models::{Transcript, Transcripts};
+
+use crate::chromosomes::{position_cmp, sort_transcripts};
+use crate::kmerge::sort_parallel;
+
+pub trait SortTranscripts {
+    /// Sorts by chromosome (natural order), start and end position
+    ///
+    /// With more than one thread, the chromosomes are sorted in parallel.
+    fn sort_by_position(&mut self, threads: usize);
+
+    /// Sorts by transcript name, transcripts with the same name by position
+    fn sort_by_name(&mut self);
+}
+
+impl SortTranscripts for Transcripts {
+    fn sort_by_position(&mut self, threads: usize) {
+        let mut sorted = take(self);
+        match threads {
+            0 | 1 => sort_transcripts(&mut sorted),
+            threads => sorted = sort_parallel(sorted, threads),
+        }
+        extend(self, sorted);
+    }
+
+    fn sort_by_name(&mut self) {
+        let mut sorted = take(self);
+        sorted.sort_by(|a, b| a.name().cmp(b.name()).then_with(|| position_cmp(a, b)));
+        extend(self, sorted);
+    }
+}
+
+/// Moves all transcripts out, leaving `transcripts` empty
+fn take(transcripts: &mut Transcripts) -> Vec<Transcript> {
+    std::mem::replace(transcripts, Transcripts::new()).to_vec()
+}
+
+fn extend(transcripts: &mut Transcripts, sorted: Vec<Transcript>) {
+    for tx in sorted {
+        transcripts.push(tx);
+    }
+}
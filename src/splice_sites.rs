@@ -0,0 +1,79 @@
+//! QC check of the splice sites of every intron
+
+use std::io::{Read, Seek};
+
+use atglib::fasta::FastaReader;
+use atglib::models::{Strand, Transcript};
+use atglib::qc::QcResult;
+use atglib::utils::errors::AtgError;
+
+use crate::utils::intron_coordinates;
+
+/// The canonical intron type
+const CANONICAL: &[(&str, &str)] = &[("GT", "AG")];
+
+/// Minor intron types that are accepted with `--allow-minor-splice-sites`
+const MINOR: &[(&str, &str)] = &[("GC", "AG"), ("AT", "AC")];
+
+/// Introns must be long enough for the donor and acceptor dinucleotides
+const MIN_INTRON_LEN: u64 = 4;
+
+/// Returns the donor and acceptor dinucleotides of every intron, in the order of transcription
+///
+/// Book-ended exons (without any bases in between) don't form an intron and are ignored.
+/// Introns that are too short for both dinucleotides are returned with empty sequences.
+pub fn splice_sites<R: Read + Seek>(
+    tx: &Transcript,
+    fasta: &mut FastaReader<R>,
+) -> Result<Vec<(String, String)>, AtgError> {
+    let mut sites = vec![];
+    for intron in intron_coordinates(tx) {
+        if intron.len() < MIN_INTRON_LEN {
+            sites.push((String::new(), String::new()));
+            continue;
+        }
+        let mut dinucleotides = vec![];
+        for (start, end) in [intron.donor(), intron.acceptor()] {
+            let mut sequence = fasta.read_sequence(tx.chrom(), start.into(), end.into())?;
+            if let Strand::Minus = tx.strand() {
+                sequence.reverse_complement();
+            }
+            dinucleotides.push(sequence.to_string().to_uppercase());
+        }
+        let acceptor = dinucleotides.pop().unwrap_or_default();
+        let donor = dinucleotides.pop().unwrap_or_default();
+        sites.push((donor, acceptor));
+    }
+    Ok(sites)
+}
+
+/// Returns `OK` if all introns have canonical (or, with `allow_minor`, minor) splice sites
+///
+/// Transcripts without introns are `NA`, as well as transcripts whose
+/// introns can't be read from the reference genome.
+pub fn check_splice_sites<R: Read + Seek>(
+    tx: &Transcript,
+    fasta: &mut FastaReader<R>,
+    allow_minor: bool,
+) -> QcResult {
+    let sites = match splice_sites(tx, fasta) {
+        Ok(sites) => sites,
+        Err(err) => {
+            debug!(target: "atg::qc", "Unable to read the introns of {}: {}", tx.name(), err);
+            return QcResult::NA;
+        }
+    };
+    if sites.is_empty() {
+        return QcResult::NA;
+    }
+    let accepted = |(donor, acceptor): &(String, String)| {
+        CANONICAL
+            .iter()
+            .chain(MINOR.iter().filter(|_| allow_minor))
+            .any(|(d, a)| donor == d && acceptor == a)
+    };
+    match sites.iter().all(accepted) {
+        true => QcResult::OK,
+        false => QcResult::NOK,
+    }
+}
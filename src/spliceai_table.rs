@@ -0,0 +1,71 @@
+//! SpliceAI annotation table with one row per transcript
+
+use std::io::{BufWriter, Write};
+
+use atglib::models::{Exon, Transcript, TranscriptWrite};
+
+/// Writes one row per transcript, in the format of the SpliceAI annotation table
+///
+/// The `NAME` column contains the transcript name. Start positions are
+/// 0-based, end positions 1-based, like in the gene-level output.
+pub struct TranscriptWriter<W: Write> {
+    inner: BufWriter<W>,
+    header_written: bool,
+}
+
+impl<W: Write> TranscriptWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            inner: BufWriter::new(writer),
+            header_written: false,
+        }
+    }
+
+    fn write_header(&mut self) -> Result<(), std::io::Error> {
+        if !self.header_written {
+            writeln!(
+                self.inner,
+                "#NAME\tCHROM\tSTRAND\tTX_START\tTX_END\tEXON_START\tEXON_END"
+            )?;
+            self.header_written = true;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> TranscriptWrite for TranscriptWriter<W> {
+    fn writeln_single_transcript(&mut self, transcript: &Transcript) -> Result<(), std::io::Error> {
+        self.write_single_transcript(transcript)
+    }
+
+    fn write_single_transcript(&mut self, transcript: &Transcript) -> Result<(), std::io::Error> {
+        self.write_header()?;
+        let mut starts = String::new();
+        let mut ends = String::new();
+        let mut exons: Vec<&Exon> = transcript.exons().iter().collect();
+        exons.sort_by_key(|exon| exon.start());
+        for exon in exons {
+            starts.push_str(&format!("{},", exon.start().saturating_sub(1)));
+            ends.push_str(&format!("{},", exon.end()));
+        }
+        writeln!(
+            self.inner,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            transcript.name(),
+            transcript.chrom(),
+            transcript.strand(),
+            transcript.tx_start().saturating_sub(1),
+            transcript.tx_end(),
+            starts,
+            ends
+        )
+    }
+
+    fn write_transcript_vec(&mut self, transcripts: &[Transcript]) -> Result<(), std::io::Error> {
+        self.write_header()?;
+        for tx in transcripts {
+            self.writeln_single_transcript(tx)?;
+        }
+        self.inner.flush()
+    }
+}
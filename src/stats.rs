@@ -0,0 +1,132 @@
+//! Summary statistics of an annotation set
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use atglib::models::Transcript;
+
+use crate::chromosomes::chrom_cmp;
+use crate::genes::genes;
+use crate::utils::cds_len;
+
+/// Counts of a set of transcripts
+#[derive(Default)]
+struct Counts {
+    genes: usize,
+    transcripts: usize,
+    exons: usize,
+    coding: usize,
+    multi_exon: usize,
+}
+
+impl Counts {
+    fn add(&mut self, tx: &Transcript) {
+        self.transcripts += 1;
+        self.exons += tx.exon_count();
+        if tx.is_coding() {
+            self.coding += 1;
+        }
+        if tx.exon_count() > 1 {
+            self.multi_exon += 1;
+        }
+    }
+
+    fn noncoding(&self) -> usize {
+        self.transcripts - self.coding
+    }
+}
+
+/// Returns the fraction as percentage with one decimal
+fn percent(count: usize, total: usize) -> String {
+    match total {
+        0 => "NA".to_string(),
+        _ => format!("{:.1}%", count as f64 * 100.0 / total as f64),
+    }
+}
+
+/// Returns the median of sorted values
+fn median(sorted: &[u64]) -> f64 {
+    let mid = sorted.len() / 2;
+    match sorted.len() % 2 {
+        0 => (sorted[mid - 1] + sorted[mid]) as f64 / 2.0,
+        _ => sorted[mid] as f64,
+    }
+}
+
+/// Writes the summary statistics of all transcripts
+///
+/// Genes are identified by their name and chromosome, like in `genes`.
+/// The CDS lengths include the stop codon.
+pub fn write_stats<W: Write>(transcripts: &[Transcript], mut out: W) -> Result<(), std::io::Error> {
+    let mut total = Counts::default();
+    let mut per_chrom: HashMap<&str, Counts> = HashMap::new();
+    let mut cds_lengths = vec![];
+    for tx in transcripts {
+        total.add(tx);
+        per_chrom.entry(tx.chrom()).or_default().add(tx);
+        if tx.is_coding() {
+            cds_lengths.push(cds_len(tx));
+        }
+    }
+    for gene in genes(transcripts) {
+        total.genes += 1;
+        per_chrom.entry(gene.chrom()).or_default().genes += 1;
+    }
+    cds_lengths.sort_unstable();
+
+    writeln!(out, "Genes\t{}", total.genes)?;
+    writeln!(out, "Transcripts\t{}", total.transcripts)?;
+    writeln!(out, "Exons\t{}", total.exons)?;
+    writeln!(
+        out,
+        "Coding transcripts\t{}\t{}",
+        total.coding,
+        percent(total.coding, total.transcripts)
+    )?;
+    writeln!(
+        out,
+        "Non-coding transcripts\t{}\t{}",
+        total.noncoding(),
+        percent(total.noncoding(), total.transcripts)
+    )?;
+    writeln!(
+        out,
+        "Multi-exon transcripts\t{}\t{}",
+        total.multi_exon,
+        percent(total.multi_exon, total.transcripts)
+    )?;
+    match (cds_lengths.first(), cds_lengths.last()) {
+        (Some(min), Some(max)) => {
+            writeln!(out, "CDS length min\t{}", min)?;
+            writeln!(out, "CDS length median\t{}", median(&cds_lengths))?;
+            writeln!(out, "CDS length max\t{}", max)?;
+        }
+        _ => {
+            writeln!(out, "CDS length min\tNA")?;
+            writeln!(out, "CDS length median\tNA")?;
+            writeln!(out, "CDS length max\tNA")?;
+        }
+    }
+
+    writeln!(out)?;
+    writeln!(
+        out,
+        "Chromosome\tGenes\tTranscripts\tExons\tCoding\tNon-coding\tMulti-exon"
+    )?;
+    let mut chroms: Vec<(&str, Counts)> = per_chrom.into_iter().collect();
+    chroms.sort_by(|a, b| chrom_cmp(a.0, b.0));
+    for (chrom, counts) in chroms {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            chrom,
+            counts.genes,
+            counts.transcripts,
+            counts.exons,
+            counts.coding,
+            counts.noncoding(),
+            counts.multi_exon
+        )?;
+    }
+    out.flush()
+}
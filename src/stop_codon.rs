@@ -0,0 +1,168 @@
+//! Restore stop codons that are excluded from the CDS
+
+use std::io::{Read, Seek};
+
+use atglib::fasta::FastaReader;
+use atglib::models::{Strand, Transcript};
+use atglib::utils::errors::AtgError;
+
+use crate::sequence::complement;
+use crate::utils::cds_len;
+use crate::utils::frame_for;
+use crate::warnings::warning;
+
+pub const STOP_CODONS: [&str; 3] = ["TAA", "TAG", "TGA"];
+
+/// Returns the positions of the `count` exonic bases after the 3' end of the CDS
+///
+/// The positions are returned in the direction of transcription, together
+/// with the index of their exon. Returns `None` if the 3'UTR is too short.
+fn positions_after_cds(tx: &Transcript, count: usize) -> Option<Vec<(usize, u32)>> {
+    let exons = tx.exons();
+    let mut order: Vec<usize> = (0..exons.len()).collect();
+    order.sort_by_key(|idx| exons[*idx].start());
+
+    let mut positions = vec![];
+    match tx.strand() {
+        Strand::Minus => {
+            let cds_start = tx.cds_start()?;
+            for idx in order.into_iter().rev() {
+                let exon = &exons[idx];
+                let last = exon.end().min(cds_start.saturating_sub(1));
+                let bases = (exon.start()..=last).rev().take(count - positions.len());
+                positions.extend(bases.map(|pos| (idx, pos)));
+            }
+        }
+        _ => {
+            let cds_end = tx.cds_end()?;
+            for idx in order {
+                let exon = &exons[idx];
+                let first = exon.start().max(cds_end.saturating_add(1));
+                let bases = (first..=exon.end()).take(count - positions.len());
+                positions.extend(bases.map(|pos| (idx, pos)));
+            }
+        }
+    }
+
+    match positions.len() == count {
+        true => Some(positions),
+        false => None,
+    }
+}
+
+/// Returns the positions of the last `count` bases of the CDS, in the direction of transcription
+pub fn last_cds_positions(tx: &Transcript, count: usize) -> Vec<u32> {
+    let mut segments: Vec<(u32, u32)> = tx
+        .exons()
+        .iter()
+        .filter_map(|exon| match (exon.cds_start(), exon.cds_end()) {
+            (Some(start), Some(end)) if start <= end => Some((*start, *end)),
+            _ => None,
+        })
+        .collect();
+    segments.sort_unstable();
+
+    let mut positions: Vec<u32> = match tx.strand() {
+        Strand::Minus => segments
+            .iter()
+            .flat_map(|(start, end)| *start..=*end)
+            .take(count)
+            .collect(),
+        _ => segments
+            .iter()
+            .rev()
+            .flat_map(|(start, end)| (*start..=*end).rev())
+            .take(count)
+            .collect(),
+    };
+    positions.reverse();
+    positions
+}
+
+/// Returns the codon at the positions, in the direction of transcription
+pub fn read_codon<R: Read + Seek>(
+    fasta: &mut FastaReader<R>,
+    tx: &Transcript,
+    positions: &[u32],
+) -> Result<String, AtgError> {
+    let mut codon = String::new();
+    for pos in positions {
+        let base = fasta.read_sequence(tx.chrom(), u64::from(*pos), u64::from(*pos))?;
+        codon.push_str(&base.to_string().to_uppercase());
+    }
+    if let Strand::Minus = tx.strand() {
+        codon = codon.chars().map(complement).collect();
+    }
+    Ok(codon)
+}
+
+/// Extends the CDS of the transcript by the three bases of the stop codon
+///
+/// If a reference genome is given, the CDS is only extended if the CDS does
+/// not end with a stop codon yet and if the following three bases are a stop codon
+/// (using the standard genetic code). Otherwise, the CDS of every coding transcript is extended.
+///
+/// Returns `true` if the CDS was extended.
+pub fn extend_cds_by_stop_codon<R: Read + Seek>(
+    tx: &mut Transcript,
+    fasta: Option<&mut FastaReader<R>>,
+) -> Result<bool, AtgError> {
+    if !tx.is_coding() {
+        return Ok(false);
+    }
+    let positions = match positions_after_cds(tx, 3) {
+        Some(positions) => positions,
+        None => {
+            warning(
+                "atg::transform",
+                "short-utr",
+                &format!(
+                    "The 3'UTR of {} is too short to contain the stop codon",
+                    tx.name()
+                ),
+            );
+            return Ok(false);
+        }
+    };
+
+    if let Some(fasta) = fasta {
+        let last_codon = read_codon(fasta, tx, &last_cds_positions(tx, 3))?;
+        if STOP_CODONS.contains(&last_codon.as_str()) {
+            return Ok(false);
+        }
+        let next: Vec<u32> = positions.iter().map(|pos| pos.1).collect();
+        let next_codon = read_codon(fasta, tx, &next)?;
+        if !STOP_CODONS.contains(&next_codon.as_str()) {
+            debug!(
+                target: "atg::transform",
+                "{} is not followed by a stop codon ({})",
+                tx.name(),
+                next_codon
+            );
+            return Ok(false);
+        }
+    }
+
+    let cds_bases = cds_len(tx);
+    let minus = matches!(tx.strand(), Strand::Minus);
+    let exons = tx.exons_mut();
+    for (cds_bases_before, (idx, pos)) in (cds_bases..).zip(positions) {
+        let exon = &mut exons[idx];
+        if exon.cds_start().is_none() || exon.cds_end().is_none() {
+            *exon.cds_start_mut() = Some(pos);
+            *exon.cds_end_mut() = Some(pos);
+            *exon.frame_offset_mut() = frame_for(cds_bases_before);
+        } else if minus {
+            *exon.cds_start_mut() = Some(pos);
+        } else {
+            *exon.cds_end_mut() = Some(pos);
+        }
+    }
+
+    debug!(
+        target: "atg::transform",
+        "Extended the CDS of {} by the stop codon",
+        tx.name()
+    );
+    Ok(true)
+}
@@ -0,0 +1,179 @@
+//! Sense and antisense sequences of exons and transcripts
+
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, Write};
+
+use atglib::fasta::FastaReader;
+use atglib::models::{Exon, Sequence, Strand, Transcript, TranscriptWrite};
+use atglib::utils::errors::{AtgError, ReadWriteError};
+
+use crate::cli::FastaFormat;
+use crate::utils::sorted_exons;
+
+/// Number of bases per line in the Fasta output
+const LINE_WIDTH: usize = 60;
+
+/// Returns the genomic strand of the antisense sequence of a transcript on `strand`
+///
+/// Transcripts with unknown strand are treated as plus-strand.
+pub fn antisense_strand(strand: Strand) -> Strand {
+    match strand {
+        Strand::Minus => Strand::Plus,
+        _ => Strand::Minus,
+    }
+}
+
+/// Sense and antisense sequences of a single exon
+pub trait ExonSequences {
+    /// Returns the sense and the antisense sequence of the exon
+    ///
+    /// The sense sequence is located on the strand of `transcript`, the antisense
+    /// sequence on the opposite strand (see [`antisense_strand`]). Both are
+    /// returned 5' to 3'.
+    fn sequences<R: Read + Seek>(
+        &self,
+        transcript: &Transcript,
+        reader: &mut FastaReader<R>,
+    ) -> Result<(Sequence, Sequence), AtgError>;
+}
+
+impl ExonSequences for Exon {
+    fn sequences<R: Read + Seek>(
+        &self,
+        transcript: &Transcript,
+        reader: &mut FastaReader<R>,
+    ) -> Result<(Sequence, Sequence), AtgError> {
+        stranded_sequences(
+            reader,
+            transcript.chrom(),
+            (self.start(), self.end()),
+            *transcript.strand(),
+        )
+    }
+}
+
+/// Returns the sense and antisense sequence of a genomic interval, for a transcript on `strand`
+fn stranded_sequences<R: Read + Seek>(
+    reader: &mut FastaReader<R>,
+    chrom: &str,
+    interval: (u32, u32),
+    strand: Strand,
+) -> Result<(Sequence, Sequence), AtgError> {
+    let plus = reader.read_sequence(chrom, interval.0.into(), interval.1.into())?;
+    let mut minus = plus.clone();
+    minus.reverse_complement();
+    match strand {
+        Strand::Minus => Ok((minus, plus)),
+        _ => Ok((plus, minus)),
+    }
+}
+
+/// Returns the genomic segments of the transcript that are part of the Fasta output
+pub fn segments(tx: &Transcript, format: &FastaFormat) -> Vec<(u32, u32)> {
+    match format {
+        FastaFormat::Transcript => vec![(tx.tx_start(), tx.tx_end())],
+        FastaFormat::Exons => sorted_exons(tx)
+            .into_iter()
+            .map(|exon| (exon.start(), exon.end()))
+            .collect(),
+        FastaFormat::Cds => sorted_exons(tx)
+            .into_iter()
+            .filter_map(|exon| match (exon.cds_start(), exon.cds_end()) {
+                (Some(start), Some(end)) if start <= end => Some((*start, *end)),
+                _ => None,
+            })
+            .collect(),
+    }
+}
+
+/// Writes the antisense sequence of every transcript as Fasta
+///
+/// This is the reverse complement of the `fasta` output, for the same `--fasta-format`.
+pub struct AntisenseWriter<W: Write, R: Read + Seek> {
+    inner: W,
+    fastareader: FastaReader<R>,
+    format: FastaFormat,
+}
+
+impl<W: Write, R: Read + Seek> AntisenseWriter<W, R> {
+    pub fn new(writer: W, fastareader: FastaReader<R>, format: FastaFormat) -> Self {
+        Self {
+            inner: writer,
+            fastareader,
+            format,
+        }
+    }
+
+    pub fn inner_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Returns the antisense sequence of the transcript, 5' to 3'
+    fn antisense(&mut self, tx: &Transcript) -> Result<String, AtgError> {
+        let mut pieces: Vec<Sequence> = vec![];
+        if let FastaFormat::Exons = self.format {
+            for exon in sorted_exons(tx) {
+                pieces.push(exon.sequences(tx, &mut self.fastareader)?.1);
+            }
+        } else {
+            for interval in segments(tx, &self.format) {
+                let strand = *tx.strand();
+                pieces.push(
+                    stranded_sequences(&mut self.fastareader, tx.chrom(), interval, strand)?.1,
+                );
+            }
+        }
+        // the 5' end of the antisense sequence is at the 3' end of the transcript
+        if !matches!(tx.strand(), Strand::Minus) {
+            pieces.reverse();
+        }
+        Ok(pieces.iter().map(|piece| piece.to_string()).collect())
+    }
+}
+
+impl<R: Read + Seek> AntisenseWriter<BufWriter<File>, R> {
+    pub fn from_file(
+        filename: &str,
+        fastareader: FastaReader<R>,
+        format: FastaFormat,
+    ) -> Result<Self, ReadWriteError> {
+        Ok(Self::new(
+            BufWriter::new(File::create(filename)?),
+            fastareader,
+            format,
+        ))
+    }
+}
+
+impl<W: Write, R: Read + Seek> TranscriptWrite for AntisenseWriter<W, R> {
+    fn writeln_single_transcript(&mut self, transcript: &Transcript) -> Result<(), std::io::Error> {
+        self.write_single_transcript(transcript)
+    }
+
+    fn write_single_transcript(&mut self, transcript: &Transcript) -> Result<(), std::io::Error> {
+        let seq = self
+            .antisense(transcript)
+            .map_err(|err| std::io::Error::other(err.to_string()))?;
+        writeln!(
+            self.inner,
+            ">{} antisense {}:{}-{}:{}",
+            transcript.name(),
+            transcript.chrom(),
+            transcript.tx_start(),
+            transcript.tx_end(),
+            antisense_strand(*transcript.strand())
+        )?;
+        for line in seq.as_bytes().chunks(LINE_WIDTH) {
+            self.inner.write_all(line)?;
+            self.inner.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    fn write_transcript_vec(&mut self, transcripts: &[Transcript]) -> Result<(), std::io::Error> {
+        for tx in transcripts {
+            self.writeln_single_transcript(tx)?;
+        }
+        self.inner.flush()
+    }
+}
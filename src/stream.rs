@@ -0,0 +1,107 @@
+use std::io::Read;
+
+use atglib::models::{Transcript, Transcripts};
+use atglib::utils::errors::AtgError;
+
+use crate::cli::{Args, InputFormat};
+use crate::gtf_groups::{BuildFailure, GroupedGtfReader};
+use crate::line_numbers::{LineFormat, NumberedReader};
+
+/// Readers that can return their transcripts one at a time
+///
+/// In contrast to `TranscriptRead::transcripts`, only the currently
+/// processed transcript must be held in memory.
+pub trait TranscriptIter {
+    /// Returns the next transcript, or `None` at the end of the input
+    fn next_transcript(&mut self) -> Option<Result<Transcript, AtgError>>;
+
+    /// Returns an iterator over all remaining transcripts
+    fn iter_transcripts(&mut self) -> IterTranscripts<'_, Self>
+    where
+        Self: Sized,
+    {
+        IterTranscripts { reader: self }
+    }
+}
+
+/// Iterator over the transcripts of a `TranscriptIter`
+pub struct IterTranscripts<'a, T> {
+    reader: &'a mut T,
+}
+
+impl<T: TranscriptIter> Iterator for IterTranscripts<'_, T> {
+    type Item = Result<Transcript, AtgError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.reader.next_transcript()
+    }
+}
+
+/// TranscriptStream yields one `Transcript` at a time
+///
+/// Line-based input formats (RefGene and GenePredExt) are parsed lazily,
+/// so only the currently processed transcript is held in memory. GTF input
+/// is parsed lazily with `--stream-gtf`, if the records of every transcript
+/// are consecutive.
+/// All other formats must be parsed completely before the first transcript
+/// can be returned, e.g. because the records of one transcript can be spread
+/// across the whole GTF file.
+pub enum TranscriptStream {
+    Lines(NumberedReader<Box<dyn Read>>),
+    Gtf(Box<GroupedGtfReader<Box<dyn Read>>>),
+    Buffered(std::vec::IntoIter<Transcript>),
+}
+
+impl TranscriptStream {
+    /// Returns a lazy stream, if the input format supports it
+    pub fn open(
+        args: &Args,
+        format: &InputFormat,
+        filename: &str,
+    ) -> Option<Result<Self, AtgError>> {
+        match format {
+            InputFormat::Refgene => Some(
+                NumberedReader::from_file(filename, LineFormat::Refgene)
+                    .map(Self::Lines)
+                    .map_err(AtgError::from),
+            ),
+            InputFormat::Genepredext => Some(
+                NumberedReader::from_file(filename, LineFormat::Genepredext)
+                    .map(Self::Lines)
+                    .map_err(AtgError::from),
+            ),
+            InputFormat::Gtf if args.stream_gtf => Some(
+                GroupedGtfReader::from_file(filename, args.lenient)
+                    .map(|mut reader| {
+                        reader.set_preserve_attributes(args.preserves_attributes());
+                        reader.set_exon_merge(args.exon_merge);
+                        Self::Gtf(Box::new(reader))
+                    })
+                    .map_err(AtgError::from),
+            ),
+            _ => None,
+        }
+    }
+
+    /// Returns a stream over already parsed transcripts
+    pub fn from_transcripts(transcripts: Transcripts) -> Self {
+        Self::Buffered(transcripts.to_vec().into_iter())
+    }
+
+    /// Returns all transcripts that could not be built, in lenient mode
+    pub fn failures(&self) -> &[BuildFailure] {
+        match self {
+            Self::Gtf(reader) => reader.failures(),
+            _ => &[],
+        }
+    }
+}
+
+impl TranscriptIter for TranscriptStream {
+    fn next_transcript(&mut self) -> Option<Result<Transcript, AtgError>> {
+        match self {
+            Self::Lines(reader) => reader.next_transcript(),
+            Self::Gtf(reader) => reader.next_transcript(),
+            Self::Buffered(iter) => iter.next().map(Ok),
+        }
+    }
+}
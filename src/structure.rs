@@ -0,0 +1,337 @@
+//! Structural checks and repairs of the exons of a transcript
+
+use atglib::models::{Exon, Frame, Transcript};
+use atglib::utils::errors::AtgError;
+
+use crate::utils::set_frames;
+
+/// A structural problem of a transcript
+#[derive(Debug, PartialEq)]
+pub enum StructureIssue {
+    /// The exon at the given position is identical to the previous exon
+    DuplicateExon(u32, u32),
+    /// The exon ends before it starts
+    InvalidExon(u32, u32),
+    /// The CDS of an exon ends before it starts
+    InvalidCds(u32, u32),
+    /// The exon overlaps the previous exon (by start position)
+    OverlappingExon(u32, u32),
+}
+
+impl std::fmt::Display for StructureIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            StructureIssue::DuplicateExon(start, end) => {
+                write!(f, "duplicate exon {}-{}", start, end)
+            }
+            StructureIssue::InvalidExon(start, end) => {
+                write!(f, "exon with zero or negative length {}-{}", start, end)
+            }
+            StructureIssue::InvalidCds(start, end) => {
+                write!(f, "CDS with zero or negative length {}-{}", start, end)
+            }
+            StructureIssue::OverlappingExon(start, end) => {
+                write!(f, "overlapping exon {}-{}", start, end)
+            }
+        }
+    }
+}
+
+/// Returns all structural issues of the transcript
+pub fn check_structure(tx: &Transcript) -> Vec<StructureIssue> {
+    let mut issues = vec![];
+    let mut previous: Option<(u32, u32, Option<u32>, Option<u32>)> = None;
+    for exon in tx.exons() {
+        let current = (exon.start(), exon.end(), *exon.cds_start(), *exon.cds_end());
+        if previous == Some(current) {
+            issues.push(StructureIssue::DuplicateExon(exon.start(), exon.end()));
+        }
+        if exon.end() < exon.start() {
+            issues.push(StructureIssue::InvalidExon(exon.start(), exon.end()));
+        }
+        if let (Some(start), Some(end)) = (exon.cds_start(), exon.cds_end()) {
+            if end < start {
+                issues.push(StructureIssue::InvalidCds(*start, *end));
+            }
+        }
+        previous = Some(current);
+    }
+    issues.extend(overlapping_exons(tx));
+    issues
+}
+
+/// Returns all exons that overlap a previous exon, by start position
+///
+/// Identical exons and exons with zero or negative length are not reported,
+/// they are separate issues.
+fn overlapping_exons(tx: &Transcript) -> Vec<StructureIssue> {
+    let mut exons: Vec<(u32, u32)> = tx
+        .exons()
+        .iter()
+        .map(|exon| (exon.start(), exon.end()))
+        .filter(|(start, end)| start <= end)
+        .collect();
+    exons.sort_unstable();
+    exons.dedup();
+    let mut issues = vec![];
+    let mut max_end = None;
+    for (start, end) in exons {
+        if max_end.is_some_and(|max_end| start <= max_end) {
+            issues.push(StructureIssue::OverlappingExon(start, end));
+        }
+        max_end = max_end.max(Some(end));
+    }
+    issues
+}
+
+/// Sorts the exons by their start position and checks that they don't overlap
+///
+/// Returns `true` if the exons were not sorted. Overlapping exons are an
+/// error, unless `allow_overlaps` is set, e.g. because they are merged
+/// with `--fix-structure` later.
+pub fn validate_exons(tx: &mut Transcript, allow_overlaps: bool) -> Result<bool, AtgError> {
+    let unsorted = tx
+        .exons()
+        .windows(2)
+        .any(|pair| pair[1].start() < pair[0].start());
+    if unsorted {
+        tx.exons_mut().sort_by_key(|exon| exon.start());
+    }
+    if !allow_overlaps {
+        if let Some(issue) = overlapping_exons(tx).first() {
+            return Err(AtgError::new(format!(
+                "transcript {} has an {}, use `--fix-structure` to merge overlapping exons",
+                tx.name(),
+                issue
+            )));
+        }
+    }
+    Ok(unsorted)
+}
+
+/// Fixes all structural issues of the transcript and returns the fixed issues
+///
+/// - Consecutive identical exons are removed
+/// - Exons with zero or negative length are removed
+/// - CDS features with zero or negative length are removed from the exon
+/// - Overlapping exons are merged
+pub fn fix_structure(tx: &mut Transcript) -> Vec<StructureIssue> {
+    let issues = check_structure(tx);
+    if issues.is_empty() {
+        return issues;
+    }
+
+    let exons = tx.exons_mut();
+    exons.dedup_by(|a, b| {
+        a.start() == b.start()
+            && a.end() == b.end()
+            && a.cds_start() == b.cds_start()
+            && a.cds_end() == b.cds_end()
+    });
+    exons.retain(|exon| exon.end() >= exon.start());
+    for exon in exons.iter_mut() {
+        if let (Some(start), Some(end)) = (exon.cds_start(), exon.cds_end()) {
+            if end < start {
+                *exon.cds_start_mut() = None;
+                *exon.cds_end_mut() = None;
+                *exon.frame_offset_mut() = Frame::None;
+            }
+        }
+    }
+    if issues
+        .iter()
+        .any(|issue| matches!(issue, StructureIssue::OverlappingExon(..)))
+    {
+        merge_where(tx, |last, exon| exon.start() <= last.end());
+    }
+    issues
+}
+
+/// Derives the frames of all exons from the CDS and the strand of the transcript
+///
+/// Missing or wrong frames, e.g. of hand-edited GenePred files, would
+/// otherwise be written to GTF output unchanged. Returns the number of
+/// exons with a changed frame.
+pub fn recompute_frames(tx: &mut Transcript) -> usize {
+    let strand = *tx.strand();
+    set_frames(tx.exons_mut(), &strand)
+}
+
+/// Merges book-ended exons, and with `overlapping` also overlapping exons, into one exon
+///
+/// The CDS of the merged exon spans the CDS of all merged exons. Returns the
+/// number of removed exons, the frames are recomputed if exons were merged.
+pub fn merge_exons(tx: &mut Transcript, overlapping: bool) -> usize {
+    merge_where(tx, |last, exon| {
+        last.end().checked_add(1) == Some(exon.start())
+            || (overlapping && exon.start() <= last.end())
+    })
+}
+
+/// Merges every exon into the previous exon (by start position) if `merge` returns `true`
+fn merge_where<F: Fn(&Exon, &Exon) -> bool>(tx: &mut Transcript, merge: F) -> usize {
+    let mut exons = tx.exons().clone();
+    exons.sort_by_key(|exon| exon.start());
+    let mut merged: Vec<Exon> = vec![];
+    for exon in exons {
+        let last = match merged.last_mut() {
+            Some(last) if merge(last, &exon) => last,
+            _ => {
+                merged.push(exon);
+                continue;
+            }
+        };
+        *last.end_mut() = last.end().max(exon.end());
+        let cds_start = match (*last.cds_start(), *exon.cds_start()) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        let cds_end = match (*last.cds_end(), *exon.cds_end()) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+        *last.cds_start_mut() = cds_start;
+        *last.cds_end_mut() = cds_end;
+    }
+    let removed = tx.exons().len() - merged.len();
+    if removed > 0 {
+        let strand = *tx.strand();
+        set_frames(&mut merged, &strand);
+        *tx.exons_mut() = merged;
+    }
+    removed
+}
+
+/// Splits exons into the book-ended exons of the input
+///
+/// `bounds` are the start and end positions of the exons of the input. An
+/// exon is split if it consists of several book-ended exons of the input,
+/// the CDS is split accordingly. Returns the number of added exons, the
+/// frames are recomputed if exons were split.
+pub fn split_exons(tx: &mut Transcript, bounds: &[(u32, u32)]) -> usize {
+    let mut bounds = bounds.to_vec();
+    bounds.sort_unstable();
+    bounds.dedup();
+    let mut exons: Vec<Exon> = vec![];
+    for exon in tx.exons() {
+        let parts: Vec<(u32, u32)> = bounds
+            .iter()
+            .filter(|(start, end)| *start >= exon.start() && *end <= exon.end())
+            .copied()
+            .collect();
+        let tiled = parts.len() > 1
+            && parts.first().map(|part| part.0) == Some(exon.start())
+            && parts.last().map(|part| part.1) == Some(exon.end())
+            && parts
+                .windows(2)
+                .all(|pair| pair[0].1.checked_add(1) == Some(pair[1].0));
+        if !tiled {
+            exons.push(exon.clone());
+            continue;
+        }
+        for (start, end) in parts {
+            let cds = match (*exon.cds_start(), *exon.cds_end()) {
+                (Some(cds_start), Some(cds_end)) if cds_start <= end && cds_end >= start => {
+                    Some((cds_start.max(start), cds_end.min(end)))
+                }
+                _ => None,
+            };
+            exons.push(Exon::new(
+                start,
+                end,
+                cds.map(|cds| cds.0),
+                cds.map(|cds| cds.1),
+                Frame::None,
+            ));
+        }
+    }
+    let added = exons.len() - tx.exons().len();
+    if added > 0 {
+        let strand = *tx.strand();
+        set_frames(&mut exons, &strand);
+        *tx.exons_mut() = exons;
+    }
+    added
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use atglib::models::{Strand, TranscriptBuilder};
+
+    fn transcript(exons: &[(u32, u32)]) -> Transcript {
+        let mut tx = TranscriptBuilder::new()
+            .name("Test-Transcript")
+            .chrom("chr1")
+            .gene("Test-Gene")
+            .strand(Strand::Plus)
+            .build()
+            .unwrap();
+        for (start, end) in exons {
+            tx.push_exon(Exon::new(*start, *end, None, None, Frame::None));
+        }
+        tx
+    }
+
+    fn bounds(tx: &Transcript) -> Vec<(u32, u32)> {
+        tx.exons()
+            .iter()
+            .map(|exon| (exon.start(), exon.end()))
+            .collect()
+    }
+
+    #[test]
+    fn test_merge_book_ended_exons() {
+        let mut tx = transcript(&[(0, 0), (1, 1), (5, 10)]);
+        assert_eq!(merge_exons(&mut tx, false), 1);
+        assert_eq!(bounds(&tx), vec![(0, 1), (5, 10)]);
+
+        let mut tx = transcript(&[(u32::MAX - 1, u32::MAX - 1), (u32::MAX, u32::MAX)]);
+        assert_eq!(merge_exons(&mut tx, false), 1);
+        assert_eq!(bounds(&tx), vec![(u32::MAX - 1, u32::MAX)]);
+    }
+
+    #[test]
+    fn test_merge_exons_ending_at_max() {
+        // `end + 1` of the first exon would overflow
+        let mut tx = transcript(&[(10, u32::MAX), (20, 30)]);
+        assert_eq!(merge_exons(&mut tx, false), 0);
+        assert_eq!(bounds(&tx), vec![(10, u32::MAX), (20, 30)]);
+
+        assert_eq!(merge_exons(&mut tx, true), 1);
+        assert_eq!(bounds(&tx), vec![(10, u32::MAX)]);
+    }
+
+    #[test]
+    fn test_validate_exons_sorts_exons() {
+        let mut tx = transcript(&[(20, 30), (1, 10)]);
+        assert!(validate_exons(&mut tx, false).unwrap());
+        assert_eq!(bounds(&tx), vec![(1, 10), (20, 30)]);
+
+        assert!(!validate_exons(&mut tx, false).unwrap());
+        assert_eq!(bounds(&tx), vec![(1, 10), (20, 30)]);
+    }
+
+    #[test]
+    fn test_validate_exons_rejects_overlaps() {
+        let mut tx = transcript(&[(15, 30), (1, 20)]);
+        assert!(validate_exons(&mut tx, false).is_err());
+
+        // overlaps are merged later with `--fix-structure`
+        let mut tx = transcript(&[(15, 30), (1, 20)]);
+        assert!(validate_exons(&mut tx, true).unwrap());
+        assert_eq!(bounds(&tx), vec![(1, 20), (15, 30)]);
+
+        let issues = fix_structure(&mut tx);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(bounds(&tx), vec![(1, 30)]);
+        assert!(validate_exons(&mut tx, false).is_ok());
+    }
+
+    #[test]
+    fn test_merge_keeps_separate_exons() {
+        let mut tx = transcript(&[(1, 10), (12, 20)]);
+        assert_eq!(merge_exons(&mut tx, true), 0);
+        assert_eq!(bounds(&tx), vec![(1, 10), (12, 20)]);
+    }
+}
@@ -0,0 +1,262 @@
+//! Sorted, bgzip compressed output with a tabix index
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use atglib::utils::errors::AtgError;
+
+use crate::chromosomes::chrom_cmp;
+use crate::compression::BgzfWriter;
+
+/// Size of the windows of the linear index (16kb)
+const LINEAR_SHIFT: u32 = 14;
+
+/// Positions must fit into the binning scheme of tabix (2^29)
+const MAX_POSITION: u64 = 1 << 29;
+
+/// The layout of the indexed file
+#[derive(Clone, Copy, Debug)]
+pub enum Preset {
+    /// 1-based, inclusive coordinates in columns 4 and 5
+    Gtf,
+    /// 0-based, half-open coordinates in columns 2 and 3
+    Bed,
+}
+
+impl Preset {
+    /// Returns the format flag and the 1-based sequence, start and end columns
+    fn columns(&self) -> (i32, i32, i32, i32) {
+        match self {
+            Preset::Gtf => (0, 1, 4, 5),
+            // 0x10000 marks 0-based, half-open coordinates
+            Preset::Bed => (0x10000, 1, 2, 3),
+        }
+    }
+
+    /// Returns the chromosome and the 0-based, half-open interval of a record
+    fn parse<'a>(&self, line: &'a str) -> Option<(&'a str, u64, u64)> {
+        let (_, seq, beg, end) = self.columns();
+        let cols: Vec<&str> = line.split('\t').collect();
+        let chrom = *cols.get(seq as usize - 1)?;
+        let beg: u64 = cols.get(beg as usize - 1)?.trim().parse().ok()?;
+        let end: u64 = cols.get(end as usize - 1)?.trim().parse().ok()?;
+        match self {
+            Preset::Gtf => Some((chrom, beg.saturating_sub(1), end)),
+            Preset::Bed => Some((chrom, beg, end)),
+        }
+    }
+}
+
+/// A single data line
+struct Record<'a> {
+    line: &'a str,
+    chrom: &'a str,
+    beg: u64,
+    end: u64,
+}
+
+/// Returns the tabix bin of a 0-based, half-open interval
+///
+/// The bins of each level start at `((1 << (3 * level)) - 1) / 7`, as in `reg2bin` of the specification.
+fn reg2bin(beg: u64, end: u64) -> u32 {
+    let end = end.max(beg + 1) - 1;
+    let bin = if beg >> 14 == end >> 14 {
+        4681 + (beg >> 14)
+    } else if beg >> 17 == end >> 17 {
+        585 + (beg >> 17)
+    } else if beg >> 20 == end >> 20 {
+        73 + (beg >> 20)
+    } else if beg >> 23 == end >> 23 {
+        9 + (beg >> 23)
+    } else if beg >> 26 == end >> 26 {
+        1 + (beg >> 26)
+    } else {
+        0
+    };
+    // positions are limited to 2^29, so the bin is at most 37448
+    bin as u32
+}
+
+/// The index of a single chromosome
+#[derive(Default)]
+struct RefIndex {
+    /// The chunks (start and end virtual offsets) of every bin
+    bins: BTreeMap<u32, Vec<(u64, u64)>>,
+    /// The virtual offset of the first record that overlaps each 16kb window
+    ///
+    /// The first record of a file without header starts at offset 0, so
+    /// windows without any record are `None`.
+    linear: Vec<Option<u64>>,
+}
+
+impl RefIndex {
+    fn add(&mut self, beg: u64, end: u64, start_offset: u64, end_offset: u64) {
+        let chunks = self.bins.entry(reg2bin(beg, end)).or_default();
+        match chunks.last_mut() {
+            // consecutive records of the same bin are merged into one chunk
+            Some(last) if last.1 == start_offset => last.1 = end_offset,
+            _ => chunks.push((start_offset, end_offset)),
+        }
+
+        let first = (beg >> LINEAR_SHIFT) as usize;
+        let last = (end.max(beg + 1).saturating_sub(1) >> LINEAR_SHIFT) as usize;
+        if self.linear.len() <= last {
+            self.linear.resize(last + 1, None);
+        }
+        for window in &mut self.linear[first..=last] {
+            if window.is_none() {
+                *window = Some(start_offset);
+            }
+        }
+    }
+
+    fn write<W: Write>(&self, out: &mut W) -> Result<(), std::io::Error> {
+        write_i32(out, self.bins.len())?;
+        for (bin, chunks) in &self.bins {
+            out.write_all(&bin.to_le_bytes())?;
+            write_i32(out, chunks.len())?;
+            for (beg, end) in chunks {
+                out.write_all(&beg.to_le_bytes())?;
+                out.write_all(&end.to_le_bytes())?;
+            }
+        }
+
+        write_i32(out, self.linear.len())?;
+        // empty windows point to the previous record, as in htslib
+        let mut previous = 0;
+        for offset in &self.linear {
+            if let Some(offset) = offset {
+                previous = *offset;
+            }
+            out.write_all(&previous.to_le_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+fn write_i32<W: Write>(out: &mut W, value: usize) -> Result<(), std::io::Error> {
+    let value = i32::try_from(value)
+        .map_err(|_| std::io::Error::other("too many entries for a tabix index"))?;
+    out.write_all(&value.to_le_bytes())
+}
+
+/// Writes the data lines as sorted bgzip file and creates the `.tbi` index
+///
+/// Header lines (starting with `#`) are written first, in their original
+/// order. All other lines are sorted by chromosome (see `--chrom-order`)
+/// and position. The index is written to `<filename>.tbi`.
+pub fn write_indexed(data: &str, preset: Preset, filename: &str) -> Result<(), AtgError> {
+    let mut headers: Vec<&str> = vec![];
+    let mut records: Vec<Record> = vec![];
+    for line in data.lines() {
+        if line.starts_with('#') || line.is_empty() {
+            headers.push(line);
+            continue;
+        }
+        let (chrom, beg, end) = preset
+            .parse(line)
+            .ok_or_else(|| AtgError::new(format!("unable to index the line `{}`", line)))?;
+        if end > MAX_POSITION {
+            return Err(AtgError::new(format!(
+                "{}:{} is too large for a tabix index",
+                chrom, end
+            )));
+        }
+        records.push(Record {
+            line,
+            chrom,
+            beg,
+            end,
+        });
+    }
+    // the sort is stable, so the records of a transcript keep their order
+    records.sort_by(|a, b| {
+        chrom_cmp(a.chrom, b.chrom)
+            .then_with(|| a.chrom.cmp(b.chrom))
+            .then_with(|| a.beg.cmp(&b.beg))
+    });
+
+    let mut out = BgzfWriter::new(BufWriter::new(File::create(filename)?));
+    for line in headers {
+        writeln!(out, "{}", line)?;
+    }
+    let mut names: Vec<&str> = vec![];
+    let mut index: Vec<RefIndex> = vec![];
+    for record in records {
+        if names.last() != Some(&record.chrom) {
+            names.push(record.chrom);
+            index.push(RefIndex::default());
+        }
+        let start_offset = out.virtual_offset();
+        writeln!(out, "{}", record.line)?;
+        // the vector always contains the entry for the current chromosome
+        if let Some(ref_index) = index.last_mut() {
+            ref_index.add(record.beg, record.end, start_offset, out.virtual_offset());
+        }
+    }
+    out.flush()?;
+    drop(out);
+
+    let mut tbi = BgzfWriter::new(BufWriter::new(File::create(format!("{}.tbi", filename))?));
+    let (format, col_seq, col_beg, col_end) = preset.columns();
+    tbi.write_all(b"TBI\x01")?;
+    write_i32(&mut tbi, names.len())?;
+    for value in [format, col_seq, col_beg, col_end, i32::from(b'#'), 0] {
+        tbi.write_all(&value.to_le_bytes())?;
+    }
+    let name_len: usize = names.iter().map(|name| name.len() + 1).sum();
+    write_i32(&mut tbi, name_len)?;
+    for name in &names {
+        tbi.write_all(name.as_bytes())?;
+        tbi.write_all(b"\0")?;
+    }
+    for ref_index in &index {
+        ref_index.write(&mut tbi)?;
+    }
+    tbi.flush()?;
+    debug!(
+        target: "atg::write",
+        "Indexed {} chromosomes in {}.tbi",
+        names.len(),
+        filename
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use super::*;
+
+    /// Returns the offsets of the linear index, as written to the `.tbi` file
+    fn written_linear_index(index: &RefIndex) -> Vec<u64> {
+        let mut out: Vec<u8> = vec![];
+        index.write(&mut out).unwrap();
+        out[out.len() - 8 * index.linear.len()..]
+            .chunks(8)
+            .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_linear_index_of_first_record() {
+        let mut index = RefIndex::default();
+        // the first record of a file without header starts at offset 0
+        index.add(100, 200, 0, 40);
+        index.add(150, 40_000, 40, 80);
+        assert_eq!(index.linear, vec![Some(0), Some(40), Some(40)]);
+        assert_eq!(written_linear_index(&index), vec![0, 40, 40]);
+    }
+
+    #[test]
+    fn test_empty_windows_point_to_previous_record() {
+        let mut index = RefIndex::default();
+        index.add(100, 200, 0, 40);
+        index.add(40_000, 40_100, 40, 80);
+        assert_eq!(index.linear, vec![Some(0), None, Some(40)]);
+        assert_eq!(written_linear_index(&index), vec![0, 0, 40]);
+    }
+}
@@ -0,0 +1,326 @@
+//! Parameterized transcripts and a matching reference genome for tests
+
+use std::io::Cursor;
+
+use atglib::fasta::FastaReader;
+use atglib::models::{CdsStat, Exon, Frame, Strand, Transcript, TranscriptBuilder};
+
+/// Length of every exon
+const EXON_LEN: u32 = 15;
+
+/// Length of every intron
+const INTRON_LEN: u32 = 10;
+
+/// Length of the flanking sequence on both ends of the chromosome
+const FLANK_LEN: u32 = 10;
+
+/// Minimum length of the 5' and 3' UTR of coding transcripts
+const UTR_LEN: u32 = 3;
+
+/// The codon that is repeated in the CDS and used for partial codons
+const CODON: &str = "GCT";
+
+/// Builds transcripts and their reference sequence for tests
+///
+/// All exons are 15 nt long, separated by introns of 10 nt, and the
+/// chromosome contains 10 nt of flanking sequence at both ends,
+/// e.g. for `Fixture::new(3)`:
+///
+/// ```text
+/// 1         11             26        36             51        61             76       85
+/// |         |              |         |              |         |              |        |
+/// TTTTTTTTTT===XXXXXXXXXXXXGTAAAAAAAGXXXXXXXXXXXXXXXGTAAAAAAAGXXXXXXXXXXXX===TTTTTTTTTT
+///
+/// TTT  Flanking sequence
+/// GTA  Intron
+/// ===  Exon (non-coding)
+/// XXX  CDS
+/// ```
+///
+/// The CDS starts 3 nt after the transcription start (5'UTR) and ends at
+/// least 3 nt before the transcription end (3'UTR). It consists of `ATG`,
+/// followed by `GCT` codons and `TAA`. Partial codons are taken from `GCT`.
+/// Transcripts on the minus strand use the same exon coordinates and the
+/// reverse complement of the plus strand sequence.
+#[derive(Clone, Debug)]
+pub struct Fixture {
+    name: String,
+    gene: String,
+    chrom: String,
+    exons: u32,
+    strand: Strand,
+    coding: bool,
+    partial_start: Option<u32>,
+    partial_end: Option<u32>,
+}
+
+impl Fixture {
+    /// Creates a coding transcript on the plus strand with `exons` exons
+    /// and a complete start and stop codon
+    ///
+    /// # Panics
+    /// If `exons` is 0
+    pub fn new(exons: u32) -> Self {
+        assert!(exons > 0, "a transcript requires at least one exon");
+        Self {
+            name: "Fixture-Transcript".to_string(),
+            gene: "Fixture-Gene".to_string(),
+            chrom: "chr1".to_string(),
+            exons,
+            strand: Strand::Plus,
+            coding: true,
+            partial_start: None,
+            partial_end: None,
+        }
+    }
+
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn gene(mut self, gene: &str) -> Self {
+        self.gene = gene.to_string();
+        self
+    }
+
+    pub fn chrom(mut self, chrom: &str) -> Self {
+        self.chrom = chrom.to_string();
+        self
+    }
+
+    pub fn strand(mut self, strand: Strand) -> Self {
+        self.strand = strand;
+        self
+    }
+
+    /// Removes the CDS
+    pub fn non_coding(mut self) -> Self {
+        self.coding = false;
+        self
+    }
+
+    /// The CDS starts without a start codon, with the last `bases` nucleotides of a codon
+    ///
+    /// `0` starts the CDS with a complete codon, that is not a start codon.
+    ///
+    /// # Panics
+    /// If `bases` is larger than 2
+    pub fn partial_start(mut self, bases: u32) -> Self {
+        assert!(bases < 3, "a partial codon has at most 2 nucleotides");
+        self.partial_start = Some(bases);
+        self
+    }
+
+    /// The CDS ends without a stop codon, with the first `bases` nucleotides of a codon
+    ///
+    /// `0` ends the CDS with a complete codon, that is not a stop codon.
+    ///
+    /// # Panics
+    /// If `bases` is larger than 2
+    pub fn partial_end(mut self, bases: u32) -> Self {
+        assert!(bases < 3, "a partial codon has at most 2 nucleotides");
+        self.partial_end = Some(bases);
+        self
+    }
+
+    /// Returns the length of the chromosome
+    pub fn chrom_len(&self) -> u32 {
+        2 * FLANK_LEN + self.exons * EXON_LEN + (self.exons - 1) * INTRON_LEN
+    }
+
+    /// Returns the start and end of all exons, in the order of transcription
+    ///
+    /// The layout is symmetrical, so the coordinates only depend on the strand
+    /// for the order.
+    fn exon_positions(&self) -> Vec<(u32, u32)> {
+        let mut exons: Vec<(u32, u32)> = (0..self.exons)
+            .map(|idx| {
+                let start = FLANK_LEN + 1 + idx * (EXON_LEN + INTRON_LEN);
+                (start, start + EXON_LEN - 1)
+            })
+            .collect();
+        if self.strand == Strand::Minus {
+            exons.reverse();
+        }
+        exons
+    }
+
+    /// Returns the length of the CDS
+    ///
+    /// The 3'UTR is extended by up to 2 nt, so that all codons between the
+    /// partial codons are complete.
+    fn cds_len(&self) -> u32 {
+        let available = self.exons * EXON_LEN - 2 * UTR_LEN;
+        let partial = self.partial_start.unwrap_or(0) + self.partial_end.unwrap_or(0);
+        available - (available - partial) % 3
+    }
+
+    /// Returns the CDS in the order of transcription, including the stop codon
+    ///
+    /// Non-coding transcripts return an empty string.
+    pub fn cds_sequence(&self) -> String {
+        if !self.coding {
+            return String::new();
+        }
+        let mut start = match self.partial_start {
+            Some(bases) => CODON[(3 - bases as usize)..].to_string(),
+            None => "ATG".to_string(),
+        };
+        let end = match self.partial_end {
+            Some(bases) => CODON[..bases as usize].to_string(),
+            None => "TAA".to_string(),
+        };
+        let codons = (self.cds_len() as usize - start.len() - end.len()) / 3;
+        start.push_str(&CODON.repeat(codons));
+        start.push_str(&end);
+        start
+    }
+
+    /// Returns the mRNA sequence of the transcript, in the order of transcription
+    pub fn mrna_sequence(&self) -> String {
+        let exon_len = (self.exons * EXON_LEN) as usize;
+        if !self.coding {
+            return "C".repeat(exon_len);
+        }
+        let cds = self.cds_sequence();
+        let utr3 = exon_len - UTR_LEN as usize - cds.len();
+        format!(
+            "{}{}{}",
+            "C".repeat(UTR_LEN as usize),
+            cds,
+            "C".repeat(utr3)
+        )
+    }
+
+    /// Returns the sequence of the plus strand of the chromosome
+    pub fn chrom_sequence(&self) -> String {
+        // the sequence is built for the plus strand and reverse complemented afterwards
+        let mrna = self.mrna_sequence();
+        let intron = format!("GT{}AG", "A".repeat(INTRON_LEN as usize - 4));
+        let exons: Vec<&str> = (0..self.exons as usize)
+            .map(|idx| &mrna[idx * EXON_LEN as usize..(idx + 1) * EXON_LEN as usize])
+            .collect();
+        let flank = "T".repeat(FLANK_LEN as usize);
+        let sequence = format!("{}{}{}", flank, exons.join(&intron), flank);
+        match self.strand {
+            Strand::Minus => reverse_complement(&sequence),
+            _ => sequence,
+        }
+    }
+
+    /// Returns the transcript
+    pub fn transcript(&self) -> Transcript {
+        let cds_stat = |partial: Option<u32>| match (self.coding, partial) {
+            (false, _) => CdsStat::None,
+            (true, Some(_)) => CdsStat::Incomplete,
+            (true, None) => CdsStat::Complete,
+        };
+        let mut transcript = TranscriptBuilder::new()
+            .name(&self.name)
+            .chrom(&self.chrom)
+            .gene(&self.gene)
+            .strand(self.strand)
+            .cds_start_codon_stat(cds_stat(self.partial_start))
+            .unwrap()
+            .cds_stop_codon_stat(cds_stat(self.partial_end))
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let mut exons = self.exons();
+        if self.strand == Strand::Minus {
+            exons.reverse();
+        }
+        for exon in exons {
+            transcript.push_exon(exon);
+        }
+        transcript
+    }
+
+    /// Returns the exons, in the order of transcription
+    fn exons(&self) -> Vec<Exon> {
+        // the CDS in mRNA coordinates (0-based, half-open)
+        let cds_start = UTR_LEN;
+        let cds_end = UTR_LEN + self.cds_len();
+        let partial = self.partial_start.unwrap_or(0);
+
+        let mut exons = vec![];
+        let mut mrna_pos = 0;
+        let mut cds_before = 0;
+        for (start, end) in self.exon_positions() {
+            let coding_start = cds_start.max(mrna_pos);
+            let coding_end = cds_end.min(mrna_pos + EXON_LEN);
+            if !self.coding || coding_start >= coding_end {
+                exons.push(Exon::new(start, end, None, None, Frame::None));
+            } else {
+                // offsets of the CDS from the 5' and 3' end of the exon
+                let offset_5 = coding_start - mrna_pos;
+                let offset_3 = mrna_pos + EXON_LEN - coding_end;
+                let (cds_start, cds_end) = match self.strand {
+                    Strand::Minus => (start + offset_3, end - offset_5),
+                    _ => (start + offset_5, end - offset_3),
+                };
+                // the number of nucleotides before the first complete codon
+                let frame = (partial + 3 - cds_before % 3) % 3;
+                exons.push(Exon::new(
+                    start,
+                    end,
+                    Some(cds_start),
+                    Some(cds_end),
+                    frame_of(frame),
+                ));
+                cds_before += coding_end - coding_start;
+            }
+            mrna_pos += EXON_LEN;
+        }
+        exons
+    }
+
+    /// Returns the fasta data and the fasta index of the chromosome
+    pub fn fasta(&self) -> (String, String) {
+        let sequence = self.chrom_sequence();
+        let header = format!(">{}\n", self.chrom);
+        let fai = format!(
+            "{}\t{}\t{}\t{}\t{}\n",
+            self.chrom,
+            sequence.len(),
+            header.len(),
+            sequence.len(),
+            sequence.len() + 1
+        );
+        (format!("{}{}\n", header, sequence), fai)
+    }
+
+    /// Returns a `FastaReader` of the chromosome, kept completely in memory
+    pub fn fasta_reader(&self) -> FastaReader<Cursor<Vec<u8>>> {
+        let (fasta, fai) = self.fasta();
+        FastaReader::from_reader(
+            Cursor::new(fasta.into_bytes()),
+            Cursor::new(fai.into_bytes()),
+        )
+        .unwrap()
+    }
+}
+
+fn frame_of(offset: u32) -> Frame {
+    match offset {
+        0 => Frame::Zero,
+        1 => Frame::One,
+        _ => Frame::Two,
+    }
+}
+
+fn reverse_complement(sequence: &str) -> String {
+    sequence
+        .chars()
+        .rev()
+        .map(|base| match base {
+            'A' => 'T',
+            'C' => 'G',
+            'G' => 'C',
+            'T' => 'A',
+            other => other,
+        })
+        .collect()
+}
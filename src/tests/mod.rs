@@ -1,3 +1,8 @@
 //! Contains some helper functions for unit and integration tests
 
+// not every test uses every transcript or fixture option
+#![allow(dead_code)]
+
 pub mod transcripts;
+
+pub mod fixtures;
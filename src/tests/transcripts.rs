@@ -4,8 +4,8 @@
 //! Some transcripts in here have been choosen deliberately, because they
 //! contain some edge cases that I tripped across during development.
 
-use crate::models;
-use crate::models::{Exon, Transcript, TranscriptBuilder};
+use atglib::models;
+use atglib::models::{Exon, Transcript, TranscriptBuilder};
 
 /// NM_001365057.2
 ///
@@ -294,8 +294,10 @@ pub fn standard_transcript() -> models::Transcript {
         .chrom("chr1")
         .strand(models::Strand::Plus)
         .gene("Test-Gene")
-        .cds_start_stat(models::CdsStat::None)
-        .cds_end_stat(models::CdsStat::None)
+        .cds_start_codon_stat(models::CdsStat::None)
+        .unwrap()
+        .cds_stop_codon_stat(models::CdsStat::None)
+        .unwrap()
         .build()
         .unwrap();
 
@@ -0,0 +1,81 @@
+//! The `bin` column of RefGene output
+
+use std::io::Write;
+
+use crate::line_filter::{FilterLines, LineFilter};
+
+/// Features that end before this position use the standard binning scheme
+const MAX_END_STANDARD: u64 = 512 * 1024 * 1024;
+
+/// The offsets of the bins of every level, from the smallest to the largest bins
+const OFFSETS_STANDARD: &[u64] = &[512 + 64 + 8 + 1, 64 + 8 + 1, 8 + 1, 1, 0];
+const OFFSETS_EXTENDED: &[u64] = &[
+    4096 + 512 + 64 + 8 + 1,
+    512 + 64 + 8 + 1,
+    64 + 8 + 1,
+    8 + 1,
+    1,
+    0,
+];
+
+/// The first extended bin, after all standard bins
+const EXTENDED_START: u64 = 4681;
+
+/// The smallest bins span 128kb
+const FIRST_SHIFT: u32 = 17;
+const NEXT_SHIFT: u32 = 3;
+
+/// Returns the UCSC bin of a 0-based, half-open interval
+pub fn bin_from_range(start: u64, end: u64) -> u64 {
+    let (offsets, extra) = match end <= MAX_END_STANDARD {
+        true => (OFFSETS_STANDARD, 0),
+        false => (OFFSETS_EXTENDED, EXTENDED_START),
+    };
+    let mut start_bin = start >> FIRST_SHIFT;
+    let mut end_bin = end.saturating_sub(1).max(start) >> FIRST_SHIFT;
+    for offset in offsets {
+        if start_bin == end_bin {
+            return extra + offset + start_bin;
+        }
+        start_bin >>= NEXT_SHIFT;
+        end_bin >>= NEXT_SHIFT;
+    }
+    // the largest bin spans the whole range
+    extra
+}
+
+/// Sets the bin column of RefGene lines
+///
+/// The bin is computed from `txStart` and `txEnd` (5th and 6th column), or
+/// set to `0` if `compute` is `false`.
+pub struct RefgeneBin {
+    compute: bool,
+}
+
+impl RefgeneBin {
+    pub fn new<W: Write>(inner: W, compute: bool) -> FilterLines<W, Self> {
+        FilterLines::new(inner, Self { compute })
+    }
+
+    /// Returns the bin of the line, `None` if it is not a RefGene record
+    fn bin(&self, line: &str) -> Option<u64> {
+        if !self.compute {
+            return Some(0);
+        }
+        let mut cols = line.split('\t').skip(4);
+        let start = cols.next()?.trim().parse::<u64>().ok()?;
+        let end = cols.next()?.trim().parse::<u64>().ok()?;
+        Some(bin_from_range(start, end))
+    }
+}
+
+impl LineFilter for RefgeneBin {
+    /// Writes the line with the new bin
+    fn write_line(&mut self, line: &[u8], out: &mut dyn Write) -> std::io::Result<()> {
+        let text = String::from_utf8_lossy(line);
+        match (text.split_once('\t'), self.bin(&text)) {
+            (Some((_, rest)), Some(bin)) => write!(out, "{}\t{}", bin, rest),
+            _ => out.write_all(line),
+        }
+    }
+}
@@ -0,0 +1,445 @@
+//! Coordinate helper functions that are shared across multiple output options
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+
+use atglib::models::{Exon, Frame, Strand, Transcript, TranscriptBuilder};
+use atglib::utils::errors::AtgError;
+
+use crate::reader_wrapper::ReadSeekWrapper;
+
+/// Returns the length of a 1-based, inclusive interval
+///
+/// The length is calculated as `u64` so that it cannot overflow, even
+/// for intervals spanning the full `u32` range. Malformed intervals
+/// with `end < start` have a length of 0.
+pub fn interval_len(start: u32, end: u32) -> u64 {
+    match end.checked_sub(start) {
+        Some(diff) => u64::from(diff) + 1,
+        None => 0,
+    }
+}
+
+/// Returns the length of an exon
+pub fn exon_len(exon: &Exon) -> u64 {
+    interval_len(exon.start(), exon.end())
+}
+
+/// Returns the length of the coding part of an exon, 0 for non-coding exons
+pub fn exon_cds_len(exon: &Exon) -> u64 {
+    match (exon.cds_start(), exon.cds_end()) {
+        (Some(start), Some(end)) => interval_len(*start, *end),
+        _ => 0,
+    }
+}
+
+/// Returns the length of the CDS, including the stop codon
+pub fn cds_len(tx: &Transcript) -> u64 {
+    tx.exons().iter().map(exon_cds_len).sum()
+}
+
+/// Returns the length of the processed transcript (cDNA), the sum of all exons
+pub fn exonic_len(tx: &Transcript) -> u64 {
+    tx.exons().iter().map(exon_len).sum()
+}
+
+/// Returns the genomic span of the transcript, including introns
+pub fn tx_len(tx: &Transcript) -> u64 {
+    interval_len(tx.tx_start(), tx.tx_end())
+}
+
+/// Returns the frame of a coding exon, based on the number of CDS bases before it
+///
+/// `cds_bases_before` counts the CDS bases of all upstream exons, in the
+/// direction of transcription.
+pub fn frame_for(cds_bases_before: u64) -> Frame {
+    match (3 - cds_bases_before % 3) % 3 {
+        0 => Frame::Zero,
+        1 => Frame::One,
+        _ => Frame::Two,
+    }
+}
+
+/// Sets the frames of all exons, based on their CDS and the strand
+///
+/// The frames are counted in the order of transcription, non-coding exons
+/// have no frame. Returns the number of exons with a changed frame.
+pub fn set_frames(exons: &mut [Exon], strand: &Strand) -> usize {
+    let mut order: Vec<&mut Exon> = exons.iter_mut().collect();
+    order.sort_by_key(|exon| exon.start());
+    if let Strand::Minus = strand {
+        order.reverse();
+    }
+    let mut cds_bases: u64 = 0;
+    let mut changed = 0;
+    for exon in order {
+        let frame = match (*exon.cds_start(), *exon.cds_end()) {
+            (Some(cds_start), Some(cds_end)) => {
+                let frame = frame_for(cds_bases);
+                cds_bases += interval_len(cds_start, cds_end);
+                frame
+            }
+            _ => Frame::None,
+        };
+        if *exon.frame_offset() != frame {
+            *exon.frame_offset_mut() = frame;
+            changed += 1;
+        }
+    }
+    changed
+}
+
+/// Returns the exons of the transcript, sorted by their genomic start position
+pub fn sorted_exons(tx: &Transcript) -> Vec<&Exon> {
+    let mut exons: Vec<&Exon> = tx.exons().iter().collect();
+    exons.sort_by_key(|exon| exon.start());
+    exons
+}
+
+/// Returns the exons with their number, in the order of transcription
+///
+/// Exon 1 is the 5'-most exon, i.e. the last exon in genomic order on the minus strand.
+pub fn numbered_exons(tx: &Transcript) -> Vec<(usize, &Exon)> {
+    let mut exons = sorted_exons(tx);
+    if let Strand::Minus = tx.strand() {
+        exons.reverse();
+    }
+    exons
+        .into_iter()
+        .enumerate()
+        .map(|(idx, exon)| (idx + 1, exon))
+        .collect()
+}
+
+/// An intron of a transcript, 1-based and inclusive
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Intron {
+    /// The number of the intron in the order of transcription, intron `n` follows exon `n`
+    pub number: usize,
+    pub start: u32,
+    pub end: u32,
+    strand: Strand,
+}
+
+impl Intron {
+    /// Returns the length of the intron
+    pub fn len(&self) -> u64 {
+        interval_len(self.start, self.end)
+    }
+
+    /// Returns the genomic positions of the first two bases of the intron (in the order of transcription)
+    pub fn donor(&self) -> (u32, u32) {
+        match self.strand {
+            Strand::Minus => (self.end - 1, self.end),
+            _ => (self.start, self.start + 1),
+        }
+    }
+
+    /// Returns the genomic positions of the last two bases of the intron (in the order of transcription)
+    pub fn acceptor(&self) -> (u32, u32) {
+        match self.strand {
+            Strand::Minus => (self.start, self.start + 1),
+            _ => (self.end - 1, self.end),
+        }
+    }
+}
+
+/// Returns the introns of the transcript, in the order of transcription
+///
+/// Book-ended (or overlapping) exons don't form an intron, but they are
+/// counted for the intron numbers, so that intron `n` always follows exon `n`.
+pub fn intron_coordinates(tx: &Transcript) -> Vec<Intron> {
+    let exons = sorted_exons(tx);
+    let count = exons.len().saturating_sub(1);
+    let mut introns: Vec<Intron> = exons
+        .windows(2)
+        .enumerate()
+        .filter(|(_, pair)| pair[1].start().saturating_sub(pair[0].end()) > 1)
+        .map(|(idx, pair)| Intron {
+            number: match tx.strand() {
+                Strand::Minus => count - idx,
+                _ => idx + 1,
+            },
+            start: pair[0].end() + 1,
+            end: pair[1].start() - 1,
+            strand: *tx.strand(),
+        })
+        .collect();
+    if let Strand::Minus = tx.strand() {
+        introns.reverse();
+    }
+    introns
+}
+
+/// Extends an interval by `upstream` and `downstream` bases
+///
+/// Up- and downstream are relative to the strand, so on the minus strand
+/// the `upstream` padding is added to the end of the interval.
+/// Transcripts with unknown strand are treated as plus-strand.
+///
+/// The padded interval never extends beyond the first base of the
+/// chromosome or beyond `chrom_len`, if specified. Coordinates close to
+/// `u32::MAX` are capped instead of wrapping around.
+///
+/// All coordinates are 1-based and inclusive.
+pub fn pad_interval(
+    start: u32,
+    end: u32,
+    upstream: u32,
+    downstream: u32,
+    strand: &Strand,
+    chrom_len: Option<u64>,
+) -> (u32, u32) {
+    let (left, right) = match strand {
+        Strand::Minus => (downstream, upstream),
+        _ => (upstream, downstream),
+    };
+    let start = start.saturating_sub(left).max(1);
+    let end = end.saturating_add(right);
+    match chrom_len {
+        // `end` is a u32, so the minimum of both always fits into u32
+        Some(len) => (start, u64::from(end).min(len) as u32),
+        None => (start, end),
+    }
+}
+
+/// Pads a transcript by extending its outermost exons
+///
+/// The CDS coordinates remain unchanged, so only the UTRs or non-coding
+/// exons are extended.
+pub fn pad_transcript(tx: &mut Transcript, upstream: u32, downstream: u32, chrom_len: Option<u64>) {
+    if tx.exons().is_empty() {
+        return;
+    }
+    let (start, end) = pad_interval(
+        tx.tx_start(),
+        tx.tx_end(),
+        upstream,
+        downstream,
+        tx.strand(),
+        chrom_len,
+    );
+    let tx_start = tx.tx_start();
+    let tx_end = tx.tx_end();
+    for exon in tx.exons_mut() {
+        if exon.start() == tx_start {
+            *exon.start_mut() = start;
+        }
+        if exon.end() == tx_end {
+            *exon.end_mut() = end;
+        }
+    }
+}
+
+/// Returns the name and length of every chromosome, in the order of the Fasta index (`.fai`) file
+pub fn fai_entries(filename: &str) -> Result<Vec<(String, u64)>, AtgError> {
+    let reader = BufReader::new(ReadSeekWrapper::from_filename(filename)?);
+    let mut entries = vec![];
+    for line in reader.lines() {
+        let line = line?;
+        let mut cols = line.split('\t');
+        if let (Some(chrom), Some(len)) = (cols.next(), cols.next()) {
+            let len = len.parse::<u64>().map_err(|err| {
+                AtgError::new(format!(
+                    "invalid length of {} in {}: {}",
+                    chrom, filename, err
+                ))
+            })?;
+            entries.push((chrom.to_string(), len));
+        }
+    }
+    Ok(entries)
+}
+
+/// Returns the length of every chromosome, based on a Fasta index (`.fai`) file
+pub fn chrom_sizes_from_fai(filename: &str) -> Result<HashMap<String, u64>, AtgError> {
+    Ok(fai_entries(filename)?.into_iter().collect())
+}
+
+/// Returns a copy of the transcript with a different name, chromosome or gene symbol
+pub fn rebuild_transcript(
+    tx: &Transcript,
+    name: &str,
+    chrom: &str,
+    gene: &str,
+) -> Result<Transcript, AtgError> {
+    let mut rebuilt = TranscriptBuilder::new()
+        .bin(*tx.bin())
+        .name(name)
+        .chrom(chrom)
+        .gene(gene)
+        .strand(*tx.strand())
+        .cds_start_codon_stat(tx.cds_start_codon_stat())?
+        .cds_stop_codon_stat(tx.cds_stop_codon_stat())?
+        .score(*tx.score())
+        .build()?;
+    for exon in tx.exons() {
+        rebuilt.push_exon(exon.clone());
+    }
+    Ok(rebuilt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::fixtures::Fixture;
+
+    #[test]
+    fn test_lengths_of_fixture() {
+        let fixture = Fixture::new(3);
+        let tx = fixture.transcript();
+        assert_eq!(exonic_len(&tx), 45);
+        assert_eq!(tx_len(&tx), 65);
+        assert_eq!(cds_len(&tx), fixture.cds_sequence().len() as u64);
+
+        let tx = Fixture::new(3).non_coding().transcript();
+        assert_eq!(cds_len(&tx), 0);
+    }
+
+    #[test]
+    fn test_interval_len() {
+        assert_eq!(interval_len(0, 0), 1);
+        assert_eq!(interval_len(1, 1), 1);
+        assert_eq!(interval_len(0, 1), 2);
+        assert_eq!(interval_len(u32::MAX - 1, u32::MAX), 2);
+        assert_eq!(interval_len(u32::MAX, u32::MAX), 1);
+        assert_eq!(interval_len(0, u32::MAX), u64::from(u32::MAX) + 1);
+        // malformed intervals
+        assert_eq!(interval_len(1, 0), 0);
+        assert_eq!(interval_len(u32::MAX, u32::MAX - 1), 0);
+        assert_eq!(interval_len(u32::MAX, 0), 0);
+    }
+
+    #[test]
+    fn test_pad_interval_at_chromosome_start() {
+        // 0 is not a valid 1-based position, the start is moved to the first base
+        assert_eq!(pad_interval(0, 0, 5, 0, &Strand::Plus, None), (1, 0));
+        assert_eq!(pad_interval(1, 1, 5, 5, &Strand::Plus, None), (1, 6));
+        assert_eq!(pad_interval(1, 1, u32::MAX, 0, &Strand::Plus, None), (1, 1));
+        // upstream is on the right side of minus strand transcripts
+        assert_eq!(pad_interval(10, 20, 3, 5, &Strand::Minus, None), (5, 23));
+        assert_eq!(
+            pad_interval(1, 20, 3, u32::MAX, &Strand::Minus, None),
+            (1, 23)
+        );
+    }
+
+    #[test]
+    fn test_pad_interval_at_u32_max() {
+        let max = u32::MAX;
+        assert_eq!(
+            pad_interval(max - 1, max - 1, 0, 1, &Strand::Plus, None),
+            (max - 1, max)
+        );
+        assert_eq!(
+            pad_interval(max - 1, max - 1, 0, 5, &Strand::Plus, None),
+            (max - 1, max)
+        );
+        assert_eq!(
+            pad_interval(max, max, 0, max, &Strand::Plus, None),
+            (max, max)
+        );
+        assert_eq!(
+            pad_interval(max, max, max, 0, &Strand::Plus, None),
+            (1, max)
+        );
+        assert_eq!(pad_interval(1, max, 0, 1, &Strand::Minus, None), (1, max));
+    }
+
+    #[test]
+    fn test_pad_interval_to_chromosome_length() {
+        assert_eq!(
+            pad_interval(1, 100, 0, 50, &Strand::Plus, Some(120)),
+            (1, 120)
+        );
+        assert_eq!(
+            pad_interval(1, 100, 0, 50, &Strand::Plus, Some(200)),
+            (1, 150)
+        );
+        let max = u32::MAX;
+        assert_eq!(
+            pad_interval(1, max - 1, 0, 5, &Strand::Plus, Some(u64::MAX)),
+            (1, max)
+        );
+        assert_eq!(
+            pad_interval(1, max - 1, 0, 5, &Strand::Plus, Some(u64::from(max) - 1)),
+            (1, max - 1)
+        );
+    }
+
+    /// Start, end and CDS of an exon
+    type TestExon = (u32, u32, Option<(u32, u32)>);
+
+    fn transcript(strand: Strand, exons: &[TestExon]) -> Transcript {
+        let mut tx = TranscriptBuilder::new()
+            .name("Test-Transcript")
+            .chrom("chr1")
+            .gene("Test-Gene")
+            .strand(strand)
+            .build()
+            .unwrap();
+        for (start, end, cds) in exons {
+            tx.push_exon(Exon::new(
+                *start,
+                *end,
+                cds.map(|cds| cds.0),
+                cds.map(|cds| cds.1),
+                Frame::None,
+            ));
+        }
+        tx
+    }
+
+    #[test]
+    fn test_pad_transcript() {
+        let mut tx = transcript(
+            Strand::Plus,
+            &[(1, 10, Some((5, 10))), (20, 30, Some((20, 25)))],
+        );
+        pad_transcript(&mut tx, 5, 5, None);
+        assert_eq!((tx.tx_start(), tx.tx_end()), (1, 35));
+        // the CDS is unchanged
+        assert_eq!((tx.cds_start(), tx.cds_end()), (Some(5), Some(25)));
+
+        let mut tx = transcript(Strand::Minus, &[(10, 20, None), (30, 40, None)]);
+        pad_transcript(&mut tx, 2, 8, None);
+        assert_eq!((tx.tx_start(), tx.tx_end()), (2, 42));
+    }
+
+    #[test]
+    fn test_pad_transcript_at_u32_max() {
+        let max = u32::MAX;
+        let mut tx = transcript(Strand::Plus, &[(max - 10, max - 1, None)]);
+        pad_transcript(&mut tx, 0, 5, None);
+        assert_eq!((tx.tx_start(), tx.tx_end()), (max - 10, max));
+
+        let mut tx = transcript(Strand::Plus, &[(max, max, None)]);
+        pad_transcript(&mut tx, max, max, None);
+        assert_eq!((tx.tx_start(), tx.tx_end()), (1, max));
+
+        let mut tx = transcript(Strand::Plus, &[(0, 0, None)]);
+        pad_transcript(&mut tx, 1, 1, Some(1));
+        assert_eq!((tx.tx_start(), tx.tx_end()), (1, 1));
+
+        let mut tx = transcript(Strand::Plus, &[]);
+        pad_transcript(&mut tx, 1, 1, None);
+        assert!(tx.exons().is_empty());
+    }
+
+    #[test]
+    fn test_intron_coordinates_at_boundaries() {
+        let tx = transcript(Strand::Plus, &[(0, 0), (2, 2)].map(|(s, e)| (s, e, None)));
+        let introns = intron_coordinates(&tx);
+        assert_eq!(introns.len(), 1);
+        assert_eq!((introns[0].start, introns[0].end), (1, 1));
+
+        // book-ended exons have no intron
+        let max = u32::MAX;
+        let tx = transcript(Strand::Plus, &[(1, max - 1, None), (max, max, None)]);
+        assert!(intron_coordinates(&tx).is_empty());
+
+        let tx = transcript(Strand::Plus, &[(1, 10, None), (max, max, None)]);
+        let introns = intron_coordinates(&tx);
+        assert_eq!((introns[0].start, introns[0].end), (11, max - 1));
+    }
+}
@@ -0,0 +1,495 @@
+//! Check input files for problems and report every issue with its line number
+
+use std::fmt;
+use std::io::{BufRead, BufReader, Write};
+
+use atglib::models::TranscriptRead;
+use atglib::utils::errors::AtgError;
+
+use crate::cli::{InputFormat, ValidateArgs};
+use crate::compression::{create_output, open_input};
+use crate::formats::reader_for;
+use crate::gtf_attributes::tokens;
+use crate::gtf_groups::GroupedGtfReader;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The file can be parsed, but the data is probably wrong
+    Warning,
+    /// The file or transcript cannot be parsed
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueKind {
+    /// The file cannot be opened or read
+    Io,
+    /// The line has the wrong number of columns
+    ColumnCount,
+    /// A column that must be a number is not
+    InvalidNumber,
+    /// The strand is not `+`, `-` (or `.` where allowed)
+    InvalidStrand,
+    /// An interval ends before it starts
+    InvalidInterval,
+    /// The CDS is not within the transcript
+    CdsOutsideTranscript,
+    /// The number of exons does not match the exon coordinates
+    ExonCount,
+    /// An unknown CDS start or end stat
+    InvalidCdsStat,
+    /// A required attribute is missing
+    MissingAttribute,
+    /// The transcript cannot be built from the records
+    BuildFailure,
+}
+
+impl fmt::Display for IssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            IssueKind::Io => "io",
+            IssueKind::ColumnCount => "column-count",
+            IssueKind::InvalidNumber => "invalid-number",
+            IssueKind::InvalidStrand => "invalid-strand",
+            IssueKind::InvalidInterval => "invalid-interval",
+            IssueKind::CdsOutsideTranscript => "cds-outside-transcript",
+            IssueKind::ExonCount => "exon-count",
+            IssueKind::InvalidCdsStat => "invalid-cds-stat",
+            IssueKind::MissingAttribute => "missing-attribute",
+            IssueKind::BuildFailure => "build-failure",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// A single problem of an input file
+#[derive(Debug, Clone)]
+pub struct Issue {
+    pub kind: IssueKind,
+    pub severity: Severity,
+    /// 1-based line number, if the issue belongs to a single line
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Issue {
+    fn error(kind: IssueKind, line: Option<usize>, message: String) -> Self {
+        Self {
+            kind,
+            severity: Severity::Error,
+            line,
+            message,
+        }
+    }
+
+    fn warning(kind: IssueKind, line: Option<usize>, message: String) -> Self {
+        Self {
+            kind,
+            severity: Severity::Warning,
+            line,
+            message,
+        }
+    }
+}
+
+/// Collects the issues of a single line
+struct LineCheck<'a> {
+    line: usize,
+    cols: Vec<&'a str>,
+    issues: Vec<Issue>,
+}
+
+impl<'a> LineCheck<'a> {
+    fn new(line: usize, content: &'a str) -> Self {
+        Self {
+            line,
+            cols: content.split('\t').collect(),
+            issues: vec![],
+        }
+    }
+
+    fn error(&mut self, kind: IssueKind, message: String) {
+        self.issues
+            .push(Issue::error(kind, Some(self.line), message))
+    }
+
+    fn warning(&mut self, kind: IssueKind, message: String) {
+        self.issues
+            .push(Issue::warning(kind, Some(self.line), message))
+    }
+
+    /// Checks that the line has at least `min` and at most `max` columns
+    fn columns(&mut self, min: usize, max: usize) -> bool {
+        let count = self.cols.len();
+        if count < min || count > max {
+            let expected = match min == max {
+                true => min.to_string(),
+                false => format!("{} to {}", min, max),
+            };
+            self.error(
+                IssueKind::ColumnCount,
+                format!("expected {} columns, found {}", expected, count),
+            );
+            return false;
+        }
+        true
+    }
+
+    /// Returns the number in column `idx`
+    fn number(&mut self, idx: usize, name: &str) -> Option<u64> {
+        let value = self.cols[idx].trim();
+        match value.parse() {
+            Ok(number) => Some(number),
+            Err(_) => {
+                self.error(
+                    IssueKind::InvalidNumber,
+                    format!("{} is not a number: `{}`", name, value),
+                );
+                None
+            }
+        }
+    }
+
+    /// Returns the comma-separated list of numbers in column `idx`
+    fn number_list(&mut self, idx: usize, name: &str) -> Option<Vec<u64>> {
+        let value = self.cols[idx].trim().trim_end_matches(',');
+        if value.is_empty() {
+            return Some(vec![]);
+        }
+        let numbers: Result<Vec<u64>, _> = value.split(',').map(|n| n.trim().parse()).collect();
+        match numbers {
+            Ok(numbers) => Some(numbers),
+            Err(_) => {
+                self.error(
+                    IssueKind::InvalidNumber,
+                    format!("{} are not a list of numbers: `{}`", name, value),
+                );
+                None
+            }
+        }
+    }
+
+    fn strand(&mut self, idx: usize, allowed: &[&str]) {
+        let value = self.cols[idx].trim();
+        if !allowed.contains(&value) {
+            self.error(
+                IssueKind::InvalidStrand,
+                format!("invalid strand `{}`", value),
+            );
+        }
+    }
+
+    fn interval(&mut self, start: u64, end: u64, name: &str) {
+        if end < start {
+            self.error(
+                IssueKind::InvalidInterval,
+                format!("{} ends before it starts ({} > {})", name, start, end),
+            );
+        }
+    }
+
+    fn cds_stat(&mut self, idx: usize) {
+        let value = self.cols[idx].trim();
+        if !["none", "unk", "incmpl", "cmpl"].contains(&value) {
+            self.error(
+                IssueKind::InvalidCdsStat,
+                format!("invalid CDS stat `{}`", value),
+            );
+        }
+    }
+}
+
+/// Checks a RefGene, GenePredExt or knownGene line with `columns` columns, `offset` is the index of the name column
+fn check_genepred_line(check: &mut LineCheck, offset: usize, columns: usize) {
+    if !check.columns(columns, columns) {
+        return;
+    }
+    check.strand(offset + 2, &["+", "-"]);
+    let tx = (
+        check.number(offset + 3, "txStart"),
+        check.number(offset + 4, "txEnd"),
+    );
+    let cds = (
+        check.number(offset + 5, "cdsStart"),
+        check.number(offset + 6, "cdsEnd"),
+    );
+    let exon_count = check.number(offset + 7, "exonCount");
+    let starts = check.number_list(offset + 8, "exonStarts");
+    let ends = check.number_list(offset + 9, "exonEnds");
+    // knownGene has no CDS stats
+    if columns == offset + 15 {
+        check.cds_stat(offset + 12);
+        check.cds_stat(offset + 13);
+    }
+
+    if let (Some(tx_start), Some(tx_end)) = tx {
+        check.interval(tx_start, tx_end, "the transcript");
+        if let (Some(cds_start), Some(cds_end)) = cds {
+            check.interval(cds_start, cds_end, "the CDS");
+            // non-coding transcripts have `cdsStart == cdsEnd`, often at `txEnd`
+            if cds_start < cds_end && (cds_start < tx_start || cds_end > tx_end) {
+                check.error(
+                    IssueKind::CdsOutsideTranscript,
+                    format!(
+                        "the CDS {}-{} is outside of the transcript {}-{}",
+                        cds_start, cds_end, tx_start, tx_end
+                    ),
+                );
+            }
+        }
+    }
+
+    if let (Some(starts), Some(ends)) = (starts, ends) {
+        if starts.len() != ends.len() {
+            check.error(
+                IssueKind::ExonCount,
+                format!("{} exonStarts, but {} exonEnds", starts.len(), ends.len()),
+            );
+        } else {
+            if let Some(count) = exon_count {
+                if count != starts.len() as u64 {
+                    check.error(
+                        IssueKind::ExonCount,
+                        format!(
+                            "exonCount is {}, but {} exons are listed",
+                            count,
+                            starts.len()
+                        ),
+                    );
+                }
+            }
+            for (idx, (start, end)) in starts.iter().zip(&ends).enumerate() {
+                check.interval(*start, *end, &format!("exon {}", idx + 1));
+            }
+        }
+    }
+}
+
+/// Checks a GTF or GFF3 line
+fn check_gff_line(check: &mut LineCheck, format: &InputFormat) {
+    if !check.columns(9, 9) {
+        return;
+    }
+    let start = check.number(3, "start");
+    let end = check.number(4, "end");
+    if let (Some(start), Some(end)) = (start, end) {
+        check.interval(start, end, "the feature");
+    }
+    check.strand(6, &["+", "-", "."]);
+    if check.cols[6].trim() == "." {
+        check.warning(
+            IssueKind::InvalidStrand,
+            "unknown strand, the transcript will not be stranded".to_string(),
+        );
+    }
+
+    let feature = check.cols[2].trim();
+    let attributes = check.cols[8];
+    let required = match format {
+        InputFormat::Gtf => "transcript_id",
+        _ => "Parent",
+    };
+    let has_attribute = match format {
+        InputFormat::Gtf => tokens(attributes).any(|(key, _)| key == required),
+        _ => attributes
+            .split(';')
+            .any(|attr| attr.trim().starts_with(&format!("{}=", required))),
+    };
+    if ["exon", "CDS"].contains(&feature) && !has_attribute {
+        check.error(
+            IssueKind::MissingAttribute,
+            format!("{} record without `{}` attribute", feature, required),
+        );
+    }
+}
+
+/// Checks a BED line
+fn check_bed_line(check: &mut LineCheck) {
+    if !check.columns(3, 12) {
+        return;
+    }
+    let start = check.number(1, "chromStart");
+    let end = check.number(2, "chromEnd");
+    if let (Some(start), Some(end)) = (start, end) {
+        check.interval(start, end, "the feature");
+    }
+    let cols = check.cols.len();
+    if cols >= 6 {
+        check.strand(5, &["+", "-", "."]);
+    }
+    if cols < 11 {
+        return;
+    }
+    // atg writes BED without the `score` column
+    let offset = match cols {
+        11 => 1,
+        _ => 0,
+    };
+    let blocks = check.number(9 - offset, "blockCount");
+    let sizes = check.number_list(10 - offset, "blockSizes");
+    let starts = match cols {
+        12 => check.number_list(11, "blockStarts"),
+        _ => None,
+    };
+    if let (Some(blocks), Some(sizes)) = (blocks, sizes) {
+        if blocks != sizes.len() as u64 {
+            check.error(
+                IssueKind::ExonCount,
+                format!(
+                    "blockCount is {}, but {} blockSizes are listed",
+                    blocks,
+                    sizes.len()
+                ),
+            );
+        }
+        if let Some(starts) = starts {
+            if starts.len() != sizes.len() {
+                check.error(
+                    IssueKind::ExonCount,
+                    format!(
+                        "{} blockStarts, but {} blockSizes",
+                        starts.len(),
+                        sizes.len()
+                    ),
+                );
+            }
+        }
+    }
+}
+
+/// Returns the issues of every line of the file
+fn check_lines(path: &str, format: &InputFormat) -> Vec<Issue> {
+    let reader = match open_input(path) {
+        Ok(reader) => BufReader::new(reader),
+        Err(err) => return vec![Issue::error(IssueKind::Io, None, err.to_string())],
+    };
+    let mut issues = vec![];
+    for (idx, line) in reader.lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                issues.push(Issue::error(IssueKind::Io, Some(idx + 1), err.to_string()));
+                break;
+            }
+        };
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        if let InputFormat::Bed = format {
+            if line.starts_with("track") || line.starts_with("browser") {
+                continue;
+            }
+        }
+        let mut check = LineCheck::new(idx + 1, &line);
+        match format {
+            InputFormat::Refgene => check_genepred_line(&mut check, 1, 16),
+            InputFormat::Genepredext => check_genepred_line(&mut check, 0, 15),
+            InputFormat::Knowngene => check_genepred_line(&mut check, 0, 12),
+            InputFormat::Gtf | InputFormat::Gff3 => check_gff_line(&mut check, format),
+            InputFormat::Bed => check_bed_line(&mut check),
+            InputFormat::Bin | InputFormat::Ensembl | InputFormat::Json => {}
+        }
+        issues.append(&mut check.issues);
+    }
+    issues
+}
+
+/// Returns all transcripts that cannot be built
+fn check_transcripts(path: &str, format: &InputFormat) -> Vec<Issue> {
+    if let InputFormat::Gtf = format {
+        let mut reader = match GroupedGtfReader::from_file(path, true) {
+            Ok(reader) => reader,
+            Err(err) => return vec![Issue::error(IssueKind::Io, None, err.to_string())],
+        };
+        if let Err(err) = reader.transcripts() {
+            return vec![Issue::error(IssueKind::BuildFailure, None, err.to_string())];
+        }
+        return reader
+            .failures()
+            .iter()
+            .map(|failure| {
+                Issue::error(
+                    IssueKind::BuildFailure,
+                    None,
+                    format!("{}: {}", failure.transcript, failure.reason),
+                )
+            })
+            .collect();
+    }
+    let result = reader_for(format, path).and_then(|mut reader| Ok(reader.transcripts()?));
+    match result {
+        Ok(transcripts) if transcripts.is_empty() => vec![Issue::warning(
+            IssueKind::BuildFailure,
+            None,
+            "the file does not contain any transcripts".to_string(),
+        )],
+        Ok(_) => vec![],
+        Err(err) => vec![Issue::error(IssueKind::BuildFailure, None, err.to_string())],
+    }
+}
+
+/// Checks the input file and returns all issues
+///
+/// Every line is checked for the expected columns, coordinates and values.
+/// If no line has an error, all transcripts are built to find e.g.
+/// inconsistent records of a transcript. An empty list means that the file
+/// can be read without problems.
+pub fn check_file(path: &str, format: &InputFormat) -> Vec<Issue> {
+    let mut issues = check_lines(path, format);
+    if !issues.iter().any(|issue| issue.severity == Severity::Error) {
+        issues.append(&mut check_transcripts(path, format));
+    }
+    issues
+}
+
+/// Writes all issues as tab-separated table
+pub fn write_issues<W: Write>(issues: &[Issue], mut out: W) -> Result<(), std::io::Error> {
+    writeln!(out, "Line\tSeverity\tKind\tMessage")?;
+    for issue in issues {
+        let line = issue
+            .line
+            .map_or_else(|| ".".to_string(), |line| line.to_string());
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}",
+            line, issue.severity, issue.kind, issue.message
+        )?;
+    }
+    out.flush()
+}
+
+/// Validates the input file and writes all issues
+///
+/// Returns an error if the file has at least one error.
+pub fn run_validate(args: &ValidateArgs) -> Result<(), AtgError> {
+    let issues = check_file(&args.input, &args.from);
+    write_issues(&issues, create_output(&args.output, None)?)?;
+
+    let errors = issues
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error)
+        .count();
+    debug!(
+        target: "atg::validate",
+        "{} errors and {} warnings in {}",
+        errors,
+        issues.len() - errors,
+        args.input
+    );
+    match errors {
+        0 => Ok(()),
+        _ => Err(AtgError::new(format!(
+            "{} contains {} errors",
+            args.input, errors
+        ))),
+    }
+}
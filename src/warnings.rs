@@ -0,0 +1,87 @@
+//! Warnings about recoverable issues in the input data
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+use atglib::utils::errors::AtgError;
+
+#[derive(Default)]
+struct Warnings {
+    counts: BTreeMap<&'static str, usize>,
+    file: Option<BufWriter<File>>,
+}
+
+static WARNINGS: OnceLock<Mutex<Warnings>> = OnceLock::new();
+
+fn warnings() -> MutexGuard<'static, Warnings> {
+    WARNINGS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+}
+
+/// Returns the value as JSON string
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// Writes all following warnings as JSON lines to the file
+pub fn set_warnings_file(filename: &str) -> Result<(), AtgError> {
+    warnings().file = Some(BufWriter::new(File::create(filename)?));
+    Ok(())
+}
+
+/// Logs and counts a warning of the given kind, e.g. `fixed-frames`
+pub fn warning(target: &str, kind: &'static str, message: &str) {
+    warn!(target: target, "{}", message);
+    let mut warnings = warnings();
+    *warnings.counts.entry(kind).or_insert(0) += 1;
+    if let Some(file) = &mut warnings.file {
+        let line = format!(
+            "{{\"kind\":{},\"target\":{},\"message\":{}}}",
+            json_string(kind),
+            json_string(target),
+            json_string(message)
+        );
+        if let Err(err) = writeln!(file, "{}", line) {
+            error!("Unable to write to the warnings file: {}", err);
+            warnings.file = None;
+        }
+    }
+}
+
+/// Flushes the warnings file and checks the number of warnings
+///
+/// Returns an error if there are more than `max_warnings` warnings.
+pub fn finish_warnings(max_warnings: Option<usize>) -> Result<(), AtgError> {
+    let mut warnings = warnings();
+    if let Some(file) = &mut warnings.file {
+        file.flush()?;
+    }
+    let total: usize = warnings.counts.values().sum();
+    for (kind, count) in &warnings.counts {
+        info!("{} warnings of kind {}", count, kind);
+    }
+    match max_warnings {
+        Some(max) if total > max => Err(AtgError::new(format!(
+            "{} warnings, but at most {} are allowed (`--max-warnings`)",
+            total, max
+        ))),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,89 @@
+//! Client-side transcript conversion with atglib
+//!
+//! Compiles to `wasm32-unknown-unknown`, so that files can be converted in
+//! the browser without uploading them. There is no file, S3 or network IO:
+//! the input is passed in as a string and the output is returned as a string.
+//!
+//! ```js
+//! import init, { convert } from "./pkg/atglib_wasm.js";
+//!
+//! await init();
+//! const refgene = convert(gtf, "gtf", "refgene");
+//! ```
+
+use std::io::Cursor;
+
+use wasm_bindgen::prelude::*;
+
+use atglib::bed;
+use atglib::genepred;
+use atglib::genepredext;
+use atglib::gtf;
+use atglib::models::{TranscriptRead, TranscriptWrite, Transcripts};
+use atglib::refgene;
+
+/// Input formats that can be converted
+pub const INPUT_FORMATS: [&str; 3] = ["gtf", "refgene", "genepredext"];
+
+/// Output formats that can be converted to
+pub const OUTPUT_FORMATS: [&str; 5] = ["gtf", "refgene", "genepred", "genepredext", "bed"];
+
+/// Parses all transcripts of the input
+pub fn read(input: &str, from: &str) -> Result<Transcripts, String> {
+    let data = Cursor::new(input.as_bytes());
+    let transcripts = match from {
+        "gtf" => gtf::Reader::new(data).transcripts(),
+        "refgene" => refgene::Reader::new(data).transcripts(),
+        "genepredext" => genepredext::Reader::new(data).transcripts(),
+        _ => {
+            return Err(format!(
+                "invalid input format {}, expected one of {}",
+                from,
+                INPUT_FORMATS.join(", ")
+            ))
+        }
+    };
+    transcripts.map_err(|err| err.to_string())
+}
+
+fn write_all<W: TranscriptWrite>(mut writer: W, transcripts: &Transcripts) -> Result<(), String> {
+    writer
+        .write_transcripts(transcripts)
+        .map_err(|err| err.to_string())
+}
+
+/// Writes all transcripts in the output format
+pub fn write(transcripts: &Transcripts, to: &str) -> Result<String, String> {
+    let mut buf = vec![];
+    match to {
+        "gtf" => write_all(gtf::Writer::new(&mut buf), transcripts)?,
+        "refgene" => write_all(refgene::Writer::new(&mut buf), transcripts)?,
+        "genepred" => write_all(genepred::Writer::new(&mut buf), transcripts)?,
+        "genepredext" => write_all(genepredext::Writer::new(&mut buf), transcripts)?,
+        "bed" => write_all(bed::Writer::new(&mut buf), transcripts)?,
+        _ => {
+            return Err(format!(
+                "invalid output format {}, expected one of {}",
+                to,
+                OUTPUT_FORMATS.join(", ")
+            ))
+        }
+    }
+    String::from_utf8(buf).map_err(|err| err.to_string())
+}
+
+/// Converts the content of a file from one format into another
+#[wasm_bindgen]
+pub fn convert(input: &str, from: &str, to: &str) -> Result<String, JsError> {
+    read(input, from)
+        .and_then(|transcripts| write(&transcripts, to))
+        .map_err(|err| JsError::new(&err))
+}
+
+/// Returns the number of transcripts in the input, e.g. to show a summary before converting
+#[wasm_bindgen]
+pub fn count_transcripts(input: &str, from: &str) -> Result<usize, JsError> {
+    read(input, from)
+        .map(|transcripts| transcripts.len())
+        .map_err(|err| JsError::new(&err))
+}